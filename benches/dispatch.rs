@@ -0,0 +1,70 @@
+//! how long the interpreter, the bytecode backend, with and without fused instructions and
+//! dispatching with a `match` or through handlers, its instructions compiled to closures and the
+//! native engine of the `native` feature take to run loop heavy programs, run with `cargo bench`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bottom::ext::Host;
+use bottom::interpreter::{interpret, Io};
+use bottom::unstack::Unstack;
+use bottom::{bytecode, closures, parse, Limits, RuntimeError};
+
+/// loops where most instructions are pairs the bytecode backend fuses
+const PROGRAMS: &[(&str, &str)] = &[
+    // halves 2^62 until it is 0
+    ("halve", "🥺4611686018427387904 💖2 🥺1 💓0 ✨1 🫂6"),
+    // halves it by dividing by 4 and multiplying by a constant folded from 🥺4 💖2
+    ("quarter", "🥺4611686018427387904 💖4 🥺4 💖2 💓0 ✨1 🫂7"),
+];
+
+const RUNS: usize = 20_000;
+
+type Run = fn(&bytecode::Program, Unstack, &mut dyn Io, &Limits) -> Result<Vec<i64>, RuntimeError>;
+
+fn time(mut run: impl FnMut() -> Vec<i64>) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        black_box(run());
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let limits = Limits::default();
+    for (name, source) in PROGRAMS {
+        let tokens = parse(source, false);
+        let interpreted =
+            time(|| interpret(tokens.clone(), Unstack::new(), &mut Host::new(), &limits).unwrap());
+        println!("{:<12} {:<25} {:>10.2?}", name, "interpreter", interpreted);
+        for fuse in [false, true] {
+            let program = bytecode::compile(&tokens, fuse).expect("compiles");
+            let runs: [(&str, Run); 2] =
+                [("match", bytecode::run_match), ("threaded", bytecode::run)];
+            for (dispatch, run) in runs {
+                let compiled =
+                    time(|| run(&program, Unstack::new(), &mut Host::new(), &limits).unwrap());
+                println!(
+                    "{:<12} bytecode, {:<8} {:<5}  {:>10.2?} ({} instructions)",
+                    name,
+                    dispatch,
+                    if fuse { "fused" } else { "" },
+                    compiled,
+                    program.code.len()
+                );
+            }
+        }
+        let compiled = closures::compile(&tokens).expect("compiles");
+        let chained =
+            time(|| closures::run(&compiled, Unstack::new(), &mut Host::new(), &limits).unwrap());
+        println!("{:<12} {:<25} {:>10.2?}", name, "closures", chained);
+        #[cfg(all(feature = "native", target_arch = "x86_64"))]
+        {
+            let native = bottom::native::compile(&tokens).expect("assembles");
+            let assembled = time(|| {
+                bottom::native::run(&native, Unstack::new(), &mut Host::new(), &limits).unwrap()
+            });
+            println!("{:<12} {:<25} {:>10.2?}", name, "native", assembled);
+        }
+    }
+}
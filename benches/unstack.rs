@@ -0,0 +1,50 @@
+//! how long shallow unstacks, which fit in the values stored inside the unstack, take to work
+//! with compared to ones that need the arena, run with `cargo bench`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bottom::ext::Host;
+use bottom::interpreter::interpret;
+use bottom::unstack::{Unstack, INLINE_VALUES};
+use bottom::{parse, Limits};
+
+/// toy programs that never hold more than a few values
+const PROGRAMS: &[(&str, &str)] = &[
+    ("answer", "🥺6 🥺7 💓0"),
+    ("swap", "🥺1 🥺2 🥺3 👉👈2 ✨2 💓0 💓0"),
+    ("dup", "🥺3 ✨1 ✨2 💓1 💓0"),
+];
+
+const RUNS: usize = 200_000;
+
+fn time(mut run: impl FnMut() -> Vec<i64>) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        black_box(run());
+    }
+    start.elapsed()
+}
+
+/// a fresh unstack filled to `depth` and emptied again
+fn fill(depth: usize) -> Vec<i64> {
+    let mut unstack = Unstack::new();
+    for value in 0..depth as i64 {
+        unstack.push(black_box(value));
+    }
+    (0..depth).map(|_| unstack.pop()).collect()
+}
+
+fn main() {
+    let limits = Limits::default();
+    for (name, source) in PROGRAMS {
+        let tokens = parse(source, false);
+        let interpreted =
+            time(|| interpret(tokens.clone(), Unstack::new(), &mut Host::new(), &limits).unwrap());
+        println!("{:<24} {:>10.2?}", name, interpreted);
+    }
+    for depth in [INLINE_VALUES / 2, INLINE_VALUES, INLINE_VALUES * 4] {
+        let filled = time(|| fill(depth));
+        println!("{:<24} {:>10.2?}", format!("fill {} values", depth), filled);
+    }
+}
@@ -0,0 +1,84 @@
+/*
+ * C bindings for the 🥺 interpreter, link with -lbottom_ffi
+ *
+ * a program is parsed once with bottom_parse and can then be run any number of times with
+ * bottom_run. everything handed out has to be given back to the matching _free function.
+ * extension operations use the stdin, stdout and stderr of the embedding process
+ *
+ * keep in sync with ffi/src/lib.rs, whose tests check the statuses and the layout of the result
+ */
+
+#ifndef BOTTOM_H
+#define BOTTOM_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* a parsed program */
+typedef struct BottomProgram BottomProgram;
+
+/* how a run ended */
+typedef enum BottomStatus {
+    BOTTOM_OK = 0,
+    BOTTOM_TIMEOUT = 1,
+    BOTTOM_DEADLOCK = 2,
+    BOTTOM_ASSERTION_FAILED = 3,
+    /* the interpreter panicked */
+    BOTTOM_PANICKED = 4,
+    /* a null pointer was passed */
+    BOTTOM_INVALID_ARGUMENT = 5,
+    BOTTOM_OUT_OF_MEMORY = 6,
+    /* a 💾 or 💿 used an address past the memory limit */
+    BOTTOM_MEMORY_OUT_OF_BOUNDS = 7,
+    /* a 💖0 divided by zero */
+    BOTTOM_DIVISION_BY_ZERO = 8,
+    /* a 🫂 jumped outside of the program */
+    BOTTOM_JUMP_OUT_OF_RANGE = 9,
+    /* an operation needed more values than the unstack had */
+    BOTTOM_UNDERFLOW = 10,
+    /* an operation could not do what it does, like a 📝 the host refused */
+    BOTTOM_FAILED = 11,
+    /* an arithmetic operation overflowed with checked overflow */
+    BOTTOM_OVERFLOW = 12,
+} BottomStatus;
+
+/* what a run left behind */
+typedef struct BottomResult {
+    BottomStatus status;
+    /* the final unstack, bottom first, NULL unless the status is BOTTOM_OK */
+    int64_t *values;
+    size_t len;
+    /* a nul-terminated description of what went wrong, NULL if the status is BOTTOM_OK */
+    char *error;
+    /* where the program stopped, if it failed with a runtime error */
+    size_t instruction_pointer;
+} BottomResult;
+
+/* parse source, recognizing the extension operations if extensions is set,
+ * NULL if source is NULL or not valid UTF-8 */
+BottomProgram *bottom_parse(const char *source, bool extensions);
+
+/* the number of operations in a parsed program, 0 for NULL */
+size_t bottom_program_len(const BottomProgram *program);
+
+/* free a program returned by bottom_parse, NULL is ignored */
+void bottom_program_free(BottomProgram *program);
+
+/* run program starting with the initial_len values at initial pushed in order,
+ * giving up after timeout_ms milliseconds unless it is 0 */
+BottomResult bottom_run(const BottomProgram *program, const int64_t *initial, size_t initial_len,
+                        uint64_t timeout_ms);
+
+/* free the values and error of a result returned by bottom_run, NULL is ignored */
+void bottom_result_free(BottomResult *result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,398 @@
+/*!
+ * # C bindings
+ *
+ * the interpreter as a shared library for programs not written in Rust, declared for C in
+ * `include/bottom.h`
+ *
+ * a program is parsed once with `bottom_parse` and can then be run any number of times with
+ * `bottom_run`. everything handed out has to be given back to the matching `_free` function.
+ * extension operations use the stdin, stdout and stderr of the embedding process
+ */
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::time::Duration;
+
+use bottom::ext::Host;
+use bottom::interpreter::interpret;
+use bottom::unstack::Unstack;
+use bottom::{parse, Limits, Operations, RuntimeError};
+
+/// a parsed program, opaque to C
+pub struct BottomProgram {
+    operations: Vec<Operations>,
+}
+
+/// how a run ended
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BottomStatus {
+    Ok = 0,
+    Timeout = 1,
+    Deadlock = 2,
+    AssertionFailed = 3,
+    /// the interpreter panicked
+    Panicked = 4,
+    /// a null pointer was passed
+    InvalidArgument = 5,
+    OutOfMemory = 6,
+    /// a 💾 or 💿 used an address past the memory limit
+    MemoryOutOfBounds = 7,
+    /// a 💖0 divided by zero
+    DivisionByZero = 8,
+    /// a 🫂 jumped outside of the program
+    JumpOutOfRange = 9,
+    /// an operation needed more values than the unstack had
+    Underflow = 10,
+    /// an operation could not do what it does, like a 📝 the host refused
+    Failed = 11,
+    /// an arithmetic operation overflowed with checked overflow
+    Overflow = 12,
+}
+
+/// what a run left behind
+#[repr(C)]
+pub struct BottomResult {
+    pub status: BottomStatus,
+    /// the final unstack, bottom first, null unless the status is `Ok`
+    pub values: *mut i64,
+    pub len: usize,
+    /// a nul-terminated description of what went wrong, null if the status is `Ok`
+    pub error: *mut c_char,
+    /// where the program stopped, if it failed with a runtime error
+    pub instruction_pointer: usize,
+}
+
+impl BottomResult {
+    fn failed(status: BottomStatus, message: &str, instruction_pointer: usize) -> Self {
+        // a message containing nul is cut off there rather than lost
+        let message = message.split('\0').next().unwrap_or_default();
+        BottomResult {
+            status,
+            values: ptr::null_mut(),
+            len: 0,
+            error: CString::new(message).map_or(ptr::null_mut(), CString::into_raw),
+            instruction_pointer,
+        }
+    }
+}
+
+/// parse `source`, recognizing the extension operations if `extensions` is set
+///
+/// returns null if `source` is null or not valid UTF-8
+///
+/// # Safety
+///
+/// `source` must be null or point to a nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn bottom_parse(
+    source: *const c_char,
+    extensions: bool,
+) -> *mut BottomProgram {
+    if source.is_null() {
+        return ptr::null_mut();
+    }
+    match CStr::from_ptr(source).to_str() {
+        Ok(source) => Box::into_raw(Box::new(BottomProgram {
+            operations: parse(source, extensions),
+        })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// the number of operations in a parsed program, 0 for null
+///
+/// # Safety
+///
+/// `program` must be null or returned by `bottom_parse` and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn bottom_program_len(program: *const BottomProgram) -> usize {
+    program
+        .as_ref()
+        .map_or(0, |program| program.operations.len())
+}
+
+/// free a program returned by `bottom_parse`, null is ignored
+///
+/// # Safety
+///
+/// `program` must be null or returned by `bottom_parse` and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn bottom_program_free(program: *mut BottomProgram) {
+    if !program.is_null() {
+        drop(Box::from_raw(program));
+    }
+}
+
+/// run `program` starting with the `initial_len` values at `initial` pushed in order,
+/// giving up after `timeout_ms` milliseconds unless it is 0
+///
+/// # Safety
+///
+/// `program` must be null or returned by `bottom_parse` and not yet freed, `initial` must be
+/// null or point to `initial_len` values
+#[no_mangle]
+pub unsafe extern "C" fn bottom_run(
+    program: *const BottomProgram,
+    initial: *const i64,
+    initial_len: usize,
+    timeout_ms: u64,
+) -> BottomResult {
+    let Some(program) = program.as_ref() else {
+        return BottomResult::failed(BottomStatus::InvalidArgument, "program is null", 0);
+    };
+    let initial = match (initial.is_null(), initial_len) {
+        (_, 0) => &[][..],
+        (true, _) => {
+            return BottomResult::failed(BottomStatus::InvalidArgument, "initial is null", 0)
+        }
+        (false, len) => slice::from_raw_parts(initial, len),
+    };
+    let unstack: Unstack = initial.iter().copied().collect();
+    let limits = Limits {
+        timeout: (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms)),
+        ..Limits::default()
+    };
+    let operations = program.operations.clone();
+    let mut host = Host::new();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        interpret(operations, unstack, &mut host, &limits)
+    }));
+    // whatever was written before a failure still reaches its file descriptor
+    let _ = host.flush();
+
+    match result {
+        Ok(Ok(values)) => {
+            let values = values.into_boxed_slice();
+            let len = values.len();
+            BottomResult {
+                status: BottomStatus::Ok,
+                values: Box::into_raw(values).cast(),
+                len,
+                error: ptr::null_mut(),
+                instruction_pointer: 0,
+            }
+        }
+        Ok(Err(err)) => {
+            let (status, instruction_pointer) = match err {
+                RuntimeError::Timeout {
+                    instruction_pointer,
+                    ..
+                } => (BottomStatus::Timeout, instruction_pointer),
+                RuntimeError::Deadlock {
+                    instruction_pointer,
+                } => (BottomStatus::Deadlock, instruction_pointer),
+                RuntimeError::OutOfMemory {
+                    instruction_pointer,
+                    ..
+                } => (BottomStatus::OutOfMemory, instruction_pointer),
+                RuntimeError::MemoryOutOfBounds {
+                    instruction_pointer,
+                    ..
+                } => (BottomStatus::MemoryOutOfBounds, instruction_pointer),
+                RuntimeError::DivisionByZero {
+                    instruction_pointer,
+                } => (BottomStatus::DivisionByZero, instruction_pointer),
+                RuntimeError::JumpOutOfRange {
+                    instruction_pointer,
+                } => (BottomStatus::JumpOutOfRange, instruction_pointer),
+                RuntimeError::AssertionFailed {
+                    instruction_pointer,
+                    ..
+                } => (BottomStatus::AssertionFailed, instruction_pointer),
+                RuntimeError::Underflow { ref fault, .. } => {
+                    (BottomStatus::Underflow, fault.instruction_pointer)
+                }
+                RuntimeError::Failed { ref fault, .. } => {
+                    (BottomStatus::Failed, fault.instruction_pointer)
+                }
+                RuntimeError::Overflow { ref fault, .. } => {
+                    (BottomStatus::Overflow, fault.instruction_pointer)
+                }
+            };
+            BottomResult::failed(status, &err.to_string(), instruction_pointer)
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "the program panicked".to_string());
+            BottomResult::failed(BottomStatus::Panicked, &message, 0)
+        }
+    }
+}
+
+/// free the values and error of a result returned by `bottom_run`, null is ignored
+///
+/// # Safety
+///
+/// `result` must be null or point to a result returned by `bottom_run` whose values and error
+/// were not freed yet, afterwards they are null
+#[no_mangle]
+pub unsafe extern "C" fn bottom_result_free(result: *mut BottomResult) {
+    let Some(result) = result.as_mut() else {
+        return;
+    };
+    if !result.values.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            result.values,
+            result.len,
+        )));
+        result.values = ptr::null_mut();
+        result.len = 0;
+    }
+    if !result.error.is_null() {
+        drop(CString::from_raw(result.error));
+        result.error = ptr::null_mut();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+    use std::mem;
+    use std::ptr;
+    use std::slice;
+
+    use crate::{
+        bottom_parse, bottom_program_free, bottom_program_len, bottom_result_free, bottom_run,
+        BottomResult, BottomStatus,
+    };
+
+    #[test]
+    fn test_run() {
+        let source = CString::new("🥺7 💓0 ✅1").unwrap();
+        unsafe {
+            let program = bottom_parse(source.as_ptr(), true);
+            assert_eq!(bottom_program_len(program), 3);
+
+            let mut result = bottom_run(program, [14, 2].as_ptr(), 2, 0);
+            assert_eq!(result.status, BottomStatus::Ok);
+            assert_eq!(
+                slice::from_raw_parts(result.values, result.len),
+                &[] as &[i64]
+            );
+            bottom_result_free(&mut result);
+
+            let mut result = bottom_run(program, [15, 2].as_ptr(), 2, 0);
+            assert_eq!(result.status, BottomStatus::AssertionFailed);
+            assert_eq!(result.instruction_pointer, 2);
+            let error = CStr::from_ptr(result.error).to_str().unwrap();
+            assert!(error.contains("assertion failed"), "{}", error);
+            bottom_result_free(&mut result);
+            assert!(result.error.is_null());
+
+            let mut result = bottom_run(program, ptr::null(), 0, 0);
+            assert_eq!(result.status, BottomStatus::Underflow);
+            bottom_result_free(&mut result);
+            bottom_program_free(program);
+
+            let result = bottom_run(ptr::null(), ptr::null(), 0, 0);
+            assert_eq!(result.status, BottomStatus::InvalidArgument);
+        }
+    }
+
+    /// every status, in the order of their discriminants
+    const STATUSES: [BottomStatus; 13] = [
+        BottomStatus::Ok,
+        BottomStatus::Timeout,
+        BottomStatus::Deadlock,
+        BottomStatus::AssertionFailed,
+        BottomStatus::Panicked,
+        BottomStatus::InvalidArgument,
+        BottomStatus::OutOfMemory,
+        BottomStatus::MemoryOutOfBounds,
+        BottomStatus::DivisionByZero,
+        BottomStatus::JumpOutOfRange,
+        BottomStatus::Underflow,
+        BottomStatus::Failed,
+        BottomStatus::Overflow,
+    ];
+
+    /// the name of `status` in C, `BOTTOM_` and its name in screaming snake case
+    fn c_name(status: BottomStatus) -> String {
+        // a new status does not compile here until it is added to STATUSES too
+        match status {
+            BottomStatus::Ok
+            | BottomStatus::Timeout
+            | BottomStatus::Deadlock
+            | BottomStatus::AssertionFailed
+            | BottomStatus::Panicked
+            | BottomStatus::InvalidArgument
+            | BottomStatus::OutOfMemory
+            | BottomStatus::MemoryOutOfBounds
+            | BottomStatus::DivisionByZero
+            | BottomStatus::JumpOutOfRange
+            | BottomStatus::Underflow
+            | BottomStatus::Failed
+            | BottomStatus::Overflow => {}
+        }
+        let mut name = "BOTTOM".to_string();
+        for ch in format!("{:?}", status).chars() {
+            if ch.is_ascii_uppercase() {
+                name.push('_');
+            }
+            name.push(ch.to_ascii_uppercase());
+        }
+        name
+    }
+
+    /// the lines of `header` between the line starting with `start` and the next one starting
+    /// with `}`, without comments
+    fn block<'a>(header: &'a str, start: &str) -> Vec<&'a str> {
+        header
+            .lines()
+            .skip_while(|line| !line.starts_with(start))
+            .skip(1)
+            .take_while(|line| !line.starts_with('}'))
+            .map(str::trim)
+            .filter(|line| !line.starts_with("/*"))
+            .collect()
+    }
+
+    #[test]
+    fn test_header() {
+        // the header is written by hand, so check it against the declarations here
+        let header = include_str!("../include/bottom.h");
+        let statuses: Vec<_> = STATUSES
+            .iter()
+            .enumerate()
+            .map(|(index, &status)| {
+                assert_eq!(status as usize, index, "{:?} is out of order", status);
+                format!("{} = {},", c_name(status), status as i32)
+            })
+            .collect();
+        assert_eq!(block(header, "typedef enum BottomStatus"), statuses);
+
+        // the fields of BottomResult in the order they are laid out in
+        let fields = [
+            (
+                mem::offset_of!(BottomResult, status),
+                "BottomStatus status;",
+            ),
+            (mem::offset_of!(BottomResult, values), "int64_t *values;"),
+            (mem::offset_of!(BottomResult, len), "size_t len;"),
+            (mem::offset_of!(BottomResult, error), "char *error;"),
+            (
+                mem::offset_of!(BottomResult, instruction_pointer),
+                "size_t instruction_pointer;",
+            ),
+        ];
+        assert!(fields.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        let fields: Vec<_> = fields.iter().map(|(_, field)| *field).collect();
+        assert_eq!(block(header, "typedef struct BottomResult"), fields);
+
+        for name in [
+            "bottom_parse",
+            "bottom_program_len",
+            "bottom_program_free",
+            "bottom_run",
+            "bottom_result_free",
+        ] {
+            assert!(header.contains(name), "{} is missing from bottom.h", name);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+/*!
+ * runs programs made of valid operations on random initial unstacks in a [`Sandbox`] and checks
+ * that the interpreter never panics and always stops within its fuel
+ *
+ * the operations get small arguments most of the time, so they find values on the unstack and
+ * get past the underflow checks, and a 🫂 jumps anywhere from before the start of the program to
+ * past its end
+ *
+ * ```sh
+ * cargo +nightly fuzz run interpret
+ * ```
+ *
+ * values wrap around when they overflow like in every release build, so without
+ * `--debug-assertions`
+ */
+
+#![no_main]
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use bottom::sandbox::{Failure, Sandbox};
+use bottom::unstack::Unstack;
+use bottom::{Operations, EXTENSIONS, OPERATIONS};
+use libfuzzer_sys::fuzz_target;
+
+/// instructions a program may execute
+const FUEL: usize = 10_000;
+
+#[derive(Debug)]
+struct Input {
+    program: Vec<Operations>,
+    /// the initial unstack, bottom first
+    unstack: Vec<i64>,
+}
+
+impl<'a> Arbitrary<'a> for Input {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let operations = OPERATIONS.len() + EXTENSIONS.len();
+        let length = u.int_in_range(0..=64)?;
+        let mut program = Vec::with_capacity(length);
+        for instruction_pointer in 0..length {
+            // one more than there are operations for a 🥺 with any value
+            let index = u.int_in_range(0..=operations)?;
+            let Some((emoji, make)) = OPERATIONS.iter().chain(EXTENSIONS).nth(index) else {
+                program.push(Operations::Push(u.arbitrary()?));
+                continue;
+            };
+            let argument = if *emoji == "🫂" {
+                u.int_in_range(0..=instruction_pointer as i64 + 3)?
+            } else if u.ratio(1, 16)? {
+                u.int_in_range(0..=i64::MAX)?
+            } else {
+                u.int_in_range(0..=8)?
+            };
+            program.push(make(argument));
+        }
+        let unstack = u.arbitrary_iter()?.take(32).collect::<Result<_>>()?;
+        Ok(Input { program, unstack })
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let sandbox = Sandbox {
+        fuel: FUEL,
+        max_values: 100_000,
+        ..Sandbox::default()
+    };
+    match sandbox.run(input.program, Unstack::from(input.unstack)) {
+        Err(Failure::Panicked(message)) => panic!("the interpreter panicked: {}", message),
+        Err(Failure::OutOfFuel { steps }) => assert!(steps <= FUEL, "ran {} instructions", steps),
+        _ => {}
+    }
+});
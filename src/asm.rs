@@ -0,0 +1,511 @@
+/*!
+ * # Assembler
+ *
+ * `bottom asm file.basm` turns a plain text mnemonic syntax into 🥺 source
+ *
+ * ```text
+ * ; print "hi" forever
+ *         PUSH 1
+ * loop:   PUSH 'h'
+ *         WRITE 1
+ *         PUSH 'i'
+ *         WRITE 1
+ *         DUP 1
+ *         HUG loop
+ * ```
+ *
+ * every line holds at most one instruction, optionally preceded by a `label:` and followed by a `; comment`.
+ * arguments are non-negative integers or character literals, 🫂 (`HUG`) also accepts a label to jump back to,
+ * 🦘 (`SKIP`) one to skip ahead to and 🛫 (`CALL`) the label of the routine. only `DEPTH`, `RET`, `BREAK`
+ * and `NOP`, which ignore their argument, may leave it out
+ *
+ * `bottom asm --labels` instead reads 🥺 source in the label dialect, where a `name:` at the start
+ * of a line labels the next operation and `🫂@name`, `🦘@name` or `🛫@name` jumps to it, and writes
+ * it with the labels replaced by counted arguments. `bottom --labels` runs such a program directly
+ *
+ * ```text
+ * 🥺8
+ * loop: 💖2 ✨1 🫂@loop
+ * ```
+ */
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::process;
+
+use bottom::ext::math;
+use bottom::{hug_target, parse_spanned, OperationEntry, Operations};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::source::read_source;
+
+/// mnemonics of all operations and how to build them from their argument
+pub const MNEMONICS: &[OperationEntry] = &[
+    ("PUSH", Operations::Push),
+    ("DIV", Operations::Pop),
+    ("SWAP", |n| Operations::Swap(n as usize)),
+    ("MUL", |n| Operations::Heart(n as usize)),
+    ("DUP", |n| Operations::Dup(n as usize)),
+    ("HUG", |n| Operations::Hug(n as usize)),
+    ("ENV", |n| Operations::Env(n as usize)),
+    ("READ", |n| Operations::Read(n as usize)),
+    ("WRITE", |n| Operations::Write(n as usize)),
+    ("PRINT", |n| Operations::Print(n as usize)),
+    ("SCAN", |n| Operations::Scan(n as usize)),
+    ("ACCEPT", |n| Operations::Accept(n as usize)),
+    ("SPAWN", |n| Operations::Spawn(n as usize)),
+    ("JOIN", |n| Operations::Join(n as usize)),
+    ("ASSERT", |n| Operations::Assert(n as usize)),
+    ("ROT", |n| Operations::Rotate(n as usize)),
+    ("REV", |n| Operations::Reverse(n as usize)),
+    ("CLEAR", |n| Operations::Clear(n as usize)),
+    ("DEPTH", |n| Operations::Depth(n as usize)),
+    ("SKIP", |n| Operations::Skip(n as usize)),
+    ("CALL", |n| Operations::Call(n as usize)),
+    ("RET", |n| Operations::Return(n as usize)),
+    ("STASH", |n| Operations::Stash(n as usize)),
+    ("UNSTASH", |n| Operations::Unstash(n as usize)),
+    ("STORE", |n| Operations::Store(n as usize)),
+    ("LOAD", |n| Operations::Load(n as usize)),
+    ("PEEK", |n| Operations::Peek(n as usize)),
+    ("BREAK", |n| Operations::Break(n as usize)),
+    ("NOP", |n| Operations::Nop(n as usize)),
+];
+
+/// the mnemonics whose argument is ignored, the only ones that may leave it out
+const IGNORED_ARGUMENT: &[&str] = &["DEPTH", "RET", "BREAK", "NOP"];
+
+/// the mnemonics of all operations including the [math intrinsics](bottom::ext::math)
+fn mnemonics() -> impl Iterator<Item = OperationEntry> {
+    MNEMONICS.iter().copied().chain(math::mnemonics())
+}
+
+/// the mnemonic of an operation
+pub fn mnemonic(operation: &Operations) -> &'static str {
+    mnemonics()
+        .find(|(_, make)| make(0).emoji() == operation.emoji())
+        .map(|(mnemonic, _)| mnemonic)
+        .expect("every operation has a mnemonic")
+}
+
+/// an error in the assembly source
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+    /// 1-based line number
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// the argument of an instruction before labels are resolved
+enum Argument {
+    Number(i64),
+    Label(String),
+}
+
+fn parse_argument(argument: &str) -> Result<Argument, String> {
+    let chars: Vec<_> = argument.chars().collect();
+    match chars[..] {
+        ['\'', ch, '\''] => return Ok(Argument::Number(ch as i64)),
+        ['\'', .., '\''] => {
+            return Err(format!(
+                "'{}' is not a character literal of one character",
+                argument
+            ))
+        }
+        ['\'', ..] => return Err(format!("unterminated character literal {}", argument)),
+        _ => {}
+    }
+    if argument.starts_with(|ch: char| ch.is_ascii_digit()) {
+        return argument
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| i64::try_from(n).ok())
+            .map(Argument::Number)
+            .ok_or_else(|| format!("'{}' is not a non-negative integer", argument));
+    }
+    if argument.starts_with('-') {
+        return Err(format!(
+            "negative arguments like '{}' can not be written in 🥺",
+            argument
+        ));
+    }
+    Ok(Argument::Label(argument.to_string()))
+}
+
+/// the argument that makes the jump `operation` at `instruction_pointer` continue at `target`,
+/// the instruction `label` is on
+fn jump_argument(
+    operation: Operations,
+    instruction_pointer: usize,
+    target: usize,
+    label: &str,
+) -> Result<usize, String> {
+    match operation {
+        Operations::Call(_) => Ok(target),
+        Operations::Skip(_) => target
+            .checked_sub(instruction_pointer + 1)
+            .ok_or_else(|| format!("🦘 can only skip ahead, '{}' is behind", label)),
+        _ => (instruction_pointer + 2)
+            .checked_sub(target)
+            .filter(|&n| n > 0 && hug_target(instruction_pointer, n) == Some(target))
+            .ok_or_else(|| format!("🫂 can only jump backwards, '{}' is ahead", label)),
+    }
+}
+
+/// the byte index of the first `delimiter` in `line` that is not inside a character literal
+fn unquoted(line: &str, delimiter: char) -> Option<usize> {
+    let mut chars = line.char_indices();
+    while let Some((index, ch)) = chars.next() {
+        if ch == delimiter {
+            return Some(index);
+        }
+        if ch == '\'' {
+            // skip the character of a literal and the quote closing it
+            let mut literal = chars.clone();
+            if let (Some(_), Some((_, '\''))) = (literal.next(), literal.next()) {
+                chars = literal;
+            }
+        }
+    }
+    None
+}
+
+/// assemble mnemonic source into operations
+pub fn assemble(source: &str) -> Result<Vec<Operations>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut instructions = vec![];
+    for (index, line) in source.lines().enumerate() {
+        let error = |message| AsmError {
+            line: index + 1,
+            message,
+        };
+        let mut line = line[..unquoted(line, ';').unwrap_or(line.len())].trim();
+        if let Some(colon) = unquoted(line, ':') {
+            let (label, rest) = (line[..colon].trim(), &line[colon + 1..]);
+            if label.is_empty() || label.contains(char::is_whitespace) {
+                return Err(error(format!("invalid label '{}'", label)));
+            }
+            if labels
+                .insert(label.to_string(), instructions.len())
+                .is_some()
+            {
+                return Err(error(format!("label '{}' is defined twice", label)));
+            }
+            line = rest.trim();
+        }
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            continue;
+        };
+        let make = mnemonics()
+            .find(|(mnemonic, _)| mnemonic.eq_ignore_ascii_case(name))
+            .map(|(_, make)| make)
+            .ok_or_else(|| error(format!("unknown mnemonic '{}'", name)))?;
+        let argument = match words.next() {
+            Some(argument) => parse_argument(argument).map_err(error)?,
+            None if IGNORED_ARGUMENT
+                .iter()
+                .any(|ignored| ignored.eq_ignore_ascii_case(name)) =>
+            {
+                Argument::Number(0)
+            }
+            None => return Err(error(format!("{} needs an argument", name))),
+        };
+        if let Some(extra) = words.next() {
+            return Err(error(format!("unexpected '{}' after the argument", extra)));
+        }
+        let jumps = ["HUG", "SKIP", "CALL"]
+            .iter()
+            .any(|jump| jump.eq_ignore_ascii_case(name));
+        if matches!(argument, Argument::Label(_)) && !jumps {
+            return Err(error(format!(
+                "only HUG, SKIP and CALL take a label, not {}",
+                name
+            )));
+        }
+        instructions.push((index + 1, make, argument));
+    }
+
+    let mut operations = vec![];
+    for (instruction_pointer, (line, make, argument)) in instructions.into_iter().enumerate() {
+        let value = match argument {
+            Argument::Number(value) => value,
+            Argument::Label(label) => {
+                let target = *labels.get(&label).ok_or_else(|| AsmError {
+                    line,
+                    message: format!("undefined label '{}'", label),
+                })?;
+                jump_argument(make(0), instruction_pointer, target, &label)
+                    .map_err(|message| AsmError { line, message })? as i64
+            }
+        };
+        operations.push(make(value));
+    }
+    Ok(operations)
+}
+
+fn is_label_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-')
+}
+
+/// turn 🥺 source of the label dialect into plain 🥺 source
+///
+/// a `name:` at the start of a line labels the operation after it, and a 🫂, 🦘 or 🛫 directly
+/// followed by `@name` jumps to that operation. an `@name` anywhere else is left alone as part
+/// of a comment. labels are replaced by the arguments they stand for, everything else stays
+pub fn resolve_labels(source: &str, extensions: bool) -> Result<String, AsmError> {
+    let line_of = |at: usize| source[..at].matches('\n').count() + 1;
+    let mut definitions = vec![];
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        let indent = line.len() - line.trim_start().len();
+        let rest = &line[indent..];
+        let length = rest.find(|ch| !is_label_char(ch)).unwrap_or(rest.len());
+        if rest.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_')
+            && rest[length..].starts_with(':')
+        {
+            let start = offset + indent;
+            definitions.push((start..start + length + 1, &rest[..length]));
+        }
+        offset += line.len();
+    }
+    let mut references = vec![];
+    for (at, _) in source.match_indices('@') {
+        let name = &source[at + 1..];
+        let length = name.find(|ch| !is_label_char(ch)).unwrap_or(name.len());
+        if length > 0 {
+            references.push((at..at + 1 + length, &name[..length]));
+        }
+    }
+
+    // labels are blanked out so they neither count as operations nor as arguments
+    let mut blanked = source.to_string();
+    for (range, _) in definitions.iter().chain(&references) {
+        blanked.replace_range(range.clone(), &" ".repeat(range.len()));
+    }
+    let tokens = parse_spanned(&blanked, extensions);
+
+    let mut labels = HashMap::new();
+    // what every label in the source is replaced by
+    let mut replacements = vec![];
+    for (range, name) in definitions {
+        let target = tokens
+            .iter()
+            .take_while(|(_, span)| span.start < range.start)
+            .count();
+        if labels.insert(name, target).is_some() {
+            return Err(AsmError {
+                line: line_of(range.start),
+                message: format!("label '{}' is defined twice", name),
+            });
+        }
+        replacements.push((range, String::new()));
+    }
+    for (range, name) in references {
+        let Some(instruction_pointer) = tokens.iter().position(|(operation, span)| {
+            span.end == range.start
+                && matches!(
+                    operation,
+                    Operations::Hug(_) | Operations::Skip(_) | Operations::Call(_)
+                )
+        }) else {
+            continue;
+        };
+        let error = |message| AsmError {
+            line: line_of(range.start),
+            message,
+        };
+        let (operation, span) = &tokens[instruction_pointer];
+        if span.len() != operation.emoji().len() {
+            return Err(error(format!(
+                "{} already has an argument and cannot also jump to '{}'",
+                operation, name
+            )));
+        }
+        let target = *labels
+            .get(name)
+            .ok_or_else(|| error(format!("undefined label '{}'", name)))?;
+        let n = jump_argument(*operation, instruction_pointer, target, name).map_err(error)?;
+        // the space keeps an operation right after the label from being read as the argument
+        replacements.push((range, format!("{} ", n)));
+    }
+
+    replacements.sort_by_key(|(range, _)| range.start);
+    let mut resolved = String::with_capacity(source.len());
+    let mut end = 0;
+    for (range, replacement) in replacements {
+        resolved += &source[end..range.start];
+        resolved += &replacement;
+        end = range.end;
+    }
+    resolved += &source[end..];
+    Ok(resolved)
+}
+
+/// write operations as 🥺 source, one per line
+pub fn to_source(operations: &[Operations]) -> String {
+    operations
+        .iter()
+        .map(|operation| format!("{}\n", operation))
+        .collect()
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("asm")
+        .about("assemble mnemonic source (PUSH 10, HUG loop, ...) into 🥺 source")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .value_name("FILE")
+                .help("assembly source to read"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("write the 🥺 source to FILE instead of stdout"),
+        )
+        .arg(
+            Arg::with_name("labels")
+                .long("labels")
+                .help("read 🥺 source with jump labels (loop: ... 🫂@loop) instead of assembly"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .requires("labels")
+                .help("recognize the extension operations in the 🥺 source"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let file = args.value_of("file").expect("required by clap");
+    let source = read_source(file).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", file, err);
+        process::exit(1);
+    });
+    let output = match args.is_present("labels") {
+        true => resolve_labels(&source, args.is_present("extensions")),
+        false => assemble(&source).map(|operations| to_source(&operations)),
+    };
+    let output = output.unwrap_or_else(|err| {
+        eprintln!("error: {}:{}", file, err);
+        process::exit(1);
+    });
+    match args.value_of("output") {
+        Some(path) => fs::write(path, output).unwrap_or_else(|err| {
+            eprintln!("error: could not write {}: {}", path, err);
+            process::exit(1);
+        }),
+        None => print!("{}", output),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::{assemble, resolve_labels, to_source, AsmError};
+    use bottom::ext::math::Intrinsic;
+    use bottom::parse;
+    use bottom::Operations::*;
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            ; count down from 3
+                    push 3
+            loop:   PUSH 1      ; decrement by multiplying and dividing
+                    DUP 2
+                    HUG loop
+                    WRITE 'a'
+        ";
+        let operations = assemble(source).unwrap();
+        assert_eq!(
+            operations,
+            vec![Push(3), Push(1), Dup(2), Hug(4), Write(97)]
+        );
+        assert_eq!(parse(&to_source(&operations), true), operations);
+
+        let skip = assemble("SKIP end\nPUSH 1\nPUSH 2\nend: PUSH 3").unwrap();
+        assert_eq!(skip, vec![Skip(2), Push(1), Push(2), Push(3)]);
+
+        let calls = assemble("CALL double\nRET\ndouble: DUP 1\nRET").unwrap();
+        assert_eq!(calls, vec![Call(2), Return(0), Dup(1), Return(0)]);
+
+        let intrinsics = assemble("POW 1\nmax 0\nxor 0\nSHR 2").unwrap();
+        assert_eq!(
+            intrinsics,
+            vec![
+                Math(Intrinsic(1), 1),
+                Math(Intrinsic(3), 0),
+                Math(Intrinsic(6), 0),
+                Math(Intrinsic(8), 2)
+            ]
+        );
+        assert_eq!(parse(&to_source(&intrinsics), true), intrinsics);
+
+        let punctuation = assemble("PUSH ';' ; a semicolon\nend: PUSH ':'\nPUSH '''").unwrap();
+        assert_eq!(punctuation, vec![Push(59), Push(58), Push(39)]);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert_eq!(assemble("NOPE 1").unwrap_err().line, 1);
+        assert!(assemble("PUSH -1").is_err());
+        assert!(assemble("HUG ahead\nPUSH 1\nahead: PUSH 1").is_err());
+        assert!(assemble("back: PUSH 1\nSKIP back").is_err());
+        assert!(assemble("PUSH label").is_err());
+        assert!(assemble("a: PUSH 1\na: PUSH 2").is_err());
+        assert_eq!(
+            assemble("PUSH 1\nPUSH").unwrap_err(),
+            AsmError {
+                line: 2,
+                message: "PUSH needs an argument".to_string()
+            }
+        );
+        assert_eq!(
+            assemble("PUSH '").unwrap_err().message,
+            "unterminated character literal '"
+        );
+        assert!(assemble("PUSH 'ab'").is_err());
+        assert_eq!(assemble("RET\nnop"), Ok(vec![Return(0), Nop(0)]));
+    }
+
+    #[test]
+    fn test_resolve_labels() {
+        let resolved = resolve_labels("🥺8\nloop: 💖2 ✨1 🫂@loop\n", false).unwrap();
+        assert_eq!(resolved, "🥺8\n 💖2 ✨1 🫂4 \n");
+
+        let source = "🥺3 🛫@double 🥺0 🦘@end\ndouble: ✨1 💓0 🛬0\nend: mail me@example.com";
+        assert_eq!(
+            parse(&resolve_labels(source, true).unwrap(), true),
+            vec![
+                Push(3),
+                Call(4),
+                Push(0),
+                Skip(3),
+                Dup(1),
+                Heart(0),
+                Return(0)
+            ]
+        );
+
+        assert_eq!(resolve_labels("🫂@nowhere", false).unwrap_err().line, 1);
+        assert_eq!(
+            resolve_labels("🫂@ahead 🥺1\nahead: 🥺1", false)
+                .unwrap_err()
+                .line,
+            1
+        );
+        assert!(resolve_labels("a: 🥺1\na: 🥺2", false).is_err());
+        assert!(resolve_labels("x: 🥺1 🫂2@x", false).is_err());
+    }
+}
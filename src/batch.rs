@@ -0,0 +1,278 @@
+/*!
+ * # Batch runner
+ *
+ * `bottom run-all DIR` interprets every `.🥺` file below a directory in parallel
+ * and prints a summary table of the results
+ */
+
+use std::any::Any;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bottom::ext::Host;
+use bottom::interpreter::interpret;
+use bottom::unstack::Unstack;
+use bottom::{parse, Limits, RuntimeError};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::parse_duration;
+use crate::source::read_source;
+
+/// the file extension of 🥺 programs
+pub const EXTENSION: &str = "🥺";
+
+/// how running a single program ended
+#[derive(Debug)]
+pub enum Outcome {
+    /// the program ran to completion and left this unstack
+    Finished(Vec<i64>),
+    /// the interpreter stopped with an error
+    Failed(RuntimeError),
+    /// the interpreter panicked with this message
+    Panicked(String),
+    /// the file could not be read
+    Unreadable(io::Error),
+}
+
+impl Outcome {
+    pub fn is_success(&self) -> bool {
+        matches!(self, Outcome::Finished(_))
+    }
+}
+
+/// the result of running one file of the batch
+#[derive(Debug)]
+pub struct BatchResult {
+    pub path: PathBuf,
+    pub duration: Duration,
+    pub outcome: Outcome,
+}
+
+/// find all `.🥺` files below `dir`, sorted by path
+pub fn discover(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = vec![];
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// the message a panic was started with
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// run `f` without printing the message of any panic to stderr, for when panics are reported another way
+pub fn without_panic_messages<T>(f: impl FnOnce() -> T) -> T {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = f();
+    panic::set_hook(hook);
+    result
+}
+
+/// run a single program with an empty unstack, turning panics into [`Outcome::Panicked`]
+pub fn run_file(path: &Path, extensions: bool, limits: &Limits) -> Outcome {
+    let source = match read_source(path) {
+        Ok(source) => source,
+        Err(err) => return Outcome::Unreadable(err),
+    };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let tokens = parse(&source, extensions);
+        interpret(tokens, Unstack::new(), &mut Host::new(), limits)
+    }));
+    match result {
+        Ok(Ok(output)) => Outcome::Finished(output),
+        Ok(Err(err)) => Outcome::Failed(err),
+        Err(payload) => Outcome::Panicked(panic_message(payload)),
+    }
+}
+
+/// run all `files` on `jobs` threads, results are in the same order as `files`
+///
+/// WASI has no threads, there all files run one after another on the calling thread
+#[cfg_attr(target_os = "wasi", allow(unused_variables))]
+pub fn run_all(
+    files: &[PathBuf],
+    extensions: bool,
+    limits: &Limits,
+    jobs: usize,
+) -> Vec<BatchResult> {
+    let run = |path: &PathBuf| {
+        let start = Instant::now();
+        let outcome = run_file(path, extensions, limits);
+        BatchResult {
+            path: path.clone(),
+            duration: start.elapsed(),
+            outcome,
+        }
+    };
+    #[cfg(not(target_os = "wasi"))]
+    if jobs > 1 {
+        use rayon::prelude::*;
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .expect("the threads can be spawned");
+        return pool.install(|| files.par_iter().map(run).collect());
+    }
+    files.iter().map(run).collect()
+}
+
+/// print one row per file and a final count of passed and failed programs
+pub fn print_summary(results: &[BatchResult], root: &Path) {
+    let rows: Vec<_> = results
+        .iter()
+        .map(|result| {
+            let path = result.path.strip_prefix(root).unwrap_or(&result.path);
+            let (status, detail) = match &result.outcome {
+                Outcome::Finished(output) => ("ok", format!("{:?}", output)),
+                Outcome::Failed(err) => ("error", err.to_string()),
+                Outcome::Panicked(message) => ("panic", message.clone()),
+                Outcome::Unreadable(err) => ("unreadable", err.to_string()),
+            };
+            (
+                path.display().to_string(),
+                status,
+                format!("{:.2?}", result.duration),
+                detail,
+            )
+        })
+        .collect();
+    let width = |column: fn(&(String, &str, String, String)) -> usize, title: &str| {
+        rows.iter().map(column).max().unwrap_or(0).max(title.len())
+    };
+    let file_width = width(|row| row.0.chars().count(), "file");
+    let status_width = width(|row| row.1.len(), "status");
+    let time_width = width(|row| row.2.len(), "time");
+
+    println!(
+        "{:file_width$}  {:status_width$}  {:>time_width$}  result",
+        "file", "status", "time"
+    );
+    for (path, status, time, detail) in &rows {
+        let detail: String = if detail.chars().count() > 60 {
+            detail.chars().take(59).chain(Some('…')).collect()
+        } else {
+            detail.clone()
+        };
+        println!(
+            "{:file_width$}  {:status_width$}  {:>time_width$}  {}",
+            path, status, time, detail
+        );
+    }
+    let passed = results.iter().filter(|r| r.outcome.is_success()).count();
+    println!(
+        "\n{} programs, {} passed, {} failed",
+        results.len(),
+        passed,
+        results.len() - passed
+    );
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("run-all")
+        .about("run every .🥺 file in a directory in parallel and summarize the results")
+        .arg(
+            Arg::with_name("dir")
+                .required(true)
+                .value_name("DIR")
+                .help("directory searched recursively for .🥺 files"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("enable the extension operations"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .value_name("DURATION")
+                .validator(|duration| parse_duration(&duration).map(|_| ()))
+                .help("abort each program after running for DURATION"),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .value_name("N")
+                .validator(|jobs| jobs.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+                .help("number of programs run at the same time, defaults to the number of cpus"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let root = Path::new(args.value_of("dir").expect("required by clap"));
+    let files = discover(root).unwrap_or_else(|err| {
+        eprintln!("error: could not search {}: {}", root.display(), err);
+        process::exit(1);
+    });
+    let mut limits = Limits::default();
+    if let Some(timeout) = args.value_of("timeout") {
+        limits.timeout = Some(parse_duration(timeout).expect("validated by clap"));
+    }
+    let jobs = match args.value_of("jobs") {
+        Some(jobs) => jobs.parse().expect("validated by clap"),
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+
+    // failures are reported in the summary instead of as they happen
+    let results =
+        without_panic_messages(|| run_all(&files, args.is_present("extensions"), &limits, jobs));
+
+    print_summary(&results, root);
+    if !results.iter().all(|result| result.outcome.is_success()) {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::batch::{discover, run_all, Outcome};
+    use bottom::{Limits, RuntimeError};
+
+    #[test]
+    fn test_run_all() {
+        let dir = std::env::temp_dir().join(format!("bottom-run-all-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("push.🥺"), "🥺42\n").unwrap();
+        fs::write(dir.join("nested/underflow.🥺"), "💖2\n").unwrap();
+        fs::write(dir.join("ignored.txt"), "🥺1\n").unwrap();
+
+        let files = discover(&dir).unwrap();
+        assert_eq!(files.len(), 2);
+        let results = run_all(&files, false, &Limits::default(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(
+            results[0].outcome,
+            Outcome::Failed(RuntimeError::Underflow { .. })
+        ));
+        assert!(matches!(&results[1].outcome, Outcome::Finished(out) if out == &[42]));
+    }
+}
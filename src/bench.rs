@@ -0,0 +1,379 @@
+/*!
+ * # Benchmarks
+ *
+ * `bottom bench PROGRAM...` runs every program, or every `.🥺` file below a directory, a few times
+ * and prints the median time of a run and the instructions it executed
+ *
+ * `--save-baseline NAME` keeps those numbers as JSON in `target/bottom-bench/NAME.json`, and
+ * `--baseline NAME` compares the run with them afterwards, failing if a program got slower or
+ * executes more instructions by more than `--threshold` percent, so both a slower interpreter and
+ * a program that does more work than it used to are caught:
+ *
+ * ```json
+ * {"programs":{"examples/fib.🥺":{"median_ns":81250,"steps":3074}}}
+ * ```
+ *
+ * programs read from an empty stdin and what they print is thrown away
+ */
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+use bottom::ext::Host;
+use bottom::interpreter::Interpreter;
+use bottom::unstack::Unstack;
+use bottom::{parse, Limits, RuntimeError};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::batch::discover;
+use crate::json::Json;
+use crate::source::read_source;
+
+/// where baselines are kept unless `--baseline-dir` says otherwise
+pub const BASELINE_DIR: &str = "target/bottom-bench";
+
+/// how a program performed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+    /// the median time of a run
+    pub median: Duration,
+    /// the instructions a run executed
+    pub steps: usize,
+}
+
+/// the measurements of all programs, by path
+pub type Baseline = BTreeMap<String, Measurement>;
+
+/// a program that got worse compared with the baseline
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    pub program: String,
+    /// what got worse, `time` or `steps`
+    pub what: &'static str,
+    /// how much worse it got in percent
+    pub percent: f64,
+}
+
+/// run `source` `runs` times and measure it
+pub fn measure(source: &str, extensions: bool, runs: usize) -> Result<Measurement, RuntimeError> {
+    let tokens = parse(source, extensions);
+    let mut times = Vec::with_capacity(runs);
+    let mut steps = 0;
+    for _ in 0..runs.max(1) {
+        let mut host = Host::captured(&[]);
+        let mut interpreter = Interpreter::new(tokens.clone(), Unstack::new(), &mut host);
+        let start = Instant::now();
+        interpreter.run(&Limits::default())?;
+        times.push(start.elapsed());
+        steps = interpreter.steps();
+    }
+    times.sort();
+    Ok(Measurement {
+        median: times[times.len() / 2],
+        steps,
+    })
+}
+
+/// the percentage by which `current` exceeds `baseline`
+fn increase(baseline: f64, current: f64) -> f64 {
+    if baseline > 0.0 {
+        (current / baseline - 1.0) * 100.0
+    } else if current > 0.0 {
+        f64::INFINITY
+    } else {
+        0.0
+    }
+}
+
+/// the programs in both `baseline` and `current` that got slower or execute more instructions by
+/// more than `threshold` percent
+pub fn compare(baseline: &Baseline, current: &Baseline, threshold: f64) -> Vec<Regression> {
+    let mut regressions = vec![];
+    for (program, now) in current {
+        let Some(before) = baseline.get(program) else {
+            continue;
+        };
+        let changes = [
+            (
+                "time",
+                increase(before.median.as_secs_f64(), now.median.as_secs_f64()),
+            ),
+            ("steps", increase(before.steps as f64, now.steps as f64)),
+        ];
+        for (what, percent) in changes {
+            if percent > threshold {
+                regressions.push(Regression {
+                    program: program.clone(),
+                    what,
+                    percent,
+                });
+            }
+        }
+    }
+    regressions
+}
+
+pub fn to_json(baseline: &Baseline) -> Json {
+    let programs = baseline
+        .iter()
+        .map(|(program, measurement)| {
+            let measurement = Json::Object(vec![
+                (
+                    "median_ns".to_string(),
+                    Json::Int(measurement.median.as_nanos() as i64),
+                ),
+                ("steps".to_string(), Json::Int(measurement.steps as i64)),
+            ]);
+            (program.clone(), measurement)
+        })
+        .collect();
+    Json::Object(vec![("programs".to_string(), Json::Object(programs))])
+}
+
+pub fn from_json(json: &Json) -> Result<Baseline, String> {
+    let Some(Json::Object(programs)) = json.get("programs") else {
+        return Err("missing `programs`".to_string());
+    };
+    programs
+        .iter()
+        .map(|(program, measurement)| {
+            let field = |key: &str| {
+                measurement
+                    .get(key)
+                    .and_then(Json::as_i64)
+                    .filter(|&value| value >= 0)
+                    .ok_or_else(|| format!("`{}` of {} is missing", key, program))
+            };
+            let measurement = Measurement {
+                median: Duration::from_nanos(field("median_ns")? as u64),
+                steps: field("steps")? as usize,
+            };
+            Ok((program.clone(), measurement))
+        })
+        .collect()
+}
+
+fn baseline_path(args: &ArgMatches, name: &str) -> PathBuf {
+    let dir = args.value_of("baseline-dir").unwrap_or(BASELINE_DIR);
+    Path::new(dir).join(format!("{}.json", name))
+}
+
+fn is_name(name: String) -> Result<(), String> {
+    let valid = |ch: char| ch.is_alphanumeric() || ch == '-' || ch == '_' || ch == '.';
+    match !name.is_empty() && !name.starts_with('.') && name.chars().all(valid) {
+        true => Ok(()),
+        false => Err("only letters, digits, `-`, `_` and `.` are allowed".to_string()),
+    }
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("bench")
+        .about("time programs and compare them with a saved baseline")
+        .arg(
+            Arg::with_name("programs")
+                .required(true)
+                .multiple(true)
+                .value_name("PROGRAM")
+                .help("programs to time, directories are searched for .🥺 files"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("enable the extension operations"),
+        )
+        .arg(
+            Arg::with_name("runs")
+                .long("runs")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("10")
+                .validator(|runs| match runs.parse::<usize>() {
+                    Ok(0) => Err("must be at least 1".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(err) => Err(err.to_string()),
+                })
+                .help("how often each program runs, the median time counts"),
+        )
+        .arg(
+            Arg::with_name("save-baseline")
+                .long("save-baseline")
+                .takes_value(true)
+                .value_name("NAME")
+                .validator(is_name)
+                .help("save the measurements as the baseline NAME"),
+        )
+        .arg(
+            Arg::with_name("baseline")
+                .long("baseline")
+                .takes_value(true)
+                .value_name("NAME")
+                .validator(is_name)
+                .help("fail if a program got worse than in the baseline NAME"),
+        )
+        .arg(
+            Arg::with_name("threshold")
+                .long("threshold")
+                .takes_value(true)
+                .value_name("PERCENT")
+                .default_value("10")
+                .validator(|percent| {
+                    percent
+                        .parse::<f64>()
+                        .map_err(|err| err.to_string())
+                        .and_then(|percent| match percent >= 0.0 {
+                            true => Ok(()),
+                            false => Err("must not be negative".to_string()),
+                        })
+                })
+                .help("how many percent worse than the baseline a program may get"),
+        )
+        .arg(
+            Arg::with_name("baseline-dir")
+                .long("baseline-dir")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("where baselines are kept, defaults to target/bottom-bench"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let runs = args
+        .value_of("runs")
+        .expect("has a default")
+        .parse()
+        .expect("validated by clap");
+    let threshold = args
+        .value_of("threshold")
+        .expect("has a default")
+        .parse()
+        .expect("validated by clap");
+    let baseline = args.value_of("baseline").map(|name| {
+        let path = baseline_path(args, name);
+        fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| text.parse::<Json>())
+            .and_then(|json| from_json(&json))
+            .unwrap_or_else(|err| {
+                eprintln!("error: could not read {}: {}", path.display(), err);
+                process::exit(1);
+            })
+    });
+
+    let mut files = vec![];
+    for program in args.values_of("programs").expect("required by clap") {
+        let path = Path::new(program);
+        if path.is_dir() {
+            files.extend(discover(path).unwrap_or_else(|err| {
+                eprintln!("error: could not search {}: {}", path.display(), err);
+                process::exit(1);
+            }));
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    let mut current = Baseline::new();
+    let mut failed = false;
+    for file in &files {
+        let name = file.display().to_string();
+        let measurement = read_source(file)
+            .map_err(|err| format!("could not read {}: {}", name, err))
+            .and_then(|source| {
+                measure(&source, args.is_present("extensions"), runs)
+                    .map_err(|err| format!("{} failed: {}", name, err))
+            });
+        let measurement = match measurement {
+            Ok(measurement) => measurement,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                failed = true;
+                continue;
+            }
+        };
+        let before = baseline.as_ref().and_then(|baseline| baseline.get(&name));
+        match before {
+            Some(before) => println!(
+                "{}  {:.2?} ({:+.1}%)  {} steps ({:+.1}%)",
+                name,
+                measurement.median,
+                increase(
+                    before.median.as_secs_f64(),
+                    measurement.median.as_secs_f64()
+                ),
+                measurement.steps,
+                increase(before.steps as f64, measurement.steps as f64)
+            ),
+            None => println!(
+                "{}  {:.2?}  {} steps",
+                name, measurement.median, measurement.steps
+            ),
+        }
+        current.insert(name, measurement);
+    }
+
+    if let Some(name) = args.value_of("save-baseline") {
+        let path = baseline_path(args, name);
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, format!("{}\n", to_json(&current))));
+        if let Err(err) = written {
+            eprintln!("error: could not write {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+    if let Some(baseline) = &baseline {
+        let regressions = compare(baseline, &current, threshold);
+        for regression in &regressions {
+            eprintln!(
+                "regression: {} {} up by {:.1}%, more than {}%",
+                regression.program, regression.what, regression.percent, threshold
+            );
+        }
+        failed |= !regressions.is_empty();
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::bench::{compare, from_json, measure, to_json, Baseline, Measurement};
+
+    #[test]
+    fn test_bench() {
+        let measurement = measure("🥺8 💖2 ✨1 🫂4", false, 3).unwrap();
+        assert_eq!(measurement.steps, 13);
+        assert!(measure("💖2", false, 3).is_err());
+
+        let at = |micros, steps| Measurement {
+            median: Duration::from_micros(micros),
+            steps,
+        };
+        let baseline = Baseline::from([
+            ("a.🥺".to_string(), at(100, 10)),
+            ("b.🥺".to_string(), at(100, 10)),
+        ]);
+        let json = to_json(&baseline).to_string().parse().unwrap();
+        assert_eq!(from_json(&json).unwrap(), baseline);
+
+        let current = Baseline::from([
+            ("a.🥺".to_string(), at(105, 10)),
+            ("b.🥺".to_string(), at(90, 12)),
+            ("new.🥺".to_string(), at(1000, 100)),
+        ]);
+        let regressions = compare(&baseline, &current, 10.0);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(
+            (regressions[0].program.as_str(), regressions[0].what),
+            ("b.🥺", "steps")
+        );
+        assert!(compare(&baseline, &current, 20.0).is_empty());
+    }
+}
@@ -0,0 +1,556 @@
+/*!
+ * # Brainfuck
+ *
+ * `bottom import-bf FILE.bf` compiles a Brainfuck program into a 🥺 program using the extension
+ * operations
+ *
+ * the tape is two unstacks: the cell under the head and every cell to the right of it are the
+ * main unstack, the current one at the bottom, and the cells to the left are on the auxiliary
+ * unstack 0. `>` 📥s the current cell and `<` 📤s it back, and the program starts by filling the
+ * main unstack with as many zeros as the tape has cells, 30000 unless `--tape` says otherwise
+ *
+ * 🥺 cannot add, so `+` and `-` look the cell up in a table in memory: for every amount a run of
+ * them changes a cell by, the program starts by storing the new value of every old one at
+ * 256 times the amount plus the old value, and 💿 with that offset then does the whole run at
+ * once. cells wrap around from 255 to 0 and `,` stores 0 at the end of the input
+ *
+ * [`to_bf`] goes the other way for `bottom compile --target bf`, see [compile](crate::compile)
+ */
+
+use std::collections::BTreeSet;
+use std::process;
+
+use bottom::ext::math;
+use bottom::{hug_target, skip_target, Operations};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::canonical::{canonicalize, ArgumentStyle};
+use crate::source::{read_source, write_output};
+
+/// the cells of the tape unless `--tape` says otherwise
+pub const TAPE: usize = 30_000;
+
+/// the values a cell can hold
+const CELL: i64 = 256;
+
+/// a Brainfuck instruction, with runs of the same one merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Instruction {
+    /// add to the current cell, modulo 256
+    Add(u8),
+    /// move the head, right if positive
+    Move(i64),
+    Output,
+    Input,
+    Open,
+    /// the index of the matching `[`
+    Close(usize),
+}
+
+/// the instructions of `source`, everything else is a comment
+fn instructions(source: &str) -> Result<Vec<Instruction>, String> {
+    let mut instructions: Vec<Instruction> = vec![];
+    let mut open = vec![];
+    for (offset, character) in source.char_indices() {
+        let instruction = match character {
+            '+' | '-' => {
+                let amount = if character == '+' { 1 } else { 255 };
+                if let Some(Instruction::Add(before)) = instructions.last_mut() {
+                    *before = before.wrapping_add(amount);
+                    continue;
+                }
+                Instruction::Add(amount)
+            }
+            '>' | '<' => {
+                let amount = if character == '>' { 1 } else { -1 };
+                if let Some(Instruction::Move(before)) = instructions.last_mut() {
+                    *before += amount;
+                    continue;
+                }
+                Instruction::Move(amount)
+            }
+            '.' => Instruction::Output,
+            ',' => Instruction::Input,
+            '[' => {
+                open.push((instructions.len(), offset));
+                Instruction::Open
+            }
+            ']' => {
+                let (start, _) = open
+                    .pop()
+                    .ok_or_else(|| format!("`]` at byte {} closes nothing", offset))?;
+                Instruction::Close(start)
+            }
+            _ => continue,
+        };
+        instructions.push(instruction);
+    }
+    if let Some((_, offset)) = open.pop() {
+        return Err(format!("`[` at byte {} is never closed", offset));
+    }
+    Ok(instructions)
+}
+
+/// compile the Brainfuck program `source` for a tape of `tape` cells
+pub fn compile(source: &str, tape: usize) -> Result<Vec<Operations>, String> {
+    let instructions = instructions(source)?;
+    let amounts: BTreeSet<u8> = instructions
+        .iter()
+        .filter_map(|instruction| match *instruction {
+            Instruction::Add(amount) if amount != 0 => Some(amount),
+            _ => None,
+        })
+        .collect();
+
+    let mut operations = vec![];
+    for &amount in &amounts {
+        for old in 0..CELL {
+            let new = (old + i64::from(amount)) % CELL;
+            // nothing stored reads as 0
+            if new != 0 {
+                operations.extend([
+                    Operations::Push(new),
+                    Operations::Push(CELL * i64::from(amount) + old),
+                    Operations::Store(0),
+                ]);
+            }
+        }
+    }
+    // push zeros as long as there are fewer than `tape` values on the unstack
+    let start = operations.len();
+    operations.extend([
+        Operations::Push(0),
+        Operations::Depth(0),
+        Operations::Push(tape as i64),
+        // whether the depth is less than the size of the tape
+        intrinsic("LT"),
+    ]);
+    operations.push(Operations::Hug(operations.len() + 2 - start));
+
+    // where the code of every instruction starts
+    let mut starts = Vec::with_capacity(instructions.len());
+    for &instruction in &instructions {
+        starts.push(operations.len());
+        match instruction {
+            Instruction::Add(0) | Instruction::Move(0) => {}
+            Instruction::Add(amount) => {
+                operations.push(Operations::Load(CELL as usize * amount as usize))
+            }
+            Instruction::Move(cells) if cells > 0 => {
+                operations.extend((0..cells).map(|_| Operations::Stash(0)))
+            }
+            Instruction::Move(cells) => {
+                operations.extend((0..-cells).map(|_| Operations::Unstash(0)))
+            }
+            Instruction::Output => operations.extend([Operations::Dup(1), Operations::Write(1)]),
+            Instruction::Input => operations.extend([
+                Operations::Clear(1),
+                Operations::Read(0),
+                Operations::Push(0),
+                intrinsic("MAX"),
+            ]),
+            // the jumps are filled in at the `]`
+            Instruction::Open => operations.extend([Operations::Dup(1), Operations::Skip(0)]),
+            Instruction::Close(open) => {
+                // the 🦘 skips to after the 🫂, which jumps back to after the 🦘
+                let skip = starts[open] + 1;
+                let hug = operations.len() + 1;
+                operations[skip] = Operations::Skip(hug - skip);
+                operations.extend([Operations::Dup(1), Operations::Hug(hug + 1 - skip)]);
+            }
+        }
+    }
+    // the tape is no result
+    operations.push(Operations::Clear(0));
+    Ok(operations)
+}
+
+/// the intrinsic called `mnemonic` with argument 0
+fn intrinsic(mnemonic: &str) -> Operations {
+    let entry = math::TABLE
+        .iter()
+        .find(|entry| entry.mnemonic == mnemonic)
+        .expect("an intrinsic of the table");
+    (entry.make)(0)
+}
+
+/// Brainfuck code working on the unstack laid out on the tape, the values left to right with the
+/// one at the bottom of the unstack under the head and only zeros to the right of it
+#[derive(Default)]
+struct Emitter {
+    code: String,
+    /// where the head is, relative to the bottom of the unstack
+    head: i64,
+}
+
+impl Emitter {
+    fn go(&mut self, cell: i64) {
+        let step = if cell > self.head { '>' } else { '<' };
+        self.code
+            .extend(std::iter::repeat_n(step, cell.abs_diff(self.head) as usize));
+        self.head = cell;
+    }
+
+    fn add(&mut self, cell: i64, amount: u8) {
+        self.go(cell);
+        match amount {
+            0..=128 => self.code.extend(std::iter::repeat_n('+', amount as usize)),
+            _ => self
+                .code
+                .extend(std::iter::repeat_n('-', 256 - amount as usize)),
+        }
+    }
+
+    fn clear(&mut self, cell: i64) {
+        self.go(cell);
+        self.code += "[-]";
+    }
+
+    /// add `from` to every cell of `to`, leaving 0 in `from`
+    fn shift(&mut self, from: i64, to: &[i64]) {
+        self.go(from);
+        self.code += "[-";
+        for &cell in to {
+            self.go(cell);
+            self.code.push('+');
+        }
+        self.go(from);
+        self.code.push(']');
+    }
+
+    /// emit `operation` with the bottom of the unstack under the head before and after it, false
+    /// if Brainfuck cannot do it
+    fn operation(&mut self, operation: Operations) -> bool {
+        match operation {
+            Operations::Push(value) if (0..CELL).contains(&value) => self.add(1, value as u8),
+            Operations::Dup(count) => {
+                let count = count as i64;
+                let spare = count + 1;
+                // every value moves to twice as far from the deepest one, so the top one goes first
+                for index in (0..count).rev() {
+                    let first = 1 - count + 2 * index;
+                    self.shift(1 - count + index, &[spare]);
+                    self.shift(spare, &[first, first + 1]);
+                }
+                self.go(count);
+            }
+            Operations::Swap(0) => {}
+            Operations::Swap(steps) => {
+                let other = -(steps as i64);
+                self.shift(0, &[1]);
+                self.shift(other, &[0]);
+                self.shift(1, &[other]);
+                self.go(0);
+            }
+            Operations::Heart(discarded) => {
+                // the product goes to 1, and 2 keeps the value at -1 while it is added
+                self.go(0);
+                self.code += "[-";
+                self.shift(-1, &[1, 2]);
+                self.shift(2, &[-1]);
+                self.go(0);
+                self.code.push(']');
+                for cell in 1..=discarded as i64 + 1 {
+                    self.clear(-cell);
+                }
+                let result = -(discarded as i64) - 1;
+                self.shift(1, &[result]);
+                self.go(result);
+            }
+            Operations::Clear(count) if count > 0 => {
+                for cell in 0..count as i64 {
+                    self.clear(-cell);
+                }
+                self.go(-(count as i64));
+            }
+            Operations::Read(0) => {
+                self.go(1);
+                self.code.push(',');
+            }
+            Operations::Write(1) => {
+                self.go(0);
+                self.code.push('.');
+                self.clear(0);
+                self.go(-1);
+            }
+            Operations::Nop(_) | Operations::Break(_) | Operations::Pop(1) => {}
+            _ => return false,
+        }
+        // the code of the next operation starts from the new bottom
+        self.head = 0;
+        true
+    }
+}
+
+/// a loop or a conditional block and the instructions it covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Block {
+    first: usize,
+    last: usize,
+    /// a 🫂 loop, otherwise the instructions a 🦘 skips
+    looping: bool,
+}
+
+impl Block {
+    /// the 🫂 or 🦘
+    fn jump(&self) -> usize {
+        match self.looping {
+            true => self.last,
+            false => self.first - 1,
+        }
+    }
+
+    /// where the block starts and ends in the code, in thirds of an instruction: a loop starts
+    /// before its first instruction and ends at its 🫂, a conditional block starts at its 🦘 and
+    /// ends after its last instruction
+    fn bounds(&self) -> (usize, usize) {
+        match self.looping {
+            true => (3 * self.first, 3 * self.last + 1),
+            false => (3 * self.jump() + 1, 3 * self.last + 2),
+        }
+    }
+}
+
+/// the blocks of `operations`, ordered by where they start and the outer one first
+fn blocks(operations: &[Operations]) -> Result<Vec<Block>, String> {
+    let mut blocks = vec![];
+    for (instruction_pointer, &operation) in operations.iter().enumerate() {
+        match operation {
+            Operations::Hug(n) => match hug_target(instruction_pointer, n) {
+                Some(first) if first <= instruction_pointer => blocks.push(Block {
+                    first,
+                    last: instruction_pointer,
+                    looping: true,
+                }),
+                _ => {
+                    return Err(format!(
+                        "{} at {} does not jump back",
+                        operation, instruction_pointer
+                    ))
+                }
+            },
+            Operations::Skip(0) => {}
+            Operations::Skip(n) if skip_target(instruction_pointer, n) <= operations.len() => {
+                blocks.push(Block {
+                    first: instruction_pointer + 1,
+                    last: instruction_pointer + n,
+                    looping: false,
+                })
+            }
+            Operations::Skip(_) => {
+                return Err(format!(
+                    "{} at {} skips past the end",
+                    operation, instruction_pointer
+                ))
+            }
+            _ => {}
+        }
+    }
+    blocks.sort_by_key(|block| {
+        let (start, end) = block.bounds();
+        (start, usize::MAX - end)
+    });
+    let mut open: Vec<Block> = vec![];
+    for block in &blocks {
+        let (start, end) = block.bounds();
+        while open.last().is_some_and(|outer| outer.bounds().1 < start) {
+            open.pop();
+        }
+        if let Some(outer) = open.last().filter(|outer| outer.bounds().1 < end) {
+            return Err(format!(
+                "the jumps at {} and {} cross, Brainfuck can only nest them",
+                outer.jump(),
+                block.jump()
+            ));
+        }
+        open.push(*block);
+    }
+    Ok(blocks)
+}
+
+/// how many values `operations` leave behind in total, `None` if that depends on the values
+fn net_effect(operations: &[Operations]) -> Option<i64> {
+    operations.iter().try_fold(0, |total, operation| {
+        let (needs, leaves) = operation.stack_effect()?;
+        Some(total + leaves as i64 - needs as i64)
+    })
+}
+
+/// compile `operations` into Brainfuck, as long as they only use operations a tape can do
+///
+/// the program runs as if it started with an empty unstack and every value is a byte
+pub fn to_bf(operations: &[Operations]) -> Result<String, String> {
+    let blocks = blocks(operations)?;
+    for block in blocks.iter().filter(|block| !block.looping) {
+        // both ways through a 🦘 have to leave the head over the same cell
+        let body = &operations[block.first..=block.last];
+        let balanced = net_effect(body) == Some(0)
+            && blocks.iter().all(|inner| {
+                !inner.looping
+                    || !(block.first..=block.last).contains(&inner.first)
+                    || net_effect(&operations[inner.first..=inner.last]) == Some(0)
+            });
+        if !balanced {
+            return Err(format!(
+                "the instructions {} to {} skipped by a 🦘 change how many values there are",
+                block.first, block.last
+            ));
+        }
+    }
+
+    let mut emitter = Emitter::default();
+    for (instruction_pointer, &operation) in operations.iter().enumerate() {
+        for _ in blocks
+            .iter()
+            .filter(|block| block.looping && block.first == instruction_pointer)
+        {
+            // a 🫂 loop runs once before it looks at a value, so it starts with a 1
+            emitter.code += ">+[[-]<";
+        }
+        match operation {
+            Operations::Hug(_) => emitter.code += "]<",
+            Operations::Skip(0) => emitter.code += "[-]<",
+            Operations::Skip(_) => emitter.code += "[[-]<",
+            operation => {
+                if !emitter.operation(operation) {
+                    return Err(format!(
+                        "{} at {} has no Brainfuck equivalent",
+                        operation, instruction_pointer
+                    ));
+                }
+            }
+        }
+        // the blocks ending here close inner first
+        for _ in blocks
+            .iter()
+            .rev()
+            .filter(|block| !block.looping && block.last == instruction_pointer)
+        {
+            // the cell right of the bottom is always 0, which leaves the block
+            emitter.code += ">]<";
+        }
+    }
+    Ok(emitter.code)
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("import-bf")
+        .about("compile a Brainfuck program into 🥺, run it with --extensions")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .value_name("FILE")
+                .help("Brainfuck program to read"),
+        )
+        .arg(
+            Arg::with_name("tape")
+                .long("tape")
+                .takes_value(true)
+                .value_name("CELLS")
+                .validator(|cells| {
+                    cells
+                        .parse::<usize>()
+                        .map(|_| ())
+                        .map_err(|err| err.to_string())
+                })
+                .help("the number of cells on the tape, 30000 by default"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("write the program to FILE instead of stdout"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let file = args.value_of("file").expect("required by clap");
+    let source = read_source(file).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", file, err);
+        process::exit(1);
+    });
+    let tape = args
+        .value_of("tape")
+        .map_or(TAPE, |cells| cells.parse().expect("validated by clap"));
+    let operations = compile(&source, tape).unwrap_or_else(|err| {
+        eprintln!("error: {}: {}", file, err);
+        process::exit(1);
+    });
+    write_output(
+        args.value_of("output"),
+        &canonicalize(&operations, ArgumentStyle::Numeric),
+        false,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use bottom::ext::Host;
+    use bottom::interpreter::interpret;
+    use bottom::unstack::Unstack;
+    use bottom::{parse, Limits};
+
+    use crate::bf::{compile, to_bf};
+
+    /// what the Brainfuck program `source` prints when reading `input`
+    fn run(source: &str, input: &[u8]) -> Vec<u8> {
+        let mut host = Host::captured(input);
+        let operations = compile(source, 100).unwrap();
+        let output = interpret(operations, Unstack::new(), &mut host, &Limits::default());
+        assert_eq!(output, Ok(vec![]));
+        host.captured_output(1)
+    }
+
+    #[test]
+    fn test_bf() {
+        // 8 times 8 plus 1
+        assert_eq!(run("++++++++[>++++++++<-]>+.", b""), b"A");
+        // cells wrap around
+        assert_eq!(run("-.+.", b""), [255, 0]);
+        // echoes its input until the end, where , reads 0
+        assert_eq!(run(",[.,]", b"hi!"), b"hi!");
+        assert_eq!(
+            run(
+                "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.",
+                b""
+            ),
+            b"Hello"
+        );
+        assert!(compile("[", 10).is_err());
+        assert!(compile("]", 10).is_err());
+    }
+
+    #[test]
+    fn test_to_bf() {
+        let run_compiled =
+            |program: &str, input: &[u8]| run(&to_bf(&parse(program, true)).unwrap(), input);
+        assert_eq!(run_compiled("🥺72 📝1 🥺105 📝1", b""), b"Hi");
+        // 6 times 11, and 11 times 9 with the 7 below thrown away
+        assert_eq!(
+            run_compiled("🥺6 🥺11 💓0 📝1 🥺7 🥺9 🥺11 💓1 📝1", b""),
+            b"Bc"
+        );
+        assert_eq!(
+            run_compiled(
+                "🥺65 🥺66 ✨2 👉👈3 📝1 📝1 📝1 📝1 🥺67 🥺1 🥺2 🧹2 📝1",
+                b""
+            ),
+            b"ABABC"
+        );
+        // echoes its input up to and including a 0
+        assert_eq!(run_compiled("📖0 ✨1 📝1 🫂5", b"hi\0rest"), b"hi\0");
+        // the same loop inside a 🦘 that does not skip it
+        assert_eq!(run_compiled("🥺1 🦘4 📖0 ✨1 📝1 🫂5", b"ok\0"), b"ok\0");
+        assert_eq!(run_compiled("🥺1 🦘2 🥺66 📝1 🥺0 🦘2 🥺67 📝1", b""), b"B");
+
+        let fails = |program: &str| to_bf(&parse(program, true)).is_err();
+        assert!(fails("🥺8 💖2"));
+        assert!(fails("🥺256"));
+        // a 🦘 leaving a value behind on one way only
+        assert!(fails("🥺0 🦘1 🥺5"));
+        // a loop ending inside a block it does not start in
+        assert!(fails("🥺1 🥺1 🦘2 🥺1 🫂4"));
+    }
+}
@@ -0,0 +1,100 @@
+/*!
+ * # Building native programs
+ *
+ * `bottom build FILE -o PROGRAM` compiles a 🥺 program ahead of time into an executable that runs
+ * without `bottom` installed. it [lowers](crate::llvm) the program to LLVM IR, optimizes it with
+ * `opt`, and hands it to `llc` and `cc`, so it needs the `llvm` feature and those tools on the
+ * `PATH`
+ *
+ * the executable prints what is left on the unstack like `bottom` does and fails with exit code 1
+ * and the error of the interpreter, without its line and column
+ */
+
+use std::path::Path;
+use std::process;
+
+use bottom::{parse, Operations, Semantics};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::source::read_source;
+use crate::{division_args, division_semantics};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("build")
+        .about("compile a 🥺 program into a native executable")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .value_name("FILE")
+                .help("🥺 program to read"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .value_name("PROGRAM")
+                .help("write the executable to PROGRAM instead of FILE without its extension"),
+        )
+        .arg(
+            Arg::with_name("opt-level")
+                .long("opt-level")
+                .takes_value(true)
+                .value_name("LEVEL")
+                .possible_values(&["0", "1", "2", "3"])
+                .default_value("2")
+                .help("optimize the IR and the machine code at -OLEVEL"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("recognize the extension operations"),
+        )
+        .args(&division_args())
+}
+
+pub fn main(args: &ArgMatches) {
+    let file = args.value_of("file").expect("required by clap");
+    let source = read_source(file).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", file, err);
+        process::exit(1);
+    });
+    let operations = parse(&source, args.is_present("extensions"));
+    let level = args
+        .value_of("opt-level")
+        .expect("has a default")
+        .parse()
+        .expect("validated by clap");
+    let output = match args.value_of("output") {
+        Some(output) => Path::new(output).to_path_buf(),
+        None => Path::new(file).with_extension(""),
+    };
+    if output == Path::new(file) {
+        eprintln!("error: the executable would overwrite {}, pass -o", file);
+        process::exit(1);
+    }
+    build(&operations, division_semantics(args), level, &output).unwrap_or_else(|err| {
+        eprintln!("error: {} cannot be built: {}", file, err);
+        process::exit(1);
+    });
+}
+
+#[cfg(feature = "llvm")]
+fn build(
+    operations: &[Operations],
+    semantics: Semantics,
+    level: u8,
+    output: &Path,
+) -> Result<(), String> {
+    let ir = crate::llvm::lower(operations, semantics)?;
+    let ir = match level {
+        0 => ir,
+        level => crate::llvm::optimize(&ir, level)?,
+    };
+    crate::llvm::link(&ir, level, output)
+}
+
+#[cfg(not(feature = "llvm"))]
+fn build(_: &[Operations], _: Semantics, _: u8, _: &Path) -> Result<(), String> {
+    Err("building executables needs the llvm feature".to_string())
+}
@@ -0,0 +1,516 @@
+/*!
+ * # Bytecode
+ *
+ * a faster way to run programs that need none of what the [`Interpreter`](crate::interpreter::Interpreter)
+ * offers besides running them: [`compile`] resolves every 🫂 and 🦘 to the instruction it jumps to
+ * and fuses pairs of operations that come up all the time into a single instruction, [`run`] executes
+ * the result in one loop without tasks, observers or breakpoints. every instruction is compiled
+ * to the function executing it, so the loop only calls the function of the next instruction
+ * instead of matching on it
+ *
+ * the fused pairs are
+ *
+ * | pair        | instruction                                  |
+ * | ----------- | -------------------------------------------- |
+ * | `🥺v 💖d`   | pushes `v / d`                               |
+ * | `🥺v 💓0`   | multiplies the bottom value by `v`            |
+ * | `🥺v 🫂N`   | always jumps if `v` is not 0, else nothing    |
+ * | `✨1 🫂N`   | jumps if the bottom value is not 0, keeps it |
+ *
+ * a pair is never fused if something jumps to its second operation. failures are the same
+ * errors as in the interpreter, naming the instruction pointer of the original operation.
+ * operations always have the default [`Semantics`]
+ */
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use crate::interpreter::{execute, Io};
+use crate::unstack::Unstack;
+use crate::{hug_target, skip_target, Limits, Operations, RuntimeError, Semantics};
+
+/// a compiled instruction, jumps go to the index of an instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    /// an operation that is executed just like in the interpreter
+    Single(Operations),
+    /// a 🫂 jumping to this instruction
+    Hug(usize),
+    /// a 🦘 skipping to this instruction
+    Skip(usize),
+    /// `🥺v 💓0`
+    Multiply(i64),
+    /// `🥺v 🫂N` with `v` not 0
+    Jump(usize),
+    /// `✨1 🫂N`
+    DupHug(usize),
+}
+
+/// a compiled program
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub code: Vec<Instruction>,
+    /// the instruction pointer of the operation each instruction was compiled from, the first
+    /// one of a pair
+    pub origins: Vec<usize>,
+    /// the operation each instruction was compiled from, the first one of a pair, to fail with
+    /// it like the interpreter would
+    pub(crate) operations: Vec<Operations>,
+    /// the handler of every instruction
+    threaded: Vec<Threaded>,
+}
+
+/// compile `tokens`, fusing pairs of operations if `fuse` is set
+///
+/// `None` if the program has [operations only the interpreter can execute](Operations::needs_interpreter)
+/// or a 🫂 that jumps before the start
+pub fn compile(tokens: &[Operations], fuse: bool) -> Option<Program> {
+    let mut targets = BTreeSet::new();
+    for (instruction_pointer, operation) in tokens.iter().enumerate() {
+        match *operation {
+            Operations::Hug(n) if n > 0 => {
+                targets.insert(hug_target(instruction_pointer, n)?);
+            }
+            Operations::Skip(n) => {
+                targets.insert(skip_target(instruction_pointer, n).min(tokens.len()));
+            }
+            Operations::Hug(_) => return None,
+            operation if operation.needs_interpreter() => return None,
+            _ => {}
+        }
+    }
+
+    let mut code = Vec::with_capacity(tokens.len());
+    let mut origins = Vec::with_capacity(tokens.len());
+    let mut operations = Vec::with_capacity(tokens.len());
+    // the instruction each operation ended up in, jumps are resolved with it at the end
+    let mut compiled = alloc::vec![0; tokens.len() + 1];
+    let mut index = 0;
+    while index < tokens.len() {
+        compiled[index] = code.len();
+        let next = tokens
+            .get(index + 1)
+            .filter(|_| fuse && !targets.contains(&(index + 1)));
+        let target = |hug: usize, n: usize| hug_target(hug, n).expect("checked above");
+        let (consumed, instruction) = match (tokens[index], next) {
+            (Operations::Push(value), Some(&Operations::Pop(divisor))) => {
+                match value.checked_div(divisor) {
+                    Some(quotient) => (2, Some(Instruction::Single(Operations::Push(quotient)))),
+                    // dividing by 0 fails where it happens
+                    None => (1, Some(Instruction::Single(tokens[index]))),
+                }
+            }
+            (Operations::Push(value), Some(&Operations::Heart(0))) => {
+                (2, Some(Instruction::Multiply(value)))
+            }
+            (Operations::Push(0), Some(&Operations::Hug(_))) => (2, None),
+            (Operations::Push(_), Some(&Operations::Hug(n))) => {
+                (2, Some(Instruction::Jump(target(index + 1, n))))
+            }
+            (Operations::Dup(1), Some(&Operations::Hug(n))) => {
+                (2, Some(Instruction::DupHug(target(index + 1, n))))
+            }
+            (Operations::Hug(n), _) => (1, Some(Instruction::Hug(target(index, n)))),
+            (Operations::Skip(n), _) => (
+                1,
+                Some(Instruction::Skip(skip_target(index, n).min(tokens.len()))),
+            ),
+            (operation, _) => (1, Some(Instruction::Single(operation))),
+        };
+        if let Some(instruction) = instruction {
+            code.push(instruction);
+            origins.push(index);
+            operations.push(tokens[index]);
+        }
+        index += consumed;
+    }
+    compiled[tokens.len()] = code.len();
+
+    for instruction in &mut code {
+        if let Instruction::Hug(target)
+        | Instruction::Skip(target)
+        | Instruction::Jump(target)
+        | Instruction::DupHug(target) = instruction
+        {
+            *target = compiled[*target];
+        }
+    }
+    let threaded = code
+        .iter()
+        .map(|instruction| instruction.threaded())
+        .collect();
+    Some(Program {
+        code,
+        origins,
+        operations,
+        threaded,
+    })
+}
+
+/// what a running program is made of besides the instruction it is at
+pub(crate) struct Machine<'a> {
+    pub(crate) program: &'a Program,
+    pub(crate) unstack: Unstack,
+    pub(crate) host: &'a mut dyn Io,
+}
+
+impl Machine<'_> {
+    /// execute `operation` like the interpreter, failing at the instruction pointer `origin`
+    pub(crate) fn execute(
+        &mut self,
+        operation: Operations,
+        origin: usize,
+    ) -> Result<(), RuntimeError> {
+        let mut instruction_pointer = origin;
+        execute(
+            operation,
+            &mut self.unstack,
+            &mut instruction_pointer,
+            &mut *self.host,
+            &Semantics::default(),
+        )
+    }
+
+    /// execute the operation the instruction at `index` was compiled from, for it to fail
+    pub(crate) fn fail(&mut self, index: usize) -> Result<(), RuntimeError> {
+        self.execute(self.program.operations[index], self.program.origins[index])
+    }
+}
+
+/// executes the instruction at an index with its argument and returns the index of the next one
+type Handler = fn(&mut Machine, usize, i64) -> Result<usize, RuntimeError>;
+
+/// an instruction as the handler that executes it, so [`run`] needs no `match` to dispatch
+#[derive(Debug, Clone, Copy)]
+struct Threaded {
+    handler: Handler,
+    /// the value, divisor or jump target the handler needs
+    argument: i64,
+}
+
+fn push(machine: &mut Machine, index: usize, value: i64) -> Result<usize, RuntimeError> {
+    machine.unstack.push(value);
+    Ok(index + 1)
+}
+
+fn pop(machine: &mut Machine, index: usize, divisor: i64) -> Result<usize, RuntimeError> {
+    if machine.unstack.is_empty() {
+        machine.fail(index)?;
+    }
+    let value = machine.unstack.pop().wrapping_div(divisor);
+    machine.unstack.push(value);
+    Ok(index + 1)
+}
+
+/// any other operation, executed by the interpreter
+fn single(machine: &mut Machine, index: usize, _: i64) -> Result<usize, RuntimeError> {
+    let Instruction::Single(operation) = machine.program.code[index] else {
+        unreachable!("only compiled for single operations");
+    };
+    machine.execute(operation, machine.program.origins[index])?;
+    Ok(index + 1)
+}
+
+fn hug(machine: &mut Machine, index: usize, target: i64) -> Result<usize, RuntimeError> {
+    if machine.unstack.is_empty() {
+        machine.fail(index)?;
+    }
+    match machine.unstack.pop() {
+        0 => Ok(index + 1),
+        _ => Ok(target as usize),
+    }
+}
+
+fn skip(machine: &mut Machine, index: usize, target: i64) -> Result<usize, RuntimeError> {
+    if machine.unstack.is_empty() {
+        machine.fail(index)?;
+    }
+    match machine.unstack.pop() {
+        0 => Ok(target as usize),
+        _ => Ok(index + 1),
+    }
+}
+
+fn multiply(machine: &mut Machine, index: usize, value: i64) -> Result<usize, RuntimeError> {
+    if machine.unstack.is_empty() {
+        // let the 💓 fail like it would have
+        machine.unstack.push(value);
+        machine.execute(Operations::Heart(0), machine.program.origins[index] + 1)?;
+    }
+    let product = machine.unstack.pop().wrapping_mul(value);
+    machine.unstack.push(product);
+    Ok(index + 1)
+}
+
+fn jump(_: &mut Machine, _: usize, target: i64) -> Result<usize, RuntimeError> {
+    Ok(target as usize)
+}
+
+fn dup_hug(machine: &mut Machine, index: usize, target: i64) -> Result<usize, RuntimeError> {
+    if machine.unstack.is_empty() {
+        machine.execute(Operations::Dup(1), machine.program.origins[index])?;
+    }
+    let value = machine.unstack.pop();
+    machine.unstack.push(value);
+    match value {
+        0 => Ok(index + 1),
+        _ => Ok(target as usize),
+    }
+}
+
+impl Instruction {
+    fn threaded(self) -> Threaded {
+        let (handler, argument): (Handler, i64) = match self {
+            Instruction::Single(Operations::Push(value)) => (push, value),
+            Instruction::Single(Operations::Pop(divisor)) if divisor != 0 => (pop, divisor),
+            Instruction::Single(_) => (single, 0),
+            Instruction::Hug(target) => (hug, target as i64),
+            Instruction::Skip(target) => (skip, target as i64),
+            Instruction::Multiply(value) => (multiply, value),
+            Instruction::Jump(target) => (jump, target as i64),
+            Instruction::DupHug(target) => (dup_hug, target as i64),
+        };
+        Threaded { handler, argument }
+    }
+}
+
+/// checks the [`Limits`] after every instruction
+pub(crate) struct Guard<'a> {
+    limits: &'a Limits,
+    #[cfg(feature = "std")]
+    deadline: Option<(Instant, core::time::Duration)>,
+    #[cfg(feature = "std")]
+    steps: usize,
+}
+
+impl<'a> Guard<'a> {
+    pub(crate) fn new(limits: &'a Limits) -> Self {
+        Guard {
+            limits,
+            #[cfg(feature = "std")]
+            deadline: limits
+                .timeout
+                .map(|timeout| (Instant::now() + timeout, timeout)),
+            #[cfg(feature = "std")]
+            steps: 0,
+        }
+    }
+
+    /// fail if the instruction compiled from `origin` broke a limit, `next` being the one to run
+    /// after it
+    pub(crate) fn check(
+        &mut self,
+        unstack: &Unstack,
+        program: &Program,
+        origin: usize,
+        next: usize,
+    ) -> Result<(), RuntimeError> {
+        if let Some(limit) = self.limits.max_values {
+            if unstack.len() > limit {
+                return Err(RuntimeError::OutOfMemory {
+                    limit,
+                    instruction_pointer: origin,
+                });
+            }
+        }
+        #[cfg(feature = "std")]
+        if let Some((deadline, timeout)) = self.deadline {
+            self.steps = self.steps.wrapping_add(1);
+            if self.steps.is_multiple_of(self.limits.check_interval.max(1))
+                && Instant::now() >= deadline
+            {
+                return Err(RuntimeError::Timeout {
+                    timeout,
+                    instruction_pointer: program.origins.get(next).copied().unwrap_or(origin),
+                });
+            }
+        }
+        #[cfg(not(feature = "std"))]
+        let _ = (program, next);
+        Ok(())
+    }
+}
+
+/// run a compiled program starting with the given unstack and return what is left on it,
+/// bottom first
+///
+/// every instruction was compiled to the function that executes it, so dispatching is a single
+/// indirect call instead of a `match`
+pub fn run(
+    program: &Program,
+    unstack: Unstack,
+    host: &mut dyn Io,
+    limits: &Limits,
+) -> Result<Vec<i64>, RuntimeError> {
+    let mut guard = Guard::new(limits);
+    let mut machine = Machine {
+        program,
+        unstack,
+        host,
+    };
+    let mut index = 0;
+    while let Some(threaded) = program.threaded.get(index) {
+        let next = (threaded.handler)(&mut machine, index, threaded.argument)?;
+        guard.check(&machine.unstack, program, program.origins[index], next)?;
+        index = next;
+    }
+    Ok(machine.unstack.values())
+}
+
+/// [`run`], dispatching with a `match` on every instruction
+///
+/// the way the backend used to run programs, kept to measure [`run`] against
+pub fn run_match(
+    program: &Program,
+    mut unstack: Unstack,
+    host: &mut dyn Io,
+    limits: &Limits,
+) -> Result<Vec<i64>, RuntimeError> {
+    let mut guard = Guard::new(limits);
+    let mut index = 0;
+    while let Some(&instruction) = program.code.get(index) {
+        let origin = program.origins[index];
+        index += 1;
+        match instruction {
+            Instruction::Single(operation) => {
+                let mut instruction_pointer = origin;
+                execute(
+                    operation,
+                    &mut unstack,
+                    &mut instruction_pointer,
+                    host,
+                    &Semantics::default(),
+                )?;
+            }
+            Instruction::Hug(target) => {
+                if unstack.is_empty() {
+                    let mut instruction_pointer = origin;
+                    execute(
+                        program.operations[index - 1],
+                        &mut unstack,
+                        &mut instruction_pointer,
+                        host,
+                        &Semantics::default(),
+                    )?;
+                }
+                if unstack.pop() != 0 {
+                    index = target;
+                }
+            }
+            Instruction::Skip(target) => {
+                if unstack.is_empty() {
+                    let mut instruction_pointer = origin;
+                    execute(
+                        program.operations[index - 1],
+                        &mut unstack,
+                        &mut instruction_pointer,
+                        host,
+                        &Semantics::default(),
+                    )?;
+                }
+                if unstack.pop() == 0 {
+                    index = target;
+                }
+            }
+            Instruction::Multiply(value) => {
+                if unstack.is_empty() {
+                    // let the 💓 fail like it would have
+                    unstack.push(value);
+                    execute(
+                        Operations::Heart(0),
+                        &mut unstack,
+                        &mut (origin + 1),
+                        host,
+                        &Semantics::default(),
+                    )?;
+                }
+                let product = unstack.pop().wrapping_mul(value);
+                unstack.push(product);
+            }
+            Instruction::Jump(target) => index = target,
+            Instruction::DupHug(target) => {
+                if unstack.is_empty() {
+                    let mut instruction_pointer = origin;
+                    execute(
+                        Operations::Dup(1),
+                        &mut unstack,
+                        &mut instruction_pointer,
+                        host,
+                        &Semantics::default(),
+                    )?;
+                }
+                let value = unstack.pop();
+                unstack.push(value);
+                if value != 0 {
+                    index = target;
+                }
+            }
+        }
+        guard.check(&unstack, program, origin, index)?;
+    }
+    Ok(unstack.values())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bytecode::{compile, run, run_match, Instruction};
+    use crate::ext::Host;
+    use crate::interpreter::interpret;
+    use crate::unstack::Unstack;
+    use crate::{parse, Limits, Operations};
+
+    #[test]
+    fn test_compile() {
+        let program = compile(&parse("🥺8 💖2 ✨1 🫂4 🥺3 💓0 🥺1 🫂3", false), true).unwrap();
+        // 💖2 is jumped to, so it is not fused with the 🥺 before it
+        assert_eq!(
+            program.code,
+            vec![
+                Instruction::Single(Operations::Push(8)),
+                Instruction::Single(Operations::Pop(2)),
+                Instruction::DupHug(1),
+                Instruction::Multiply(3),
+                Instruction::Jump(4),
+            ]
+        );
+        assert_eq!(program.origins, vec![0, 1, 2, 4, 6]);
+        // jumping before the start or spawning tasks is left to the interpreter
+        assert!(compile(&parse("🥺1 🫂5", false), true).is_none());
+        assert!(compile(&parse("🥺1 🧵0", true), true).is_none());
+    }
+
+    #[test]
+    fn test_run() {
+        let programs = [
+            "🥺8 💖2 ✨1 🫂4",
+            "🥺1 🥺1000 ✨1 💖3 ✨1 🫂4 🥺7 💓0 🥺5 💖2",
+            "🥺2 🥺3 ✨2 👉👈1 💓1 🥺4 🥺0 🫂1",
+            "🥺3 🥺9 🥺1 🫂1 💖3",
+            "🥺3 🥺0 🦘2 🥺5 💓0 🥺2 🦘9 🥺7",
+            "🥺1 🦘1 🥺4 🥺0 🦘9 🥺7",
+        ];
+        for source in programs {
+            let tokens = parse(source, true);
+            let expected = interpret(
+                tokens.clone(),
+                Unstack::new(),
+                &mut Host::new(),
+                &Limits::default(),
+            );
+            for fuse in [false, true] {
+                let program = compile(&tokens, fuse).unwrap();
+                for run in [run, run_match] {
+                    let result = run(
+                        &program,
+                        Unstack::new(),
+                        &mut Host::new(),
+                        &Limits::default(),
+                    );
+                    assert_eq!(result, expected, "{} (fused: {})", source, fuse);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+/*!
+ * # Canonical form
+ *
+ * an argument can be written either as digits (`🥺3`) or as a word whose length in characters is the argument (`🥺🥺🥺🥺`),
+ * and anything that is not part of an operation separates operations
+ *
+ * `bottom canonicalize` rewrites a program with every argument in one representation and one operation per line,
+ * giving a stable form for diffs. comments do not survive this
+ */
+
+use std::process;
+
+use bottom::{parse, Operations};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::source::{read_source, write_output};
+
+/// the character length encoded arguments are made of
+const LENGTH_CHAR: char = '🥺';
+
+/// how arguments are written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgumentStyle {
+    /// as digits, `🥺3`
+    Numeric,
+    /// as a word of that many characters, `🥺🥺🥺🥺`
+    Length,
+}
+
+/// write a single operation with its argument in `style`
+pub fn write_operation(operation: &Operations, style: ArgumentStyle) -> String {
+    match style {
+        ArgumentStyle::Numeric => operation.to_string(),
+        ArgumentStyle::Length => {
+            let length = operation.argument().max(0) as usize;
+            let mut written = operation.emoji().to_string();
+            written.extend(std::iter::repeat_n(LENGTH_CHAR, length));
+            written
+        }
+    }
+}
+
+/// write `operations` one per line with every argument in `style`
+pub fn canonicalize(operations: &[Operations], style: ArgumentStyle) -> String {
+    operations
+        .iter()
+        .map(|operation| write_operation(operation, style) + "\n")
+        .collect()
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("canonicalize")
+        .about("rewrite a 🥺 program with one operation per line and uniform arguments")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .value_name("FILE")
+                .help("🥺 program to read"),
+        )
+        .arg(
+            Arg::with_name("args")
+                .long("args")
+                .takes_value(true)
+                .value_name("STYLE")
+                .possible_values(&["numeric", "length"])
+                .default_value("numeric")
+                .help("write arguments as digits or as the length of a word"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("recognize the extension operations"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("write the program to FILE instead of stdout"),
+        )
+        .arg(
+            Arg::with_name("compress")
+                .long("compress")
+                .help("write the program gzip compressed, e.g. to a .🥺.gz file"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let file = args.value_of("file").expect("required by clap");
+    let source = read_source(file).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", file, err);
+        process::exit(1);
+    });
+    let style = match args.value_of("args") {
+        Some("length") => ArgumentStyle::Length,
+        _ => ArgumentStyle::Numeric,
+    };
+    let operations = parse(&source, args.is_present("extensions"));
+    let output = canonicalize(&operations, style);
+    write_output(
+        args.value_of("output"),
+        &output,
+        args.is_present("compress"),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canonical::{canonicalize, ArgumentStyle};
+    use bottom::parse;
+
+    #[test]
+    fn test_canonicalize() {
+        let source = "🥺🥺🥺 comment 👉👈2\t✨🥺\n🫂0";
+        let operations = parse(source, false);
+        let numeric = canonicalize(&operations, ArgumentStyle::Numeric);
+        assert_eq!(numeric, "🥺2\n👉👈2\n✨1\n🫂0\n");
+        let length = canonicalize(&operations, ArgumentStyle::Length);
+        assert_eq!(length, "🥺🥺🥺\n👉👈🥺🥺\n✨🥺\n🫂\n");
+        assert_eq!(parse(&length, false), operations);
+        assert_eq!(
+            canonicalize(&parse(&numeric, false), ArgumentStyle::Numeric),
+            numeric
+        );
+    }
+}
@@ -0,0 +1,143 @@
+/*!
+ * # Cells
+ *
+ * the integers an unstack holds. the [`Interpreter`](crate::interpreter::Interpreter) is generic
+ * over them so a program can run with the overflow behavior of another implementation, or with
+ * 128 bits of room: `bottom --int-width 32` runs it with [`i32`] cells
+ *
+ * what goes in and out of a program is an [`i64`] whatever the cells are: arguments, what
+ * [`Io`](crate::interpreter::Io) reads and writes, the values observers see and the ones a
+ * [`RuntimeError`](crate::RuntimeError) keeps, so a cell wider than that wraps around on its way
+ * out. the math intrinsics compute in 64 bits too
+ */
+
+use core::fmt::{Debug, Display};
+use core::hash::Hash;
+use core::ops::{Div, Mul, Rem, Sub};
+use core::str::FromStr;
+
+/// an integer type the unstack can hold
+pub trait Cell:
+    Copy
+    + Default
+    + Ord
+    + Hash
+    + Debug
+    + Display
+    + FromStr
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+    + Sub<Output = Self>
+    + Send
+    + Sync
+    + 'static
+{
+    /// the width of the cell, what `--int-width` takes
+    const BITS: u32;
+    const MIN: Self;
+    const ZERO: Self;
+    const ONE: Self;
+
+    /// `value` wrapped around to fit in the cell
+    fn from_i64(value: i64) -> Self;
+
+    /// `value` if it fits in the cell
+    fn try_from_i64(value: i64) -> Option<Self>;
+
+    /// the cell wrapped around to fit in an [`i64`]
+    fn to_i64(self) -> i64;
+
+    /// the cell as an [`i128`], which holds every cell
+    fn to_i128(self) -> i128;
+
+    /// the cell as an address or count, `None` if it is negative or too big
+    fn to_usize(self) -> Option<usize>;
+
+    /// the product, `None` if it overflows
+    fn checked_mul(self, other: Self) -> Option<Self>;
+
+    /// the quotient rounded toward 0, `None` if it overflows or `other` is 0
+    fn checked_div(self, other: Self) -> Option<Self>;
+
+    /// the product, wrapped around to fit in the cell
+    fn wrapping_mul(self, other: Self) -> Self;
+
+    /// the quotient rounded toward 0, wrapped around to fit in the cell, `other` must not be 0
+    fn wrapping_div(self, other: Self) -> Self;
+
+    /// the remainder of [`Cell::wrapping_div`], `other` must not be 0
+    fn wrapping_rem(self, other: Self) -> Self;
+}
+
+macro_rules! cell {
+    ($($int:ty),*) => {$(
+        impl Cell for $int {
+            const BITS: u32 = <$int>::BITS;
+            const MIN: Self = <$int>::MIN;
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+
+            fn from_i64(value: i64) -> Self {
+                value as $int
+            }
+
+            fn try_from_i64(value: i64) -> Option<Self> {
+                <$int>::try_from(value).ok()
+            }
+
+            fn to_i64(self) -> i64 {
+                self as i64
+            }
+
+            fn to_i128(self) -> i128 {
+                self as i128
+            }
+
+            fn to_usize(self) -> Option<usize> {
+                usize::try_from(self).ok()
+            }
+
+            fn checked_mul(self, other: Self) -> Option<Self> {
+                <$int>::checked_mul(self, other)
+            }
+
+            fn checked_div(self, other: Self) -> Option<Self> {
+                <$int>::checked_div(self, other)
+            }
+
+            fn wrapping_mul(self, other: Self) -> Self {
+                <$int>::wrapping_mul(self, other)
+            }
+
+            fn wrapping_div(self, other: Self) -> Self {
+                <$int>::wrapping_div(self, other)
+            }
+
+            fn wrapping_rem(self, other: Self) -> Self {
+                <$int>::wrapping_rem(self, other)
+            }
+        }
+    )*};
+}
+
+cell!(i32, i64, i128);
+
+#[cfg(test)]
+mod tests {
+    use crate::cell::Cell;
+
+    #[test]
+    fn test_cell() {
+        assert_eq!(i32::from_i64(1 << 32 | 5), 5);
+        assert_eq!(i32::from_i64(-1), -1);
+        assert_eq!((i128::MAX).to_i64(), -1);
+        assert_eq!(i128::from_i64(i64::MIN).to_i64(), i64::MIN);
+        assert_eq!((-1i32).to_usize(), None);
+        assert_eq!(i32::try_from_i64(1 << 31), None);
+        assert_eq!(Cell::checked_mul(1i32 << 16, 1 << 15), None);
+        assert_eq!(Cell::wrapping_div(i32::MIN, -1), i32::MIN);
+        assert_eq!(Cell::wrapping_rem(i128::MIN, -1), 0);
+        assert_eq!(<i128 as Cell>::BITS, 128);
+    }
+}
@@ -0,0 +1,288 @@
+/*!
+ * # Chrome traces
+ *
+ * `--chrome-trace run.json` writes the run in the trace event format of chrome://tracing and
+ * Perfetto: every executed instruction is an event on the timeline of its task and every 🫂 loop
+ * a span around the instructions it ran, nested like the loops are
+ *
+ * time on the timeline is counted in instructions, one microsecond each, so the same run always
+ * gives the same trace and the width of a span is the number of instructions executed inside it
+ */
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use bottom::interpreter::ExecutionObserver;
+use bottom::unstack::Unstack;
+use bottom::{hug_target, Operations};
+
+use crate::json::Json;
+use crate::report::Span;
+
+/// a loop a task is inside of
+struct OpenLoop {
+    /// the instruction pointer of the first instruction of the body
+    start: usize,
+    /// the instruction pointer of the 🫂
+    end: usize,
+    /// when the first time around started
+    begin: u64,
+    /// how often the body ran so far
+    iterations: usize,
+}
+
+#[derive(Default)]
+struct TaskState {
+    /// the instruction the task executed last
+    previous: Option<usize>,
+    /// when the task last executed each instruction
+    last: Vec<u64>,
+    /// the loops the task is in, innermost last
+    open: Vec<OpenLoop>,
+}
+
+/// writes the instructions a run executes and the loops they ran in as a Chrome trace
+pub struct ChromeTrace {
+    out: BufWriter<File>,
+    /// the operations of the program and where they are in its source, if known
+    program: Vec<(Operations, Option<Span>)>,
+    tasks: Vec<TaskState>,
+    /// instructions executed by all tasks, the current time
+    now: u64,
+}
+
+impl ChromeTrace {
+    /// start the trace of `program` at `path`, `spans` says where its operations are if not empty
+    pub fn create(path: &str, program: &[Operations], spans: &[Span]) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(b"[\n")?;
+        let program = program
+            .iter()
+            .enumerate()
+            .map(|(index, &operation)| (operation, spans.get(index).copied()))
+            .collect();
+        Ok(ChromeTrace {
+            out,
+            program,
+            tasks: vec![],
+            now: 0,
+        })
+    }
+
+    fn event(&mut self, event: Vec<(&str, Json)>) {
+        let event = Json::Object(
+            event
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        );
+        writeln!(self.out, "{},", event).expect("could not write the trace");
+    }
+
+    fn span(&mut self, task: usize, name: String, category: &str, begin: u64, args: Json) {
+        self.event(vec![
+            ("name", Json::String(name)),
+            ("cat", Json::String(category.to_string())),
+            ("ph", Json::String("X".to_string())),
+            ("ts", Json::Int(begin as i64)),
+            ("dur", Json::Int((self.now - begin) as i64)),
+            ("pid", Json::Int(0)),
+            ("tid", Json::Int(task as i64)),
+            ("args", args),
+        ]);
+    }
+
+    fn close(&mut self, task: usize, open: OpenLoop) {
+        let name = match self.program.get(open.start).and_then(|(_, span)| *span) {
+            Some(start) => {
+                let end = self.program[open.end].1.map_or(start.end, |span| span.end);
+                format!("🫂 {}", Span { end, ..start })
+            }
+            None => format!("🫂 {}-{}", open.start, open.end),
+        };
+        let args = Json::Object(vec![
+            ("start".to_string(), Json::Int(open.start as i64)),
+            ("end".to_string(), Json::Int(open.end as i64)),
+            ("iterations".to_string(), Json::Int(open.iterations as i64)),
+        ]);
+        self.span(task, name, "loop", open.begin, args);
+    }
+
+    /// close the loops of `task` that do not contain `instruction_pointer` anymore and open the
+    /// one its last instruction jumped back to
+    fn track_loops(&mut self, task: usize, instruction_pointer: usize) {
+        let state = &self.tasks[task];
+        let jumped_from = state.previous.filter(|&previous| {
+            let Some((Operations::Hug(n), _)) = self.program.get(previous) else {
+                return false;
+            };
+            hug_target(previous, *n) == Some(instruction_pointer) && instruction_pointer <= previous
+        });
+        // a 🫂 jumping back also ends the loops inside its body
+        while let Some(open) = self.tasks[task].open.last() {
+            let inside = (open.start..=open.end).contains(&instruction_pointer)
+                && jumped_from.is_none_or(|end| open.end >= end);
+            if inside {
+                break;
+            }
+            let mut open = self.tasks[task].open.pop().expect("just looked at it");
+            if self.tasks[task].previous == Some(open.end) {
+                // it went through the 🫂 once more without jumping back
+                open.iterations += 1;
+            }
+            self.close(task, open);
+        }
+        let Some(end) = jumped_from else {
+            return;
+        };
+        let state = &mut self.tasks[task];
+        match state.open.last_mut() {
+            Some(open) if (open.start, open.end) == (instruction_pointer, end) => {
+                open.iterations += 1;
+            }
+            _ => state.open.push(OpenLoop {
+                start: instruction_pointer,
+                end,
+                begin: state.last[instruction_pointer],
+                iterations: 1,
+            }),
+        }
+    }
+}
+
+impl ExecutionObserver for ChromeTrace {
+    fn after_instruction(
+        &mut self,
+        task: usize,
+        instruction_pointer: usize,
+        operation: Operations,
+        _: &Unstack,
+    ) {
+        while self.tasks.len() <= task {
+            let id = self.tasks.len();
+            self.tasks.push(TaskState {
+                last: vec![0; self.program.len()],
+                ..TaskState::default()
+            });
+            self.event(vec![
+                ("name", Json::String("thread_name".to_string())),
+                ("ph", Json::String("M".to_string())),
+                ("pid", Json::Int(0)),
+                ("tid", Json::Int(id as i64)),
+                (
+                    "args",
+                    Json::Object(vec![(
+                        "name".to_string(),
+                        Json::String(format!("task {}", id)),
+                    )]),
+                ),
+            ]);
+        }
+        self.track_loops(task, instruction_pointer);
+        let mut args = vec![(
+            "instruction_pointer".to_string(),
+            Json::Int(instruction_pointer as i64),
+        )];
+        if let Some((_, Some(span))) = self.program.get(instruction_pointer) {
+            args.push(("span".to_string(), Json::String(span.to_string())));
+        }
+        let begin = self.now;
+        self.now += 1;
+        self.span(
+            task,
+            operation.to_string(),
+            "instruction",
+            begin,
+            Json::Object(args),
+        );
+        let state = &mut self.tasks[task];
+        if let Some(last) = state.last.get_mut(instruction_pointer) {
+            *last = begin;
+        }
+        state.previous = Some(instruction_pointer);
+    }
+}
+
+impl Drop for ChromeTrace {
+    fn drop(&mut self) {
+        for task in 0..self.tasks.len() {
+            while let Some(mut open) = self.tasks[task].open.pop() {
+                if self.tasks[task].previous == Some(open.end) {
+                    open.iterations += 1;
+                }
+                self.close(task, open);
+            }
+        }
+        // every event so far is followed by a comma, so end with one that is not
+        let whole = Json::Object(vec![
+            ("name".to_string(), Json::String("process_name".to_string())),
+            ("ph".to_string(), Json::String("M".to_string())),
+            ("pid".to_string(), Json::Int(0)),
+            (
+                "args".to_string(),
+                Json::Object(vec![(
+                    "name".to_string(),
+                    Json::String("bottom".to_string()),
+                )]),
+            ),
+        ]);
+        let _ = writeln!(self.out, "{}\n]", whole).and_then(|_| self.out.flush());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bottom::ext::Host;
+    use bottom::interpreter::Interpreter;
+    use bottom::unstack::Unstack;
+    use bottom::{parse, Limits};
+
+    use crate::chrome::ChromeTrace;
+    use crate::json::Json;
+
+    #[test]
+    fn test_chrome_trace() {
+        let path = std::env::temp_dir().join(format!("bottom-chrome-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        // an outer loop going around twice, each time running an inner loop going around 3 times
+        let tokens = parse("🥺2 🥺4 💖2 ✨1 🫂4 🧹1 💖2 ✨1 🫂9", true);
+        let mut host = Host::new();
+        let trace = ChromeTrace::create(path, &tokens, &[]).unwrap();
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.observe(Box::new(trace));
+        interpreter.run(&Limits::default()).unwrap();
+        drop(interpreter);
+
+        let json: Json = std::fs::read_to_string(path).unwrap().parse().unwrap();
+        std::fs::remove_file(path).unwrap();
+        let events = json.as_array().unwrap();
+        let of = |category: &str| -> Vec<&Json> {
+            events
+                .iter()
+                .filter(|event| event.get("cat").and_then(Json::as_str) == Some(category))
+                .collect()
+        };
+        let instructions = of("instruction");
+        assert_eq!(instructions.len(), 1 + 2 * (1 + 3 * 3 + 4));
+        let field = |event: &Json, key: &str| event.get(key).and_then(Json::as_i64).unwrap();
+        let loops: Vec<_> = of("loop")
+            .into_iter()
+            .map(|event| {
+                let args = event.get("args").unwrap();
+                (
+                    field(args, "start"),
+                    field(event, "ts"),
+                    field(event, "dur"),
+                    field(args, "iterations"),
+                )
+            })
+            .collect();
+        // the inner loops close before the outer one, each inside it
+        assert_eq!(
+            loops,
+            [(2, 2, 9, 3), (2, 16, 9, 3), (1, 1, 28, 2)],
+            "{:?}",
+            loops
+        );
+    }
+}
@@ -0,0 +1,183 @@
+/*!
+ * # Closures
+ *
+ * a middle ground between the [`bytecode`] and native code:
+ * [`compile`] turns every instruction of the bytecode into a closure that already holds its value,
+ * divisor or jump target and the operation it fails with, and [`run`] only calls the closure of
+ * the next instruction, with neither a `match` nor an argument to look up
+ *
+ * failures are the errors of the interpreter and the semantics are the default ones, like for
+ * the bytecode
+ */
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::bytecode::{self, Guard, Instruction, Machine};
+use crate::interpreter::Io;
+use crate::unstack::Unstack;
+use crate::{Limits, Operations, RuntimeError};
+
+/// executes an instruction and returns the index of the next one
+type Closure = Box<dyn Fn(&mut Machine) -> Result<usize, RuntimeError> + Send + Sync>;
+
+/// a program compiled to closures, see [`compile`]
+pub struct Closures {
+    program: bytecode::Program,
+    closures: Vec<Closure>,
+}
+
+impl Closures {
+    /// the bytecode the closures were compiled from
+    pub fn program(&self) -> &bytecode::Program {
+        &self.program
+    }
+}
+
+/// compile `tokens` to a closure for every instruction of their fused bytecode
+///
+/// `None` for the same programs the [bytecode](crate::bytecode::compile) cannot run
+pub fn compile(tokens: &[Operations]) -> Option<Closures> {
+    let program = bytecode::compile(tokens, true)?;
+    let closures = program
+        .code
+        .iter()
+        .enumerate()
+        .map(|(index, &instruction)| {
+            closure(
+                instruction,
+                index,
+                program.origins[index],
+                program.operations[index],
+            )
+        })
+        .collect();
+    Some(Closures { program, closures })
+}
+
+/// the closure executing `instruction` at `index`, compiled from `operation` at `origin`
+fn closure(
+    instruction: Instruction,
+    index: usize,
+    origin: usize,
+    operation: Operations,
+) -> Closure {
+    let next = index + 1;
+    match instruction {
+        Instruction::Single(Operations::Push(value)) => Box::new(move |machine| {
+            machine.unstack.push(value);
+            Ok(next)
+        }),
+        Instruction::Single(Operations::Pop(divisor)) if divisor != 0 => Box::new(move |machine| {
+            if machine.unstack.is_empty() {
+                machine.execute(operation, origin)?;
+            }
+            let value = machine.unstack.pop().wrapping_div(divisor);
+            machine.unstack.push(value);
+            Ok(next)
+        }),
+        Instruction::Single(operation) => Box::new(move |machine| {
+            machine.execute(operation, origin)?;
+            Ok(next)
+        }),
+        Instruction::Hug(target) => Box::new(move |machine| {
+            if machine.unstack.is_empty() {
+                machine.execute(operation, origin)?;
+            }
+            match machine.unstack.pop() {
+                0 => Ok(next),
+                _ => Ok(target),
+            }
+        }),
+        Instruction::Skip(target) => Box::new(move |machine| {
+            if machine.unstack.is_empty() {
+                machine.execute(operation, origin)?;
+            }
+            match machine.unstack.pop() {
+                0 => Ok(target),
+                _ => Ok(next),
+            }
+        }),
+        Instruction::Multiply(value) => Box::new(move |machine| {
+            if machine.unstack.is_empty() {
+                // let the 💓 fail like it would have
+                machine.unstack.push(value);
+                machine.execute(Operations::Heart(0), origin + 1)?;
+            }
+            let product = machine.unstack.pop().wrapping_mul(value);
+            machine.unstack.push(product);
+            Ok(next)
+        }),
+        Instruction::Jump(target) => Box::new(move |_| Ok(target)),
+        Instruction::DupHug(target) => Box::new(move |machine| {
+            if machine.unstack.is_empty() {
+                machine.execute(Operations::Dup(1), origin)?;
+            }
+            let value = machine.unstack.pop();
+            machine.unstack.push(value);
+            match value {
+                0 => Ok(next),
+                _ => Ok(target),
+            }
+        }),
+    }
+}
+
+/// run a program compiled by [`compile`] starting with the given unstack and return what is
+/// left on it, bottom first
+pub fn run(
+    closures: &Closures,
+    unstack: Unstack,
+    host: &mut dyn Io,
+    limits: &Limits,
+) -> Result<Vec<i64>, RuntimeError> {
+    let program = &closures.program;
+    let mut guard = Guard::new(limits);
+    let mut machine = Machine {
+        program,
+        unstack,
+        host,
+    };
+    let mut index = 0;
+    while let Some(closure) = closures.closures.get(index) {
+        let next = closure(&mut machine)?;
+        guard.check(&machine.unstack, program, program.origins[index], next)?;
+        index = next;
+    }
+    Ok(machine.unstack.values())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::closures::{compile, run};
+    use crate::ext::Host;
+    use crate::interpreter::interpret;
+    use crate::unstack::Unstack;
+    use crate::{parse, Limits};
+
+    #[test]
+    fn test_closures() {
+        let programs = [
+            "🥺8 💖2 ✨1 🫂4",
+            "🥺1 🥺1000 ✨1 💖3 ✨1 🫂4 🥺7 💓0 🥺5 💖2",
+            "🥺3 🥺0 🦘2 🥺5 💓0 🥺2 🦘9 🥺7",
+            "🥺1 🦘1 🥺4 🥺0 🦘9 🥺7",
+            "💓0",
+            "🥺1 🥺0 💖0",
+            // failing in the middle of a fused pair
+            "🥺3 💓0",
+            "✨1 🫂1",
+            // products wrap around
+            "🥺4294967296 ✨1 💓0 ✨1 💓0",
+        ];
+        let limits = Limits::default();
+        for source in programs {
+            let tokens = parse(source, true);
+            let expected = interpret(tokens.clone(), Unstack::new(), &mut Host::new(), &limits);
+            let closures = compile(&tokens).unwrap();
+            let result = run(&closures, Unstack::new(), &mut Host::new(), &limits);
+            assert_eq!(result, expected, "{}", source);
+        }
+        assert!(compile(&parse("🥺1 🫂5", false)).is_none());
+    }
+}
@@ -0,0 +1,114 @@
+/*!
+ * # Compiling to other languages
+ *
+ * `bottom compile --target bf FILE` writes a Brainfuck program doing what the 🥺 program does,
+ * as long as it only uses operations a tape machine can do: 🥺 with values from 0 to 255, 👉👈,
+ * 💓, ✨, 🧹 with a count, 📖0, 📝1, 💖1, and 🫂 and 🦘 jumping so that their blocks nest
+ *
+ * the unstack is laid out on the tape, so cells wrapping around from 255 to 0 is the only
+ * arithmetic there is, and what the program writes to stdout is all that is left of a run. a
+ * Brainfuck `,` at the end of the input usually leaves 0 or the cell alone where 📖 pushes -1
+ *
+ * `--target llvm` writes [LLVM IR](crate::llvm) instead, with the `llvm` feature. `--floor-div`
+ * and `--div-by-zero` choose what its 💖 does, Brainfuck has only 💖1, which they do not change
+ */
+
+use std::process;
+
+use bottom::{parse, Operations, Semantics};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::bf::to_bf;
+use crate::source::{read_source, write_output};
+use crate::{division_args, division_semantics};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("compile")
+        .about("compile a 🥺 program into another language")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .value_name("FILE")
+                .help("🥺 program to read"),
+        )
+        .arg(
+            Arg::with_name("target")
+                .long("target")
+                .takes_value(true)
+                .required(true)
+                .possible_values(&["bf", "llvm"])
+                .help("the language to compile to"),
+        )
+        .arg(
+            Arg::with_name("opt-level")
+                .long("opt-level")
+                .takes_value(true)
+                .value_name("LEVEL")
+                .possible_values(&["0", "1", "2", "3"])
+                .help("run LLVM's opt at -OLEVEL on the IR of --target llvm"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("recognize the extension operations"),
+        )
+        .args(&division_args())
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("write the compiled program to FILE instead of stdout"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let file = args.value_of("file").expect("required by clap");
+    let source = read_source(file).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", file, err);
+        process::exit(1);
+    });
+    let operations = parse(&source, args.is_present("extensions"));
+    let (language, compiled) = match args.value_of("target") {
+        Some("llvm") => {
+            let level = args
+                .value_of("opt-level")
+                .map(|level| level.parse().expect("validated by clap"));
+            (
+                "LLVM IR",
+                llvm(&operations, division_semantics(args), level),
+            )
+        }
+        _ => (
+            "Brainfuck",
+            to_bf(&operations).map(|compiled| format!("{}\n", compiled)),
+        ),
+    };
+    let compiled = compiled.unwrap_or_else(|err| {
+        eprintln!(
+            "error: {} cannot be compiled to {}: {}",
+            file, language, err
+        );
+        process::exit(1);
+    });
+    write_output(args.value_of("output"), &compiled, false);
+}
+
+#[cfg(feature = "llvm")]
+fn llvm(
+    operations: &[Operations],
+    semantics: Semantics,
+    level: Option<u8>,
+) -> Result<String, String> {
+    let ir = crate::llvm::lower(operations, semantics)?;
+    match level {
+        Some(level) => crate::llvm::optimize(&ir, level),
+        None => Ok(ir),
+    }
+}
+
+#[cfg(not(feature = "llvm"))]
+fn llvm(_: &[Operations], _: Semantics, _: Option<u8>) -> Result<String, String> {
+    Err("LLVM IR needs the llvm feature".to_string())
+}
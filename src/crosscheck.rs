@@ -0,0 +1,305 @@
+/*!
+ * # Cross-implementation checks
+ *
+ * `bottom crosscheck DIR --other "python3 ref.py"` runs every `.🥺` program below a directory with
+ * this interpreter and with another implementation of 🥺, and reports the programs they disagree
+ * on together with a minimized program that still shows the difference
+ *
+ * the other command is split at whitespace and gets the path of the program as its last argument
+ * and an empty stdin. it should print what `bottom` prints to stdout: the bytes written to file
+ * descriptor 1 followed by the final unstack like `[1, 2]`. it agrees with a failing program when
+ * it exits with a status other than 0, whatever it printed, and its stderr is ignored
+ *
+ * programs are minimized by removing instructions and making 🥺 arguments smaller as long as the
+ * implementations still disagree, which runs the other command once for every attempt. programs
+ * this interpreter does not finish in time are skipped
+ */
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::{self, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bottom::{parse, Limits, Operations, RuntimeError};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::asm::to_source;
+use crate::batch::{discover, without_panic_messages};
+use crate::golden::{capture, Captured};
+use crate::parse_duration;
+use crate::source::read_source;
+
+/// how the other implementation ran a program
+#[derive(Debug)]
+struct Run {
+    /// what it printed to stdout
+    output: String,
+    /// how it exited, `None` if it did not finish in time
+    status: Option<ExitStatus>,
+}
+
+impl Run {
+    /// whether it did what this interpreter did
+    fn agrees(&self, captured: &Captured) -> bool {
+        match self.status {
+            None => false,
+            Some(status) if captured.failed => !status.success(),
+            Some(status) => status.success() && self.output == captured.output,
+        }
+    }
+}
+
+/// the other implementation, and how long it may take for a program
+struct Other {
+    program: String,
+    arguments: Vec<String>,
+    timeout: Duration,
+}
+
+impl Other {
+    /// run the program in `file`
+    fn run(&self, file: &Path) -> io::Result<Run> {
+        let mut child = Command::new(&self.program)
+            .args(&self.arguments)
+            .arg(file)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut stdout = child.stdout.take().expect("piped");
+        // read while waiting, a full pipe would block the child forever
+        let reader = thread::spawn(move || {
+            let mut output = vec![];
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break Some(status);
+            }
+            if Instant::now() >= deadline {
+                child.kill()?;
+                child.wait()?;
+                break None;
+            }
+            thread::sleep(Duration::from_millis(5));
+        };
+        let output = reader.join().expect("reader does not panic")?;
+        Ok(Run {
+            output: String::from_utf8_lossy(&output).into_owned(),
+            status,
+        })
+    }
+
+    /// run `operations` written to a temporary file
+    fn run_operations(&self, operations: &[Operations]) -> io::Result<Run> {
+        let file = std::env::temp_dir().join(format!("bottom-crosscheck-{}.🥺", process::id()));
+        fs::write(&file, to_source(operations))?;
+        let run = self.run(&file);
+        fs::remove_file(&file)?;
+        run
+    }
+}
+
+/// `operations` made smaller for as long as `diverges` holds, which it does for `operations`
+///
+/// first removes ever smaller runs of instructions, then tries 0, 1 and half of the argument of
+/// every 🥺
+pub fn minimize(
+    operations: &[Operations],
+    mut diverges: impl FnMut(&[Operations]) -> bool,
+) -> Vec<Operations> {
+    let mut program = operations.to_vec();
+    let mut chunk = (program.len() / 2).max(1);
+    loop {
+        let mut start = 0;
+        while start < program.len() {
+            let mut candidate = program.clone();
+            candidate.drain(start..(start + chunk).min(program.len()));
+            if diverges(&candidate) {
+                program = candidate;
+            } else {
+                start += chunk;
+            }
+        }
+        if chunk == 1 {
+            break;
+        }
+        chunk /= 2;
+    }
+    for index in 0..program.len() {
+        while let Operations::Push(value) = program[index] {
+            let smaller = [0, 1, value / 2]
+                .into_iter()
+                .filter(|smaller| smaller.unsigned_abs() < value.unsigned_abs())
+                .find(|&smaller| {
+                    let mut candidate = program.clone();
+                    candidate[index] = Operations::Push(smaller);
+                    diverges(&candidate)
+                });
+            match smaller {
+                Some(smaller) => program[index] = Operations::Push(smaller),
+                None => break,
+            }
+        }
+    }
+    program
+}
+
+/// a one line description of how the other implementation ran a program
+fn describe(run: &Run, timeout: Duration) -> String {
+    match run.status {
+        None => format!("did not finish within {:?}", timeout),
+        Some(status) if status.success() => format!("printed {:?}", run.output),
+        Some(status) => format!("failed with {}", status),
+    }
+}
+
+/// a one line description of how this interpreter ran a program
+fn describe_captured(captured: &Captured) -> String {
+    match &captured.error {
+        Some(err) => format!("failed: {}", err),
+        None if captured.failed => "panicked".to_string(),
+        None => format!("printed {:?}", captured.output),
+    }
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("crosscheck")
+        .about("run .🥺 programs with another implementation and report where it disagrees")
+        .arg(
+            Arg::with_name("dir")
+                .required(true)
+                .value_name("DIR")
+                .help("directory searched recursively for .🥺 files"),
+        )
+        .arg(
+            Arg::with_name("other")
+                .long("other")
+                .required(true)
+                .takes_value(true)
+                .value_name("COMMAND")
+                .help("command running the program whose path is appended to it"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("recognize the extension operations"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .value_name("DURATION")
+                .default_value("10s")
+                .validator(|duration| parse_duration(&duration).map(|_| ()))
+                .help("give up on a program after it ran for DURATION in either implementation"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let root = Path::new(args.value_of("dir").expect("required by clap"));
+    let extensions = args.is_present("extensions");
+    let timeout = parse_duration(args.value_of("timeout").expect("has a default"))
+        .expect("validated by clap");
+    let mut words = args
+        .value_of("other")
+        .expect("required by clap")
+        .split_whitespace()
+        .map(str::to_string);
+    let other = Other {
+        program: words.next().unwrap_or_else(|| {
+            eprintln!("error: --other is empty");
+            process::exit(1);
+        }),
+        arguments: words.collect(),
+        timeout,
+    };
+    let limits = Limits {
+        timeout: Some(timeout),
+        ..Limits::default()
+    };
+    let programs = discover(root).unwrap_or_else(|err| {
+        eprintln!("error: could not search {}: {}", root.display(), err);
+        process::exit(1);
+    });
+
+    let run_other = |run: io::Result<Run>| {
+        run.unwrap_or_else(|err| {
+            eprintln!("error: could not run {}: {}", other.program, err);
+            process::exit(1);
+        })
+    };
+    let (mut agreed, mut diverged, mut skipped) = (0, 0, 0);
+    for program in &programs {
+        let name = program.strip_prefix(root).unwrap_or(program).display();
+        let operations = match read_source(program) {
+            Ok(source) => parse(&source, extensions),
+            Err(err) => {
+                eprintln!("error: could not read {}: {}", name, err);
+                process::exit(1);
+            }
+        };
+        let ours = without_panic_messages(|| capture(operations.clone(), &[], &limits));
+        if let Some(RuntimeError::Timeout { .. }) = ours.error {
+            println!("skip {}: did not finish within {:?}", name, timeout);
+            skipped += 1;
+            continue;
+        }
+        let theirs = run_other(other.run(program));
+        if theirs.agrees(&ours) {
+            println!("ok   {}", name);
+            agreed += 1;
+            continue;
+        }
+        println!("DIFF {}", name);
+        println!("  bottom: {}", describe_captured(&ours));
+        println!("  other:  {}", describe(&theirs, timeout));
+        let minimized = minimize(&operations, |candidate| {
+            let ours = without_panic_messages(|| capture(candidate.to_vec(), &[], &limits));
+            !matches!(ours.error, Some(RuntimeError::Timeout { .. }))
+                && !run_other(other.run_operations(candidate)).agrees(&ours)
+        });
+        let source: Vec<_> = minimized
+            .iter()
+            .map(|operation| operation.to_string())
+            .collect();
+        println!("  minimized: {}", source.join(" "));
+        diverged += 1;
+    }
+    println!(
+        "{} agreed, {} diverged, {} skipped",
+        agreed, diverged, skipped
+    );
+    if diverged > 0 {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crosscheck::minimize;
+    use bottom::{parse, Operations};
+
+    #[test]
+    fn test_minimize() {
+        // pretend the other implementation multiplies wrong once a product exceeds 100
+        let program = parse("🥺3 🥺7 ✨1 🥺1000 🥺20 💓0 🥺2 💖2 🥺5", false);
+        let minimized = minimize(&program, |candidate| {
+            candidate.windows(3).any(|window| match window {
+                [Operations::Push(a), Operations::Push(b), Operations::Heart(_)] => a * b > 100,
+                _ => false,
+            })
+        });
+        assert_eq!(minimized, parse("🥺7 🥺20 💓0", false));
+        let minimized = minimize(&program, |candidate| {
+            candidate
+                .iter()
+                .any(|operation| matches!(operation, Operations::Push(value) if *value > 100))
+        });
+        assert_eq!(minimized, parse("🥺125", false));
+        assert_eq!(minimize(&[], |_| true), []);
+    }
+}
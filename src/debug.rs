@@ -0,0 +1,219 @@
+/*!
+ * # Debugger
+ *
+ * `bottom debug FILE` loads a program and executes it as told by commands read from stdin, one
+ * per line, printing the next instruction and the unstack after each:
+ *
+ * - `step` or `s` executes one instruction, `back` or `b` goes back before the last one
+ * - `continue` or `c` runs to the next breakpoint or the end, `reverse` or `r` goes back to the
+ *   latest breakpoint that was passed
+ * - `break N` stops at the instruction N, counted from 0, and `quit` or `q` stops debugging
+ *
+ * every 🛑 of the program and every `--break N` is a breakpoint. going back executes the program
+ * again from the latest [snapshot](bottom::interpreter::Snapshot), so the program should not
+ * read stdin, which the commands come from
+ */
+
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::process;
+
+use bottom::ext::Host;
+use bottom::interpreter::Interpreter;
+use bottom::unstack::Unstack;
+use bottom::{parse, Limits};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::expand_includes;
+use crate::source::read_source;
+
+/// the number of steps between snapshots, a step back executes at most this many again
+const SNAPSHOT_EVERY: usize = 256;
+/// the most snapshots kept, limiting how far back the program can go
+const SNAPSHOTS: usize = 4096;
+
+/// a line read from stdin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Step,
+    Back,
+    Continue,
+    Reverse,
+    Break(usize),
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next().unwrap_or("") {
+            "step" | "s" => Command::Step,
+            "back" | "b" => Command::Back,
+            "continue" | "c" => Command::Continue,
+            "reverse" | "r" => Command::Reverse,
+            "quit" | "q" => Command::Quit,
+            "break" => {
+                let instruction = words.next().ok_or("break needs an instruction")?;
+                let instruction = instruction
+                    .parse()
+                    .map_err(|_| format!("'{}' is not an instruction", instruction))?;
+                Command::Break(instruction)
+            }
+            word => return Err(format!("unknown command '{}'", word)),
+        };
+        match words.next() {
+            Some(word) => Err(format!("unexpected '{}' after the command", word)),
+            None => Ok(command),
+        }
+    }
+}
+
+/// the instruction `interpreter` executes next and the unstack, or the output once it finished
+pub fn position(interpreter: &Interpreter) -> String {
+    if interpreter.is_finished() {
+        return format!("finished {:?}", interpreter.output());
+    }
+    let instruction_pointer = interpreter.instruction_pointer();
+    let operation = interpreter.tokens().get(instruction_pointer);
+    format!(
+        "{}: {} {:?}",
+        instruction_pointer,
+        operation.map_or("end".to_string(), ToString::to_string),
+        interpreter.unstack().values()
+    )
+}
+
+/// carry out `command`, the line to print if it worked
+pub fn execute(interpreter: &mut Interpreter, command: Command) -> Result<String, String> {
+    let moved = match command {
+        Command::Step => interpreter.step().map(|_| true),
+        Command::Back => interpreter.step_back(),
+        Command::Continue => interpreter.run(&Limits::default()).map(|_| true),
+        Command::Reverse => interpreter.reverse_continue(),
+        Command::Break(instruction_pointer) => {
+            interpreter.add_breakpoint(instruction_pointer);
+            return Ok(format!("breakpoint at {}", instruction_pointer));
+        }
+        // stopping is up to the caller, there is nothing to execute
+        Command::Quit => return Ok(position(interpreter)),
+    };
+    match moved {
+        Ok(true) => Ok(position(interpreter)),
+        Ok(false) if command == Command::Back => {
+            Err("there is no snapshot to go back to".to_string())
+        }
+        Ok(false) => Err("no breakpoint was passed since the oldest snapshot".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("debug")
+        .about("step through a 🥺 program forwards and backwards, commands are read from stdin")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .value_name("FILE")
+                .help("🥺 program to debug"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("recognize the extension operations"),
+        )
+        .arg(
+            Arg::with_name("break")
+                .long("break")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("N")
+                .validator(|n| {
+                    n.parse::<usize>()
+                        .map(|_| ())
+                        .map_err(|_| format!("'{}' is not an instruction", n))
+                })
+                .help("stop before the instruction N, counted from 0"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let file = args.value_of("file").expect("required by clap");
+    let source = read_source(file).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", file, err);
+        process::exit(1);
+    });
+    let extensions = args.is_present("extensions");
+    let dir = Path::new(file).parent().unwrap_or(Path::new("."));
+    let tokens = parse(&expand_includes(&source, dir, extensions), extensions);
+    let mut host = Host::new();
+    let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+    interpreter.add_source_breakpoints();
+    for instruction_pointer in args.values_of("break").into_iter().flatten() {
+        interpreter.add_breakpoint(instruction_pointer.parse().expect("validated by clap"));
+    }
+    interpreter.set_snapshots(SNAPSHOT_EVERY, SNAPSHOTS);
+
+    println!("{}", position(&interpreter));
+    for line in io::stdin().lock().lines() {
+        let line = line.unwrap_or_else(|err| {
+            eprintln!("error: could not read stdin: {}", err);
+            process::exit(1);
+        });
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match Command::parse(&line) {
+            Ok(Command::Quit) => break,
+            Ok(command) => execute(&mut interpreter, command),
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(line) => println!("{}", line),
+            Err(err) => eprintln!("error: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bottom::ext::Host;
+    use bottom::interpreter::Interpreter;
+    use bottom::parse;
+    use bottom::unstack::Unstack;
+
+    use crate::debug::{execute, position, Command};
+
+    #[test]
+    fn test_command() {
+        assert_eq!(Command::parse("s"), Ok(Command::Step));
+        assert_eq!(Command::parse(" break 3 "), Ok(Command::Break(3)));
+        assert!(Command::parse("break").is_err());
+        assert!(Command::parse("break x").is_err());
+        assert!(Command::parse("step 2").is_err());
+        assert!(Command::parse("jump").is_err());
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut host = Host::new();
+        let tokens = parse("🥺2 🥺3 🛑0 💓0 🥺7", true);
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.add_source_breakpoints();
+        interpreter.set_snapshots(2, 8);
+        assert_eq!(position(&interpreter), "0: 🥺2 []");
+
+        let mut run = |command| execute(&mut interpreter, command);
+        assert_eq!(run(Command::Step), Ok("1: 🥺3 [2]".to_string()));
+        assert_eq!(run(Command::Continue), Ok("2: 🛑0 [3, 2]".to_string()));
+        assert_eq!(run(Command::Continue), Ok("finished [7, 6]".to_string()));
+        assert_eq!(run(Command::Back), Ok("4: 🥺7 [6]".to_string()));
+        assert_eq!(run(Command::Reverse), Ok("2: 🛑0 [3, 2]".to_string()));
+        assert_eq!(run(Command::Break(4)), Ok("breakpoint at 4".to_string()));
+        assert_eq!(run(Command::Continue), Ok("4: 🥺7 [6]".to_string()));
+        assert!(run(Command::Reverse).is_ok());
+        assert!(run(Command::Back).is_ok());
+        assert!(run(Command::Back).is_ok());
+        assert!(run(Command::Back).is_err());
+    }
+}
@@ -0,0 +1,386 @@
+/*!
+ * # Dependencies
+ *
+ * the `[deps]` of a manifest name the packages a project includes:
+ *
+ * ```toml
+ * [deps]
+ * fib = "../fib"
+ * negate = { url = "https://example.com/negate.🥺" }
+ * ```
+ *
+ * a package is a directory of programs, maybe with its own `bottom.toml` naming its entry, or a
+ * single program which becomes the entry of the package. `bottom fetch` copies every package
+ * into `vendor/NAME` and records where it came from and the hash of its files in `bottom.lock`,
+ * so a project can be checked in together with exactly the code it was tested with
+ *
+ * packages are not fetched recursively, a project lists every package it includes, even the
+ * ones only its packages include
+ */
+
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::include::vendor_dir;
+use crate::json::Json;
+use crate::manifest::{self, Manifest};
+use crate::sha256::{hex, Sha256};
+#[cfg(feature = "fetch")]
+use crate::source::fetch_source;
+use crate::source::is_url;
+use crate::toml;
+
+/// the name of the lockfile next to the manifest
+pub const LOCKFILE: &str = "bottom.lock";
+
+/// where a package comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// a directory or program, relative to the project
+    Path(String),
+    Url(String),
+}
+
+impl Source {
+    /// how the source is written in the lockfile
+    pub fn as_str(&self) -> &str {
+        match self {
+            Source::Path(path) | Source::Url(path) => path,
+        }
+    }
+}
+
+/// whether `name` can name a package, which becomes a directory right below `vendor`
+fn is_package_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-'))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+    pub source: Source,
+}
+
+impl Dependency {
+    /// the dependency `name = value` of a `[deps]` table
+    pub fn parse(name: &str, value: &Json) -> Result<Self, String> {
+        if !is_package_name(name) {
+            return Err(format!("'{}' is not a valid package name", name));
+        }
+        let source = match (value.as_str(), value.get("path"), value.get("url")) {
+            (Some(location), _, _) if is_url(location) => Source::Url(location.to_string()),
+            (Some(path), _, _) => Source::Path(path.to_string()),
+            (None, Some(path), None) => Source::Path(
+                path.as_str()
+                    .ok_or(format!("the path of '{}' must be a string", name))?
+                    .to_string(),
+            ),
+            (None, None, Some(url)) => Source::Url(
+                url.as_str()
+                    .ok_or(format!("the url of '{}' must be a string", name))?
+                    .to_string(),
+            ),
+            _ => {
+                return Err(format!(
+                    "'{}' needs either a path or a url, e.g. {} = \"../{}\"",
+                    name, name, name
+                ))
+            }
+        };
+        Ok(Dependency {
+            name: name.to_string(),
+            source,
+        })
+    }
+}
+
+/// a package as recorded in the lockfile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locked {
+    pub name: String,
+    pub source: String,
+    pub sha256: String,
+}
+
+/// the packages recorded in `bottom.lock`, in the order they are written
+pub fn read_lock(root: &Path) -> Result<Vec<Locked>, String> {
+    let path = root.join(LOCKFILE);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let source = fs::read_to_string(&path).map_err(|err| err.to_string())?;
+    let Some(Json::Object(packages)) = toml::parse(&source)?.get("deps").cloned() else {
+        return Ok(vec![]);
+    };
+    packages
+        .into_iter()
+        .map(|(name, package)| {
+            if !is_package_name(&name) {
+                return Err(format!(
+                    "'{}' in {} is not a valid package name",
+                    name, LOCKFILE
+                ));
+            }
+            let field = |key| {
+                package
+                    .get(key)
+                    .and_then(Json::as_str)
+                    .map(String::from)
+                    .ok_or(format!("'{}' in {} has no {}", name, LOCKFILE, key))
+            };
+            Ok(Locked {
+                source: field("source")?,
+                sha256: field("sha256")?,
+                name,
+            })
+        })
+        .collect()
+}
+
+fn write_lock(root: &Path, locked: &[Locked]) -> io::Result<()> {
+    let mut lock = "# written by `bottom fetch`, do not edit\n".to_string();
+    for package in locked {
+        let source = Json::String(package.source.clone());
+        write!(
+            lock,
+            "\n[deps.{}]\nsource = {}\nsha256 = \"{}\"\n",
+            package.name, source, package.sha256
+        )
+        .expect("writing to a string cannot fail");
+    }
+    fs::write(root.join(LOCKFILE), lock)
+}
+
+/// all files below `dir` relative to it, sorted, skipping hidden ones
+fn files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = vec![];
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        for entry in fs::read_dir(dir.join(&relative))? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = relative.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+            } else {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// the hash of the names and contents of the files of the package vendored in `dir`
+pub fn hash_package(dir: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    for file in files(dir)? {
+        let contents = fs::read(dir.join(&file))?;
+        // with the separator and length no two packages hash the same bytes
+        hasher.update(file.to_string_lossy().replace('\\', "/").as_bytes());
+        hasher.update(&[0]);
+        hasher.update(&(contents.len() as u64).to_be_bytes());
+        hasher.update(&contents);
+    }
+    Ok(hex(&hasher.finish()))
+}
+
+fn copy_package(from: &Path, to: &Path) -> io::Result<()> {
+    if from.is_file() {
+        fs::create_dir_all(to)?;
+        return fs::copy(from, to.join("main.🥺")).map(|_| ());
+    }
+    for file in files(from)? {
+        // a package's own packages are not part of it
+        if file.starts_with("vendor") || file == Path::new(LOCKFILE) {
+            continue;
+        }
+        let target = to.join(&file);
+        fs::create_dir_all(target.parent().expect("files are inside the package"))?;
+        fs::copy(from.join(&file), target)?;
+    }
+    Ok(())
+}
+
+/// copy or download `dependency` into the vendor directory of the project in `root`
+fn vendor(root: &Path, dependency: &Dependency) -> Result<(), String> {
+    let target = vendor_dir(root).join(&dependency.name);
+    if target.exists() {
+        fs::remove_dir_all(&target).map_err(|err| err.to_string())?;
+    }
+    match &dependency.source {
+        Source::Path(path) => copy_package(&root.join(path), &target)
+            .map_err(|err| format!("could not copy {}: {}", path, err)),
+        #[cfg(feature = "fetch")]
+        Source::Url(url) => {
+            let program =
+                fetch_source(url).map_err(|err| format!("could not download {}: {}", url, err))?;
+            fs::create_dir_all(&target).map_err(|err| err.to_string())?;
+            fs::write(target.join("main.🥺"), program).map_err(|err| err.to_string())
+        }
+        #[cfg(not(feature = "fetch"))]
+        Source::Url(url) => Err(format!("downloading {} needs the fetch feature", url)),
+    }
+}
+
+/// vendor every dependency of `manifest` and write the lockfile
+pub fn fetch(manifest: &Manifest) -> Result<Vec<Locked>, String> {
+    let previous = read_lock(&manifest.root)?;
+    let vendored = vendor_dir(&manifest.root);
+    for stale in previous
+        .iter()
+        .filter(|locked| !manifest.deps.iter().any(|dep| dep.name == locked.name))
+    {
+        let dir = vendored.join(&stale.name);
+        if dir.parent() != Some(vendored.as_path()) {
+            return Err(format!("'{}' is not a package in vendor", stale.name));
+        }
+        if dir.exists() {
+            fs::remove_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+    }
+    let mut locked = vec![];
+    for dependency in &manifest.deps {
+        vendor(&manifest.root, dependency)?;
+        let sha256 = hash_package(&vendor_dir(&manifest.root).join(&dependency.name))
+            .map_err(|err| err.to_string())?;
+        locked.push(Locked {
+            name: dependency.name.clone(),
+            source: dependency.source.as_str().to_string(),
+            sha256,
+        });
+    }
+    write_lock(&manifest.root, &locked).map_err(|err| err.to_string())?;
+    Ok(locked)
+}
+
+/// make sure every dependency is vendored as the lockfile says, if `verify` is set down to the
+/// hash of its files
+pub fn check(manifest: &Manifest, verify: bool) -> Result<(), String> {
+    let locked = read_lock(&manifest.root)?;
+    for dependency in &manifest.deps {
+        let package = locked
+            .iter()
+            .find(|package| package.name == dependency.name)
+            .filter(|package| package.source == dependency.source.as_str());
+        let dir = vendor_dir(&manifest.root).join(&dependency.name);
+        let Some(package) = package.filter(|_| dir.is_dir()) else {
+            return Err(format!(
+                "package '{}' is not fetched, run `bottom fetch`",
+                dependency.name
+            ));
+        };
+        if verify && hash_package(&dir).map_err(|err| err.to_string())? != package.sha256 {
+            return Err(format!(
+                "vendor/{} does not match {}",
+                dependency.name, LOCKFILE
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("fetch")
+        .about("copy the packages in the [deps] of bottom.toml into vendor and lock them")
+        .arg(
+            Arg::with_name("manifest-path")
+                .long("manifest-path")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("use this manifest instead of looking for bottom.toml"),
+        )
+        .arg(
+            Arg::with_name("locked")
+                .long("locked")
+                .help("fetch nothing, only check that vendor matches bottom.lock"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let manifest = manifest::open(args.value_of("manifest-path"));
+    if args.is_present("locked") {
+        if let Err(err) = check(&manifest, true) {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+    match fetch(&manifest) {
+        Ok(locked) => {
+            for package in locked {
+                println!("{:>12} {} ({})", "fetched", package.name, package.source);
+            }
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use crate::deps::{check, fetch, read_lock, Dependency, Source};
+    use crate::json::Json;
+    use crate::manifest::Manifest;
+
+    #[test]
+    fn test_dependency() {
+        let path = Dependency::parse("fib", &Json::String("../fib".to_string())).unwrap();
+        assert_eq!(path.source, Source::Path("../fib".to_string()));
+        let url = Json::Object(vec![(
+            "url".to_string(),
+            Json::String("https://example.com/n.🥺".to_string()),
+        )]);
+        assert!(matches!(
+            Dependency::parse("negate", &url).unwrap().source,
+            Source::Url(_)
+        ));
+        assert!(Dependency::parse("../evil", &Json::String("x".to_string())).is_err());
+        assert!(Dependency::parse("", &Json::String("x".to_string())).is_err());
+        assert!(Dependency::parse("fib", &Json::Int(1)).is_err());
+    }
+
+    #[test]
+    fn test_fetch() {
+        let dir = env::temp_dir().join(format!("bottom-deps-{}", process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::create_dir_all(dir.join("app")).unwrap();
+        fs::write(dir.join("lib/main.🥺"), "🥺2\n").unwrap();
+        let manifest = Manifest::parse("[deps]\ntwo = \"../lib\"\n", &dir.join("app")).unwrap();
+
+        assert!(check(&manifest, false).is_err());
+        let locked = fetch(&manifest).unwrap();
+        assert_eq!(read_lock(&manifest.root).unwrap(), locked);
+        assert_eq!(
+            fs::read_to_string(dir.join("app/vendor/two/main.🥺")).unwrap(),
+            "🥺2\n"
+        );
+        assert_eq!(check(&manifest, true), Ok(()));
+        fs::write(dir.join("app/vendor/two/main.🥺"), "🥺3\n").unwrap();
+        assert!(check(&manifest, true).is_err());
+
+        // a lockfile naming a package outside vendor deletes nothing
+        fs::create_dir_all(dir.join("victim")).unwrap();
+        let lock = "[deps.\"../../victim\"]\nsource = \"x\"\nsha256 = \"0\"\n";
+        fs::write(dir.join("app/bottom.lock"), lock).unwrap();
+        assert!(fetch(&manifest).is_err());
+        assert!(dir.join("victim").is_dir());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
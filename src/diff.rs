@@ -0,0 +1,300 @@
+/*!
+ * # Semantic diff
+ *
+ * `bottom diff a.🥺 b.🥺` compares two programs operation by operation instead of byte by byte,
+ * pairs up operations whose argument changed and reports every 🫂, 🦘 or 🛫 that lands somewhere else than before
+ */
+
+use std::io::{self, IsTerminal};
+use std::process;
+
+use bottom::{hug_target, parse, skip_target, Operations};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::source::read_source;
+
+/// one step of turning the old sequence into the new one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edit {
+    /// the item is in both, at these indices
+    Same(usize, usize),
+    /// the item at this index is only in the old sequence
+    Removed(usize),
+    /// the item at this index is only in the new sequence
+    Added(usize),
+}
+
+/// the shortest edit script turning `old` into `new`, based on their longest common subsequence
+pub fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut edits = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            edits.push(Edit::Removed(i));
+            i += 1;
+        } else {
+            edits.push(Edit::Added(j));
+            j += 1;
+        }
+    }
+    edits
+}
+
+/// a difference between two programs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+    /// the same operation with a different argument
+    Argument(usize, usize),
+}
+
+/// the operation by operation differences of two programs
+pub fn changes(old: &[Operations], new: &[Operations]) -> Vec<Change> {
+    let edits = diff(old, new);
+    let mut changes = vec![];
+    let mut index = 0;
+    while index < edits.len() {
+        // a run of removals followed by a run of additions, pairing up operations of the same kind
+        let removed: Vec<_> = edits[index..]
+            .iter()
+            .map_while(|edit| match edit {
+                Edit::Removed(i) => Some(*i),
+                _ => None,
+            })
+            .collect();
+        let added: Vec<_> = edits[index + removed.len()..]
+            .iter()
+            .map_while(|edit| match edit {
+                Edit::Added(j) => Some(*j),
+                _ => None,
+            })
+            .collect();
+        if removed.is_empty() && added.is_empty() {
+            if let Edit::Same(i, j) = edits[index] {
+                changes.push(Change::Same(i, j));
+            }
+            index += 1;
+            continue;
+        }
+        let mut next_added = 0;
+        for &i in &removed {
+            let same_kind = added[next_added..]
+                .iter()
+                .position(|&j| old[i].emoji() == new[j].emoji());
+            match same_kind {
+                Some(offset) => {
+                    let pairs_with = next_added + offset;
+                    changes.extend(
+                        added[next_added..pairs_with]
+                            .iter()
+                            .map(|&j| Change::Added(j)),
+                    );
+                    changes.push(Change::Argument(i, added[pairs_with]));
+                    next_added = pairs_with + 1;
+                }
+                None => changes.push(Change::Removed(i)),
+            }
+        }
+        changes.extend(added[next_added..].iter().map(|&j| Change::Added(j)));
+        index += removed.len() + added.len();
+    }
+    changes
+}
+
+/// a 🫂, 🦘 or 🛫 in both programs that jumps to a different operation than before
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShiftedJump {
+    /// the jump in the old and new program
+    pub at: (usize, usize),
+    /// where the old jump went, as an index in the new program if the operation is still there
+    pub was: Option<usize>,
+    /// where the new jump goes
+    pub now: Option<usize>,
+}
+
+/// the jumps that land on a different operation after the change
+pub fn shifted_jumps(
+    old: &[Operations],
+    new: &[Operations],
+    changes: &[Change],
+) -> Vec<ShiftedJump> {
+    // the index in the new program of every operation of the old program that is in both
+    let mut moved = vec![None; old.len() + 1];
+    moved[old.len()] = Some(new.len());
+    for change in changes {
+        if let Change::Same(i, j) | Change::Argument(i, j) = *change {
+            moved[i] = Some(j);
+        }
+    }
+    changes
+        .iter()
+        .filter_map(|change| {
+            let (Change::Same(i, j) | Change::Argument(i, j)) = *change else {
+                return None;
+            };
+            let (was, now) = match (old[i], new[j]) {
+                (Operations::Hug(n), Operations::Hug(m)) => (
+                    hug_target(i, n).and_then(|target| moved.get(target).copied().flatten()),
+                    hug_target(j, m),
+                ),
+                // skipping past the end ends the program just like skipping to it
+                (Operations::Skip(n), Operations::Skip(m)) => (
+                    moved[skip_target(i, n).min(old.len())],
+                    Some(skip_target(j, m).min(new.len())),
+                ),
+                (Operations::Call(n), Operations::Call(m)) => {
+                    (moved[n.min(old.len())], Some(m.min(new.len())))
+                }
+                _ => return None,
+            };
+            (was != now).then_some(ShiftedJump {
+                at: (i, j),
+                was,
+                now,
+            })
+        })
+        .collect()
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("diff")
+        .about("compare two 🥺 programs operation by operation")
+        .arg(
+            Arg::with_name("old")
+                .required(true)
+                .value_name("OLD")
+                .help("🥺 program to compare against"),
+        )
+        .arg(
+            Arg::with_name("new")
+                .required(true)
+                .value_name("NEW")
+                .help("🥺 program to compare"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("recognize the extension operations"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let read = |name| {
+        let file = args.value_of(name).expect("required by clap");
+        let source = read_source(file).unwrap_or_else(|err| {
+            eprintln!("error: could not read {}: {}", file, err);
+            process::exit(2);
+        });
+        parse(&source, args.is_present("extensions"))
+    };
+    let (old, new) = (read("old"), read("new"));
+    let changes = changes(&old, &new);
+
+    let color = io::stdout().is_terminal();
+    let paint = |code: &str, line: String| {
+        if color {
+            format!("\x1b[{}m{}\x1b[0m", code, line)
+        } else {
+            line
+        }
+    };
+    let width = old.len().max(new.len()).to_string().len().max(2);
+    let mut different = false;
+    for change in &changes {
+        let line = match *change {
+            Change::Same(i, j) => format!("  {:0w$} {:0w$}  {}", i, j, old[i], w = width),
+            Change::Removed(i) => paint(
+                "31",
+                format!("- {:0w$} {:w$}  {}", i, "", old[i], w = width),
+            ),
+            Change::Added(j) => paint(
+                "32",
+                format!("+ {:w$} {:0w$}  {}", "", j, new[j], w = width),
+            ),
+            Change::Argument(i, j) => paint(
+                "33",
+                format!("~ {:0w$} {:0w$}  {} -> {}", i, j, old[i], new[j], w = width),
+            ),
+        };
+        different |= !matches!(change, Change::Same(..));
+        println!("{}", line);
+    }
+
+    let shifted = shifted_jumps(&old, &new, &changes);
+    if !shifted.is_empty() {
+        println!();
+        different = true;
+    }
+    let describe = |target: Option<usize>| match target {
+        Some(target) => format!("{:0w$}", target, w = width),
+        None => "an operation that was removed".to_string(),
+    };
+    for jump in shifted {
+        println!(
+            "{}",
+            paint(
+                "1",
+                format!(
+                    "jump at {:0w$} (now {:0w$}) used to land on {} and now lands on {}",
+                    jump.at.0,
+                    jump.at.1,
+                    describe(jump.was),
+                    describe(jump.now),
+                    w = width
+                )
+            )
+        );
+    }
+    if different {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::{changes, shifted_jumps, Change, ShiftedJump};
+    use bottom::Operations::*;
+
+    #[test]
+    fn test_changes() {
+        let old = [Push(1), Dup(1), Pop(2), Hug(4)];
+        let new = [Push(1), Dup(1), Push(3), Pop(4), Hug(4)];
+        let changes = changes(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                Change::Same(0, 0),
+                Change::Same(1, 1),
+                Change::Added(2),
+                Change::Argument(2, 3),
+                Change::Same(3, 4),
+            ]
+        );
+        // the loop used to go back to ✨, the inserted 🥺 makes it land on the 🥺 instead
+        assert_eq!(
+            shifted_jumps(&old, &new, &changes),
+            vec![ShiftedJump {
+                at: (3, 4),
+                was: Some(1),
+                now: Some(2),
+            }]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+/*!
+ * # Disassembler
+ *
+ * `bottom disasm file.🥺` prints a numbered listing of the parsed program with resolved jump targets
+ *
+ * ```text
+ * 07: HUG 3  ; jump to 06
+ * ```
+ *
+ * with `--mnemonics` the listing is valid input for `bottom asm`, the instruction numbers become labels
+ *
+ * the [pragma](bottom::pragma) of the program, if it has one, comes first as a comment
+ */
+
+use std::process;
+
+use bottom::pragma::Pragma;
+use bottom::{hug_target, parse, skip_target, Operations};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::asm::mnemonic;
+use crate::source::read_source;
+
+/// a numbered listing of `operations`, one line per operation
+pub fn disassemble(operations: &[Operations], mnemonics: bool) -> String {
+    let width = operations.len().saturating_sub(1).to_string().len().max(2);
+    let mut listing = String::new();
+    for (instruction_pointer, operation) in operations.iter().enumerate() {
+        let instruction = if mnemonics {
+            format!("{} {}", mnemonic(operation), operation.argument())
+        } else {
+            operation.to_string()
+        };
+        listing += &format!("{:0width$}: {}", instruction_pointer, instruction);
+        match *operation {
+            Operations::Hug(n) => match hug_target(instruction_pointer, n) {
+                Some(target) => listing += &format!("  ; jump to {:0width$}", target),
+                None => listing += "  ; jump before the start",
+            },
+            Operations::Skip(n) => match skip_target(instruction_pointer, n) {
+                target if target < operations.len() => {
+                    listing += &format!("  ; skip to {:0width$}", target)
+                }
+                _ => listing += "  ; skip to the end",
+            },
+            Operations::Call(target) => listing += &format!("  ; call {:0width$}", target),
+            _ => {}
+        }
+        listing += "\n";
+    }
+    listing
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("disasm")
+        .about("print a numbered listing of a 🥺 program with resolved jump targets")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .value_name("FILE")
+                .help("🥺 program to read"),
+        )
+        .arg(
+            Arg::with_name("mnemonics")
+                .long("mnemonics")
+                .help("print mnemonics (PUSH 10) instead of emoji"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("recognize the extension operations"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let file = args.value_of("file").expect("required by clap");
+    let source = read_source(file).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", file, err);
+        process::exit(1);
+    });
+    match Pragma::of(&source) {
+        Ok(Some(pragma)) => println!("; {}", pragma),
+        Ok(None) => {}
+        Err(err) => eprintln!("warning: {}", err),
+    }
+    let operations = parse(&source, args.is_present("extensions"));
+    print!("{}", disassemble(&operations, args.is_present("mnemonics")));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::disasm::disassemble;
+    use bottom::Operations::*;
+
+    #[test]
+    fn test_disassemble() {
+        let operations = vec![Push(1), Dup(1), Hug(3)];
+        assert_eq!(
+            disassemble(&operations, false),
+            "00: 🥺1\n01: ✨1\n02: 🫂3  ; jump to 01\n"
+        );
+        let listing = disassemble(&operations, true);
+        assert_eq!(listing, "00: PUSH 1\n01: DUP 1\n02: HUG 3  ; jump to 01\n");
+        assert_eq!(assemble(&listing), Ok(operations));
+    }
+}
@@ -0,0 +1,241 @@
+/*!
+ * # Equivalence checker
+ *
+ * `bottom equiv` runs two programs on the same generated initial unstacks and reports the
+ * first one they behave differently on, to gain confidence that a rewritten program (from the
+ * [minifier](crate::minify), the [optimizer](crate::optimize) or by hand) still does the same
+ *
+ * programs that agree on every generated input may still differ, unless [symbolic execution](crate::symbolic)
+ * also proves them the same for every initial unstack up to the deepest generated one
+ */
+
+use std::process;
+use std::time::Duration;
+
+use bottom::{parse, Limits, Operations, RuntimeError};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::batch::without_panic_messages;
+use crate::golden::{capture, Captured};
+use crate::rng::Rng;
+use crate::source::read_source;
+use crate::symbolic::proven_equivalent;
+
+/// the deepest generated unstack
+const MAX_DEPTH: u64 = 8;
+/// generated values are in `-MAX_VALUE..=MAX_VALUE`
+const MAX_VALUE: i64 = 100;
+
+/// initial unstacks that commonly expose differences, tried before the random ones
+const EDGE_CASES: &[&[i64]] = &[
+    &[],
+    &[0],
+    &[1],
+    &[-1],
+    &[2],
+    &[0, 0],
+    &[1, 1],
+    &[i64::MAX],
+    &[i64::MIN],
+    &[1, 0],
+    &[0, 1],
+];
+
+/// `count` initial unstacks, the edge cases first and then random ones
+pub fn inputs(count: usize, rng: &mut Rng) -> Vec<Vec<i64>> {
+    let mut inputs: Vec<_> = EDGE_CASES
+        .iter()
+        .take(count)
+        .map(|input| input.to_vec())
+        .collect();
+    while inputs.len() < count {
+        let depth = rng.below(MAX_DEPTH + 1);
+        inputs.push(
+            (0..depth)
+                .map(|_| rng.range(-MAX_VALUE, MAX_VALUE))
+                .collect(),
+        );
+    }
+    inputs
+}
+
+/// the outcome of comparing two programs
+#[derive(Debug)]
+pub enum Equivalence {
+    /// both programs behaved the same on every input, `skipped` of them timed out in the first
+    Same { checked: usize, skipped: usize },
+    /// the first input the programs behaved differently on
+    Different {
+        input: Vec<i64>,
+        first: Captured,
+        second: Captured,
+    },
+}
+
+/// run both programs on every input and stop at the first difference
+pub fn compare(
+    first: &[Operations],
+    second: &[Operations],
+    inputs: &[Vec<i64>],
+    limits: &Limits,
+) -> Equivalence {
+    let mut skipped = 0;
+    for input in inputs {
+        let (before, after) = without_panic_messages(|| {
+            (
+                capture(first.to_vec(), input, limits),
+                capture(second.to_vec(), input, limits),
+            )
+        });
+        if let Some(RuntimeError::Timeout { .. }) = before.error {
+            skipped += 1;
+            continue;
+        }
+        if !before.same_behavior(&after) {
+            return Equivalence::Different {
+                input: input.clone(),
+                first: before,
+                second: after,
+            };
+        }
+    }
+    Equivalence::Same {
+        checked: inputs.len(),
+        skipped,
+    }
+}
+
+/// a one line description of how a run ended
+fn describe(captured: &Captured) -> String {
+    match (&captured.error, captured.failed) {
+        (Some(err), _) => format!("failed: {}", err),
+        (None, true) => "panicked".to_string(),
+        (None, false) => format!("printed {:?}", captured.output),
+    }
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("equiv")
+        .about("check whether two 🥺 programs behave the same on generated inputs")
+        .arg(
+            Arg::with_name("first")
+                .required(true)
+                .value_name("FIRST")
+                .help("🥺 program to compare"),
+        )
+        .arg(
+            Arg::with_name("second")
+                .required(true)
+                .value_name("SECOND")
+                .help("🥺 program to compare against"),
+        )
+        .arg(
+            Arg::with_name("inputs")
+                .long("inputs")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("1000")
+                .validator(|n| n.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+                .help("number of initial unstacks to run both programs on"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .value_name("N")
+                .validator(|seed| seed.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                .help("seed for the generated inputs, defaults to the current time"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("recognize the extension operations"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let read = |name| {
+        let file = args.value_of(name).expect("required by clap");
+        let source = read_source(file).unwrap_or_else(|err| {
+            eprintln!("error: could not read {}: {}", file, err);
+            process::exit(1);
+        });
+        parse(&source, args.is_present("extensions"))
+    };
+    let (first, second) = (read("first"), read("second"));
+    let count = args
+        .value_of("inputs")
+        .and_then(|n| n.parse().ok())
+        .expect("validated by clap");
+    let seed = match args.value_of("seed") {
+        Some(seed) => seed.parse().expect("validated by clap"),
+        None => Rng::seed_from_time(),
+    };
+    let inputs = inputs(count, &mut Rng::new(seed));
+
+    let limits = Limits {
+        timeout: Some(Duration::from_millis(100)),
+        ..Limits::default()
+    };
+    match compare(&first, &second, &inputs, &limits) {
+        Equivalence::Same { .. }
+            if (0..=MAX_DEPTH as usize).all(|depth| proven_equivalent(&first, &second, depth)) =>
+        {
+            println!(
+                "proven equivalent for initial unstacks of up to {} values",
+                MAX_DEPTH
+            );
+        }
+        Equivalence::Same { checked, skipped } => {
+            println!("equivalent on {} inputs (seed {})", checked - skipped, seed);
+            if skipped > 0 {
+                eprintln!(
+                    "warning: {} inputs were skipped because the first program did not finish",
+                    skipped
+                );
+            }
+        }
+        Equivalence::Different {
+            input,
+            first,
+            second,
+        } => {
+            println!("different on initial unstack {:?} (seed {})", input, seed);
+            println!("first:  {}", describe(&first));
+            println!("second: {}", describe(&second));
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::equiv::{compare, inputs, Equivalence};
+    use crate::rng::Rng;
+    use bottom::{parse, Limits};
+
+    #[test]
+    fn test_compare() {
+        let inputs = inputs(50, &mut Rng::new(1));
+        assert_eq!(inputs.len(), 50);
+        assert_eq!(inputs[0], Vec::<i64>::new());
+
+        // doubling the top value directly or by first computing the 2
+        let doubled = parse("🥺2 💓0", false);
+        let computed = parse("🥺1 🥺2 💓0 💓0", false);
+        let tripled = parse("🥺3 💓0", false);
+        let limits = Limits::default();
+        assert!(matches!(
+            compare(&doubled, &doubled, &inputs, &limits),
+            Equivalence::Same { checked: 50, .. }
+        ));
+        assert!(matches!(
+            compare(&doubled, &computed, &inputs, &limits),
+            Equivalence::Same { .. }
+        ));
+        match compare(&doubled, &tripled, &inputs, &limits) {
+            Equivalence::Different { input, .. } => assert!(!input.is_empty()),
+            other => panic!("expected a difference, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,285 @@
+/*!
+ * # Extensions
+ *
+ * state the extension operations need besides the unstack, and the [math intrinsics](math),
+ * which only need the unstack and are available without the `std` feature too
+ *
+ * extension operations are only recognized with `--extensions` and never touch the outside world directly,
+ * everything goes through the [`Host`] so the interpreter stays in control of what a program may access
+ */
+
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, VecDeque};
+#[cfg(feature = "std")]
+use std::env;
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+#[cfg(feature = "std")]
+use crate::interpreter::Io;
+#[cfg(feature = "net")]
+use crate::net::Socket;
+#[cfg(feature = "std")]
+use crate::replay::{Event, Session};
+
+pub mod math;
+
+#[cfg(feature = "std")]
+/// something 📖 can read bytes from and 📝 can write bytes to
+#[derive(Debug)]
+pub enum Descriptor {
+    Stdin,
+    Stdout,
+    Stderr,
+    /// an in-memory pipe, reading takes bytes from the front and writing appends them
+    Memory(VecDeque<u8>),
+    /// a file that is opened for reading or writing the first time it is used that way
+    File {
+        path: PathBuf,
+        reader: Option<BufReader<File>>,
+        writer: Option<BufWriter<File>>,
+    },
+    /// a listening TCP socket, reading and writing use the connection last accepted with 📞
+    #[cfg(feature = "net")]
+    Socket(Socket),
+}
+
+#[cfg(feature = "std")]
+impl Descriptor {
+    /// a descriptor for the file at `path`, nothing is opened yet
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Descriptor::File {
+            path: path.into(),
+            reader: None,
+            writer: None,
+        }
+    }
+
+    /// read a single byte, `None` at the end of the input
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut buf = [0];
+        let read = match self {
+            Descriptor::Stdin => io::stdin().read(&mut buf)?,
+            Descriptor::Stdout | Descriptor::Stderr => {
+                return Err(io::Error::new(ErrorKind::Unsupported, "not readable"))
+            }
+            Descriptor::Memory(buffer) => return Ok(buffer.pop_front()),
+            Descriptor::File { path, reader, .. } => {
+                if reader.is_none() {
+                    *reader = Some(BufReader::new(File::open(path)?));
+                }
+                reader.as_mut().unwrap().read(&mut buf)?
+            }
+            #[cfg(feature = "net")]
+            Descriptor::Socket(socket) => return socket.read_byte(),
+        };
+        Ok(if read == 0 { None } else { Some(buf[0]) })
+    }
+
+    fn write_byte(&mut self, byte: u8) -> io::Result<()> {
+        match self {
+            Descriptor::Stdin => Err(io::Error::new(ErrorKind::Unsupported, "not writable")),
+            Descriptor::Stdout => io::stdout().write_all(&[byte]),
+            Descriptor::Stderr => io::stderr().write_all(&[byte]),
+            Descriptor::Memory(buffer) => {
+                buffer.push_back(byte);
+                Ok(())
+            }
+            Descriptor::File { path, writer, .. } => {
+                if writer.is_none() {
+                    *writer = Some(BufWriter::new(File::create(path)?));
+                }
+                writer.as_mut().unwrap().write_all(&[byte])
+            }
+            #[cfg(feature = "net")]
+            Descriptor::Socket(socket) => socket.write_byte(byte),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Descriptor::Stdin | Descriptor::Memory(_) => Ok(()),
+            Descriptor::Stdout => io::stdout().flush(),
+            Descriptor::Stderr => io::stderr().flush(),
+            Descriptor::File { writer, .. } => match writer {
+                Some(writer) => writer.flush(),
+                None => Ok(()),
+            },
+            #[cfg(feature = "net")]
+            Descriptor::Socket(socket) => socket.flush(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+/// everything extension operations can reach outside of the unstack
+#[derive(Debug)]
+pub struct Host {
+    /// names of the environment variables readable with 🏠, by index
+    pub env_names: Vec<String>,
+    /// descriptors usable with 📖 and 📝, by number
+    pub descriptors: BTreeMap<usize, Descriptor>,
+    /// where the values read by the program come from
+    pub session: Session,
+}
+
+#[cfg(feature = "std")]
+impl Default for Host {
+    fn default() -> Self {
+        let mut descriptors = BTreeMap::new();
+        descriptors.insert(0, Descriptor::Stdin);
+        descriptors.insert(1, Descriptor::Stdout);
+        descriptors.insert(2, Descriptor::Stderr);
+        Host {
+            env_names: vec![],
+            descriptors,
+            session: Session::Live,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Host {
+    /// create a host with only the standard streams as descriptors 0, 1 and 2
+    pub fn new() -> Self {
+        Host::default()
+    }
+
+    /// create a host whose standard streams are in-memory pipes, with `stdin` ready to be read
+    pub fn captured(stdin: &[u8]) -> Self {
+        let mut host = Host::default();
+        host.descriptors
+            .insert(0, Descriptor::Memory(stdin.iter().copied().collect()));
+        host.descriptors
+            .insert(1, Descriptor::Memory(VecDeque::new()));
+        host.descriptors
+            .insert(2, Descriptor::Memory(VecDeque::new()));
+        host
+    }
+
+    /// everything written to the in-memory descriptor `fd` so far, empty for other descriptors
+    pub fn captured_output(&self, fd: usize) -> Vec<u8> {
+        match self.descriptors.get(&fd) {
+            Some(Descriptor::Memory(buffer)) => buffer.iter().copied().collect(),
+            _ => vec![],
+        }
+    }
+
+    fn descriptor(&mut self, fd: usize) -> io::Result<&mut Descriptor> {
+        self.descriptors.get_mut(&fd).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                format!("file descriptor {} is not configured", fd),
+            )
+        })
+    }
+
+    /// read a byte from descriptor `fd`, -1 at the end of the input
+    pub fn read(&mut self, fd: usize) -> io::Result<i64> {
+        if self.session.is_replaying() {
+            return match self.session.replay_event(Event::Read { fd, value: 0 })? {
+                Event::Read { value, .. } => Ok(value),
+                _ => unreachable!("replay_event checks the kind"),
+            };
+        }
+        let value = match self.descriptor(fd)?.read_byte()? {
+            Some(byte) => byte as i64,
+            None => -1,
+        };
+        self.session.record_event(Event::Read { fd, value })?;
+        Ok(value)
+    }
+
+    /// write the lowest byte of `value` to descriptor `fd`
+    pub fn write(&mut self, fd: usize, value: i64) -> io::Result<()> {
+        // sockets never get a connection when replaying
+        #[cfg(feature = "net")]
+        if self.session.is_replaying() && matches!(self.descriptor(fd)?, Descriptor::Socket(_)) {
+            return Ok(());
+        }
+        self.descriptor(fd)?.write_byte((value & 0xff) as u8)
+    }
+
+    /// wait for a connection on the socket `fd`
+    pub fn accept(&mut self, fd: usize) -> io::Result<()> {
+        if self.session.is_replaying() {
+            return self.session.replay_event(Event::Accept { fd }).map(|_| ());
+        }
+        let accepted = match self.descriptor(fd)? {
+            #[cfg(feature = "net")]
+            Descriptor::Socket(socket) => socket.accept(),
+            _ => Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("file descriptor {} is not a socket", fd),
+            )),
+        };
+        accepted?;
+        self.session.record_event(Event::Accept { fd })
+    }
+
+    /// flush everything written to any descriptor and the session log
+    pub fn flush(&mut self) -> io::Result<()> {
+        for descriptor in self.descriptors.values_mut() {
+            descriptor.flush()?;
+        }
+        self.session.flush()
+    }
+
+    /// name of the Nth environment variable
+    ///
+    /// indices without a configured name fall back to `BOTTOM_N`
+    pub fn env_name(&self, index: usize) -> String {
+        match self.env_names.get(index) {
+            Some(name) => name.clone(),
+            None => format!("BOTTOM_{}", index),
+        }
+    }
+
+    /// integer value of the Nth environment variable, 0 if it is unset
+    pub fn env(&mut self, index: usize) -> io::Result<i64> {
+        if self.session.is_replaying() {
+            return match self.session.replay_event(Event::Env { index, value: 0 })? {
+                Event::Env { value, .. } => Ok(value),
+                _ => unreachable!("replay_event checks the kind"),
+            };
+        }
+        let name = self.env_name(index);
+        let value = match env::var(&name) {
+            Ok(value) => value.trim().parse().map_err(|_| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "environment variable {} is not an integer: {:?}",
+                        name, value
+                    ),
+                )
+            })?,
+            Err(_) => 0,
+        };
+        self.session.record_event(Event::Env { index, value })?;
+        Ok(value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Io for Host {
+    fn env(&mut self, index: usize) -> Result<i64, String> {
+        Host::env(self, index).map_err(|err| err.to_string())
+    }
+
+    fn read(&mut self, fd: usize) -> Result<i64, String> {
+        Host::read(self, fd).map_err(|err| err.to_string())
+    }
+
+    fn write(&mut self, fd: usize, value: i64) -> Result<(), String> {
+        Host::write(self, fd, value).map_err(|err| err.to_string())
+    }
+
+    fn accept(&mut self, fd: usize) -> Result<(), String> {
+        Host::accept(self, fd).map_err(|err| err.to_string())
+    }
+}
@@ -0,0 +1,189 @@
+/*!
+ * # Math intrinsics
+ *
+ * extension operations that compute a value from the bottom values of the unstack, like the
+ * absolute value or a bitwise and, all described by a row of [`TABLE`]
+ *
+ * like 💓, an intrinsic pops its operands, then pops and discards N values, then pushes the
+ * result. the first operand is the value popped first, so `🥺2 🥺10 💪0` pushes 2 to the 10th
+ * and `🥺1 🥺4 ⏪0` pushes 1 shifted left by 4
+ *
+ * comparisons push 1 if they hold and 0 otherwise, ready for a 🫂
+ */
+
+use crate::{OperationEntry, Operations};
+
+/// an intrinsic, the index of its row in [`TABLE`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Intrinsic(pub u8);
+
+/// the most operands an intrinsic takes
+pub const MAX_ARITY: usize = 2;
+
+/// why an intrinsic has no result if the result does not fit in an `i64`
+pub const OVERFLOW: &str = "overflow";
+
+/// everything there is to know about an intrinsic
+pub struct Entry {
+    pub emoji: &'static str,
+    /// the name of the intrinsic in assembly
+    pub mnemonic: &'static str,
+    /// the number of operands, at most [`MAX_ARITY`]
+    pub arity: usize,
+    /// the result for the operands, first popped first, or why there is none
+    pub apply: fn(&[i64]) -> Result<i64, &'static str>,
+    /// the operation with argument N
+    pub make: fn(i64) -> Operations,
+}
+
+/// the intrinsic in row `INDEX` with argument `n`
+fn make<const INDEX: u8>(n: i64) -> Operations {
+    Operations::Math(Intrinsic(INDEX), n as usize)
+}
+
+/// all intrinsics, a new one only needs a row here
+pub const TABLE: &[Entry] = &[
+    Entry {
+        emoji: "📏",
+        mnemonic: "ABS",
+        arity: 1,
+        apply: |operands| operands[0].checked_abs().ok_or(OVERFLOW),
+        make: make::<0>,
+    },
+    Entry {
+        emoji: "💪",
+        mnemonic: "POW",
+        arity: 2,
+        apply: |operands| {
+            let exponent = u32::try_from(operands[0]).map_err(|_| "negative exponent")?;
+            operands[1].checked_pow(exponent).ok_or(OVERFLOW)
+        },
+        make: make::<1>,
+    },
+    Entry {
+        emoji: "🔻",
+        mnemonic: "MIN",
+        arity: 2,
+        apply: |operands| Ok(operands[0].min(operands[1])),
+        make: make::<2>,
+    },
+    Entry {
+        emoji: "🔺",
+        mnemonic: "MAX",
+        arity: 2,
+        apply: |operands| Ok(operands[0].max(operands[1])),
+        make: make::<3>,
+    },
+    Entry {
+        emoji: "🤝",
+        mnemonic: "AND",
+        arity: 2,
+        apply: |operands| Ok(operands[0] & operands[1]),
+        make: make::<4>,
+    },
+    Entry {
+        emoji: "🔀",
+        mnemonic: "OR",
+        arity: 2,
+        apply: |operands| Ok(operands[0] | operands[1]),
+        make: make::<5>,
+    },
+    Entry {
+        emoji: "❌",
+        mnemonic: "XOR",
+        arity: 2,
+        apply: |operands| Ok(operands[0] ^ operands[1]),
+        make: make::<6>,
+    },
+    Entry {
+        emoji: "⏪",
+        mnemonic: "SHL",
+        arity: 2,
+        apply: |operands| Ok(operands[1] << shift(operands[0])?),
+        make: make::<7>,
+    },
+    Entry {
+        emoji: "⏩",
+        mnemonic: "SHR",
+        arity: 2,
+        // arithmetic, negative values stay negative
+        apply: |operands| Ok(operands[1] >> shift(operands[0])?),
+        make: make::<8>,
+    },
+    Entry {
+        emoji: "🤏",
+        mnemonic: "LT",
+        arity: 2,
+        apply: |operands| Ok((operands[1] < operands[0]) as i64),
+        make: make::<9>,
+    },
+    Entry {
+        emoji: "🟰",
+        mnemonic: "EQ",
+        arity: 2,
+        apply: |operands| Ok((operands[1] == operands[0]) as i64),
+        make: make::<10>,
+    },
+];
+
+/// the number of bits to shift by, if it fits an `i64`
+fn shift(bits: i64) -> Result<u32, &'static str> {
+    u32::try_from(bits)
+        .ok()
+        .filter(|&bits| bits < i64::BITS)
+        .ok_or("shift out of range")
+}
+
+impl Intrinsic {
+    pub fn entry(self) -> &'static Entry {
+        &TABLE[self.0 as usize]
+    }
+}
+
+/// the emoji of every intrinsic for the tokenizer
+pub fn operations() -> impl Iterator<Item = OperationEntry> {
+    TABLE.iter().map(|entry| (entry.emoji, entry.make))
+}
+
+/// the mnemonic of every intrinsic for the assembler
+pub fn mnemonics() -> impl Iterator<Item = OperationEntry> {
+    TABLE.iter().map(|entry| (entry.mnemonic, entry.make))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ext::math::{Intrinsic, MAX_ARITY, TABLE};
+    use crate::ext::Host;
+    use crate::interpreter::interpret;
+    use crate::unstack::Unstack;
+    use crate::{parse, Limits, Operations, RuntimeError};
+
+    #[test]
+    fn test_table() {
+        for (index, entry) in TABLE.iter().enumerate() {
+            let intrinsic = Intrinsic(index as u8);
+            assert_eq!((entry.make)(3), Operations::Math(intrinsic, 3));
+            assert!(entry.arity <= MAX_ARITY);
+        }
+    }
+
+    #[test]
+    fn test_intrinsics() {
+        let run = |source, init: &[i64]| -> Result<Vec<i64>, RuntimeError> {
+            let unstack = Unstack::from(init.to_vec());
+            let tokens = parse(source, true);
+            interpret(tokens, unstack, &mut Host::new(), &Limits::default())
+        };
+        assert_eq!(run("📏0", &[-5]), Ok(vec![5]));
+        assert_eq!(run("🥺2 🥺10 💪0", &[]), Ok(vec![1024]));
+        assert_eq!(run("🥺4 🥺9 🔻1", &[7]), Ok(vec![4]));
+        assert_eq!(run("🥺9 🔺0", &[-4]), Ok(vec![9]));
+        assert_eq!(run("🥺12 🥺10 🤝0", &[]), Ok(vec![8]));
+        assert_eq!(run("🥺12 🥺10 🔀0", &[]), Ok(vec![14]));
+        assert_eq!(run("🥺12 🥺10 ❌0", &[]), Ok(vec![6]));
+        assert_eq!(run("🥺1 🥺4 ⏪0", &[]), Ok(vec![16]));
+        assert_eq!(run("🥺2 ⏩0", &[-16]), Ok(vec![-4]));
+        assert_eq!(run("🥺3 🥺5 🤏0 🥺5 🥺3 🤏0", &[]), Ok(vec![0, 1]));
+        assert_eq!(run("🥺7 🟰0 🥺7 🥺7 🟰0", &[7]), Ok(vec![1, 1]));
+    }
+}
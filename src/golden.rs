@@ -0,0 +1,256 @@
+/*!
+ * # Golden file tests
+ *
+ * `bottom test DIR` runs every `foo.🥺` below a directory that has a sibling `foo.expected`
+ * and compares what the program prints with that file
+ *
+ * the expected file holds exactly what `bottom foo.🥺` would print to stdout: the bytes written to
+ * file descriptor 1 followed by the final unstack, or by `error: ...` if the program failed
+ *
+ * with `--bless` the expected files are (re)written from the actual output instead
+ *
+ * programs without an expected file still run and pass as long as they finish without an error,
+ * so programs checking themselves with ✅ need no expected file, and a failed ✅ is never blessed
+ */
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use bottom::ext::Host;
+use bottom::interpreter::interpret;
+use bottom::unstack::Unstack;
+use bottom::{parse, Limits, Operations, RuntimeError};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::batch::{discover, panic_message, without_panic_messages};
+use crate::diff::{diff, Edit};
+use crate::parse_duration;
+use crate::source::read_source;
+
+/// what a program printed, and how it ended
+#[derive(Debug)]
+pub struct Captured {
+    /// everything the program would print to stdout
+    pub output: String,
+    /// whether the program stopped with an error or a panic
+    pub failed: bool,
+    /// the error the program stopped with, `None` if it finished or panicked
+    pub error: Option<RuntimeError>,
+}
+
+impl Captured {
+    /// whether the program stopped at a ✅
+    pub fn failed_assertion(&self) -> bool {
+        matches!(self.error, Some(RuntimeError::AssertionFailed { .. }))
+    }
+
+    /// whether both runs behaved the same
+    ///
+    /// error messages mention instruction pointers, which differ between equivalent programs,
+    /// so any two failures count as the same
+    pub fn same_behavior(&self, other: &Captured) -> bool {
+        if self.failed {
+            other.failed
+        } else {
+            !other.failed && self.output == other.output
+        }
+    }
+}
+
+/// run `source` with an empty stdin and capture what it would print to stdout
+pub fn run_captured(source: &str, extensions: bool, limits: &Limits) -> Captured {
+    capture(parse(source, extensions), &[], limits)
+}
+
+/// run `operations` starting with the `initial` values pushed onto the unstack and an empty stdin
+pub fn capture(operations: Vec<Operations>, initial: &[i64], limits: &Limits) -> Captured {
+    let mut host = Host::captured(&[]);
+    let unstack = Unstack::from(initial.to_vec());
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        interpret(operations, unstack, &mut host, limits)
+    }));
+    let mut output = String::from_utf8_lossy(&host.captured_output(1)).into_owned();
+    let (failed, error) = match result {
+        Ok(Ok(unstack)) => {
+            output += &format!("{:?}\n", unstack);
+            (false, None)
+        }
+        Ok(Err(err)) => {
+            output += &format!("error: {}\n", err);
+            (true, Some(err))
+        }
+        Err(payload) => {
+            output += &format!("error: {}\n", panic_message(payload));
+            (true, None)
+        }
+    };
+    Captured {
+        output,
+        failed,
+        error,
+    }
+}
+
+/// the expected file belonging to a program
+pub fn expected_path(program: &Path) -> PathBuf {
+    program.with_extension("expected")
+}
+
+/// a line based diff of `expected` and `actual`, lines only in one of them are marked with `-` or `+`
+pub fn diff_lines(expected: &str, actual: &str) -> Vec<String> {
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+    diff(&expected, &actual)
+        .into_iter()
+        .map(|edit| match edit {
+            Edit::Same(i, _) => format!("  {}", expected[i]),
+            Edit::Removed(i) => format!("- {}", expected[i]),
+            Edit::Added(j) => format!("+ {}", actual[j]),
+        })
+        .collect()
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("test")
+        .about("run .🥺 programs and compare their output with their .expected files")
+        .arg(
+            Arg::with_name("dir")
+                .required(true)
+                .value_name("DIR")
+                .help("directory searched recursively for .🥺 files"),
+        )
+        .arg(
+            Arg::with_name("bless")
+                .long("bless")
+                .help("write the actual output to the .expected files instead of comparing"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("enable the extension operations"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .value_name("DURATION")
+                .validator(|duration| parse_duration(&duration).map(|_| ()))
+                .help("abort each program after running for DURATION"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let root = Path::new(args.value_of("dir").expect("required by clap"));
+    let bless = args.is_present("bless");
+    let extensions = args.is_present("extensions");
+    let mut limits = Limits::default();
+    if let Some(timeout) = args.value_of("timeout") {
+        limits.timeout = Some(parse_duration(timeout).expect("validated by clap"));
+    }
+    let programs = discover(root).unwrap_or_else(|err| {
+        eprintln!("error: could not search {}: {}", root.display(), err);
+        process::exit(1);
+    });
+
+    let (mut passed, mut failed, mut blessed) = (0, 0, 0);
+    for program in &programs {
+        let name = program.strip_prefix(root).unwrap_or(program).display();
+        let expected_path = expected_path(program);
+        let expected = fs::read_to_string(&expected_path).ok();
+        let captured = match read_source(program) {
+            Ok(source) => without_panic_messages(|| run_captured(&source, extensions, &limits)),
+            Err(err) => {
+                println!("FAIL {}: could not read: {}", name, err);
+                failed += 1;
+                continue;
+            }
+        };
+        if captured.failed_assertion() {
+            println!(
+                "FAIL {}: {}",
+                name,
+                captured.error.expect("failed assertion")
+            );
+            failed += 1;
+            continue;
+        }
+        let actual = captured.output;
+        if expected.is_none() && !bless {
+            if captured.failed {
+                println!("FAIL {}", name);
+                print!("{}", actual);
+                failed += 1;
+            } else {
+                println!("ok   {}", name);
+                passed += 1;
+            }
+        } else if expected.as_deref() == Some(actual.as_str()) {
+            println!("ok   {}", name);
+            passed += 1;
+        } else if bless {
+            if let Err(err) = fs::write(&expected_path, &actual) {
+                println!(
+                    "FAIL {}: could not write {}: {}",
+                    name,
+                    expected_path.display(),
+                    err
+                );
+                failed += 1;
+            } else {
+                println!("bless {}", name);
+                blessed += 1;
+            }
+        } else {
+            println!("FAIL {}", name);
+            for line in diff_lines(expected.as_deref().unwrap_or_default(), &actual) {
+                println!("     {}", line);
+            }
+            failed += 1;
+        }
+    }
+
+    println!(
+        "\n{} passed, {} failed{}",
+        passed,
+        failed,
+        if bless {
+            format!(", {} blessed", blessed)
+        } else {
+            String::new()
+        }
+    );
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::golden::{diff_lines, run_captured};
+    use bottom::Limits;
+
+    #[test]
+    fn test_run_captured() {
+        let limits = Limits::default();
+        let captured = run_captured("🥺104 📝1 🥺2\n", true, &limits);
+        assert_eq!(captured.output, "h[2]\n");
+        assert!(!captured.failed);
+
+        let captured = run_captured("💖2\n", false, &limits);
+        assert!(captured.output.starts_with("error: 💖"));
+        assert!(captured.failed && !captured.failed_assertion());
+
+        let captured = run_captured("🥺1 🥺2 ✅0\n", true, &limits);
+        assert!(captured.failed && captured.failed_assertion());
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        assert_eq!(
+            diff_lines("a\nb\nc\n", "a\nx\nc\n"),
+            vec!["  a", "- b", "+ x", "  c"]
+        );
+    }
+}
@@ -0,0 +1,454 @@
+/*!
+ * # gzip
+ *
+ * reading and writing gzip files (RFC 1952) around DEFLATE (RFC 1951), so sources can be
+ * stored compressed
+ *
+ * decompression understands everything DEFLATE allows, compression writes a single block
+ * with the fixed Huffman codes, which is plenty for the few distinct bytes emoji are made of
+ */
+
+/// the first two bytes of every gzip file
+pub const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// base lengths of the length codes 257 to 285 and the number of extra bits after them
+const LENGTHS: [(u16, u8); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+
+/// base distances of the distance codes and the number of extra bits after them
+const DISTANCES: [(u16, u8); 30] = [
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 1),
+    (7, 1),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 4),
+    (49, 4),
+    (65, 5),
+    (97, 5),
+    (129, 6),
+    (193, 6),
+    (257, 7),
+    (385, 7),
+    (513, 8),
+    (769, 8),
+    (1025, 9),
+    (1537, 9),
+    (2049, 10),
+    (3073, 10),
+    (4097, 11),
+    (6145, 11),
+    (8193, 12),
+    (12289, 12),
+    (16385, 13),
+    (24577, 13),
+];
+
+/// order in which the code lengths of the code length alphabet are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const WINDOW: usize = 32768;
+
+/// the CRC-32 gzip stores of the uncompressed data
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// reads bits starting with the least significant one of each byte
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit: u8,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> Result<u32, String> {
+        let byte = *self
+            .data
+            .get(self.position)
+            .ok_or("compressed data ends early")?;
+        let bit = (byte >> self.bit) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.position += 1;
+        }
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, count: u8) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.position += 1;
+        }
+    }
+}
+
+/// a canonical Huffman code given by the code length of every symbol
+struct Huffman {
+    /// number of codes of every length
+    counts: [u16; 16],
+    /// symbols ordered by their code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<_> = (0..lengths.len() as u16)
+            .filter(|&symbol| lengths[symbol as usize] != 0)
+            .collect();
+        symbols.sort_by_key(|&symbol| lengths[symbol as usize]);
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        // codes of each length follow the last code of the length before
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= bits.bit()? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [8u8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literals = bits.bits(5)? as usize + 257;
+    let distances = bits.bits(5)? as usize + 1;
+    let code_lengths = bits.bits(4)? as usize + 4;
+    let mut lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[symbol] = bits.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&lengths);
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (length, repeat) = match code_length_code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or("repeat without a code length")?;
+                (previous, 3 + bits.bits(2)?)
+            }
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    if lengths.len() != literals + distances {
+        return Err("code lengths overflow".to_string());
+    }
+    Ok((
+        Huffman::new(&lengths[..literals]),
+        Huffman::new(&lengths[literals..]),
+    ))
+}
+
+/// decompress raw DEFLATE data, returning the data and the number of bytes it took up
+pub fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let mut bits = Bits {
+        data,
+        position: 0,
+        bit: 0,
+    };
+    let mut output = vec![];
+    loop {
+        let last = bits.bit()? == 1;
+        let (literals, distances) = match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = data
+                    .get(bits.position..bits.position + 4)
+                    .ok_or("stored block ends early")?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let start = bits.position + 4;
+                let stored = data
+                    .get(start..start + length)
+                    .ok_or("stored block ends early")?;
+                output.extend_from_slice(stored);
+                bits.position = start + length;
+                if last {
+                    return Ok((output, bits.position));
+                }
+                continue;
+            }
+            1 => fixed_codes(),
+            2 => dynamic_codes(&mut bits)?,
+            _ => return Err("invalid block type".to_string()),
+        };
+        loop {
+            let symbol = literals.decode(&mut bits)?;
+            match symbol {
+                0..=255 => output.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let (base, extra) = *LENGTHS
+                        .get(symbol as usize - 257)
+                        .ok_or("invalid length code")?;
+                    let length = base as usize + bits.bits(extra)? as usize;
+                    let (base, extra) = *DISTANCES
+                        .get(distances.decode(&mut bits)? as usize)
+                        .ok_or("invalid distance code")?;
+                    let distance = base as usize + bits.bits(extra)? as usize;
+                    if distance > output.len() {
+                        return Err("distance too far back".to_string());
+                    }
+                    let start = output.len() - distance;
+                    for i in 0..length {
+                        output.push(output[start + i]);
+                    }
+                }
+            }
+        }
+        if last {
+            bits.align();
+            return Ok((output, bits.position));
+        }
+    }
+}
+
+/// the contents of a gzip file, which may consist of several members
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut output = vec![];
+    let mut rest = data;
+    while !rest.is_empty() {
+        if rest.len() < 18 || rest[..2] != MAGIC || rest[2] != 8 {
+            return Err("not a gzip file".to_string());
+        }
+        let flags = rest[3];
+        let mut position = 10;
+        if flags & 0x04 != 0 {
+            let extra = rest
+                .get(position..position + 2)
+                .ok_or("header ends early")?;
+            position += 2 + u16::from_le_bytes([extra[0], extra[1]]) as usize;
+        }
+        // file name and comment
+        for flag in [0x08, 0x10] {
+            if flags & flag != 0 {
+                let end = rest
+                    .get(position..)
+                    .and_then(|rest| rest.iter().position(|&byte| byte == 0))
+                    .ok_or("header ends early")?;
+                position += end + 1;
+            }
+        }
+        if flags & 0x02 != 0 {
+            position += 2;
+        }
+        let (member, length) = inflate(rest.get(position..).ok_or("header ends early")?)?;
+        position += length;
+        let trailer = rest
+            .get(position..position + 8)
+            .ok_or("trailer is missing")?;
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        if crc != crc32(&member) {
+            return Err("checksum mismatch".to_string());
+        }
+        output.extend(member);
+        rest = &rest[position + 8..];
+    }
+    Ok(output)
+}
+
+/// writes bits starting with the least significant one of each byte
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit: u8,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, count: u8) {
+        for i in 0..count {
+            if self.bit == 0 {
+                self.bytes.push(0);
+            }
+            let bit = ((value >> i) & 1) as u8;
+            *self.bytes.last_mut().unwrap() |= bit << self.bit;
+            self.bit = (self.bit + 1) % 8;
+        }
+    }
+
+    /// a Huffman code, which is stored starting with its most significant bit
+    fn code(&mut self, code: u32, length: u8) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.bits(reversed, length);
+    }
+
+    /// a symbol of the fixed literal and length code
+    fn literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xc0 + symbol - 280, 8),
+        }
+    }
+}
+
+/// the code and extra bits of `value` in a table of base values
+fn encode_base(table: &[(u16, u8)], value: usize) -> (usize, u32, u8) {
+    let code = table
+        .iter()
+        .rposition(|&(base, _)| base as usize <= value)
+        .expect("values start at the first base");
+    let (base, extra) = table[code];
+    (code, (value - base as usize) as u32, extra)
+}
+
+/// compress `data` into raw DEFLATE, repeats are found through a hash of the next three bytes
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    // a single, final block with the fixed codes
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+    let hash = |i: usize| {
+        ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & 0x7fff
+    };
+    let mut last_seen = vec![usize::MAX; 0x8000];
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            let candidate = last_seen[h];
+            last_seen[h] = i;
+            if candidate != usize::MAX && i - candidate <= WINDOW {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[i..])
+                    .take(258)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length >= 3 {
+                    best = (length, i - candidate);
+                }
+            }
+        }
+        let (length, distance) = best;
+        if length == 0 {
+            writer.literal(data[i] as u16);
+            i += 1;
+            continue;
+        }
+        let (code, extra, count) = encode_base(&LENGTHS, length);
+        writer.literal(257 + code as u16);
+        writer.bits(extra, count);
+        let (code, extra, count) = encode_base(&DISTANCES, distance);
+        writer.code(code as u32, 5);
+        writer.bits(extra, count);
+        for j in i + 1..(i + length).min(data.len().saturating_sub(2)) {
+            last_seen[hash(j)] = j;
+        }
+        i += length;
+    }
+    writer.literal(256);
+    writer.bytes
+}
+
+/// `data` as a gzip file
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    // no flags, no modification time, unknown operating system
+    let mut output = vec![MAGIC[0], MAGIC[1], 8, 0, 0, 0, 0, 0, 0, 255];
+    output.extend(deflate(data));
+    output.extend_from_slice(&crc32(data).to_le_bytes());
+    output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gzip::{compress, decompress};
+
+    #[test]
+    fn test_round_trip() {
+        let source = "🥺🥺🥺 🥺104 📝1 ".repeat(50) + "✨1\n";
+        let compressed = compress(source.as_bytes());
+        assert!(compressed.len() < source.len() / 10);
+        assert_eq!(decompress(&compressed).unwrap(), source.as_bytes());
+        assert_eq!(decompress(&compress(b"")).unwrap(), b"");
+
+        // `printf 'hello hello hello\n' | gzip -n`
+        let gzipped = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0xc8, 0x40, 0x90, 0x5c, 0x00, 0x3b, 0x7c, 0x8a, 0xdf, 0x12, 0x00, 0x00,
+            0x00,
+        ];
+        assert_eq!(decompress(&gzipped).unwrap(), b"hello hello hello\n");
+        assert!(decompress(&gzipped[..20]).is_err());
+    }
+}
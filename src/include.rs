@@ -0,0 +1,294 @@
+/*!
+ * # Includes
+ *
+ * a line of the form
+ *
+ * ```text
+ * include "lib/negate.🥺"
+ * include fib
+ * include fib/table.🥺
+ * include <std/square>
+ * ```
+ *
+ * is replaced by the program it names before parsing. a quoted path is relative to the file
+ * the line is in, a bare name is a package from the `[deps]` of the manifest, fetched into
+ * its `vendor` directory by `bottom fetch`. naming just the package includes its entry.
+ * `<std/NAME>` is a routine of the [standard library](STD) built into `bottom`
+ *
+ * an included program is pasted in every time it is included, like a macro. its 🫂 and 🦘 only
+ * jump within itself and stay valid wherever it ends up, but a 🛫 names an instruction counted
+ * from the start of the whole program
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::manifest::{self, Manifest};
+use crate::source::read_source;
+
+/// what an `include` line refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target<'a> {
+    /// a file relative to the including one
+    File(&'a str),
+    /// a package, or a file inside it if the name is followed by a path
+    Package(&'a str),
+    /// a routine of the standard library
+    Std(&'a str),
+}
+
+/// the standard library, routines working on the bottom values of the unstack
+///
+/// 🥺 can only multiply and divide, and its literals are never negative, so negation relies on
+/// wrapping overflow and modulo on the bit operations of the extensions. `std/tests` has a test
+/// program for each routine
+pub const STD: &[(&str, &str)] = &[
+    ("drop", include_str!("../std/drop.🥺")),
+    ("modulo", include_str!("../std/modulo.🥺")),
+    ("negate", include_str!("../std/negate.🥺")),
+    ("next-pow2", include_str!("../std/next-pow2.🥺")),
+    ("print-number", include_str!("../std/print-number.🥺")),
+    ("square", include_str!("../std/square.🥺")),
+];
+
+/// the routines of [`STD`] that use extension operations, which only include with extensions on
+pub const STD_EXTENSIONS: &[&str] = &["modulo", "print-number"];
+
+/// the target of `line` if it is an include
+///
+/// anything else on the line means it is not an include, so prose like "include the header" in
+/// comments is left alone
+pub fn directive(line: &str) -> Option<Target<'_>> {
+    let target = line.trim().strip_prefix("include")?;
+    if !target.starts_with([' ', '\t']) {
+        return None;
+    }
+    let target = target.trim();
+    if let Some(name) = target.strip_prefix("<std/") {
+        return name.strip_suffix('>').map(Target::Std);
+    }
+    if let Some(path) = target.strip_prefix('"') {
+        return path
+            .strip_suffix('"')
+            .filter(|path| !path.is_empty() && !path.contains('"'))
+            .map(Target::File);
+    }
+    let (package, path) = target.split_once('/').unwrap_or((target, ""));
+    let valid = package.starts_with(|ch: char| ch.is_ascii_alphanumeric())
+        && package
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-'))
+        && !path.contains(|ch: char| ch.is_whitespace() || ch == '"');
+    valid.then_some(Target::Package(target))
+}
+
+/// where `bottom fetch` puts the dependencies of the project in `root`
+pub fn vendor_dir(root: &Path) -> PathBuf {
+    root.join("vendor")
+}
+
+/// the program a package includes when it is named without a path
+fn package_entry(package: &Path) -> Result<PathBuf, String> {
+    let manifest = package.join(manifest::FILENAME);
+    if manifest.is_file() {
+        return Manifest::load(&manifest).map(|manifest| manifest.entry);
+    }
+    Ok(package.join("main.🥺"))
+}
+
+/// expands includes, keeping track of the files being expanded to catch cycles
+pub struct Expander {
+    /// the vendor directory packages are looked up in, `None` outside of a project
+    vendor: Option<PathBuf>,
+    /// whether the program runs with extensions, which some of [`STD`] needs
+    extensions: bool,
+    stack: Vec<PathBuf>,
+}
+
+impl Expander {
+    pub fn new(vendor: Option<PathBuf>, extensions: bool) -> Self {
+        Expander {
+            vendor,
+            extensions,
+            stack: vec![],
+        }
+    }
+
+    fn resolve(&self, target: Target, dir: &Path) -> Result<PathBuf, String> {
+        match target {
+            Target::File(path) => Ok(dir.join(path)),
+            Target::Package(name) => {
+                let (package, path) = name.split_once('/').unwrap_or((name, ""));
+                let Some(vendor) = &self.vendor else {
+                    return Err(format!(
+                        "cannot include package '{}' outside of a project with a {}",
+                        package,
+                        manifest::FILENAME
+                    ));
+                };
+                let dir = vendor.join(package);
+                if !dir.is_dir() {
+                    return Err(format!(
+                        "unknown package '{}', add it to [deps] and run `bottom fetch`",
+                        package
+                    ));
+                }
+                match path {
+                    "" => package_entry(&dir),
+                    path => Ok(dir.join(path)),
+                }
+            }
+            Target::Std(_) => unreachable!("the standard library has no files"),
+        }
+    }
+
+    /// expand the included `source`, known as `key` to find cycles, whose quoted includes are
+    /// relative to `dir`
+    fn expand_included(
+        &mut self,
+        key: PathBuf,
+        source: &str,
+        dir: &Path,
+    ) -> Result<String, String> {
+        if let Some(start) = self.stack.iter().position(|file| *file == key) {
+            let cycle: Vec<_> = self.stack[start..]
+                .iter()
+                .chain([&key])
+                .map(|file| file.display().to_string())
+                .collect();
+            return Err(format!("include cycle: {}", cycle.join(" -> ")));
+        }
+        self.stack.push(key);
+        let expanded = self.expand(source, dir);
+        self.stack.pop();
+        expanded
+    }
+
+    /// the program in the file at `path` with all includes replaced
+    pub fn expand_file(&mut self, path: &Path) -> Result<String, String> {
+        let error = |err| format!("could not include {}: {}", path.display(), err);
+        let canonical = fs::canonicalize(path).map_err(error)?;
+        let source = read_source(path).map_err(error)?;
+        // a shebang only means something at the start of the main program
+        let source = match source.starts_with("#!") {
+            true => source.split_once('\n').map_or("", |(_, rest)| rest),
+            false => &source,
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        self.expand_included(canonical, source, dir)
+    }
+
+    fn expand_std(&mut self, name: &str) -> Result<String, String> {
+        let (_, source) = STD
+            .iter()
+            .find(|(routine, _)| *routine == name)
+            .ok_or_else(|| format!("there is no <std/{}> in the standard library", name))?;
+        if !self.extensions && STD_EXTENSIONS.contains(&name) {
+            return Err(format!("<std/{}> needs extensions", name));
+        }
+        let key = PathBuf::from(format!("<std/{}>", name));
+        self.expand_included(key, source, Path::new("std"))
+    }
+
+    /// `source` with all includes replaced, quoted paths being relative to `dir`
+    pub fn expand(&mut self, source: &str, dir: &Path) -> Result<String, String> {
+        let mut expanded = String::with_capacity(source.len());
+        for line in source.split_inclusive('\n') {
+            match directive(line) {
+                Some(target) => {
+                    expanded += &match target {
+                        Target::Std(name) => self.expand_std(name)?,
+                        target => {
+                            let path = self.resolve(target, dir)?;
+                            self.expand_file(&path)?
+                        }
+                    };
+                    if !expanded.ends_with('\n') {
+                        expanded.push('\n');
+                    }
+                }
+                None => expanded += line,
+            }
+        }
+        Ok(expanded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use bottom::ext::Host;
+    use bottom::interpreter::interpret;
+    use bottom::unstack::Unstack;
+    use bottom::{parse, Limits};
+
+    use crate::batch::discover;
+    use crate::include::{directive, Expander, Target, STD};
+
+    #[test]
+    fn test_directive() {
+        assert_eq!(
+            directive("include \"lib/a.🥺\"\n"),
+            Some(Target::File("lib/a.🥺"))
+        );
+        assert_eq!(directive("  include fib"), Some(Target::Package("fib")));
+        assert_eq!(
+            directive("include fib/table.🥺"),
+            Some(Target::Package("fib/table.🥺"))
+        );
+        assert_eq!(
+            directive("include <std/square>"),
+            Some(Target::Std("square"))
+        );
+        assert_eq!(directive("include the header"), None);
+        assert_eq!(directive("include it."), None);
+        assert_eq!(directive("included"), None);
+        assert_eq!(directive("🥺1 include"), None);
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = env::temp_dir().join(format!("bottom-include-{}", process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib/two.🥺"), "#!/usr/bin/env bottom\n🥺2").unwrap();
+        fs::write(dir.join("lib/four.🥺"), "include \"two.🥺\"\n✨1 💓0\n").unwrap();
+        fs::write(dir.join("loop.🥺"), "include \"loop.🥺\"\n").unwrap();
+
+        let mut expander = Expander::new(None, true);
+        assert_eq!(
+            expander.expand("include \"lib/four.🥺\"\n🥺1\n", &dir),
+            Ok("🥺2\n✨1 💓0\n🥺1\n".to_string())
+        );
+        let cycle = expander.expand_file(&dir.join("loop.🥺")).unwrap_err();
+        assert!(cycle.starts_with("include cycle"), "{}", cycle);
+        assert!(expander.expand("include fib\n", &dir).is_err());
+        assert_eq!(
+            Expander::new(None, false).expand("include <std/modulo>\n", &dir),
+            Err("<std/modulo> needs extensions".to_string())
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_std() {
+        let tests = env::current_dir().unwrap().join("std/tests");
+        let programs = discover(&tests).unwrap();
+        for (routine, _) in STD {
+            let test = tests.join(format!("{}.🥺", routine));
+            assert!(programs.contains(&test), "<std/{}> has no test", routine);
+        }
+        for program in programs {
+            let source = Expander::new(None, true).expand_file(&program).unwrap();
+            let result = interpret(
+                parse(&source, true),
+                Unstack::new(),
+                &mut Host::new(),
+                &Limits::default(),
+            );
+            assert_eq!(result, Ok(vec![]), "{}", program.display());
+        }
+    }
+}
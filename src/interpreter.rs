@@ -0,0 +1,1874 @@
+/*!
+ * # Interpreter
+ *
+ * an [`Interpreter`] holds everything a running program needs and executes it one instruction
+ * at a time with [`step`](Interpreter::step), so debuggers and embedders can look at the state
+ * in between. [`interpret`] runs a program to the end in one go
+ *
+ * the `run` methods execute many instructions at once and return early once another thread
+ * sets the flag from [`pause_handle`](Interpreter::pause_handle) or before executing an instruction
+ * with a [breakpoint](Interpreter::add_breakpoint). breakpoints can also be written into the
+ * program as 🛑, which only pause a run after [`add_source_breakpoints`](Interpreter::add_source_breakpoints)
+ *
+ * tools that need to see every instruction, like tracers or profilers, attach an
+ * [`ExecutionObserver`] instead of looking at the state after every step
+ *
+ * a [`Snapshot`] of the whole state can be taken at any time and restored later, and
+ * [`set_snapshots`](Interpreter::set_snapshots) takes them periodically, so a debugger can go
+ * back in time without running the program again from the start. [`step_back`](Interpreter::step_back)
+ * and [`reverse_continue`](Interpreter::reverse_continue) do so by executing again from the latest
+ * snapshot before where they go
+ *
+ * tasks spawned with 🧵 are scheduled round-robin, each running for [`QUANTUM`] instructions at a time,
+ * the program ends when the main task does. every task has its own return stack for 🛫 and 🛬 and
+ * its own [`AUXILIARY_UNSTACKS`] for 📥 and 📤, which start out empty. the memory of 💾 and 💿 is
+ * shared by all tasks
+ */
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use crate::cell::Cell;
+use crate::ext::math::{MAX_ARITY, OVERFLOW};
+use crate::unstack::{Representation, Unstack};
+use crate::{
+    hug_target, skip_target, DivisionByZero, Fault, Limits, Operations, Overflow, RuntimeError,
+    Semantics,
+};
+
+/// number of instructions a task executes before the next task gets to run
+const QUANTUM: usize = 64;
+
+/// number of auxiliary unstacks of every task for 📥 and 📤
+pub const AUXILIARY_UNSTACKS: usize = 8;
+
+/// what 🧮 pushes at the end of the input, written `🥺1 🥺63 ⏪0` in a program, or the smallest
+/// value of another [`Cell`]
+pub const SCAN_END: i64 = i64::MIN;
+
+/// a slice of the program running with its own unstack, see 🧵
+#[derive(Clone)]
+struct Task<C: Cell> {
+    unstack: Unstack<C>,
+    instruction_pointer: usize,
+    /// the task is finished once its instruction pointer reaches this
+    end: usize,
+    /// where every 🛬 continues, the innermost 🛫 last
+    calls: Vec<usize>,
+    /// the scratch space of 📥 and 📤, the most recent value last
+    auxiliary: [Vec<C>; AUXILIARY_UNSTACKS],
+}
+
+impl<C: Cell> Task<C> {
+    fn new(unstack: Unstack<C>, instruction_pointer: usize, end: usize) -> Self {
+        Task {
+            unstack,
+            instruction_pointer,
+            end,
+            calls: vec![],
+            auxiliary: Default::default(),
+        }
+    }
+
+    /// the number of values on all of its unstacks
+    fn value_count(&self) -> usize {
+        self.unstack.len() + self.auxiliary.iter().map(Vec::len).sum::<usize>()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.instruction_pointer >= self.end
+    }
+}
+
+/// everything the extension operations need from outside the interpreter
+///
+/// the command line uses the [`Host`](crate::ext::Host), embedders without an operating system
+/// provide their own, failures are reported as a message
+pub trait Io {
+    /// the value of the `index`th environment variable, see 🏠
+    fn env(&mut self, index: usize) -> Result<i64, String>;
+    /// the next byte of file descriptor `fd` or -1 at its end, see 📖
+    fn read(&mut self, fd: usize) -> Result<i64, String>;
+    /// write the lowest byte of `value` to file descriptor `fd`, see 📝
+    fn write(&mut self, fd: usize, value: i64) -> Result<(), String>;
+    /// wait for a connection on file descriptor `fd`, see 📞
+    fn accept(&mut self, fd: usize) -> Result<(), String>;
+}
+
+/// execute a single operation that does not involve other tasks
+pub fn execute<C: Cell>(
+    operation: Operations,
+    unstack: &mut Unstack<C>,
+    instruction_pointer: &mut usize,
+    host: &mut dyn Io,
+    semantics: &Semantics,
+) -> Result<(), RuntimeError> {
+    if let Some((needs, _)) = operation.stack_effect() {
+        if unstack.len() < needs {
+            return Err(RuntimeError::underflow(
+                operation,
+                *instruction_pointer,
+                needs,
+                unstack,
+            ));
+        }
+    }
+    match operation {
+        Operations::Push(val) => match C::try_from_i64(val) {
+            Some(value) => unstack.push(value),
+            None if semantics.overflow == Overflow::Checked => {
+                return Err(RuntimeError::Overflow {
+                    fault: Box::new(Fault::new(operation, *instruction_pointer, unstack)),
+                    operands: vec![val as i128],
+                })
+            }
+            None => unstack.push(C::from_i64(val)),
+        },
+        Operations::Pop(val) => {
+            // a divisor wrapping around to 0 divides by zero too
+            let divisor = C::from_i64(val);
+            if divisor == C::ZERO {
+                match semantics.division_by_zero {
+                    DivisionByZero::Error => {
+                        return Err(RuntimeError::DivisionByZero {
+                            instruction_pointer: *instruction_pointer,
+                        })
+                    }
+                    DivisionByZero::Zero => {
+                        unstack.pop();
+                        unstack.push(C::ZERO);
+                    }
+                    DivisionByZero::Skip => {}
+                }
+                return Ok(());
+            }
+            if semantics.overflow == Overflow::Checked {
+                let value = unstack.bottom(1)[0];
+                if value.checked_div(divisor).is_none() {
+                    return Err(RuntimeError::overflow(
+                        operation,
+                        *instruction_pointer,
+                        unstack,
+                        &[value, divisor],
+                    ));
+                }
+            }
+            let value = semantics.quotient(unstack.pop(), divisor);
+            unstack.push(value);
+        }
+        Operations::Swap(steps) => unstack.swap_first(steps),
+        Operations::Heart(val) => {
+            if semantics.overflow == Overflow::Checked {
+                let operands = unstack.bottom(2);
+                if operands[0].checked_mul(operands[1]).is_none() {
+                    return Err(RuntimeError::overflow(
+                        operation,
+                        *instruction_pointer,
+                        unstack,
+                        &operands,
+                    ));
+                }
+            }
+            let value = unstack.pop().wrapping_mul(unstack.pop());
+            unstack.truncate(unstack.len() - val);
+            unstack.push(value);
+        }
+        Operations::Dup(val) => {
+            let values: Vec<C> = unstack.drain(val).collect();
+            for value in values.into_iter().rev() {
+                unstack.push(value);
+                unstack.push(value);
+            }
+        }
+        Operations::Hug(val) => {
+            if unstack.pop() != C::ZERO {
+                if semantics.strict_jumps
+                    && (val == 0 || hug_target(*instruction_pointer, val).is_none())
+                {
+                    return Err(RuntimeError::JumpOutOfRange {
+                        instruction_pointer: *instruction_pointer,
+                    });
+                }
+                // the caller moves on to the instruction after this one, which for a jump to
+                // the start means going through usize::MAX
+                *instruction_pointer = instruction_pointer.wrapping_add(1).wrapping_sub(val);
+            }
+        }
+        Operations::Skip(n) => {
+            if unstack.pop() == C::ZERO {
+                // the caller moves on to the instruction after this one
+                *instruction_pointer = skip_target(*instruction_pointer, n) - 1;
+            }
+        }
+        Operations::Env(index) => match host.env(index) {
+            Ok(value) => unstack.push(C::from_i64(value)),
+            Err(err) => {
+                return Err(RuntimeError::failed(
+                    operation,
+                    *instruction_pointer,
+                    unstack,
+                    err,
+                ))
+            }
+        },
+        Operations::Read(fd) => match host.read(fd) {
+            Ok(value) => unstack.push(C::from_i64(value)),
+            Err(err) => {
+                return Err(RuntimeError::failed(
+                    operation,
+                    *instruction_pointer,
+                    unstack,
+                    err,
+                ))
+            }
+        },
+        Operations::Write(fd) => {
+            if let Err(err) = host.write(fd, unstack.pop().to_i64()) {
+                return Err(RuntimeError::failed(
+                    operation,
+                    *instruction_pointer,
+                    unstack,
+                    err,
+                ));
+            }
+        }
+        Operations::Scan(fd) => {
+            let mut line = vec![];
+            let mut end = false;
+            loop {
+                match host.read(fd) {
+                    Ok(-1) => {
+                        end = line.is_empty();
+                        break;
+                    }
+                    Ok(10) => break,
+                    Ok(byte) => line.push(byte as u8),
+                    Err(err) => {
+                        return Err(RuntimeError::failed(
+                            operation,
+                            *instruction_pointer,
+                            unstack,
+                            err,
+                        ))
+                    }
+                }
+            }
+            let value = match core::str::from_utf8(&line).map(str::trim) {
+                _ if end => C::MIN,
+                Ok(number) => match number.parse() {
+                    Ok(value) => value,
+                    Err(_) => {
+                        let message = format!("'{}' is not an integer", number);
+                        return Err(RuntimeError::failed(
+                            operation,
+                            *instruction_pointer,
+                            unstack,
+                            message,
+                        ));
+                    }
+                },
+                Err(_) => {
+                    return Err(RuntimeError::failed(
+                        operation,
+                        *instruction_pointer,
+                        unstack,
+                        "the line is not UTF-8",
+                    ))
+                }
+            };
+            unstack.push(value);
+        }
+        Operations::Print(fd) => {
+            for byte in format!("{}\n", unstack.pop()).bytes() {
+                if let Err(err) = host.write(fd, byte as i64) {
+                    return Err(RuntimeError::failed(
+                        operation,
+                        *instruction_pointer,
+                        unstack,
+                        err,
+                    ));
+                }
+            }
+        }
+        Operations::Break(_) | Operations::Nop(_) => {}
+        Operations::Peek(label) => {
+            let line = format!("👀{} {:?}\n", label, unstack.values());
+            for byte in line.bytes() {
+                if let Err(err) = host.write(2, byte as i64) {
+                    return Err(RuntimeError::failed(
+                        operation,
+                        *instruction_pointer,
+                        unstack,
+                        err,
+                    ));
+                }
+            }
+        }
+        Operations::Accept(fd) => {
+            if let Err(err) = host.accept(fd) {
+                return Err(RuntimeError::failed(
+                    operation,
+                    *instruction_pointer,
+                    unstack,
+                    err,
+                ));
+            }
+        }
+        Operations::Assert(id) => {
+            let left = unstack.pop();
+            let right = unstack.pop();
+            if left != right {
+                return Err(RuntimeError::AssertionFailed {
+                    id,
+                    left: left.to_i64(),
+                    right: right.to_i64(),
+                    instruction_pointer: *instruction_pointer,
+                });
+            }
+        }
+        Operations::Rotate(count) => unstack.rotate_bottom(count),
+        Operations::Reverse(count) => {
+            let count = match count {
+                0 => unstack.len(),
+                count => count,
+            };
+            // the bottom value is pushed first and ends up furthest from the bottom
+            let values: Vec<C> = unstack.drain(count).collect();
+            unstack.extend(values);
+        }
+        Operations::Clear(count) => match count {
+            0 => unstack.truncate(0),
+            count => unstack.truncate(unstack.len() - count),
+        },
+        Operations::Depth(_) => unstack.push(C::from_i64(unstack.len() as i64)),
+        Operations::Math(intrinsic, count) => {
+            let entry = intrinsic.entry();
+            let cells = unstack.bottom(entry.arity);
+            let mut operands = [0; MAX_ARITY];
+            for (operand, cell) in operands.iter_mut().zip(&cells) {
+                *operand = cell.to_i64();
+            }
+            let checked = semantics.overflow == Overflow::Checked;
+            // operands wider than 64 bits do not fit, and neither may the result in the cells
+            let result = match (entry.apply)(&operands[..entry.arity]) {
+                _ if checked
+                    && cells
+                        .iter()
+                        .any(|cell| cell.to_i128() != cell.to_i64() as i128) =>
+                {
+                    Err(OVERFLOW)
+                }
+                Ok(value) if checked && C::try_from_i64(value).is_none() => Err(OVERFLOW),
+                result => result,
+            };
+            let value = match result {
+                Ok(value) => value,
+                Err(OVERFLOW) if checked => {
+                    return Err(RuntimeError::overflow(
+                        operation,
+                        *instruction_pointer,
+                        unstack,
+                        &cells,
+                    ))
+                }
+                Err(err) => {
+                    return Err(RuntimeError::failed(
+                        operation,
+                        *instruction_pointer,
+                        unstack,
+                        err,
+                    ))
+                }
+            };
+            for _ in 0..entry.arity + count {
+                unstack.pop();
+            }
+            unstack.push(C::from_i64(value));
+        }
+        Operations::Spawn(_) | Operations::Join(_) => {
+            unreachable!("tasks are handled by the scheduler")
+        }
+        Operations::Call(_)
+        | Operations::Return(_)
+        | Operations::Stash(_)
+        | Operations::Unstash(_) => {
+            unreachable!("the return stack and auxiliary unstacks belong to the task")
+        }
+        Operations::Store(_) | Operations::Load(_) => {
+            unreachable!("the memory belongs to the interpreter")
+        }
+    }
+    Ok(())
+}
+
+/// callbacks for every instruction the interpreter executes, in the order they are declared here
+///
+/// a 🪢 waiting for its task is announced every time it tries, but only followed by the other
+/// callbacks once it is executed
+pub trait ExecutionObserver<C: Cell = i64> {
+    /// `task` is about to execute the `operation` at `instruction_pointer`
+    fn before_instruction(
+        &mut self,
+        task: usize,
+        instruction_pointer: usize,
+        operation: Operations,
+    ) {
+        let _ = (task, instruction_pointer, operation);
+    }
+
+    /// `value` was taken off the unstack of `task`
+    fn on_pop(&mut self, task: usize, value: C) {
+        let _ = (task, value);
+    }
+
+    /// `value` was put on the unstack of `task`
+    fn on_push(&mut self, task: usize, value: C) {
+        let _ = (task, value);
+    }
+
+    /// `task` executed the `operation` at `instruction_pointer`, leaving it with `unstack`
+    fn after_instruction(
+        &mut self,
+        task: usize,
+        instruction_pointer: usize,
+        operation: Operations,
+        unstack: &Unstack<C>,
+    ) {
+        let _ = (task, instruction_pointer, operation, unstack);
+    }
+}
+
+/// whether there is anything left to execute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    /// stopped before executing the instruction at this instruction pointer because it was asked to
+    Paused(usize),
+    /// stopped because the instruction budget ran out, running again continues where it stopped
+    Exhausted,
+    /// the main task reached the end of the program
+    Finished,
+}
+
+/// the state of an [`Interpreter`] after some number of steps, to go back to with
+/// [`restore`](Interpreter::restore)
+///
+/// taking one copies the unstacks of all tasks, which is O(1) for
+/// [`Representation::Persistent`] ones
+#[derive(Clone)]
+pub struct Snapshot<C: Cell = i64> {
+    tasks: Vec<Task<C>>,
+    current: usize,
+    slice: usize,
+    progressed: bool,
+    stalled: usize,
+    steps: usize,
+    memory: BTreeMap<usize, C>,
+    skipped: usize,
+}
+
+impl<C: Cell> Snapshot<C> {
+    /// the number of instructions executed before it was taken
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// the id of the task that was executing instructions
+    pub fn current_task(&self) -> usize {
+        self.current
+    }
+
+    /// the instruction the current task was about to execute
+    pub fn instruction_pointer(&self) -> usize {
+        self.tasks[self.current].instruction_pointer
+    }
+
+    /// the unstack of the current task
+    pub fn unstack(&self) -> &Unstack<C> {
+        &self.tasks[self.current].unstack
+    }
+
+    /// the instructions the 🛬 of the current task return to, the innermost 🛫 last
+    pub fn calls(&self) -> &[usize] {
+        &self.tasks[self.current].calls
+    }
+
+    /// the memory cells 💾 stored to by their address
+    pub fn memory(&self) -> &BTreeMap<usize, C> {
+        &self.memory
+    }
+}
+
+/// a program in the middle of being executed
+///
+/// extension operations reach the outside world only through the borrowed [`Io`], the values
+/// on its unstacks are [`i64`] unless it runs with another [`Cell`]
+pub struct Interpreter<'host, C: Cell = i64> {
+    tokens: Vec<Operations>,
+    tasks: Vec<Task<C>>,
+    host: &'host mut dyn Io,
+    /// the task executing instructions right now
+    current: usize,
+    /// instructions the current task executed since it was scheduled
+    slice: usize,
+    /// whether the current task executed anything since it was scheduled
+    progressed: bool,
+    /// number of tasks in a row that could not execute anything
+    stalled: usize,
+    /// instructions executed so far
+    steps: usize,
+    /// set from another thread to stop the running `run` method
+    pause: Arc<AtomicBool>,
+    /// instructions the `run` methods stop at
+    breakpoints: BTreeSet<usize>,
+    /// the memory cells of 💾 and 💿 that were stored to, shared by all tasks
+    memory: BTreeMap<usize, C>,
+    /// the [`Limits::max_memory`] of the running `run` method, addresses of 💾 and 💿 must be
+    /// below it
+    max_memory: Option<usize>,
+    semantics: Semantics,
+    /// whether to skip instructions that fail instead of stopping
+    keep_going: bool,
+    /// the errors of the instructions skipped so far
+    skipped: Vec<RuntimeError>,
+    /// with `keep_going`, the task executing an instruction, the length of its unstack and the
+    /// values the instruction needs from it, bottom first, to put back if it fails
+    taken: Option<(usize, usize, Vec<C>)>,
+    /// the number of steps between snapshots, 0 to take none
+    snapshot_every: usize,
+    /// the most snapshots kept, older ones are dropped first
+    snapshot_limit: usize,
+    /// the snapshots taken so far, the oldest first
+    snapshots: VecDeque<Snapshot<C>>,
+    observers: Vec<Box<dyn ExecutionObserver<C> + 'host>>,
+}
+
+impl<'host> Interpreter<'host> {
+    /// an interpreter about to execute the first of `tokens`, starting with the given unstack
+    pub fn new(tokens: Vec<Operations>, unstack: Unstack, host: &'host mut dyn Io) -> Self {
+        Interpreter::with_cells(tokens, unstack, host)
+    }
+}
+
+impl<'host, C: Cell> Interpreter<'host, C> {
+    /// [`new`](Interpreter::new), with an unstack of any [`Cell`]
+    pub fn with_cells(
+        tokens: Vec<Operations>,
+        unstack: Unstack<C>,
+        host: &'host mut dyn Io,
+    ) -> Self {
+        let end = tokens.len();
+        Interpreter {
+            tokens,
+            tasks: vec![Task::new(unstack, 0, end)],
+            host,
+            current: 0,
+            slice: 0,
+            progressed: false,
+            stalled: 0,
+            steps: 0,
+            pause: Arc::new(AtomicBool::new(false)),
+            breakpoints: BTreeSet::new(),
+            memory: BTreeMap::new(),
+            max_memory: None,
+            semantics: Semantics::default(),
+            keep_going: false,
+            skipped: vec![],
+            taken: None,
+            snapshot_every: 0,
+            snapshot_limit: 0,
+            snapshots: VecDeque::new(),
+            observers: vec![],
+        }
+    }
+
+    pub fn tokens(&self) -> &[Operations] {
+        &self.tokens
+    }
+
+    /// call `observer` for every instruction executed from now on
+    pub fn observe(&mut self, observer: Box<dyn ExecutionObserver<C> + 'host>) {
+        self.observers.push(observer);
+    }
+
+    /// make the `run` methods stop before executing the instruction at `instruction_pointer`,
+    /// false if there already was a breakpoint
+    ///
+    /// the instruction a run starts at never stops it, so running again continues past the breakpoint
+    pub fn add_breakpoint(&mut self, instruction_pointer: usize) -> bool {
+        self.breakpoints.insert(instruction_pointer)
+    }
+
+    /// add a breakpoint at every 🛑 of the program, returns how many were new
+    ///
+    /// 🛑 does nothing otherwise, so a debugger calls this and a plain run does not
+    pub fn add_source_breakpoints(&mut self) -> usize {
+        let stops: Vec<usize> = (self.tokens.iter().enumerate())
+            .filter(|(_, operation)| matches!(operation, Operations::Break(_)))
+            .map(|(instruction_pointer, _)| instruction_pointer)
+            .collect();
+        stops
+            .into_iter()
+            .filter(|&instruction_pointer| self.add_breakpoint(instruction_pointer))
+            .count()
+    }
+
+    /// false if there was no breakpoint at `instruction_pointer`
+    pub fn remove_breakpoint(&mut self, instruction_pointer: usize) -> bool {
+        self.breakpoints.remove(&instruction_pointer)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// a flag that makes the running `run` method return [`Status::Paused`] before the next
+    /// instruction when set, it is cleared again when it does
+    pub fn pause_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.pause)
+    }
+
+    /// the instruction the current task executes next
+    pub fn instruction_pointer(&self) -> usize {
+        self.tasks[self.current].instruction_pointer
+    }
+
+    /// execute the operations where implementations disagree as `semantics` say
+    pub fn set_semantics(&mut self, semantics: Semantics) {
+        self.semantics = semantics;
+    }
+
+    /// record the errors of instructions that fail and move on to the next instruction instead
+    /// of stopping, for the errors that are [recoverable](RuntimeError::is_recoverable)
+    pub fn set_keep_going(&mut self, keep_going: bool) {
+        self.keep_going = keep_going;
+    }
+
+    /// the errors of the instructions skipped with [`set_keep_going`](Interpreter::set_keep_going),
+    /// in the order they happened
+    pub fn skipped(&self) -> &[RuntimeError] {
+        &self.skipped
+    }
+
+    /// the state of the interpreter right now
+    pub fn snapshot(&self) -> Snapshot<C> {
+        Snapshot {
+            tasks: self.tasks.clone(),
+            current: self.current,
+            slice: self.slice,
+            progressed: self.progressed,
+            stalled: self.stalled,
+            steps: self.steps,
+            memory: self.memory.clone(),
+            skipped: self.skipped.len(),
+        }
+    }
+
+    /// go back to the state of `snapshot`, which must have been taken of this interpreter
+    ///
+    /// the program continues from there as if the later steps never happened, except for what
+    /// they did through the [`Io`]
+    pub fn restore(&mut self, snapshot: &Snapshot<C>) {
+        self.tasks.clone_from(&snapshot.tasks);
+        self.current = snapshot.current;
+        self.slice = snapshot.slice;
+        self.progressed = snapshot.progressed;
+        self.stalled = snapshot.stalled;
+        self.steps = snapshot.steps;
+        self.memory.clone_from(&snapshot.memory);
+        self.skipped.truncate(snapshot.skipped);
+        let later = self
+            .snapshots
+            .iter()
+            .position(|taken| taken.steps > snapshot.steps);
+        if let Some(later) = later {
+            self.snapshots.truncate(later);
+        }
+    }
+
+    /// take a [`snapshot`](Interpreter::snapshot) now and every `every` steps from now on,
+    /// keeping the latest `limit` of them, `every` being 0 takes none
+    pub fn set_snapshots(&mut self, every: usize, limit: usize) {
+        self.snapshot_every = every;
+        self.snapshot_limit = limit;
+        if every != 0 && limit != 0 {
+            self.snapshots.push_back(self.snapshot());
+        }
+        while self.snapshots.len() > limit {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// the snapshots taken so far, the oldest first
+    pub fn snapshots(&self) -> impl Iterator<Item = &Snapshot<C>> + '_ {
+        self.snapshots.iter()
+    }
+
+    /// store the unstack of every task, and of the tasks spawned later, as `representation`
+    ///
+    /// [`Representation::Persistent`] unstacks make [`Unstack::snapshot`] O(1), at the cost of
+    /// slower pushes and pops
+    pub fn set_representation(&mut self, representation: Representation) {
+        for task in &mut self.tasks {
+            task.unstack.set_representation(representation);
+        }
+    }
+
+    /// the unstack of the current task
+    pub fn unstack(&self) -> &Unstack<C> {
+        &self.tasks[self.current].unstack
+    }
+
+    /// the instructions the 🛬 of the current task return to, the innermost 🛫 last
+    pub fn calls(&self) -> &[usize] {
+        &self.tasks[self.current].calls
+    }
+
+    /// the auxiliary unstack `index` of the current task, the most recent value last, empty if
+    /// there is none
+    pub fn auxiliary(&self, index: usize) -> &[C] {
+        self.tasks[self.current]
+            .auxiliary
+            .get(index)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// the memory cells 💾 stored to so far by their address, every other cell holds 0
+    pub fn memory(&self) -> &BTreeMap<usize, C> {
+        &self.memory
+    }
+
+    /// the id of the task executing instructions right now, the main task is 0
+    pub fn current_task(&self) -> usize {
+        self.current
+    }
+
+    /// the number of instructions executed so far
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn host(&mut self) -> &mut dyn Io {
+        &mut *self.host
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tasks[0].is_finished()
+    }
+
+    /// the values left on the unstack of the main task, bottom first
+    pub fn output(&self) -> Vec<C> {
+        self.tasks[0].unstack.values()
+    }
+
+    /// let the next task run
+    fn schedule_next(&mut self) -> Result<(), RuntimeError> {
+        if self.progressed {
+            self.stalled = 0;
+        } else {
+            self.stalled += 1;
+            if self.stalled > self.tasks.len() {
+                return Err(RuntimeError::Deadlock {
+                    instruction_pointer: self.tasks[0].instruction_pointer,
+                });
+            }
+        }
+        self.progressed = false;
+        self.slice = 0;
+        self.current = (self.current + 1) % self.tasks.len();
+        Ok(())
+    }
+
+    /// switch tasks until the current one may execute an instruction
+    fn schedule(&mut self) -> Result<(), RuntimeError> {
+        while !self.is_finished()
+            && (self.slice == QUANTUM || self.tasks[self.current].is_finished())
+        {
+            self.schedule_next()?;
+        }
+        Ok(())
+    }
+
+    /// execute a single instruction, switching to the next task first if the current one
+    /// used up its [`QUANTUM`], finished or is waiting for another task
+    pub fn step(&mut self) -> Result<Status, RuntimeError> {
+        let status = match self.step_task() {
+            Err(err) if self.keep_going && err.is_recoverable() => {
+                // leave the unstack as it was before the instruction
+                if let Some((task, length, values)) = self.taken.take() {
+                    let unstack = &mut self.tasks[task].unstack;
+                    while unstack.len() > length - values.len() {
+                        unstack.pop();
+                    }
+                    for value in values.into_iter().rev() {
+                        unstack.push(value);
+                    }
+                }
+                self.tasks[self.current].instruction_pointer += 1;
+                self.skipped.push(err);
+                self.progressed = true;
+                self.slice += 1;
+                Status::Running
+            }
+            result => result?,
+        };
+        if status == Status::Running
+            && self.snapshot_every != 0
+            && self.snapshot_limit != 0
+            && self.steps.is_multiple_of(self.snapshot_every)
+        {
+            if self.snapshots.len() == self.snapshot_limit {
+                self.snapshots.pop_front();
+            }
+            self.snapshots.push_back(self.snapshot());
+        }
+        Ok(status)
+    }
+
+    fn step_task(&mut self) -> Result<Status, RuntimeError> {
+        loop {
+            self.schedule()?;
+            if self.is_finished() {
+                return Ok(Status::Finished);
+            }
+            let next_id = self.tasks.len();
+            let current = self.current;
+            let task = &mut self.tasks[current];
+            let instruction_pointer = task.instruction_pointer;
+            let operation = self.tokens[instruction_pointer];
+            self.steps = self.steps.wrapping_add(1);
+            for observer in &mut self.observers {
+                observer.before_instruction(current, instruction_pointer, operation);
+            }
+            let needs = match operation {
+                Operations::Spawn(count) => count + 1,
+                Operations::Join(_) => 1,
+                operation => operation.stack_effect().map_or(0, |(needs, _)| needs),
+            };
+            if self.keep_going {
+                let length = task.unstack.len();
+                self.taken = Some((current, length, task.unstack.bottom(needs)));
+            }
+            if task.unstack.len() < needs {
+                return Err(RuntimeError::underflow(
+                    operation,
+                    instruction_pointer,
+                    needs,
+                    &task.unstack,
+                ));
+            }
+            // what the operation takes off the unstack, only looked at if someone is watching
+            let (depth, popped) = if self.observers.is_empty() {
+                (0, vec![])
+            } else {
+                let taken = match operation {
+                    Operations::Clear(0) | Operations::Reverse(0) => task.unstack.len(),
+                    _ => needs,
+                };
+                (task.unstack.len(), task.unstack.bottom(taken))
+            };
+            match operation {
+                Operations::Spawn(count) => {
+                    let unstack = &mut task.unstack;
+                    let length = unstack.pop().max(C::ZERO).to_usize().unwrap_or(usize::MAX);
+                    let values: Vec<C> = unstack.drain(count).collect();
+                    let mut copy = Unstack::new();
+                    copy.set_representation(unstack.representation());
+                    for value in values.into_iter().rev() {
+                        unstack.push(value);
+                        copy.push(value);
+                    }
+                    let start = instruction_pointer + 1;
+                    let end = start.saturating_add(length).min(self.tokens.len());
+                    unstack.push(C::from_i64(next_id as i64));
+                    task.instruction_pointer = end;
+                    self.tasks.push(Task::new(copy, start, end));
+                }
+                Operations::Join(count) => {
+                    let id = task.unstack.pop();
+                    let Some(joined) = id
+                        .to_usize()
+                        .filter(|&id| id != 0 && id != current && id < next_id)
+                    else {
+                        let message = format!("no task {} to join", id);
+                        return Err(RuntimeError::failed(
+                            operation,
+                            instruction_pointer,
+                            &task.unstack,
+                            message,
+                        ));
+                    };
+                    if !self.tasks[joined].is_finished() {
+                        // wait for the task, trying again next time around
+                        self.tasks[current].unstack.push(id);
+                        self.schedule_next()?;
+                        continue;
+                    }
+                    let mut results = vec![];
+                    let finished = &mut self.tasks[joined].unstack;
+                    while !finished.is_empty() && (count == 0 || results.len() < count) {
+                        results.push(finished.pop());
+                    }
+                    let task = &mut self.tasks[current];
+                    for value in results.into_iter().rev() {
+                        task.unstack.push(value);
+                    }
+                    task.instruction_pointer += 1;
+                }
+                Operations::Call(target) => {
+                    task.calls.push(instruction_pointer + 1);
+                    task.instruction_pointer = target;
+                }
+                Operations::Return(_) => {
+                    let Some(target) = task.calls.pop() else {
+                        return Err(RuntimeError::failed(
+                            operation,
+                            instruction_pointer,
+                            &task.unstack,
+                            "empty return stack",
+                        ));
+                    };
+                    task.instruction_pointer = target;
+                }
+                Operations::Store(offset) | Operations::Load(offset) => {
+                    let address = task.unstack.pop();
+                    let Some(address) = address
+                        .to_usize()
+                        .and_then(|address| address.checked_add(offset))
+                    else {
+                        let message = format!("invalid address {}", address);
+                        return Err(RuntimeError::failed(
+                            operation,
+                            instruction_pointer,
+                            &task.unstack,
+                            message,
+                        ));
+                    };
+                    if let Some(limit) = self.max_memory.filter(|&limit| address >= limit) {
+                        return Err(RuntimeError::MemoryOutOfBounds {
+                            address,
+                            limit,
+                            instruction_pointer,
+                        });
+                    }
+                    match operation {
+                        Operations::Store(_) => {
+                            let value = task.unstack.pop();
+                            self.memory.insert(address, value);
+                        }
+                        _ => {
+                            let value = self.memory.get(&address).copied().unwrap_or(C::ZERO);
+                            task.unstack.push(value);
+                        }
+                    }
+                    task.instruction_pointer += 1;
+                }
+                Operations::Stash(index) => {
+                    let Some(auxiliary) = task.auxiliary.get_mut(index) else {
+                        let message = format!("no auxiliary unstack {}", index);
+                        return Err(RuntimeError::failed(
+                            operation,
+                            instruction_pointer,
+                            &task.unstack,
+                            message,
+                        ));
+                    };
+                    auxiliary.push(task.unstack.pop());
+                    task.instruction_pointer += 1;
+                }
+                Operations::Unstash(index) => {
+                    let Some(value) = task.auxiliary.get_mut(index).map(Vec::pop) else {
+                        let message = format!("no auxiliary unstack {}", index);
+                        return Err(RuntimeError::failed(
+                            operation,
+                            instruction_pointer,
+                            &task.unstack,
+                            message,
+                        ));
+                    };
+                    let Some(value) = value else {
+                        let message = format!("empty auxiliary unstack {}", index);
+                        return Err(RuntimeError::failed(
+                            operation,
+                            instruction_pointer,
+                            &task.unstack,
+                            message,
+                        ));
+                    };
+                    task.unstack.push(value);
+                    task.instruction_pointer += 1;
+                }
+                operation => {
+                    let mut instruction_pointer = instruction_pointer;
+                    execute(
+                        operation,
+                        &mut task.unstack,
+                        &mut instruction_pointer,
+                        self.host,
+                        &self.semantics,
+                    )?;
+                    task.instruction_pointer = instruction_pointer.wrapping_add(1);
+                }
+            }
+            if !self.observers.is_empty() {
+                let unstack = &self.tasks[current].unstack;
+                let mut pushed = unstack.bottom(unstack.len() + popped.len() - depth);
+                pushed.reverse();
+                for observer in &mut self.observers {
+                    for value in &popped {
+                        observer.on_pop(current, *value);
+                    }
+                    for value in &pushed {
+                        observer.on_push(current, *value);
+                    }
+                    observer.after_instruction(current, instruction_pointer, operation, unstack);
+                }
+            }
+            self.progressed = true;
+            self.slice += 1;
+            return Ok(Status::Running);
+        }
+    }
+
+    /// go back to the latest state before now in which `stop` holds, as far back as the oldest
+    /// snapshot, false if there is none and the interpreter stays where it is
+    ///
+    /// there is no way to undo an instruction, so this restores the latest snapshot before now
+    /// and executes the instructions after it again, trying earlier snapshots if `stop` does
+    /// not hold in between
+    fn rewind(&mut self, mut stop: impl FnMut(&Self) -> bool) -> Result<bool, RuntimeError> {
+        let now = self.steps;
+        let earlier: Vec<Snapshot<C>> = (self.snapshots.iter().rev())
+            .filter(|snapshot| snapshot.steps < now)
+            .cloned()
+            .collect();
+        for snapshot in earlier {
+            self.restore(&snapshot);
+            let mut found = None;
+            while self.steps < now {
+                self.schedule()?;
+                if stop(self) {
+                    found = Some(self.steps);
+                }
+                self.step()?;
+            }
+            if let Some(steps) = found {
+                self.restore(&snapshot);
+                while self.steps < steps {
+                    self.step()?;
+                }
+                self.schedule()?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// go back to before the last instruction executed, false if there is no snapshot before it
+    ///
+    /// the instructions since the snapshot are executed again, [`Io`] and observers included,
+    /// so programs that read input should replay it from a recorded session
+    pub fn step_back(&mut self) -> Result<bool, RuntimeError> {
+        self.rewind(|_| true)
+    }
+
+    /// go back to the latest breakpoint that was passed, false if none was since the oldest
+    /// snapshot, see [`step_back`](Interpreter::step_back)
+    pub fn reverse_continue(&mut self) -> Result<bool, RuntimeError> {
+        self.rewind(|interpreter| {
+            let instruction_pointer = interpreter.instruction_pointer();
+            interpreter.breakpoints.contains(&instruction_pointer)
+        })
+    }
+
+    /// execute instructions until `stop` returns a status, the program ends or is paused
+    fn run_while(
+        &mut self,
+        limits: &Limits,
+        mut stop: impl FnMut(&Self) -> Option<Status>,
+    ) -> Result<Status, RuntimeError> {
+        // there is no clock to check the timeout against without std
+        #[cfg(feature = "std")]
+        let deadline = limits
+            .timeout
+            .map(|timeout| (Instant::now() + timeout, timeout));
+        #[cfg(not(feature = "std"))]
+        let _ = limits;
+        let start = self.steps;
+        self.max_memory = limits.max_memory;
+        loop {
+            self.schedule()?;
+            if self.is_finished() {
+                return Ok(Status::Finished);
+            }
+            let instruction_pointer = self.instruction_pointer();
+            if self.pause.swap(false, Ordering::Relaxed)
+                || (self.steps != start && self.breakpoints.contains(&instruction_pointer))
+            {
+                return Ok(Status::Paused(instruction_pointer));
+            }
+            if let Some(status) = stop(self) {
+                return Ok(status);
+            }
+            self.step()?;
+            if let Some(limit) = limits.max_values {
+                let values: usize = self.tasks.iter().map(Task::value_count).sum();
+                if values > limit {
+                    return Err(RuntimeError::OutOfMemory {
+                        limit,
+                        instruction_pointer,
+                    });
+                }
+            }
+            #[cfg(feature = "std")]
+            if let Some((deadline, timeout)) = deadline {
+                let check_interval = limits.check_interval.max(1);
+                if self.steps.is_multiple_of(check_interval) && Instant::now() >= deadline {
+                    return Err(RuntimeError::Timeout {
+                        timeout,
+                        instruction_pointer: self.instruction_pointer(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// execute instructions until the program ends or is paused
+    pub fn run(&mut self, limits: &Limits) -> Result<Status, RuntimeError> {
+        self.run_while(limits, |_| None)
+    }
+
+    /// execute instructions until the next one to execute is `instruction_pointer`,
+    /// returns [`Status::Running`] when it got there
+    pub fn run_until(&mut self, instruction_pointer: usize) -> Result<Status, RuntimeError> {
+        self.run_while(&Limits::default(), |interpreter| {
+            (interpreter.instruction_pointer() == instruction_pointer).then_some(Status::Running)
+        })
+    }
+
+    /// execute at most `count` instructions, returns [`Status::Exhausted`] if there are more
+    pub fn run_steps(&mut self, count: usize) -> Result<Status, RuntimeError> {
+        self.run_budgeted(count, &Limits::default())
+    }
+
+    /// execute at most `budget` instructions and give up after the timeout in `limits`,
+    /// returns [`Status::Exhausted`] if there are more so untrusted programs can be run a
+    /// little at a time without ever blocking the caller for long
+    pub fn run_budgeted(&mut self, budget: usize, limits: &Limits) -> Result<Status, RuntimeError> {
+        let end = self.steps.wrapping_add(budget);
+        self.run_while(limits, |interpreter| {
+            (interpreter.steps == end).then_some(Status::Exhausted)
+        })
+    }
+}
+
+/// interpret the tokens of a 🥺  program, starting with the given unstack,
+/// and return what is left on the unstack of the main task, bottom first
+pub fn interpret(
+    tokens: Vec<Operations>,
+    unstack: Unstack,
+    host: &mut dyn Io,
+    limits: &Limits,
+) -> Result<Vec<i64>, RuntimeError> {
+    let mut interpreter = Interpreter::new(tokens, unstack, host);
+    // nothing else has the pause handle, so this only returns at the end
+    interpreter.run(limits)?;
+    Ok(interpreter.output())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::ext::Host;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use proptest::prelude::*;
+
+    use crate::interpreter::{
+        execute, interpret, ExecutionObserver, Interpreter, Status, SCAN_END,
+    };
+    use crate::unstack::{Representation, Unstack};
+    use crate::{
+        parse, parse_spanned, DivisionByZero, Fault, Limits, Operations, Overflow, RuntimeError,
+        Semantics, EXTENSIONS, OPERATIONS,
+    };
+
+    #[test]
+    fn test_timeout() {
+        // 🫂3 on a nonzero value continues at the ✨ forever
+        let tokens = vec![Operations::Push(1), Operations::Dup(1), Operations::Hug(3)];
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(10)),
+            check_interval: 16,
+            ..Limits::default()
+        };
+        let result = interpret(tokens, Unstack::new(), &mut Host::new(), &limits);
+        assert!(matches!(result, Err(RuntimeError::Timeout { .. })));
+    }
+
+    #[test]
+    fn test_max_values() {
+        // 🫂3 continues at the ✨2, which adds one value every time around
+        use Operations::*;
+        let tokens = vec![Push(1), Push(1), Dup(2), Hug(3)];
+        let limits = Limits {
+            max_values: Some(100),
+            ..Limits::default()
+        };
+        let result = interpret(tokens, Unstack::new(), &mut Host::new(), &limits);
+        assert!(matches!(
+            result,
+            Err(RuntimeError::OutOfMemory { limit: 100, .. })
+        ));
+    }
+
+    #[test]
+    fn test_print() {
+        let mut host = Host::captured(b"");
+        let tokens = parse("🥺6 🥺7 💓0 🔢1 🥺104 📝1", true);
+        assert_eq!(
+            interpret(tokens, Unstack::new(), &mut host, &Limits::default()),
+            Ok(vec![])
+        );
+        assert_eq!(host.captured_output(1), b"42\nh");
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let run = |division_by_zero| {
+            let mut host = Host::new();
+            let tokens = parse("🥺7 🥺8 💖0", false);
+            let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+            interpreter.set_semantics(Semantics {
+                division_by_zero,
+                ..Semantics::default()
+            });
+            interpreter
+                .run(&Limits::default())
+                .map(|_| interpreter.output())
+        };
+        assert_eq!(
+            run(DivisionByZero::Error),
+            Err(RuntimeError::DivisionByZero {
+                instruction_pointer: 2
+            })
+        );
+        assert_eq!(run(DivisionByZero::Zero), Ok(vec![0, 7]));
+        assert_eq!(run(DivisionByZero::Skip), Ok(vec![8, 7]));
+    }
+
+    #[test]
+    fn test_cells() {
+        let mut host = Host::new();
+        // the arguments wrap around, so the divisor of the 💖 is 0 in 32 bits
+        let tokens = parse("🥺4294967297 🥺4294967296 💖0", false);
+        let mut narrow = Interpreter::with_cells(tokens, Unstack::<i32>::new(), &mut host);
+        assert!(matches!(
+            narrow.run(&Limits::default()),
+            Err(RuntimeError::DivisionByZero { .. })
+        ));
+        assert_eq!(narrow.output(), [0, 1]);
+
+        drop(narrow);
+        let tokens = parse("🥺4611686018427387904 🥺4 💓0 ✨1", false);
+        let mut wide = Interpreter::with_cells(tokens, Unstack::<i128>::new(), &mut host);
+        wide.run(&Limits::default()).unwrap();
+        assert_eq!(wide.output(), [1 << 64, 1 << 64]);
+
+        // MIN / -1 wraps around instead of panicking
+        drop(wide);
+        let tokens = parse("🥺2147483648 💖4294967295", false);
+        let mut narrow = Interpreter::with_cells(tokens, Unstack::<i32>::new(), &mut host);
+        narrow.run(&Limits::default()).unwrap();
+        assert_eq!(narrow.output(), [i32::MIN]);
+        let floor = Semantics {
+            floor_division: true,
+            ..Semantics::default()
+        };
+        for semantics in [Semantics::default(), floor] {
+            assert_eq!(semantics.quotient(i128::MIN, -1), i128::MIN);
+            assert_eq!(semantics.quotient(i32::MIN, -1), i32::MIN);
+        }
+    }
+
+    #[test]
+    fn test_overflow() {
+        let checked = Semantics {
+            overflow: Overflow::Checked,
+            ..Semantics::default()
+        };
+        let mut host = Host::new();
+        let tokens = parse("🥺4294967296 ✨1 💓0 ✨1 💓0", false);
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.run(&Limits::default()).unwrap();
+        assert_eq!(interpreter.output(), [0]);
+
+        drop(interpreter);
+        let tokens = parse("🥺4611686018427387904 🥺4 💓0", false);
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.set_semantics(checked);
+        match interpreter.run(&Limits::default()) {
+            Err(RuntimeError::Overflow { fault, operands }) => {
+                assert_eq!(operands, [4, 1 << 62]);
+                assert_eq!(fault.instruction_pointer, 2);
+                assert_eq!(fault.bottom, [4, 1 << 62]);
+            }
+            result => panic!("{:?}", result),
+        }
+
+        drop(interpreter);
+        let tokens = parse("🥺2147483648", false);
+        let mut narrow = Interpreter::with_cells(tokens, Unstack::<i32>::new(), &mut host);
+        narrow.set_semantics(checked);
+        assert!(matches!(
+            narrow.run(&Limits::default()),
+            Err(RuntimeError::Overflow { .. })
+        ));
+
+        drop(narrow);
+        let tokens = parse("📏0", true);
+        let mut interpreter = Interpreter::new(tokens, Unstack::from(vec![i64::MIN]), &mut host);
+        interpreter.set_semantics(checked);
+        let err = interpreter.run(&Limits::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "📏0 : overflow computing with -9223372036854775808 at 0"
+        );
+    }
+
+    #[test]
+    fn test_floor_division() {
+        let truncating = Semantics::default();
+        let floor = Semantics {
+            floor_division: true,
+            ..Semantics::default()
+        };
+        for (value, divisor, truncated, floored) in [
+            (7i64, 2, 3, 3),
+            (-7, 2, -3, -4),
+            (7, -2, -3, -4),
+            (-7, -2, 3, 3),
+            (-8, 2, -4, -4),
+            (0, -3, 0, 0),
+        ] {
+            assert_eq!(truncating.quotient(value, divisor), truncated);
+            assert_eq!(floor.quotient(value, divisor), floored);
+        }
+        let mut host = Host::new();
+        let tokens = parse("💖2", false);
+        let mut interpreter = Interpreter::new(tokens, Unstack::from(vec![-1]), &mut host);
+        interpreter.set_semantics(floor);
+        interpreter.run(&Limits::default()).unwrap();
+        assert_eq!(interpreter.output(), vec![-1]);
+    }
+
+    #[test]
+    fn test_strict_jumps() {
+        let run = |source, semantics| {
+            let mut host = Host::new();
+            // the same whichever end is the bottom
+            let unstack = Unstack::from(vec![0, 1, 1, 0]);
+            let mut interpreter = Interpreter::new(parse(source, false), unstack, &mut host);
+            interpreter.set_semantics(semantics);
+            interpreter
+                .run(&Limits::default())
+                .map(|_| interpreter.output())
+        };
+        // jumps back to the start once
+        assert_eq!(run("🫂1 🫂3", Semantics::spec()), Ok(vec![]));
+        assert_eq!(run("🫂1 🫂4", Semantics::default()), Ok(vec![1, 0]));
+        assert_eq!(
+            run("🫂1 🫂4", Semantics::spec()),
+            Err(RuntimeError::JumpOutOfRange {
+                instruction_pointer: 1
+            })
+        );
+        assert!(run("🫂1 🫂0 🥺5", Semantics::spec()).is_err());
+    }
+
+    #[test]
+    fn test_peek() {
+        let mut host = Host::captured(b"");
+        let tokens = parse("🥺1 🥺2 👀5 💓0 👀0", true);
+        assert_eq!(
+            interpret(tokens, Unstack::new(), &mut host, &Limits::default()),
+            Ok(vec![2])
+        );
+        assert_eq!(host.captured_output(1), b"");
+        assert_eq!(
+            String::from_utf8(host.captured_output(2)).unwrap(),
+            "👀5 [2, 1]\n👀0 [2]\n"
+        );
+    }
+
+    #[test]
+    fn test_scan() {
+        let mut host = Host::captured(b"12\n -3 \r\n7");
+        let tokens = parse("🧮0 🧮0 🧮0 🧮0 🧮0", true);
+        assert_eq!(
+            interpret(tokens, Unstack::new(), &mut host, &Limits::default()),
+            Ok(vec![SCAN_END, SCAN_END, 7, -3, 12])
+        );
+    }
+
+    #[test]
+    fn test_skip() {
+        let run = |source, init: &[i64]| {
+            let tokens = parse(source, true);
+            let unstack = Unstack::from(init.to_vec());
+            interpret(tokens, unstack, &mut Host::new(), &Limits::default())
+        };
+        assert_eq!(run("🦘2 🥺1 🥺2 🥺3", &[0]), Ok(vec![3]));
+        assert_eq!(run("🦘2 🥺1 🥺2 🥺3", &[5]), Ok(vec![3, 2, 1]));
+        assert_eq!(run("🦘9 🥺1", &[0]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_calls() {
+        // the routine at 4 pushes a 2, the 🦘 ends the main program before it
+        let tokens = parse("🛫4 🛫4 🥺0 🦘9 🥺2 🛬0", true);
+        let mut host = Host::new();
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.run_steps(2).unwrap();
+        assert_eq!(interpreter.calls(), [1]);
+        interpreter.run(&Limits::default()).unwrap();
+        assert_eq!(interpreter.output(), vec![2, 2]);
+    }
+
+    #[test]
+    fn test_faults() {
+        let run = |source| {
+            let tokens = parse(source, true);
+            interpret(tokens, Unstack::new(), &mut Host::new(), &Limits::default())
+        };
+        let err = run("🥺1 🥺2 💓1").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "💓 : unstack too small (expected at least 3, had 2) at 2"
+        );
+        let source = "🥺1 🥺2\n💓1";
+        let spans: Vec<_> = parse_spanned(source, true)
+            .into_iter()
+            .map(|(_, span)| span)
+            .collect();
+        assert_eq!(
+            err.locate(&spans),
+            RuntimeError::Underflow {
+                fault: Box::new(Fault {
+                    operation: Operations::Heart(1),
+                    instruction_pointer: 2,
+                    span: Some(source.find('💓').unwrap()..source.len()),
+                    bottom: vec![2, 1],
+                }),
+                needed: 3,
+                had: 2,
+            }
+        );
+
+        let err = run("🥺1 🛬0").unwrap_err();
+        assert_eq!(err.to_string(), "🛬 : empty return stack at 1");
+        assert!(matches!(err, RuntimeError::Failed { .. }));
+        assert_eq!(err.fault().map(|fault| fault.bottom.clone()), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_keep_going() {
+        let mut host = Host::new();
+        let tokens = parse("🥺2 💓0 👉👈3 🥺3 💓0 🛬0", true);
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.set_keep_going(true);
+        assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Finished));
+        assert_eq!(interpreter.output(), vec![6]);
+        let skipped: Vec<_> = (interpreter.skipped().iter())
+            .map(RuntimeError::instruction_pointer)
+            .collect();
+        assert_eq!(skipped, [1, 2, 5]);
+
+        // a skipped instruction leaves the unstack as it found it
+        for (source, output) in [
+            ("🥺7 🥺5 🪢0 🥺1", &[1, 5, 7][..]),
+            ("🥺7 📤0 💖0 🥺1", &[1, 7]),
+        ] {
+            let mut host = Host::new();
+            let mut interpreter = Interpreter::new(parse(source, true), Unstack::new(), &mut host);
+            interpreter.set_keep_going(true);
+            assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Finished));
+            assert_eq!(interpreter.output(), output, "{}", source);
+        }
+        let mut host = Host::new();
+        let tokens = parse("🥺3 🥺4 ✅0 🥺1", true);
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.set_keep_going(true);
+        assert!(matches!(
+            interpreter.run(&Limits::default()),
+            Err(RuntimeError::AssertionFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let mut host = Host::new();
+        let tokens = parse("🥺3 🥺4 💓0 ✨1 🥺0 💾0 🥺2 💓0 🥺0 💿0", true);
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.set_snapshots(2, 3);
+        assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Finished));
+        assert_eq!(interpreter.output(), vec![12, 24]);
+        let steps: Vec<_> = interpreter.snapshots().map(|taken| taken.steps()).collect();
+        assert_eq!(steps, [6, 8, 10]);
+
+        // going back to before 💾 forgets the stored value
+        let snapshot = interpreter.snapshots().next().unwrap().clone();
+        interpreter.restore(&snapshot);
+        assert_eq!(interpreter.instruction_pointer(), 6);
+        assert_eq!(interpreter.unstack().values(), vec![12]);
+        assert_eq!(interpreter.snapshots().count(), 1);
+        assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Finished));
+        assert_eq!(interpreter.output(), vec![12, 24]);
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut host = Host::new();
+        let tokens = parse("🥺3 🥺2 💓0 🥺1 👉👈1 🥺7 💓0 🥺5", true);
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        assert_eq!(interpreter.step_back(), Ok(false));
+        interpreter.set_snapshots(3, 4);
+        interpreter.add_breakpoint(4);
+        assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Paused(4)));
+        assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Finished));
+
+        assert_eq!(interpreter.step_back(), Ok(true));
+        assert_eq!(interpreter.instruction_pointer(), 7);
+        assert_eq!(interpreter.unstack().values(), vec![42, 1]);
+        assert_eq!(interpreter.step_back(), Ok(true));
+        assert_eq!(interpreter.unstack().values(), vec![7, 6, 1]);
+        assert_eq!(interpreter.reverse_continue(), Ok(true));
+        assert_eq!(interpreter.instruction_pointer(), 4);
+        assert_eq!(interpreter.unstack().values(), vec![1, 6]);
+        assert_eq!(interpreter.reverse_continue(), Ok(false));
+        assert_eq!(interpreter.instruction_pointer(), 4);
+        assert_eq!(interpreter.step_back(), Ok(true));
+        assert_eq!(interpreter.steps(), 3);
+    }
+
+    #[test]
+    fn test_auxiliary() {
+        // moves the 1 and 2 aside, then gets them back in the same order
+        let tokens = parse("🥺1 🥺2 📥3 📥3 🥺7 📤3 📤3", true);
+        let mut host = Host::new();
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.run_steps(5).unwrap();
+        assert_eq!(interpreter.auxiliary(3), [2, 1]);
+        assert_eq!(interpreter.unstack().values(), vec![7]);
+        interpreter.run(&Limits::default()).unwrap();
+        assert_eq!(interpreter.output(), vec![2, 1, 7]);
+        assert_eq!(interpreter.auxiliary(3), []);
+    }
+
+    #[test]
+    fn test_memory() {
+        // stores 6 at 2 + 1, then loads it back twice with the address given differently
+        let tokens = parse("🥺6 🥺2 💾1 🥺3 💿0 🥺1 💿2", true);
+        let mut host = Host::new();
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.run(&Limits::default()).unwrap();
+        assert_eq!(interpreter.output(), vec![6, 6]);
+        assert_eq!(interpreter.memory().get(&3), Some(&6));
+
+        let limits = Limits {
+            max_memory: Some(4),
+            ..Limits::default()
+        };
+        let run = |source| {
+            interpret(
+                parse(source, true),
+                Unstack::new(),
+                &mut Host::new(),
+                &limits,
+            )
+        };
+        assert_eq!(run("🥺1 🥺3 💾0 🥺3 💿0"), Ok(vec![1]));
+        assert!(matches!(
+            run("🥺1 🥺3 💾1"),
+            Err(RuntimeError::MemoryOutOfBounds {
+                address: 4,
+                limit: 4,
+                ..
+            })
+        ));
+        assert!(matches!(
+            run("🥺9 💿0"),
+            Err(RuntimeError::MemoryOutOfBounds { address: 9, .. })
+        ));
+
+        // the cell is not stored before the address is refused
+        let tokens = parse("🥺1 🥺5 💾0", true);
+        let mut host = Host::new();
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        assert!(interpreter.run(&limits).is_err());
+        assert!(interpreter.memory().is_empty());
+    }
+
+    #[test]
+    fn test_shuffling() {
+        let run = |source| {
+            let tokens = parse(source, true);
+            interpret(tokens, Unstack::new(), &mut Host::new(), &Limits::default())
+        };
+        // like rot in Forth, the deepest of the three comes to the bottom
+        assert_eq!(run("🥺1 🥺2 🥺3 🔄3"), Ok(vec![1, 3, 2]));
+        assert_eq!(run("🥺1 🥺2 🔄0 🔄1"), Ok(vec![2, 1]));
+        assert_eq!(run("🥺1 🥺2 🥺3 🪞2"), Ok(vec![2, 3, 1]));
+        assert_eq!(run("🥺1 🥺2 🥺3 🪞0"), Ok(vec![1, 2, 3]));
+        assert_eq!(run("🥺1 🥺2 🥺3 🧹2"), Ok(vec![1]));
+        assert_eq!(run("🥺1 🥺2 🥺3 🧹0"), Ok(vec![]));
+        assert_eq!(run("📚0 📚0 🥺7 📚0"), Ok(vec![3, 7, 1, 0]));
+    }
+
+    #[test]
+    fn test_tasks() {
+        use Operations::*;
+        let run = |tokens| interpret(tokens, Unstack::new(), &mut Host::new(), &Limits::default());
+
+        // the task multiplies its copy of 6 by 7, the main task keeps its 6
+        let tokens = vec![Push(6), Push(2), Spawn(1), Push(7), Heart(0), Join(0)];
+        assert_eq!(run(tokens), Ok(vec![42, 6]));
+
+        // two tasks waiting for each other
+        let waiting_for = |id| [Push(2), Spawn(0), Push(id), Join(0)];
+        let mut tokens = vec![];
+        tokens.extend(waiting_for(2));
+        tokens.extend(waiting_for(1));
+        tokens.push(Join(0));
+        assert!(matches!(run(tokens), Err(RuntimeError::Deadlock { .. })));
+    }
+
+    #[test]
+    fn test_representation() {
+        use Operations::*;
+        let tokens = vec![Push(6), Push(2), Spawn(1), Push(7), Heart(0), Join(0)];
+        let mut host = Host::new();
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.set_representation(Representation::Persistent);
+        assert_eq!(interpreter.step(), Ok(Status::Running));
+        let snapshot = interpreter.unstack().snapshot();
+        assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Finished));
+        assert_eq!(interpreter.output(), vec![42, 6]);
+        assert_eq!(snapshot.values(), vec![6]);
+    }
+
+    #[test]
+    fn test_assert() {
+        let run = |source| {
+            let tokens = parse(source, true);
+            interpret(tokens, Unstack::new(), &mut Host::new(), &Limits::default())
+        };
+        assert_eq!(run("🥺3 🥺3 ✅1 🥺1\n"), Ok(vec![1]));
+        assert_eq!(
+            run("🥺3 🥺4 ✅7\n"),
+            Err(RuntimeError::AssertionFailed {
+                id: 7,
+                left: 4,
+                right: 3,
+                instruction_pointer: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_step() {
+        let mut host = Host::new();
+        let mut interpreter =
+            Interpreter::new(parse("🥺6 🥺7 💓0", false), Unstack::new(), &mut host);
+        assert_eq!(interpreter.step(), Ok(Status::Running));
+        assert_eq!(interpreter.instruction_pointer(), 1);
+        assert_eq!(interpreter.unstack().values(), vec![6]);
+        assert_eq!(interpreter.step(), Ok(Status::Running));
+        assert_eq!(interpreter.step(), Ok(Status::Running));
+        assert!(interpreter.is_finished());
+        assert_eq!(interpreter.step(), Ok(Status::Finished));
+        assert_eq!(interpreter.steps(), 3);
+        assert_eq!(interpreter.output(), vec![42]);
+    }
+
+    #[test]
+    fn test_run_until() {
+        // halves 16 until it reaches 0, 🫂5 jumps back to the first ✨
+        let tokens = parse("🥺16 ✨1 💖2 ✨1 🫂5", false);
+        let mut host = Host::new();
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        assert_eq!(interpreter.run_until(3), Ok(Status::Running));
+        assert_eq!(interpreter.instruction_pointer(), 3);
+        assert_eq!(interpreter.run_steps(2), Ok(Status::Exhausted));
+        assert_eq!(interpreter.steps(), 5);
+        assert_eq!(interpreter.instruction_pointer(), 1);
+        assert_eq!(interpreter.run_until(100), Ok(Status::Finished));
+    }
+
+    #[test]
+    fn test_run_budgeted() {
+        // 🫂3 on a nonzero value continues at the ✨ forever
+        let tokens = vec![Operations::Push(1), Operations::Dup(1), Operations::Hug(3)];
+        let mut host = Host::new();
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        for round in 1..=3 {
+            let status = interpreter.run_budgeted(1000, &Limits::default());
+            assert_eq!(status, Ok(Status::Exhausted));
+            assert_eq!(interpreter.steps(), round * 1000);
+        }
+        // the 3000th instruction was a ✨
+        assert_eq!(interpreter.unstack().values(), vec![1, 1]);
+    }
+
+    #[test]
+    fn test_pause() {
+        // 🫂3 on a nonzero value continues at the ✨ forever
+        let tokens = vec![Operations::Push(1), Operations::Dup(1), Operations::Hug(3)];
+        let mut host = Host::new();
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        let pause = interpreter.pause_handle();
+        let pauser = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            pause.store(true, std::sync::atomic::Ordering::Relaxed);
+        });
+        assert!(matches!(
+            interpreter.run(&Limits::default()),
+            Ok(Status::Paused(_))
+        ));
+        pauser.join().unwrap();
+        assert!(interpreter.steps() > 0);
+        assert_eq!(interpreter.run_steps(10), Ok(Status::Exhausted));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let tokens = parse("🥺16 ✨1 💖2 ✨1 🫂5", false);
+        let mut host = Host::new();
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        assert!(interpreter.add_breakpoint(2));
+        assert!(!interpreter.add_breakpoint(2));
+        // every time around the loop
+        for halved in [16, 8, 4, 2, 1] {
+            assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Paused(2)));
+            assert_eq!(interpreter.unstack().values()[0], halved);
+        }
+        assert!(interpreter.remove_breakpoint(2));
+        assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Finished));
+    }
+
+    #[test]
+    fn test_source_breakpoints() {
+        let source = "🥺2 🛑0 🥺3 🛑1 💓0";
+        let run = interpret(
+            parse(source, true),
+            Unstack::new(),
+            &mut Host::new(),
+            &Limits::default(),
+        );
+        assert_eq!(run, Ok(vec![6]));
+        let mut host = Host::new();
+        let mut interpreter = Interpreter::new(parse(source, true), Unstack::new(), &mut host);
+        assert_eq!(interpreter.add_source_breakpoints(), 2);
+        assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Paused(1)));
+        assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Paused(3)));
+        assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Finished));
+        assert_eq!(interpreter.output(), vec![6]);
+    }
+
+    #[test]
+    fn test_observer() {
+        #[derive(Default)]
+        struct Recorder {
+            executed: Vec<usize>,
+            events: Vec<String>,
+        }
+        struct Observer(Rc<RefCell<Recorder>>);
+        impl ExecutionObserver for Observer {
+            fn on_pop(&mut self, _task: usize, value: i64) {
+                self.0.borrow_mut().events.push(format!("pop {}", value));
+            }
+            fn on_push(&mut self, _task: usize, value: i64) {
+                self.0.borrow_mut().events.push(format!("push {}", value));
+            }
+            fn after_instruction(&mut self, _: usize, ip: usize, _: Operations, _: &Unstack) {
+                self.0.borrow_mut().executed.push(ip);
+            }
+        }
+
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        let mut host = Host::new();
+        let tokens = parse("🥺6 🥺7 💓0 ✨1", false);
+        let mut interpreter = Interpreter::new(tokens, Unstack::new(), &mut host);
+        interpreter.observe(Box::new(Observer(Rc::clone(&recorder))));
+        assert_eq!(interpreter.run(&Limits::default()), Ok(Status::Finished));
+        let recorder = recorder.borrow();
+        assert_eq!(recorder.executed, vec![0, 1, 2, 3]);
+        assert_eq!(
+            recorder.events,
+            ["push 6", "push 7", "pop 7", "pop 6", "push 42", "pop 42", "push 42", "push 42"]
+        );
+    }
+
+    /// any operation that runs on an unstack alone, with a small argument so it fits on one
+    fn operation() -> impl Strategy<Value = Operations> {
+        let made = (0..OPERATIONS.len() + EXTENSIONS.len(), 0..5i64).prop_map(|(index, n)| {
+            let (_, make) = OPERATIONS.iter().chain(EXTENSIONS).nth(index).unwrap();
+            make(n)
+        });
+        prop_oneof![any::<i64>().prop_map(Operations::Push), made].prop_filter(
+            "needs the interpreter or the unstack depth",
+            |operation| {
+                operation.stack_effect().is_some()
+                    && !operation.needs_interpreter()
+                    && !matches!(operation, Operations::Accept(_))
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn prop_stack_effect(
+            operation in operation(),
+            // small enough that 💓 does not overflow
+            values in proptest::collection::vec(-1_000_000..1_000_000i64, 0..12),
+        ) {
+            let (needs, leaves) = operation.stack_effect().unwrap();
+            let mut unstack = Unstack::from(values.clone());
+            let mut host = Host::captured(&[]);
+            let result = execute(operation, &mut unstack, &mut 0, &mut host, &Semantics::default());
+            match result {
+                Ok(()) => prop_assert_eq!(unstack.len(), values.len() - needs + leaves),
+                Err(RuntimeError::Underflow { .. }) => prop_assert!(values.len() < needs),
+                // like 💖0 or ✅ on different values
+                Err(_) => {}
+            }
+        }
+
+        #[test]
+        fn prop_swap_keeps_values(
+            values in proptest::collection::vec(any::<i64>(), 1..12),
+            steps in 0..12usize,
+        ) {
+            prop_assume!(steps < values.len());
+            let mut unstack = Unstack::from(values.clone());
+            let mut host = Host::captured(&[]);
+            execute(Operations::Swap(steps), &mut unstack, &mut 0, &mut host, &Semantics::default())
+                .unwrap();
+            let mut before = values;
+            let mut after = unstack.values();
+            before.sort_unstable();
+            after.sort_unstable();
+            prop_assert_eq!(before, after);
+        }
+    }
+}
@@ -0,0 +1,322 @@
+/*!
+ * # JSON
+ *
+ * just enough JSON for the requests and responses of `bottom serve`
+ *
+ * numbers without a fraction or exponent are kept as exact integers, since they usually end up
+ * on an unstack
+ */
+
+use std::fmt::{self, Display, Formatter, Write};
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// the members in the order they were written
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// the value of the member `key`, `None` if this is no object or has no such member
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Json::Int(int) => Some(int),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Json::Bool(bool) => Some(bool),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// write `string` as a quoted JSON string
+fn write_string(f: &mut Formatter, string: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for ch in string.chars() {
+        match ch {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            ch if (ch as u32) < 0x20 => write!(f, "\\u{:04x}", ch as u32)?,
+            ch => f.write_char(ch)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// writes the value on a single line without any unnecessary whitespace
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(bool) => write!(f, "{}", bool),
+            Json::Int(int) => write!(f, "{}", int),
+            Json::Float(float) if float.is_finite() => write!(f, "{}", float),
+            // JSON has no infinity or NaN
+            Json::Float(_) => f.write_str("null"),
+            Json::String(string) => write_string(f, string),
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(members) => {
+                f.write_char('{')?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn error(&mut self, expected: &str) -> String {
+        match self.chars.peek() {
+            Some(&(at, ch)) => format!("expected {} at byte {}, found '{}'", expected, at, ch),
+            None => format!("expected {} at the end of the input", expected),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .next_if(|(_, ch)| matches!(ch, ' ' | '\t' | '\n' | '\r'))
+            .is_some()
+        {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next_if(|&(_, ch)| ch == expected) {
+            Some(_) => Ok(()),
+            None => Err(self.error(&format!("'{}'", expected))),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        for expected in keyword.chars() {
+            if self.chars.next_if(|&(_, ch)| ch == expected).is_none() {
+                return Err(self.error(keyword));
+            }
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.chars.peek().map(|&(_, ch)| ch) {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some(ch) if ch == '-' || ch.is_ascii_digit() => self.number(),
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.chars.peek().map_or(self.source.len(), |&(at, _)| at);
+        let mut end = start;
+        while let Some((at, ch)) = self
+            .chars
+            .next_if(|&(_, ch)| ch.is_ascii_digit() || matches!(ch, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            end = at + ch.len_utf8();
+        }
+        let number = &self.source[start..end];
+        if let Ok(int) = number.parse() {
+            return Ok(Json::Int(int));
+        }
+        number
+            .parse()
+            .map(Json::Float)
+            .map_err(|_| format!("'{}' at byte {} is not a number", number, start))
+    }
+
+    fn hex_escape(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .chars
+                .peek()
+                .and_then(|&(_, ch)| ch.to_digit(16))
+                .ok_or_else(|| self.error("a hex digit"))?;
+            self.chars.next();
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            let Some((_, ch)) = self.chars.next() else {
+                return Err(self.error("'\"'"));
+            };
+            match ch {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escaped = match self.chars.next().map(|(_, ch)| ch) {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let mut code = self.hex_escape()?;
+                            // characters outside the basic plane are written as a surrogate pair
+                            if (0xd800..0xdc00).contains(&code) {
+                                self.expect('\\')?;
+                                self.expect('u')?;
+                                let low = self.hex_escape()?;
+                                code =
+                                    0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00));
+                            }
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(self.error("an escape sequence")),
+                    };
+                    string.push(escaped);
+                }
+                ch => string.push(ch),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut values = vec![];
+        self.skip_whitespace();
+        if self.chars.next_if(|&(_, ch)| ch == ']').is_some() {
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            if self.chars.next_if(|&(_, ch)| ch == ',').is_none() {
+                self.expect(']')?;
+                return Ok(Json::Array(values));
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.chars.next_if(|&(_, ch)| ch == '}').is_some() {
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let name = self.string()?;
+            self.expect(':')?;
+            members.push((name, self.value()?));
+            self.skip_whitespace();
+            if self.chars.next_if(|&(_, ch)| ch == ',').is_none() {
+                self.expect('}')?;
+                return Ok(Json::Object(members));
+            }
+        }
+    }
+}
+
+impl FromStr for Json {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            source,
+            chars: source.char_indices().peekable(),
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.chars.peek() {
+            None => Ok(value),
+            Some(_) => Err(parser.error("the end of the input")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::Json;
+
+    #[test]
+    fn test_round_trip() {
+        let source =
+            r#"{"source":"🥺1 \"x\"\n","init":[-3,9007199254740993],"ok":true,"x":null,"f":1.5}"#;
+        let json: Json = source.parse().unwrap();
+        assert_eq!(json.get("source").unwrap().as_str(), Some("🥺1 \"x\"\n"));
+        assert_eq!(
+            json.get("init").unwrap().as_array().unwrap()[1].as_i64(),
+            Some(9007199254740993)
+        );
+        assert_eq!(json.to_string(), source);
+
+        let escaped: Json = r#" [ "\u00e9\ud83e\udd7a" , {} ] "#.parse().unwrap();
+        assert_eq!(escaped.as_array().unwrap()[0].as_str(), Some("é🥺"));
+        assert!("[1,]".parse::<Json>().is_err());
+        assert!("{\"a\" 1}".parse::<Json>().is_err());
+        assert!("1 2".parse::<Json>().is_err());
+    }
+}
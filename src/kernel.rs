@@ -0,0 +1,696 @@
+/*!
+ * # Jupyter kernel
+ *
+ * `bottom kernel CONNECTION_FILE` lets Jupyter notebooks run 🥺 cells. the unstack is carried
+ * from one cell to the next and shown after every cell, a cell that fails leaves it untouched
+ *
+ * `bottom kernel --write-spec DIR` writes the `kernel.json` that tells Jupyter how to start the
+ * kernel, install it with `jupyter kernelspec install --user DIR`
+ *
+ * the kernel speaks version 5.3 of the messaging protocol over [ZMTP](crate::zmtp), with every
+ * message signed with HMAC-SHA256 under the key from the connection file
+ */
+
+use std::fs;
+use std::net::TcpListener;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bottom::ext::Host;
+use bottom::interpreter::{Interpreter, Status};
+use bottom::unstack::Unstack;
+use bottom::{parse, Limits};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::batch::panic_message;
+use crate::json::Json;
+use crate::parse_duration;
+use crate::rng::Rng;
+use crate::sha256::{hex, hmac};
+use crate::zmtp::Connection;
+
+/// separates the routing identities of a message from its content
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+const PROTOCOL_VERSION: &str = "5.3";
+
+/// a message of the Jupyter protocol
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub identities: Vec<Vec<u8>>,
+    pub header: Json,
+    pub parent_header: Json,
+    pub metadata: Json,
+    pub content: Json,
+}
+
+impl Message {
+    pub fn msg_type(&self) -> &str {
+        self.header
+            .get("msg_type")
+            .and_then(Json::as_str)
+            .unwrap_or_default()
+    }
+}
+
+/// how a cell ended
+#[derive(Debug, PartialEq)]
+pub enum Cell {
+    /// the cell ran to its end and left this unstack
+    Finished(Vec<i64>),
+    /// the cell failed with an error of the kind `name`
+    Failed { name: &'static str, message: String },
+}
+
+/// what running a cell produced
+#[derive(Debug)]
+pub struct Execution {
+    pub cell: Cell,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// the current time as an ISO 8601 timestamp in UTC
+fn timestamp() -> String {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    // days to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+        since_epoch.subsec_micros()
+    )
+}
+
+/// whether `a` and `b` are equal, taking as long for every pair of the same length so the time
+/// does not give away how much of a signature is right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn object(members: Vec<(&str, Json)>) -> Json {
+    Json::Object(
+        members
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+    )
+}
+
+fn string(value: impl Into<String>) -> Json {
+    Json::String(value.into())
+}
+
+/// everything shared by the threads answering the sockets of the kernel
+pub struct Kernel {
+    key: Vec<u8>,
+    session: String,
+    extensions: bool,
+    limits: Limits,
+    messages: AtomicU64,
+    /// the unstack carried from cell to cell, bottom first
+    values: Mutex<Vec<i64>>,
+    execution_count: Mutex<i64>,
+    /// the pause handle of the running cell, set to interrupt it
+    running: Mutex<Option<Arc<AtomicBool>>>,
+    /// connections to the iopub socket
+    subscribers: Mutex<Vec<Connection>>,
+}
+
+impl Kernel {
+    pub fn new(key: &str, extensions: bool, limits: Limits) -> Self {
+        Kernel {
+            key: key.as_bytes().to_vec(),
+            session: format!("{:016x}", Rng::new(Rng::seed_from_time()).next_u64()),
+            extensions,
+            limits,
+            messages: AtomicU64::new(0),
+            values: Mutex::new(vec![]),
+            execution_count: Mutex::new(0),
+            running: Mutex::new(None),
+            subscribers: Mutex::new(vec![]),
+        }
+    }
+
+    fn sign(&self, parts: &[&[u8]]) -> String {
+        if self.key.is_empty() {
+            String::new()
+        } else {
+            hex(&hmac(&self.key, parts))
+        }
+    }
+
+    /// turn `message` into frames, signing it
+    pub fn encode(&self, message: &Message) -> Vec<Vec<u8>> {
+        let parts = [
+            message.header.to_string(),
+            message.parent_header.to_string(),
+            message.metadata.to_string(),
+            message.content.to_string(),
+        ];
+        let signature = self.sign(&parts.each_ref().map(|part| part.as_bytes()));
+        let mut frames = message.identities.clone();
+        frames.push(DELIMITER.to_vec());
+        frames.push(signature.into_bytes());
+        frames.extend(parts.map(String::into_bytes));
+        frames
+    }
+
+    /// the message in `frames`, `None` if it is malformed or its signature is wrong
+    pub fn decode(&self, mut frames: Vec<Vec<u8>>) -> Option<Message> {
+        let delimiter = frames.iter().position(|frame| frame == DELIMITER)?;
+        let rest = frames.split_off(delimiter + 1);
+        frames.pop();
+        let [signature, header, parent_header, metadata, content, ..] = rest.as_slice() else {
+            return None;
+        };
+        let expected = self.sign(&[header, parent_header, metadata, content]);
+        if !constant_time_eq(signature, expected.as_bytes()) {
+            return None;
+        }
+        let parse = |part: &[u8]| std::str::from_utf8(part).ok()?.parse::<Json>().ok();
+        Some(Message {
+            identities: frames,
+            header: parse(header)?,
+            parent_header: parse(parent_header)?,
+            metadata: parse(metadata)?,
+            content: parse(content)?,
+        })
+    }
+
+    /// a new message of type `msg_type` in reply to `parent`
+    fn message(
+        &self,
+        identities: Vec<Vec<u8>>,
+        parent: &Message,
+        msg_type: &str,
+        content: Json,
+    ) -> Message {
+        let id = self.messages.fetch_add(1, Ordering::Relaxed);
+        let header = object(vec![
+            ("msg_id", string(format!("{}-{}", self.session, id))),
+            ("session", string(&self.session)),
+            ("username", string("kernel")),
+            ("date", string(timestamp())),
+            ("msg_type", string(msg_type)),
+            ("version", string(PROTOCOL_VERSION)),
+        ]);
+        Message {
+            identities,
+            header,
+            parent_header: parent.header.clone(),
+            metadata: object(vec![]),
+            content,
+        }
+    }
+
+    /// send a message to everyone listening on iopub, forgetting subscribers that went away
+    fn publish(&self, parent: &Message, msg_type: &str, content: Json) {
+        let message = self.message(
+            vec![msg_type.as_bytes().to_vec()],
+            parent,
+            msg_type,
+            content,
+        );
+        let frames = self.encode(&message);
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain_mut(|subscriber| subscriber.send(&frames).is_ok());
+    }
+
+    fn reply(&self, connection: &mut Connection, request: &Message, msg_type: &str, content: Json) {
+        let message = self.message(request.identities.clone(), request, msg_type, content);
+        // a client that went away will not miss its reply
+        let _ = connection.send(&self.encode(&message));
+    }
+
+    /// run `code` on the unstack left by the previous cells
+    pub fn run_cell(&self, code: &str) -> Execution {
+        let values = self.values.lock().unwrap().clone();
+        let unstack: Unstack = values.iter().rev().copied().collect();
+        let mut host = Host::captured(&[]);
+        let mut interpreter = Interpreter::new(parse(code, self.extensions), unstack, &mut host);
+        *self.running.lock().unwrap() = Some(interpreter.pause_handle());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| interpreter.run(&self.limits)));
+        *self.running.lock().unwrap() = None;
+        let cell = match result {
+            Ok(Ok(Status::Finished)) => {
+                let output = interpreter.output();
+                self.values.lock().unwrap().clone_from(&output);
+                Cell::Finished(output)
+            }
+            Ok(Ok(_)) => Cell::Failed {
+                name: "Interrupted",
+                message: format!("interrupted at {}", interpreter.instruction_pointer()),
+            },
+            Ok(Err(err)) => Cell::Failed {
+                name: "RuntimeError",
+                message: err.to_string(),
+            },
+            Err(payload) => Cell::Failed {
+                name: "Panic",
+                message: panic_message(payload),
+            },
+        };
+        drop(interpreter);
+        let captured = |fd| String::from_utf8_lossy(&host.captured_output(fd)).into_owned();
+        Execution {
+            cell,
+            stdout: captured(1),
+            stderr: captured(2),
+        }
+    }
+
+    fn execute(&self, connection: &mut Connection, request: &Message) {
+        let code = request
+            .content
+            .get("code")
+            .and_then(Json::as_str)
+            .unwrap_or_default();
+        let silent = request
+            .content
+            .get("silent")
+            .and_then(Json::as_bool)
+            .unwrap_or(false);
+        let execution_count = {
+            let mut count = self.execution_count.lock().unwrap();
+            if !silent {
+                *count += 1;
+            }
+            Json::Int(*count)
+        };
+        if !silent {
+            let input = object(vec![
+                ("code", string(code)),
+                ("execution_count", execution_count.clone()),
+            ]);
+            self.publish(request, "execute_input", input);
+        }
+
+        let execution = self.run_cell(code);
+        for (name, text) in [("stdout", execution.stdout), ("stderr", execution.stderr)] {
+            if !text.is_empty() && !silent {
+                let stream = object(vec![("name", string(name)), ("text", string(text))]);
+                self.publish(request, "stream", stream);
+            }
+        }
+        let reply = match execution.cell {
+            Cell::Finished(values) => {
+                if !silent {
+                    let result = object(vec![
+                        ("execution_count", execution_count.clone()),
+                        (
+                            "data",
+                            object(vec![("text/plain", string(format!("{:?}", values)))]),
+                        ),
+                        ("metadata", object(vec![])),
+                    ]);
+                    self.publish(request, "execute_result", result);
+                }
+                object(vec![
+                    ("status", string("ok")),
+                    ("execution_count", execution_count),
+                    ("user_expressions", object(vec![])),
+                    ("payload", Json::Array(vec![])),
+                ])
+            }
+            Cell::Failed { name, message } => {
+                let error = vec![
+                    ("ename", string(name)),
+                    ("evalue", string(&message)),
+                    ("traceback", Json::Array(vec![string(&message)])),
+                ];
+                self.publish(request, "error", object(error.clone()));
+                let mut reply = vec![
+                    ("status", string("error")),
+                    ("execution_count", execution_count),
+                ];
+                reply.extend(error);
+                object(reply)
+            }
+        };
+        self.reply(connection, request, "execute_reply", reply);
+    }
+
+    fn kernel_info() -> Json {
+        object(vec![
+            ("status", string("ok")),
+            ("protocol_version", string(PROTOCOL_VERSION)),
+            ("implementation", string("bottom")),
+            ("implementation_version", string(env!("CARGO_PKG_VERSION"))),
+            (
+                "language_info",
+                object(vec![
+                    ("name", string("bottom")),
+                    ("version", string(env!("CARGO_PKG_VERSION"))),
+                    ("mimetype", string("text/plain")),
+                    ("file_extension", string(".🥺")),
+                ]),
+            ),
+            (
+                "banner",
+                string("🥺 interpreter, the unstack is kept between cells"),
+            ),
+            ("help_links", Json::Array(vec![])),
+        ])
+    }
+
+    /// answer a request on the shell or control socket
+    fn handle(&self, connection: &mut Connection, request: &Message) {
+        self.publish(
+            request,
+            "status",
+            object(vec![("execution_state", string("busy"))]),
+        );
+        match request.msg_type() {
+            "kernel_info_request" => self.reply(
+                connection,
+                request,
+                "kernel_info_reply",
+                Kernel::kernel_info(),
+            ),
+            "execute_request" => self.execute(connection, request),
+            "is_complete_request" => {
+                let reply = object(vec![("status", string("complete"))]);
+                self.reply(connection, request, "is_complete_reply", reply);
+            }
+            "comm_info_request" => {
+                let reply = object(vec![("status", string("ok")), ("comms", object(vec![]))]);
+                self.reply(connection, request, "comm_info_reply", reply);
+            }
+            "history_request" => {
+                let reply = object(vec![
+                    ("status", string("ok")),
+                    ("history", Json::Array(vec![])),
+                ]);
+                self.reply(connection, request, "history_reply", reply);
+            }
+            "interrupt_request" => {
+                if let Some(pause) = &*self.running.lock().unwrap() {
+                    pause.store(true, Ordering::Relaxed);
+                }
+                self.reply(
+                    connection,
+                    request,
+                    "interrupt_reply",
+                    object(vec![("status", string("ok"))]),
+                );
+            }
+            "shutdown_request" => {
+                let restart = request
+                    .content
+                    .get("restart")
+                    .cloned()
+                    .unwrap_or(Json::Bool(false));
+                let reply = object(vec![("status", string("ok")), ("restart", restart)]);
+                self.reply(connection, request, "shutdown_reply", reply);
+                process::exit(0);
+            }
+            _ => {}
+        }
+        self.publish(
+            request,
+            "status",
+            object(vec![("execution_state", string("idle"))]),
+        );
+    }
+}
+
+/// the ports and key Jupyter chose for the kernel, as written to the connection file
+#[derive(Debug, PartialEq)]
+pub struct ConnectionInfo {
+    pub ip: String,
+    pub shell_port: u16,
+    pub iopub_port: u16,
+    pub stdin_port: u16,
+    pub control_port: u16,
+    pub hb_port: u16,
+    pub key: String,
+}
+
+impl ConnectionInfo {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let json: Json = source.parse()?;
+        let text = |name: &str| {
+            json.get(name)
+                .and_then(Json::as_str)
+                .ok_or_else(|| format!("'{}' is missing", name))
+        };
+        let port = |name: &str| {
+            json.get(name)
+                .and_then(Json::as_i64)
+                .and_then(|port| u16::try_from(port).ok())
+                .ok_or_else(|| format!("'{}' is not a port", name))
+        };
+        if text("transport")? != "tcp" {
+            return Err("only the tcp transport is supported".to_string());
+        }
+        let key = text("key").unwrap_or_default().to_string();
+        if !key.is_empty() && text("signature_scheme")? != "hmac-sha256" {
+            return Err("only hmac-sha256 signatures are supported".to_string());
+        }
+        Ok(ConnectionInfo {
+            ip: text("ip")?.to_string(),
+            shell_port: port("shell_port")?,
+            iopub_port: port("iopub_port")?,
+            stdin_port: port("stdin_port")?,
+            control_port: port("control_port")?,
+            hb_port: port("hb_port")?,
+            key,
+        })
+    }
+}
+
+/// accept connections on `port` in the background, handing each to `serve` on its own thread
+fn listen(
+    ip: &str,
+    port: u16,
+    socket_type: &'static str,
+    serve: impl Fn(Connection) + Send + Sync + Clone + 'static,
+) -> thread::JoinHandle<()> {
+    let listener = TcpListener::bind((ip, port)).unwrap_or_else(|err| {
+        eprintln!("error: could not listen on {}:{}: {}", ip, port, err);
+        process::exit(1);
+    });
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let serve = serve.clone();
+            thread::spawn(move || {
+                if let Ok(connection) = Connection::accept(stream, socket_type) {
+                    serve(connection);
+                }
+            });
+        }
+    })
+}
+
+/// answer requests on `connection` until the client goes away
+fn serve_requests(kernel: &Kernel, mut connection: Connection) {
+    while let Ok(frames) = connection.recv() {
+        if let Some(request) = kernel.decode(frames) {
+            kernel.handle(&mut connection, &request);
+        }
+    }
+}
+
+/// run the kernel described by `info` until it is asked to shut down
+pub fn run(info: &ConnectionInfo, kernel: Kernel) {
+    let kernel = Arc::new(kernel);
+    let ip = info.ip.as_str();
+    let shell = {
+        let kernel = Arc::clone(&kernel);
+        listen(ip, info.shell_port, "ROUTER", move |connection| {
+            serve_requests(&kernel, connection)
+        })
+    };
+    {
+        let kernel = Arc::clone(&kernel);
+        listen(ip, info.control_port, "ROUTER", move |connection| {
+            serve_requests(&kernel, connection)
+        });
+    }
+    {
+        let kernel = Arc::clone(&kernel);
+        listen(ip, info.iopub_port, "PUB", move |mut connection| {
+            if let Ok(publisher) = connection.try_clone() {
+                kernel.subscribers.lock().unwrap().push(publisher);
+            }
+            // subscriptions are ignored, every subscriber gets everything
+            while connection.recv().is_ok() {}
+        });
+    }
+    // cells never ask for input
+    listen(ip, info.stdin_port, "ROUTER", |mut connection| {
+        while connection.recv().is_ok() {}
+    });
+    listen(ip, info.hb_port, "REP", |mut connection| {
+        while let Ok(message) = connection.recv() {
+            if connection.send(&message).is_err() {
+                break;
+            }
+        }
+    });
+    let _ = shell.join();
+}
+
+/// the `kernel.json` that starts this executable as a kernel
+fn kernel_spec(extensions: bool) -> Json {
+    let executable = std::env::current_exe()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "bottom".to_string());
+    let mut argv = vec![string(executable), string("kernel")];
+    if extensions {
+        argv.push(string("--extensions"));
+    }
+    argv.push(string("{connection_file}"));
+    object(vec![
+        ("argv", Json::Array(argv)),
+        ("display_name", string("🥺")),
+        ("language", string("bottom")),
+    ])
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("kernel")
+        .about("run as a Jupyter kernel")
+        .arg(
+            Arg::with_name("connection-file")
+                .required_unless("write-spec")
+                .value_name("CONNECTION_FILE")
+                .help("connection file written by Jupyter"),
+        )
+        .arg(
+            Arg::with_name("write-spec")
+                .long("write-spec")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("write a kernel.json for `jupyter kernelspec install` to DIR and exit"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("enable the extension operations"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .value_name("DURATION")
+                .validator(|duration| parse_duration(&duration).map(|_| ()))
+                .help("abort each cell after running for DURATION"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let extensions = args.is_present("extensions");
+    if let Some(dir) = args.value_of("write-spec") {
+        let dir = Path::new(dir);
+        let written = fs::create_dir_all(dir)
+            .and_then(|_| fs::write(dir.join("kernel.json"), kernel_spec(extensions).to_string()));
+        if let Err(err) = written {
+            eprintln!("error: could not write {}: {}", dir.display(), err);
+            process::exit(1);
+        }
+        return;
+    }
+    let path = args.value_of("connection-file").expect("required by clap");
+    let info = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|source| ConnectionInfo::parse(&source))
+        .unwrap_or_else(|err| {
+            eprintln!("error: could not read connection file {}: {}", path, err);
+            process::exit(1);
+        });
+    let mut limits = Limits::default();
+    if let Some(timeout) = args.value_of("timeout") {
+        limits.timeout = Some(parse_duration(timeout).expect("validated by clap"));
+    }
+    // failed cells are shown in the notebook
+    panic::set_hook(Box::new(|_| {}));
+    run(&info, Kernel::new(&info.key, extensions, limits));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::Json;
+    use crate::kernel::{constant_time_eq, Cell, ConnectionInfo, Kernel, Message};
+    use bottom::Limits;
+
+    #[test]
+    fn test_signature() {
+        let kernel = Kernel::new("secret", false, Limits::default());
+        let parent = Message {
+            identities: vec![],
+            header: Json::Object(vec![]),
+            parent_header: Json::Object(vec![]),
+            metadata: Json::Object(vec![]),
+            content: Json::Object(vec![]),
+        };
+        let message = kernel.message(vec![b"client".to_vec()], &parent, "status", Json::Int(1));
+        let frames = kernel.encode(&message);
+        assert_eq!(frames[0], b"client");
+        assert_eq!(kernel.decode(frames.clone()), Some(message));
+
+        let mut tampered = frames;
+        *tampered.last_mut().unwrap() = b"2".to_vec();
+        assert_eq!(kernel.decode(tampered), None);
+
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
+    }
+
+    #[test]
+    fn test_cells() {
+        let kernel = Kernel::new("", true, Limits::default());
+        assert_eq!(kernel.run_cell("🥺6 🥺7").cell, Cell::Finished(vec![7, 6]));
+        let execution = kernel.run_cell("💓0 🥺104 📝1");
+        assert_eq!(execution.cell, Cell::Finished(vec![42]));
+        assert_eq!(execution.stdout, "h");
+        // a failed cell keeps the unstack of the cells before
+        assert!(matches!(
+            kernel.run_cell("✨1 💓5").cell,
+            Cell::Failed {
+                name: "RuntimeError",
+                ..
+            }
+        ));
+        assert_eq!(kernel.run_cell("").cell, Cell::Finished(vec![42]));
+    }
+
+    #[test]
+    fn test_connection_info() {
+        let source = r#"{"shell_port": 1, "iopub_port": 2, "stdin_port": 3, "control_port": 4,
+            "hb_port": 5, "ip": "127.0.0.1", "key": "k", "transport": "tcp",
+            "signature_scheme": "hmac-sha256", "kernel_name": "bottom"}"#;
+        let info = ConnectionInfo::parse(source).unwrap();
+        assert_eq!((info.shell_port, info.hb_port), (1, 5));
+        assert_eq!(info.key, "k");
+        assert!(ConnectionInfo::parse(&source.replace("tcp", "ipc")).is_err());
+    }
+}
@@ -0,0 +1,1644 @@
+/*!
+ * # 🥺
+ *
+ * 🥺 is an unstack-based esoteric programming language written in 2021 by [User:RocketRace](https://esolangs.org/wiki/User:RocketRace).
+ * It is inspired by [bottom](https://github.com/kaylynn234/bottom) by [kaylynn234](https://github.com/kaylynn234).
+ * It is a dialect of "bottom", a natural language used by bottoms.
+ *
+ * See: [🥺 on esolangs.org](https://esolangs.org/wiki/%F0%9F%A5%BA)
+ *
+ * |    Please  |  🥺🥺🥺                                                                                                                                                           |
+ * | -------    | ------                                                                                                                                                            |
+ * |     🥺N    | Pushes an integer `N` to the bottom of the unstack                                                                                                                |
+ * |     💖N    | Pops an integer from the unstack, and pushes the result of floor division of that integer by `N` to the unstack.                                                  |
+ * |     👉👈N  | Take the `N`th value in the unstack and swap it with the bottom value.                                                                                            |
+ * |     💓N    | Pops two integers from the unstack, then pops and discards `N` values from the unstack, then pushes the product of the two popped integers to the unstack         |
+ * |     ✨N    | Duplicates the `N` values at the bottom of the unstack                                                                                                            |
+ * |    🫂N     | Pop a value from the bottom of the unstack. Jump back `N` instructions if the value is nonzero                                                                    |
+ *
+ *
+ */
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+use core::ops::Range;
+use core::time::Duration;
+
+use crate::cell::Cell;
+
+pub mod bytecode;
+pub mod cell;
+pub mod closures;
+pub mod ext;
+pub mod interpreter;
+#[cfg(all(feature = "native", target_arch = "x86_64"))]
+pub mod native;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod pragma;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod sandbox;
+
+/**
+ * # Possible Operations
+ *
+ * enum that represents an action and the argument associated
+ *
+ * would allow for remixing the commands associated with an operation
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operations {
+    /// 🥺      :
+    /// Pushes an integer N to the bottom of the unstack.
+    Push(i64),
+    /// 💖      :
+    /// Pops an integer from the unstack, and pushes the result of floor division of that integer by N to the unstack.
+    Pop(i64),
+    /// 👉👈    :
+    /// Take the Nth value in the unstack and swap it with the bottom value.
+    Swap(usize),
+    /// 💓      :
+    /// Pops two integers from the unstack, then pops and discards N values from the unstack, then pushes the product of the two popped integers to the unstack.
+    Heart(usize),
+    /// ✨      :
+    /// Duplicates the N values at the bottom of the unstack.
+    Dup(usize),
+    /// 🫂      :
+    /// Pop a value from the bottom of the unstack. Jump back N instructions if the value is nonzero.
+    Hug(usize),
+    /// 🏠      : (extension)
+    /// Pushes the integer value of the Nth environment variable to the unstack, or 0 if it is unset.
+    Env(usize),
+    /// 📖      : (extension)
+    /// Reads a byte from file descriptor N and pushes it to the unstack, or -1 at the end of the input.
+    Read(usize),
+    /// 📝      : (extension)
+    /// Pops an integer from the unstack and writes its lowest byte to file descriptor N.
+    Write(usize),
+    /// 🧮      : (extension)
+    /// Reads a line from file descriptor N and pushes it parsed as an integer, or i64::MIN at the end of the input.
+    Scan(usize),
+    /// 🔢      : (extension)
+    /// Pops an integer from the unstack and writes it to file descriptor N as a decimal number followed by a newline.
+    Print(usize),
+    /// 📞      : (extension, requires the `net` feature)
+    /// Waits for a connection on the socket N, which 📖N and 📝N then read from and write to.
+    Accept(usize),
+    /// 🧵      : (extension)
+    /// Pops an integer L, then runs the next L instructions as a concurrent task starting with a copy of the N bottom values of the unstack. Pushes the id of the task to the unstack and continues after those L instructions.
+    Spawn(usize),
+    /// 🪢      : (extension)
+    /// Pops a task id and waits for that task to finish, then pushes the N bottom values of its unstack (all of them for N = 0) to the unstack.
+    Join(usize),
+    /// ✅      : (extension)
+    /// Pops two integers from the unstack and aborts the program if they differ. N identifies the assertion in the error.
+    Assert(usize),
+    /// 🔄      : (extension)
+    /// Moves the value N - 1 above the bottom of the unstack to the bottom, the values below it move up by one.
+    Rotate(usize),
+    /// 🪞      : (extension)
+    /// Reverses the order of the N values at the bottom of the unstack, of all of them for N = 0.
+    Reverse(usize),
+    /// 🧹      : (extension)
+    /// Pops and discards N values from the unstack, all of them for N = 0.
+    Clear(usize),
+    /// 📚      : (extension)
+    /// Pushes the number of values on the unstack before it. N is ignored.
+    Depth(usize),
+    /// 🦘      : (extension)
+    /// Pops a value from the bottom of the unstack. Skips the next N instructions if the value is zero.
+    Skip(usize),
+    /// 🛫      : (extension)
+    /// Pushes the instruction after it to the return stack of the task and continues at instruction N, counted from the start of the program.
+    Call(usize),
+    /// 🛬      : (extension)
+    /// Pops an instruction from the return stack of the task and continues there. N is ignored.
+    Return(usize),
+    /// 📥      : (extension)
+    /// Pops a value from the unstack and pushes it to the auxiliary unstack N of the task.
+    Stash(usize),
+    /// 📤      : (extension)
+    /// Pops a value from the auxiliary unstack N of the task and pushes it to the unstack.
+    Unstash(usize),
+    /// 💾      : (extension)
+    /// Pops an address and then a value from the unstack and stores the value in the memory cell at the address plus N.
+    Store(usize),
+    /// 💿      : (extension)
+    /// Pops an address from the unstack and pushes the value in the memory cell at the address plus N, 0 if nothing was stored there.
+    Load(usize),
+    /// 👀      : (extension)
+    /// Writes the unstack to stderr, bottom first and labelled with N, without changing it.
+    Peek(usize),
+    /// 🛑      : (extension)
+    /// Does nothing, but pauses a debugger that [honors it](interpreter::Interpreter::add_source_breakpoints). N is ignored.
+    Break(usize),
+    /// 💤      : (extension)
+    /// Does nothing, e.g. as padding that keeps the jumps around it the same while editing. N is ignored.
+    Nop(usize),
+    /// 📏 💪 🔻 🔺 : (extension)
+    /// Pops the operands of the intrinsic, then pops and discards N values, then pushes its result, see [`ext::math`].
+    Math(ext::math::Intrinsic, usize),
+}
+
+impl Operations {
+    /// the emoji this operation is written as
+    pub fn emoji(&self) -> &'static str {
+        match self {
+            Operations::Push(_) => "🥺",
+            Operations::Pop(_) => "💖",
+            Operations::Swap(_) => "👉👈",
+            Operations::Heart(_) => "💓",
+            Operations::Dup(_) => "✨",
+            Operations::Hug(_) => "🫂",
+            Operations::Env(_) => "🏠",
+            Operations::Read(_) => "📖",
+            Operations::Write(_) => "📝",
+            Operations::Print(_) => "🔢",
+            Operations::Scan(_) => "🧮",
+            Operations::Accept(_) => "📞",
+            Operations::Spawn(_) => "🧵",
+            Operations::Join(_) => "🪢",
+            Operations::Assert(_) => "✅",
+            Operations::Rotate(_) => "🔄",
+            Operations::Reverse(_) => "🪞",
+            Operations::Clear(_) => "🧹",
+            Operations::Depth(_) => "📚",
+            Operations::Skip(_) => "🦘",
+            Operations::Call(_) => "🛫",
+            Operations::Return(_) => "🛬",
+            Operations::Stash(_) => "📥",
+            Operations::Unstash(_) => "📤",
+            Operations::Store(_) => "💾",
+            Operations::Load(_) => "💿",
+            Operations::Peek(_) => "👀",
+            Operations::Break(_) => "🛑",
+            Operations::Nop(_) => "💤",
+            Operations::Math(intrinsic, _) => intrinsic.entry().emoji,
+        }
+    }
+
+    /// the number of values the operation needs and the number it leaves behind in their place,
+    /// `None` for 🧵 and 🪢 whose effect depends on the values and 🧹0 whose effect depends on
+    /// the depth of the unstack
+    pub fn stack_effect(&self) -> Option<(usize, usize)> {
+        let effect = match *self {
+            Operations::Push(_)
+            | Operations::Env(_)
+            | Operations::Read(_)
+            | Operations::Scan(_)
+            | Operations::Depth(_)
+            | Operations::Unstash(_) => (0, 1),
+            Operations::Pop(_) | Operations::Load(_) => (1, 1),
+            Operations::Swap(steps) => (steps + 1, steps + 1),
+            Operations::Heart(count) => (count + 2, 1),
+            Operations::Dup(count) => (count, count * 2),
+            Operations::Hug(_)
+            | Operations::Skip(_)
+            | Operations::Stash(_)
+            | Operations::Write(_)
+            | Operations::Print(_) => (1, 0),
+            Operations::Accept(_)
+            | Operations::Call(_)
+            | Operations::Return(_)
+            | Operations::Peek(_)
+            | Operations::Break(_)
+            | Operations::Nop(_) => (0, 0),
+            Operations::Assert(_) | Operations::Store(_) => (2, 0),
+            Operations::Rotate(count) | Operations::Reverse(count) => (count, count),
+            Operations::Clear(0) => return None,
+            Operations::Clear(count) => (count, 0),
+            Operations::Math(intrinsic, count) => (intrinsic.entry().arity + count, 1),
+            Operations::Spawn(_) | Operations::Join(_) => return None,
+        };
+        Some(effect)
+    }
+
+    /// the argument `N` of this operation
+    pub fn argument(&self) -> i64 {
+        match *self {
+            Operations::Push(n) | Operations::Pop(n) => n,
+            Operations::Swap(n)
+            | Operations::Heart(n)
+            | Operations::Dup(n)
+            | Operations::Hug(n)
+            | Operations::Env(n)
+            | Operations::Read(n)
+            | Operations::Write(n)
+            | Operations::Print(n)
+            | Operations::Scan(n)
+            | Operations::Accept(n)
+            | Operations::Spawn(n)
+            | Operations::Join(n)
+            | Operations::Assert(n)
+            | Operations::Rotate(n)
+            | Operations::Reverse(n)
+            | Operations::Clear(n)
+            | Operations::Depth(n)
+            | Operations::Skip(n)
+            | Operations::Call(n)
+            | Operations::Return(n)
+            | Operations::Stash(n)
+            | Operations::Unstash(n)
+            | Operations::Store(n)
+            | Operations::Load(n)
+            | Operations::Peek(n)
+            | Operations::Break(n)
+            | Operations::Nop(n)
+            | Operations::Math(_, n) => n as i64,
+        }
+    }
+
+    /// whether only the [`Interpreter`](interpreter::Interpreter) can execute the operation,
+    /// because it needs more of the program than the unstack of the task
+    pub fn needs_interpreter(&self) -> bool {
+        matches!(
+            self,
+            Operations::Spawn(_)
+                | Operations::Join(_)
+                | Operations::Call(_)
+                | Operations::Return(_)
+                | Operations::Stash(_)
+                | Operations::Unstash(_)
+                | Operations::Store(_)
+                | Operations::Load(_)
+        )
+    }
+}
+
+/// writes the operation as 🥺 source with a numeric argument, e.g. `🫂3`
+impl Display for Operations {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.emoji(), self.argument())
+    }
+}
+
+/// the instruction a 🫂N at `instruction_pointer` continues at when it jumps
+///
+/// `None` if the jump would go before the start of the program
+pub fn hug_target(instruction_pointer: usize, n: usize) -> Option<usize> {
+    (instruction_pointer + 2).checked_sub(n)
+}
+
+/// the instruction a 🦘N at `instruction_pointer` continues at when it skips
+///
+/// past the end of the program if it skips over the last instruction
+pub fn skip_target(instruction_pointer: usize, n: usize) -> usize {
+    instruction_pointer.saturating_add(n).saturating_add(1)
+}
+
+pub mod unstack {
+    use alloc::format;
+    use alloc::string::ToString;
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use core::fmt::{Debug, Formatter};
+    use core::iter;
+
+    use crate::cell::Cell;
+
+    #[derive(Clone, Copy)]
+    struct UnstackNode<C> {
+        /// the index of the node pushed before this one
+        prev: Option<usize>,
+        value: C,
+    }
+
+    /// the number of values an unstack holds without allocating
+    pub const INLINE_VALUES: usize = 8;
+
+    /// a persistent linked list, its nodes are shared by every snapshot taken of it
+    #[derive(Clone)]
+    struct Shared<C>(Option<Arc<SharedNode<C>>>);
+
+    impl<C> Default for Shared<C> {
+        fn default() -> Self {
+            Shared(None)
+        }
+    }
+
+    struct SharedNode<C> {
+        prev: Shared<C>,
+        value: C,
+    }
+
+    impl<C> Drop for Shared<C> {
+        fn drop(&mut self) {
+            // node by node, dropping a long list recursively would overflow the stack
+            let mut next = self.0.take();
+            while let Some(node) = next {
+                next = match Arc::try_unwrap(node) {
+                    Ok(mut node) => node.prev.0.take(),
+                    Err(_) => None,
+                };
+            }
+        }
+    }
+
+    /// how the values of an unstack are stored
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum Representation {
+        /// in an arena owned by the unstack, the fastest to push to and pop from
+        #[default]
+        Arena,
+        /// in a persistent list shared with its snapshots, so taking one is O(1)
+        Persistent,
+    }
+
+    /**
+     * # Unstack
+     *
+     * it's like a stack but you push to the bottom
+     *
+     * this is a linked list implementation of an Unstack. its nodes live in an arena owned by
+     * the unstack, nodes that are popped are reused by later pushes, so pushing and popping only
+     * allocate when the unstack grows past the most values it ever held. the first
+     * [`INLINE_VALUES`] nodes are stored inside the unstack itself, so shallow unstacks never
+     * allocate at all
+     *
+     * a [`Representation::Persistent`] unstack keeps its values in a list shared with its
+     * snapshots instead, for debuggers that keep many of them around
+     *
+     * ## Usage
+     *
+     * ```rust
+     * use bottom::unstack::Unstack;
+     *
+     * let mut unstack = Unstack::new();
+     *
+     * unstack.push(10);
+     * unstack.push(42);
+     *
+     * assert_eq!(unstack.pop(), 42);
+     * assert_eq!(unstack.pop(), 10);
+     * assert!(unstack.is_empty());
+     * ```
+     *
+     * the values are [`i64`] unless the unstack holds another [`Cell`]
+     */
+    pub struct Unstack<C = i64> {
+        /// every node, the first ones `inline` and the rest in `arena`. the ones not in use are
+        /// linked from `free`
+        inline: [UnstackNode<C>; INLINE_VALUES],
+        arena: Vec<UnstackNode<C>>,
+        /// the number of nodes ever used
+        allocated: usize,
+        free: Option<usize>,
+        bottom: Option<usize>,
+        /// the values instead of the arena if the unstack is [`Representation::Persistent`]
+        shared: Option<Shared<C>>,
+        size: usize,
+    }
+
+    impl<C: Cell> Debug for Unstack<C> {
+        fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+            if self.size == 0 {
+                write!(f, "[]")
+            } else {
+                let tmp = self.values();
+                let mut builder = "[ ".to_string();
+                for (i, j) in tmp.iter().rev().enumerate() {
+                    builder += &*format!("{}", j);
+                    if i != tmp.len() - 1 {
+                        builder += ", "
+                    }
+                }
+                write!(f, "{} ] ", builder)
+            }
+        }
+    }
+
+    impl<C: Cell> Default for Unstack<C> {
+        fn default() -> Self {
+            Unstack::new()
+        }
+    }
+
+    impl<C: Cell> Clone for Unstack<C> {
+        fn clone(&self) -> Self {
+            self.snapshot()
+        }
+    }
+
+    /// unstacks are equal if they hold the same values, however they are stored
+    impl<C: Cell> PartialEq for Unstack<C> {
+        fn eq(&self, other: &Self) -> bool {
+            self.size == other.size && self.iter().eq(other.iter())
+        }
+    }
+
+    impl<C: Cell> Eq for Unstack<C> {}
+
+    /// an unstack with `values` pushed in order, the last one ends up at the bottom
+    impl<C: Cell> From<Vec<C>> for Unstack<C> {
+        fn from(values: Vec<C>) -> Self {
+            let mut unstack = Unstack::with_capacity(values.len());
+            unstack.extend(values);
+            unstack
+        }
+    }
+
+    /// an unstack with the values pushed in order, the last one ends up at the bottom
+    impl<C: Cell> FromIterator<C> for Unstack<C> {
+        fn from_iter<I: IntoIterator<Item = C>>(values: I) -> Self {
+            let mut unstack = Unstack::new();
+            unstack.extend(values);
+            unstack
+        }
+    }
+
+    /// push the values in order
+    impl<C: Cell> Extend<C> for Unstack<C> {
+        fn extend<I: IntoIterator<Item = C>>(&mut self, values: I) {
+            for value in values {
+                self.push(value);
+            }
+        }
+    }
+
+    /// pops the values one by one, from the bottom to the top
+    pub struct IntoIter<C = i64>(Unstack<C>);
+
+    impl<C: Cell> Iterator for IntoIter<C> {
+        type Item = C;
+
+        fn next(&mut self) -> Option<C> {
+            (!self.0.is_empty()).then(|| self.0.pop())
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (self.0.len(), Some(self.0.len()))
+        }
+    }
+
+    impl<C: Cell> ExactSizeIterator for IntoIter<C> {}
+
+    /// the values popped by [`Unstack::drain`]
+    pub struct Drain<'a, C: Cell = i64> {
+        unstack: &'a mut Unstack<C>,
+        remaining: usize,
+    }
+
+    impl<C: Cell> Iterator for Drain<'_, C> {
+        type Item = C;
+
+        fn next(&mut self) -> Option<C> {
+            self.remaining = self.remaining.checked_sub(1)?;
+            Some(self.unstack.pop())
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (self.remaining, Some(self.remaining))
+        }
+    }
+
+    impl<C: Cell> ExactSizeIterator for Drain<'_, C> {}
+
+    impl<C: Cell> Drop for Drain<'_, C> {
+        fn drop(&mut self) {
+            self.for_each(drop);
+        }
+    }
+
+    impl<C: Cell> IntoIterator for Unstack<C> {
+        type Item = C;
+        type IntoIter = IntoIter<C>;
+
+        fn into_iter(self) -> IntoIter<C> {
+            IntoIter(self)
+        }
+    }
+
+    /// the values in the order they were pushed, the reverse of [`Unstack::values`]
+    impl<C: Cell> From<Unstack<C>> for Vec<C> {
+        fn from(unstack: Unstack<C>) -> Self {
+            let mut values = unstack.values();
+            values.reverse();
+            values
+        }
+    }
+
+    impl<C: Cell> Unstack<C> {
+        /// create an empty Unstack
+        pub fn new() -> Self {
+            Unstack::with_capacity(0)
+        }
+
+        /// create an empty Unstack with room for `capacity` values before it allocates
+        pub fn with_capacity(capacity: usize) -> Self {
+            Unstack {
+                inline: [UnstackNode {
+                    prev: None,
+                    value: C::ZERO,
+                }; INLINE_VALUES],
+                arena: Vec::with_capacity(capacity.saturating_sub(INLINE_VALUES)),
+                allocated: 0,
+                free: None,
+                bottom: None,
+                shared: None,
+                size: 0,
+            }
+        }
+
+        /// create an empty [`Representation::Persistent`] Unstack
+        pub fn persistent() -> Self {
+            Unstack {
+                shared: Some(Shared::default()),
+                ..Unstack::new()
+            }
+        }
+
+        pub fn representation(&self) -> Representation {
+            match self.shared {
+                Some(_) => Representation::Persistent,
+                None => Representation::Arena,
+            }
+        }
+
+        /// store the values as `representation` from now on
+        pub fn set_representation(&mut self, representation: Representation) {
+            if self.representation() == representation {
+                return;
+            }
+            let values = self.values();
+            *self = match representation {
+                Representation::Arena => Unstack::with_capacity(values.len()),
+                Representation::Persistent => Unstack::persistent(),
+            };
+            for value in values.into_iter().rev() {
+                self.push(value);
+            }
+        }
+
+        /// an unstack with the same values, sharing them in O(1) if this one is
+        /// [`Representation::Persistent`] and copying them otherwise
+        pub fn snapshot(&self) -> Unstack<C> {
+            match &self.shared {
+                Some(shared) => Unstack {
+                    shared: Some(shared.clone()),
+                    size: self.size,
+                    ..Unstack::new()
+                },
+                None => {
+                    let mut copy = Unstack::with_capacity(self.size);
+                    for value in self.values().into_iter().rev() {
+                        copy.push(value);
+                    }
+                    copy
+                }
+            }
+        }
+
+        /// the number of values the arena holds room for without allocating, 0 for a
+        /// [`Representation::Persistent`] unstack that has none
+        pub fn capacity(&self) -> usize {
+            match self.shared {
+                Some(_) => 0,
+                None => INLINE_VALUES + self.arena.capacity(),
+            }
+        }
+
+        fn node(&self, index: usize) -> &UnstackNode<C> {
+            match index.checked_sub(INLINE_VALUES) {
+                Some(index) => &self.arena[index],
+                None => &self.inline[index],
+            }
+        }
+
+        fn node_mut(&mut self, index: usize) -> &mut UnstackNode<C> {
+            match index.checked_sub(INLINE_VALUES) {
+                Some(index) => &mut self.arena[index],
+                None => &mut self.inline[index],
+            }
+        }
+
+        /// the node at the bottom of the unstack
+        fn bottom_node(&self) -> Option<&UnstackNode<C>> {
+            self.bottom.map(|index| self.node(index))
+        }
+
+        /// push new value to the bottom of the unstack
+        pub fn push(&mut self, value: C) {
+            self.size += 1;
+            if let Some(shared) = &mut self.shared {
+                let prev = Shared(shared.0.take());
+                shared.0 = Some(Arc::new(SharedNode { prev, value }));
+                return;
+            }
+            let node = UnstackNode {
+                prev: self.bottom,
+                value,
+            };
+            let index = match self.free {
+                Some(index) => {
+                    self.free = self.node(index).prev;
+                    *self.node_mut(index) = node;
+                    index
+                }
+                None if self.allocated < INLINE_VALUES => {
+                    self.inline[self.allocated] = node;
+                    self.allocated += 1;
+                    self.allocated - 1
+                }
+                None => {
+                    self.arena.push(node);
+                    self.allocated += 1;
+                    self.allocated - 1
+                }
+            };
+            self.bottom = Some(index);
+        }
+
+        /// pop a value off the bottom of the unstack and return it
+        pub fn pop(&mut self) -> C {
+            if let Some(shared) = &mut self.shared {
+                let Some(node) = shared.0.take() else {
+                    panic!("out of bounds");
+                };
+                let (prev, value) = match Arc::try_unwrap(node) {
+                    Ok(SharedNode { prev, value }) => (prev, value),
+                    Err(node) => (node.prev.clone(), node.value),
+                };
+                *shared = prev;
+                self.size -= 1;
+                return value;
+            }
+            let Some(index) = self.bottom else {
+                panic!("out of bounds");
+            };
+            let node = *self.node(index);
+            self.bottom = node.prev;
+            self.node_mut(index).prev = self.free;
+            self.free = Some(index);
+            self.size -= 1;
+            node.value
+        }
+
+        /// pop the `count` values at the bottom of the unstack, bottom first
+        ///
+        /// the values are removed even if the iterator is dropped before reaching them
+        pub fn drain(&mut self, count: usize) -> Drain<'_, C> {
+            if count > self.size {
+                panic!("out of bounds");
+            }
+            Drain {
+                unstack: self,
+                remaining: count,
+            }
+        }
+
+        /// pop values off the bottom until only `len` are left, nothing happens if there are
+        /// not more than that
+        pub fn truncate(&mut self, len: usize) {
+            while self.size > len {
+                self.pop();
+            }
+        }
+
+        /// swaps the bottom of the unstack with the value `steps` above it, 0 swaps it with
+        /// itself
+        pub fn swap_first(&mut self, steps: usize) {
+            self.swap(0, steps);
+        }
+
+        /// swaps the values `i` and `j` above the bottom of the unstack
+        ///
+        /// the values are swapped in place, in O(max(i, j)) since unstacks do not support indexing
+        pub fn swap(&mut self, i: usize, j: usize) {
+            if i.max(j) >= self.size {
+                panic!("out of bounds");
+            }
+            if self.shared.is_some() {
+                return self.rebuild_bottom(i.max(j) + 1, |values| values.swap(i, j));
+            }
+            let (first, second) = (self.index_at(i), self.index_at(j));
+            let value = self.node(first).value;
+            self.node_mut(first).value = self.node(second).value;
+            self.node_mut(second).value = value;
+        }
+
+        /// moves the value `count - 1` above the bottom of the unstack to the bottom, the ones
+        /// below it move up by one
+        pub fn rotate_bottom(&mut self, count: usize) {
+            if count > self.size {
+                panic!("out of bounds");
+            }
+            if count < 2 {
+                return;
+            }
+            if self.shared.is_some() {
+                return self.rebuild_bottom(count, |values| values.rotate_right(1));
+            }
+            let mut carried = self.node(self.index_at(count - 1)).value;
+            let mut index = self.bottom;
+            for _ in 0..count {
+                let node = self.node_mut(index.unwrap());
+                carried = core::mem::replace(&mut node.value, carried);
+                index = node.prev;
+            }
+        }
+
+        /// the arena index of the node `depth` above the bottom
+        fn index_at(&self, depth: usize) -> usize {
+            let mut index = self.bottom.unwrap();
+            for _ in 0..depth {
+                index = self.node(index).prev.unwrap();
+            }
+            index
+        }
+
+        /// let `change` rearrange the `count` values at the bottom of a persistent unstack, bottom
+        /// first. shared nodes cannot change, so they are replaced
+        fn rebuild_bottom(&mut self, count: usize, change: impl FnOnce(&mut [C])) {
+            let mut values: Vec<C> = self.drain(count).collect();
+            change(&mut values);
+            self.extend(values.into_iter().rev());
+        }
+
+        /// the values from the bottom to the top
+        fn iter(&self) -> impl Iterator<Item = C> + '_ {
+            let mut node = self.bottom_node();
+            let mut shared = self.shared.as_ref().and_then(|shared| shared.0.as_deref());
+            iter::from_fn(move || {
+                if let Some(current) = shared {
+                    shared = current.prev.0.as_deref();
+                    return Some(current.value);
+                }
+                let current = node?;
+                node = current.prev.map(|prev| self.node(prev));
+                Some(current.value)
+            })
+        }
+
+        /// the values from the bottom to the top of the unstack
+        pub fn values(&self) -> Vec<C> {
+            self.iter().collect()
+        }
+
+        /// the `count` values at the bottom of the unstack, bottom first
+        pub fn bottom(&self, count: usize) -> Vec<C> {
+            self.iter().take(count).collect()
+        }
+
+        /// returns the size of the unstack
+        pub fn len(&self) -> usize {
+            self.size
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.size == 0
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::unstack::{Representation, Unstack, INLINE_VALUES};
+
+        #[test]
+        fn test_push() {
+            let mut unstack: Unstack = Unstack::new();
+            assert_eq!(unstack.size, 0);
+            assert!(unstack.is_empty());
+            assert!(unstack.bottom.is_none());
+
+            let to_test = [420, 69, 42069, -1, -1948];
+            for (size, i) in to_test.iter().enumerate() {
+                unstack.push(*i);
+                assert!(unstack.bottom.is_some());
+                assert_eq!(unstack.bottom_node().unwrap().value, *i);
+                assert_eq!(unstack.len(), size + 1);
+            }
+
+            let prev = unstack.bottom_node().unwrap().prev.unwrap();
+            assert_eq!(unstack.node(prev).value, -1);
+        }
+
+        #[test]
+        fn test_pop() {
+            let mut unstack = Unstack::new();
+            assert!(unstack.is_empty());
+            assert!(unstack.bottom.is_none());
+
+            unstack.push(1);
+            unstack.push(2);
+            unstack.push(3);
+            unstack.push(4);
+
+            unstack.pop();
+            assert!(unstack.bottom.is_some());
+            assert_eq!(unstack.bottom_node().unwrap().value, 3);
+            unstack.pop();
+            assert!(unstack.bottom.is_some());
+            assert_eq!(unstack.bottom_node().unwrap().value, 2);
+
+            assert!(!unstack.is_empty());
+        }
+
+        #[test]
+        fn test_arena() {
+            let mut unstack = Unstack::with_capacity(4);
+            assert_eq!(unstack.capacity(), INLINE_VALUES);
+            for round in 0..100 {
+                unstack.push(round);
+                unstack.push(round + 1);
+                assert_eq!(unstack.pop(), round + 1);
+            }
+            // popped nodes are reused, so the arena only grew with the unstack
+            assert_eq!(unstack.len(), 100);
+            assert_eq!(unstack.allocated, 101);
+            assert_eq!(unstack.arena.len(), 101 - INLINE_VALUES);
+            assert_eq!(unstack.values()[..3], [99, 98, 97]);
+        }
+
+        #[test]
+        fn test_inline() {
+            let mut unstack = Unstack::new();
+            for value in 0..INLINE_VALUES as i64 {
+                unstack.push(value);
+            }
+            unstack.swap_first(3);
+            assert_eq!(unstack.arena.capacity(), 0);
+            unstack.push(-1);
+            assert_eq!(unstack.arena.len(), 1);
+            assert_eq!(unstack.pop(), -1);
+            assert_eq!(unstack.values(), [4, 6, 5, 7, 3, 2, 1, 0]);
+        }
+
+        #[test]
+        fn test_conversions() {
+            let mut unstack = Unstack::from(vec![1, 2, 3]);
+            assert_eq!(unstack.values(), [3, 2, 1]);
+            assert_eq!(Vec::from(unstack.clone()), [1, 2, 3]);
+            assert_eq!(unstack, unstack.clone());
+            assert_eq!(Unstack::<i64>::default(), Unstack::from(vec![]));
+
+            let mut persistent = unstack.clone();
+            persistent.set_representation(Representation::Persistent);
+            assert_eq!(persistent, unstack);
+            unstack.pop();
+            assert_ne!(persistent, unstack);
+        }
+
+        #[test]
+        fn test_iterators() {
+            let mut unstack: Unstack = (1..=3).collect();
+            assert_eq!(unstack, Unstack::from(vec![1, 2, 3]));
+            unstack.extend([4, 5]);
+            let drained = unstack.into_iter();
+            assert_eq!(drained.len(), 5);
+            assert_eq!(
+                drained.map(|value| value * 10).collect::<Vec<_>>(),
+                [50, 40, 30, 20, 10]
+            );
+        }
+
+        #[test]
+        fn test_swap_first() {
+            for representation in [Representation::Arena, Representation::Persistent] {
+                let mut unstack = Unstack::from(vec![1, 2, 3, 4]);
+                unstack.set_representation(representation);
+                unstack.swap_first(0);
+                assert_eq!(unstack.values(), [4, 3, 2, 1]);
+                unstack.swap_first(1);
+                assert_eq!(unstack.values(), [3, 4, 2, 1]);
+                unstack.swap_first(3);
+                assert_eq!(unstack.values(), [1, 4, 2, 3]);
+                assert_eq!(unstack.len(), 4);
+            }
+        }
+
+        #[test]
+        fn test_swap_rotate() {
+            for representation in [Representation::Arena, Representation::Persistent] {
+                let mut unstack = Unstack::from(vec![1, 2, 3, 4, 5]);
+                unstack.set_representation(representation);
+                unstack.swap(1, 3);
+                assert_eq!(unstack.values(), [5, 2, 3, 4, 1]);
+                unstack.rotate_bottom(3);
+                assert_eq!(unstack.values(), [3, 5, 2, 4, 1]);
+                unstack.rotate_bottom(1);
+                unstack.rotate_bottom(5);
+                assert_eq!(unstack.values(), [1, 3, 5, 2, 4]);
+            }
+        }
+
+        #[test]
+        #[should_panic(expected = "out of bounds")]
+        fn test_swap_first_too_far() {
+            // there is no value 4 above the bottom
+            Unstack::from(vec![1, 2, 3, 4]).swap_first(4);
+        }
+
+        #[test]
+        fn test_drain() {
+            let mut unstack = Unstack::from(vec![1, 2, 3, 4, 5]);
+            assert_eq!(unstack.drain(2).collect::<Vec<_>>(), [5, 4]);
+            assert_eq!(unstack.values(), [3, 2, 1]);
+            // not reading the values still removes them
+            assert_eq!(unstack.drain(1).len(), 1);
+            assert_eq!(unstack.values(), [2, 1]);
+
+            unstack.truncate(5);
+            assert_eq!(unstack.len(), 2);
+            unstack.truncate(1);
+            assert_eq!(unstack.values(), [1]);
+        }
+
+        #[test]
+        fn test_persistent() {
+            let mut unstack = Unstack::persistent();
+            unstack.push(1);
+            unstack.push(2);
+            let snapshot = unstack.snapshot();
+            assert_eq!(unstack.pop(), 2);
+            unstack.push(3);
+            unstack.swap_first(1);
+            assert_eq!(unstack.values(), [1, 3]);
+            assert_eq!(snapshot.values(), [2, 1]);
+            assert_eq!(snapshot.bottom(1), [2]);
+            assert_eq!(snapshot.len(), 2);
+
+            unstack.set_representation(Representation::Arena);
+            assert_eq!(unstack.representation(), Representation::Arena);
+            assert_eq!(unstack.values(), [1, 3]);
+
+            assert_eq!(snapshot, Unstack::from(vec![1, 2]));
+            assert_ne!(snapshot, unstack);
+
+            // dropped node by node
+            let mut deep = Unstack::persistent();
+            for value in 0..200_000 {
+                deep.push(value);
+            }
+            drop(deep);
+        }
+    }
+}
+
+/// name of an operation and how to build it from its argument
+pub type OperationEntry = (&'static str, fn(i64) -> Operations);
+
+/// emoji of the standard operations
+pub const OPERATIONS: &[OperationEntry] = &[
+    ("🥺", Operations::Push),
+    ("💖", Operations::Pop),
+    ("👉👈", |n| Operations::Swap(n as usize)),
+    ("💓", |n| Operations::Heart(n as usize)),
+    ("✨", |n| Operations::Dup(n as usize)),
+    ("🫂", |n| Operations::Hug(n as usize)),
+];
+
+/// emoji of the operations only recognized with `--extensions`
+pub const EXTENSIONS: &[OperationEntry] = &[
+    ("🏠", |n| Operations::Env(n as usize)),
+    ("📖", |n| Operations::Read(n as usize)),
+    ("📝", |n| Operations::Write(n as usize)),
+    ("🔢", |n| Operations::Print(n as usize)),
+    ("🧮", |n| Operations::Scan(n as usize)),
+    ("📞", |n| Operations::Accept(n as usize)),
+    ("🧵", |n| Operations::Spawn(n as usize)),
+    ("🪢", |n| Operations::Join(n as usize)),
+    ("✅", |n| Operations::Assert(n as usize)),
+    ("🔄", |n| Operations::Rotate(n as usize)),
+    ("🪞", |n| Operations::Reverse(n as usize)),
+    ("🧹", |n| Operations::Clear(n as usize)),
+    ("📚", |n| Operations::Depth(n as usize)),
+    ("🦘", |n| Operations::Skip(n as usize)),
+    ("🛫", |n| Operations::Call(n as usize)),
+    ("🛬", |n| Operations::Return(n as usize)),
+    ("📥", |n| Operations::Stash(n as usize)),
+    ("📤", |n| Operations::Unstash(n as usize)),
+    ("💾", |n| Operations::Store(n as usize)),
+    ("💿", |n| Operations::Load(n as usize)),
+    ("👀", |n| Operations::Peek(n as usize)),
+    ("🛑", |n| Operations::Break(n as usize)),
+    ("💤", |n| Operations::Nop(n as usize)),
+];
+
+/// build the operation for `op` from its argument
+///
+/// the argument is either a number or a word, in which case its length in characters is used
+fn make_operation(table: &[OperationEntry], op: &str, word: &str) -> Operations {
+    let value: i64 = if let Ok(num) = word.parse() {
+        num
+    } else {
+        word.chars().count() as i64
+    };
+    let (_, make) = table
+        .iter()
+        .find(|(emoji, _)| *emoji == op)
+        .expect("operation taken from table");
+    make(value)
+}
+
+/// `source` without a leading `#!` line, which makes 🥺 files executable on Unix
+fn strip_shebang(source: &str) -> &str {
+    if source.starts_with("#!") {
+        source.find('\n').map_or("", |end| &source[end..])
+    } else {
+        source
+    }
+}
+
+/// tokenize a 🥺 program, ignoring a leading `#!` line and its [`pragma`]
+pub fn parse(source: &str, extensions: bool) -> Vec<Operations> {
+    parse_spanned(source, extensions)
+        .into_iter()
+        .map(|(operation, _)| operation)
+        .collect()
+}
+
+/// tokenize a 🥺 program like [`parse`], keeping the byte range in `source` each operation and
+/// its argument were read from
+pub fn parse_spanned(source: &str, extensions: bool) -> Vec<(Operations, Range<usize>)> {
+    parse_lenient(source, extensions).0
+}
+
+/// tokenize a 🥺 program like [`parse_spanned`], but fail at the first construct the lenient
+/// parser would skip over
+pub fn parse_strict(
+    source: &str,
+    extensions: bool,
+) -> Result<Vec<(Operations, Range<usize>)>, ParseError> {
+    let (tokens, skipped) = parse_lenient(source, extensions);
+    match skipped.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(tokens),
+    }
+}
+
+/// tokenize a 🥺 program without extensions like [`parse_strict`], also rejecting arguments
+/// whose meaning other implementations might disagree on
+pub fn parse_spec(source: &str) -> Result<Vec<(Operations, Range<usize>)>, ParseError> {
+    let tokens = parse_strict(source, false)?;
+    for (operation, span) in &tokens {
+        let word = &source[span.clone()][operation.emoji().len()..];
+        if word.contains(|ch: char| ch.is_ascii_digit()) && word.parse::<i64>().is_err() {
+            return Err(ParseError {
+                malformed: Malformed::MixedArgument,
+                span: span.clone(),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// what the lenient parser skips over without an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformed {
+    /// digits that do not follow an operation
+    StrayDigits,
+    /// part of an emoji that is not an operation on its own, like a lone 👉
+    IncompleteOperation,
+    /// an operation at the very end of the program, without anything after it
+    MissingArgument,
+    /// an argument with digits that is not a number, like the `1🥺2` of `🥺1🥺2`, which is taken
+    /// as its length. only [`parse_spec`] rejects it
+    MixedArgument,
+}
+
+/// a malformed construct and where it is in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub malformed: Malformed,
+    /// the byte range of the construct in the source
+    pub span: Range<usize>,
+}
+
+impl Display for Malformed {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Malformed::StrayDigits => "digits without an operation before them",
+            Malformed::IncompleteOperation => "not an operation",
+            Malformed::MissingArgument => "the operation at the end has no argument",
+            Malformed::MixedArgument => "the argument mixes digits with other characters",
+        })
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at bytes {}..{}",
+            self.malformed, self.span.start, self.span.end
+        )
+    }
+}
+
+/// tokenize a 🥺 program like [`parse_spanned`], also returning everything that was skipped over
+pub fn parse_lenient(
+    source: &str,
+    extensions: bool,
+) -> (Vec<(Operations, Range<usize>)>, Vec<ParseError>) {
+    let stripped = match pragma::span(source) {
+        Some(span) => &source[span.end..],
+        None => strip_shebang(source),
+    };
+    let offset = source.len() - stripped.len();
+    let table: Vec<_> = if extensions {
+        let standard = OPERATIONS.iter().chain(EXTENSIONS).copied();
+        standard.chain(ext::math::operations()).collect()
+    } else {
+        OPERATIONS.to_vec()
+    };
+    let mut tokens = vec![];
+    let mut skipped = vec![];
+    // a word that never became an operation
+    let skip = |word: &str, span: Range<usize>| {
+        let malformed = match word.starts_with(|ch: char| ch.is_ascii_digit()) {
+            true => Malformed::StrayDigits,
+            false => Malformed::IncompleteOperation,
+        };
+        ParseError { malformed, span }
+    };
+
+    let mut word: String = "".to_string();
+    let mut operation: Option<String> = None;
+    // where the word the operation was recognized in started
+    let mut start = 0;
+    for (index, ch) in stripped.char_indices() {
+        if !(ch.is_ascii_digit() || table.iter().any(|(emoji, _)| emoji.contains(ch))) {
+            let span = offset + start..offset + index;
+            match &operation {
+                Some(op) => tokens.push((make_operation(&table, op, &word), span)),
+                None if !word.is_empty() => skipped.push(skip(&word, span)),
+                None => {}
+            }
+            word = "".to_string();
+            operation = None;
+            continue;
+        }
+        if operation.is_none() && word.is_empty() {
+            start = index;
+        }
+        word += ch.to_string().as_ref();
+
+        if operation.is_none() && table.iter().any(|(emoji, _)| *emoji == word) {
+            operation = Some(word);
+            word = "".to_string();
+        }
+    }
+    let span = offset + start..source.len();
+    match &operation {
+        Some(op) if !word.is_empty() => tokens.push((make_operation(&table, op, &word), span)),
+        Some(_) => skipped.push(ParseError {
+            malformed: Malformed::MissingArgument,
+            span,
+        }),
+        None if !word.is_empty() => skipped.push(skip(&word, span)),
+        None => {}
+    }
+    (tokens, skipped)
+}
+
+/// restrictions on how long a program may run
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// wall-clock time after which interpretation is aborted
+    pub timeout: Option<Duration>,
+    /// number of instructions executed between two checks of the timeout
+    pub check_interval: usize,
+    /// number of values all unstacks together may hold before interpretation is aborted
+    pub max_values: Option<usize>,
+    /// number of memory cells 💾 and 💿 may use, their addresses go from 0 up to it
+    pub max_memory: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            timeout: None,
+            check_interval: 1024,
+            max_values: None,
+            max_memory: None,
+        }
+    }
+}
+
+/// what 💖0 does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DivisionByZero {
+    /// stop with [`RuntimeError::DivisionByZero`]
+    #[default]
+    Error,
+    /// push 0 as the quotient
+    Zero,
+    /// leave the unstack as it is, as if the 💖 was not there
+    Skip,
+}
+
+/// what arithmetic does when its result does not fit in a value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// wrap around, like two's complement hardware does
+    #[default]
+    Wrap,
+    /// stop with [`RuntimeError::Overflow`], naming the operands
+    Checked,
+}
+
+/// choices about what operations do where implementations of 🥺 disagree, the default follows
+/// this crate as it always behaved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Semantics {
+    pub division_by_zero: DivisionByZero,
+    /// whether 💖 rounds toward negative infinity, the floor division of the spec, instead of
+    /// toward zero like Rust's `/`. they differ when exactly one of the operands is negative
+    pub floor_division: bool,
+    /// whether a 🫂 that jumps before the start of the program or jumps forward, as 🫂0 does,
+    /// fails with [`RuntimeError::JumpOutOfRange`]
+    pub strict_jumps: bool,
+    /// what 💓, 💖 and the math intrinsics do when they overflow, and 🥺 with an argument that
+    /// does not fit in a [`Cell`]
+    pub overflow: Overflow,
+}
+
+impl Semantics {
+    /// the semantics of the reference on esolangs.org, leaving nothing to chance: 💖 is floor
+    /// division, and dividing by 0 or jumping outside of the program is an error
+    pub fn spec() -> Self {
+        Semantics {
+            division_by_zero: DivisionByZero::Error,
+            floor_division: true,
+            strict_jumps: true,
+            overflow: Overflow::Wrap,
+        }
+    }
+
+    /// the quotient 💖 pushes for `value` and a `divisor` that is not 0, the one overflowing
+    /// quotient `MIN / -1` wraps around to `MIN`
+    pub fn quotient<C: Cell>(&self, value: C, divisor: C) -> C {
+        let quotient = value.wrapping_div(divisor);
+        let inexact = value.wrapping_rem(divisor) != C::ZERO;
+        if self.floor_division && inexact && (value < C::ZERO) != (divisor < C::ZERO) {
+            quotient - C::ONE
+        } else {
+            quotient
+        }
+    }
+}
+
+/// the number of values at the bottom of the unstack a [`Fault`] keeps
+pub const FAULT_VALUES: usize = 8;
+
+/// where and in what state an instruction failed, so tools do not have to parse messages
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    pub operation: Operations,
+    pub instruction_pointer: usize,
+    /// the byte range of the instruction in the source, only known after
+    /// [`RuntimeError::locate`] since the interpreter never sees the source
+    pub span: Option<Range<usize>>,
+    /// at most [`FAULT_VALUES`] values at the bottom of the unstack when it failed, bottom first
+    pub bottom: Vec<i64>,
+}
+
+impl Fault {
+    pub(crate) fn new<C: Cell>(
+        operation: Operations,
+        instruction_pointer: usize,
+        unstack: &unstack::Unstack<C>,
+    ) -> Self {
+        Fault {
+            operation,
+            instruction_pointer,
+            span: None,
+            bottom: (unstack.bottom(FAULT_VALUES).into_iter())
+                .map(Cell::to_i64)
+                .collect(),
+        }
+    }
+}
+
+/// reasons for interpretation to stop before the end of the program
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    /// the program ran longer than `--timeout`
+    Timeout {
+        timeout: Duration,
+        instruction_pointer: usize,
+    },
+    /// every task is waiting with 🪢 for another task
+    Deadlock { instruction_pointer: usize },
+    /// the unstacks grew past [`Limits::max_values`]
+    OutOfMemory {
+        limit: usize,
+        instruction_pointer: usize,
+    },
+    /// a 💾 or 💿 used an address past [`Limits::max_memory`]
+    MemoryOutOfBounds {
+        address: usize,
+        limit: usize,
+        instruction_pointer: usize,
+    },
+    /// a 💖0 divided by zero, which is what it does unless [`Semantics::division_by_zero`] says
+    /// otherwise
+    DivisionByZero { instruction_pointer: usize },
+    /// a 🫂 jumped outside of the program with [`Semantics::strict_jumps`]
+    JumpOutOfRange { instruction_pointer: usize },
+    /// the two values popped by ✅ differed
+    AssertionFailed {
+        /// the argument of the ✅
+        id: usize,
+        /// the value popped first
+        left: i64,
+        /// the value popped second
+        right: i64,
+        instruction_pointer: usize,
+    },
+    /// an instruction needed more values than the unstack had
+    Underflow {
+        fault: Box<Fault>,
+        needed: usize,
+        had: usize,
+    },
+    /// an instruction could not do what it does, like a 📝 the host refused or a 🛬 without a 🛫
+    Failed { fault: Box<Fault>, message: String },
+    /// an arithmetic operation overflowed with [`Overflow::Checked`]
+    Overflow {
+        fault: Box<Fault>,
+        /// the values it computed with, the first popped first, or the argument of a 🥺
+        operands: Vec<i128>,
+    },
+}
+
+impl RuntimeError {
+    /// `operation` needed `needed` values but `unstack` had fewer
+    pub(crate) fn underflow<C: Cell>(
+        operation: Operations,
+        instruction_pointer: usize,
+        needed: usize,
+        unstack: &unstack::Unstack<C>,
+    ) -> Self {
+        RuntimeError::Underflow {
+            fault: Box::new(Fault::new(operation, instruction_pointer, unstack)),
+            needed,
+            had: unstack.len(),
+        }
+    }
+
+    /// `operation` overflowed computing with `operands`, which it did not pop yet
+    pub(crate) fn overflow<C: Cell>(
+        operation: Operations,
+        instruction_pointer: usize,
+        unstack: &unstack::Unstack<C>,
+        operands: &[C],
+    ) -> Self {
+        RuntimeError::Overflow {
+            fault: Box::new(Fault::new(operation, instruction_pointer, unstack)),
+            operands: operands.iter().map(|operand| operand.to_i128()).collect(),
+        }
+    }
+
+    /// `operation` failed for the reason in `message`
+    pub(crate) fn failed<C: Cell>(
+        operation: Operations,
+        instruction_pointer: usize,
+        unstack: &unstack::Unstack<C>,
+        message: impl ToString,
+    ) -> Self {
+        RuntimeError::Failed {
+            fault: Box::new(Fault::new(operation, instruction_pointer, unstack)),
+            message: message.to_string(),
+        }
+    }
+
+    /// whether only the failing instruction is to blame, so a program can go on by skipping it
+    /// instead of hitting a limit or a deadlock or stopping at a ✅ that aborts on purpose
+    pub fn is_recoverable(&self) -> bool {
+        !matches!(
+            self,
+            RuntimeError::Timeout { .. }
+                | RuntimeError::Deadlock { .. }
+                | RuntimeError::OutOfMemory { .. }
+                | RuntimeError::MemoryOutOfBounds { .. }
+                | RuntimeError::AssertionFailed { .. }
+        )
+    }
+
+    /// the details of the failed instruction, if the error is about one
+    pub fn fault(&self) -> Option<&Fault> {
+        match self {
+            RuntimeError::Underflow { fault, .. }
+            | RuntimeError::Failed { fault, .. }
+            | RuntimeError::Overflow { fault, .. } => Some(fault),
+            _ => None,
+        }
+    }
+
+    /// fill in the span of the failed instruction from the span of every instruction
+    pub fn locate(mut self, spans: &[Range<usize>]) -> Self {
+        if let RuntimeError::Underflow { fault, .. }
+        | RuntimeError::Failed { fault, .. }
+        | RuntimeError::Overflow { fault, .. } = &mut self
+        {
+            fault.span = spans.get(fault.instruction_pointer).cloned();
+        }
+        self
+    }
+
+    /// the instruction the main task, or the task that failed, was at
+    pub fn instruction_pointer(&self) -> usize {
+        match *self {
+            RuntimeError::Timeout {
+                instruction_pointer,
+                ..
+            }
+            | RuntimeError::Deadlock {
+                instruction_pointer,
+            }
+            | RuntimeError::OutOfMemory {
+                instruction_pointer,
+                ..
+            }
+            | RuntimeError::MemoryOutOfBounds {
+                instruction_pointer,
+                ..
+            }
+            | RuntimeError::DivisionByZero {
+                instruction_pointer,
+            }
+            | RuntimeError::JumpOutOfRange {
+                instruction_pointer,
+            }
+            | RuntimeError::AssertionFailed {
+                instruction_pointer,
+                ..
+            } => instruction_pointer,
+            RuntimeError::Underflow { ref fault, .. }
+            | RuntimeError::Failed { ref fault, .. }
+            | RuntimeError::Overflow { ref fault, .. } => fault.instruction_pointer,
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::Timeout {
+                timeout,
+                instruction_pointer,
+            } => write!(
+                f,
+                "timed out after {:?} at {}",
+                timeout, instruction_pointer
+            ),
+            RuntimeError::Deadlock {
+                instruction_pointer,
+            } => write!(
+                f,
+                "all tasks are waiting for each other, main task at {}",
+                instruction_pointer
+            ),
+            RuntimeError::OutOfMemory {
+                limit,
+                instruction_pointer,
+            } => write!(
+                f,
+                "more than {} values on the unstack at {}",
+                limit, instruction_pointer
+            ),
+            RuntimeError::MemoryOutOfBounds {
+                address,
+                limit,
+                instruction_pointer,
+            } => write!(
+                f,
+                "address {} is past the {} memory cells at {}",
+                address, limit, instruction_pointer
+            ),
+            RuntimeError::DivisionByZero {
+                instruction_pointer,
+            } => write!(f, "💖0 : division by zero at {}", instruction_pointer),
+            RuntimeError::JumpOutOfRange {
+                instruction_pointer,
+            } => write!(
+                f,
+                "🫂 : jump outside of the program at {}",
+                instruction_pointer
+            ),
+            RuntimeError::AssertionFailed {
+                id,
+                left,
+                right,
+                instruction_pointer,
+            } => write!(
+                f,
+                "✅{} : assertion failed, {} != {} at {}",
+                id, left, right, instruction_pointer
+            ),
+            RuntimeError::Underflow { fault, had: 0, .. } => write!(
+                f,
+                "{} : empty unstack at {}",
+                fault.operation.emoji(),
+                fault.instruction_pointer
+            ),
+            RuntimeError::Underflow { fault, needed, had } => write!(
+                f,
+                "{} : unstack too small (expected at least {}, had {}) at {}",
+                fault.operation.emoji(),
+                needed,
+                had,
+                fault.instruction_pointer
+            ),
+            RuntimeError::Failed { fault, message } => write!(
+                f,
+                "{} : {} at {}",
+                fault.operation.emoji(),
+                message,
+                fault.instruction_pointer
+            ),
+            RuntimeError::Overflow { fault, operands } => {
+                write!(f, "{} : overflow computing with ", fault.operation)?;
+                for (index, operand) in operands.iter().enumerate() {
+                    match index {
+                        0 => write!(f, "{}", operand)?,
+                        _ => write!(f, " and {}", operand)?,
+                    }
+                }
+                write!(f, " at {}", fault.instruction_pointer)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse, parse_lenient, parse_spanned, parse_spec, parse_strict, Malformed, Operations,
+    };
+
+    #[test]
+    fn test_shebang() {
+        let source = "#!/usr/bin/env -S bottom --timeout 5s\n🥺1 ✨1";
+        assert_eq!(
+            parse(source, false),
+            vec![Operations::Push(1), Operations::Dup(1)]
+        );
+        assert_eq!(parse("#!🥺4", false), vec![]);
+    }
+
+    #[test]
+    fn test_parse_extensions() {
+        let source = "🥺🥺🥺 🏠2 ✨1";
+        assert_eq!(
+            parse(source, false),
+            vec![Operations::Push(2), Operations::Dup(1)]
+        );
+        assert_eq!(
+            parse(source, true),
+            vec![Operations::Push(2), Operations::Env(2), Operations::Dup(1)]
+        );
+    }
+
+    #[test]
+    fn test_parse_spanned() {
+        let source = "#!bottom\n🥺12 👉👈1 # done\n🥺🥺🥺";
+        let spanned = parse_spanned(source, false);
+        let slices: Vec<_> = spanned
+            .iter()
+            .map(|(_, span)| &source[span.clone()])
+            .collect();
+        assert_eq!(slices, ["🥺12", "👉👈1", "🥺🥺🥺"]);
+        assert_eq!(spanned[1].0, Operations::Swap(1));
+    }
+
+    #[test]
+    fn test_parse_strict() {
+        let source = "12 🥺1 👉 ✨";
+        let (tokens, skipped) = parse_lenient(source, false);
+        assert_eq!(tokens, [(Operations::Push(1), 3..8)]);
+        let skipped: Vec<_> = skipped
+            .iter()
+            .map(|error| (error.malformed, &source[error.span.clone()]))
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                (Malformed::StrayDigits, "12"),
+                (Malformed::IncompleteOperation, "👉"),
+                (Malformed::MissingArgument, "✨"),
+            ]
+        );
+        assert_eq!(parse_strict(source, false).unwrap_err().span, 0..2);
+        assert_eq!(
+            parse_spec("🥺1🥺2").unwrap_err().malformed,
+            Malformed::MixedArgument
+        );
+        assert!(parse_spec("🥺12 🥺🥺🥺").is_ok());
+        // an empty word is an argument of 0 everywhere but at the end
+        assert_eq!(
+            parse_strict("✨ 🥺🥺🥺", false).map(|tokens| tokens.len()),
+            Ok(2)
+        );
+    }
+}
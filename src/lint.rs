@@ -0,0 +1,238 @@
+/*!
+ * # Lints
+ *
+ * warnings about a program that still runs but probably does not do what was meant: what the
+ * lenient parser skips over, arguments that make an operation useless or fail and constructs
+ * that are deprecated. every lint has a code like `W003` and a name like `missing-argument`
+ *
+ * warnings are printed to stderr before the program runs. `--allow CODE` silences a lint and
+ * `--deny CODE` turns it into an error that stops the program from running, `warnings` stands
+ * for all of them, so `--deny warnings --allow W005` denies everything but ✨0
+ */
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use bottom::{parse_lenient, Malformed, Operations};
+use clap::{Arg, ArgMatches};
+
+use crate::report::position;
+
+/// a kind of warning
+#[derive(Debug, PartialEq, Eq)]
+pub struct Lint {
+    pub code: &'static str,
+    pub name: &'static str,
+    /// what is wrong, shown with every warning
+    pub message: &'static str,
+}
+
+pub const STRAY_DIGITS: Lint = Lint {
+    code: "W001",
+    name: "stray-digits",
+    message: "digits without an operation before them are skipped",
+};
+pub const INCOMPLETE_OPERATION: Lint = Lint {
+    code: "W002",
+    name: "incomplete-operation",
+    message: "part of an emoji that is not an operation is skipped",
+};
+pub const MISSING_ARGUMENT: Lint = Lint {
+    code: "W003",
+    name: "missing-argument",
+    message: "the operation at the end has no argument and is skipped",
+};
+pub const DIVISION_BY_ZERO: Lint = Lint {
+    code: "W004",
+    name: "division-by-zero",
+    message: "💖0 divides by zero",
+};
+pub const USELESS_DUP: Lint = Lint {
+    code: "W005",
+    name: "useless-dup",
+    message: "✨0 duplicates nothing",
+};
+pub const FORWARD_HUG: Lint = Lint {
+    code: "W006",
+    name: "forward-hug",
+    message: "🫂0 jumps forward over the next instruction, which is deprecated, use 🦘1 on the negated condition",
+};
+
+/// every lint, by code
+pub const LINTS: &[&Lint] = &[
+    &STRAY_DIGITS,
+    &INCOMPLETE_OPERATION,
+    &MISSING_ARGUMENT,
+    &DIVISION_BY_ZERO,
+    &USELESS_DUP,
+    &FORWARD_HUG,
+];
+
+/// a lint that applies somewhere in the source
+#[derive(Debug, PartialEq, Eq)]
+pub struct Warning {
+    pub lint: &'static Lint,
+    /// the byte range in the source
+    pub span: Range<usize>,
+}
+
+/// the warnings for `source`, in the order they appear
+pub fn check(source: &str, extensions: bool) -> Vec<Warning> {
+    let (tokens, skipped) = parse_lenient(source, extensions);
+    let skipped = skipped.into_iter().map(|error| Warning {
+        lint: match error.malformed {
+            Malformed::StrayDigits => &STRAY_DIGITS,
+            Malformed::IncompleteOperation => &INCOMPLETE_OPERATION,
+            Malformed::MissingArgument => &MISSING_ARGUMENT,
+            Malformed::MixedArgument => unreachable!("only parse_spec rejects mixed arguments"),
+        },
+        span: error.span,
+    });
+    let suspicious = tokens.into_iter().filter_map(|(operation, span)| {
+        let lint = match operation {
+            Operations::Pop(0) => &DIVISION_BY_ZERO,
+            Operations::Dup(0) => &USELESS_DUP,
+            Operations::Hug(0) => &FORWARD_HUG,
+            _ => return None,
+        };
+        Some(Warning { lint, span })
+    });
+    let mut warnings: Vec<_> = skipped.chain(suspicious).collect();
+    warnings.sort_by_key(|warning| warning.span.start);
+    warnings
+}
+
+/// what happens when a lint applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// the level of every lint, warning unless told otherwise
+#[derive(Debug, Default)]
+pub struct Levels(BTreeMap<&'static str, Level>);
+
+/// the lint `name` is a code or a name of, `None` for `warnings`
+fn find(name: &str) -> Result<Option<&'static Lint>, String> {
+    if name == "warnings" {
+        return Ok(None);
+    }
+    LINTS
+        .iter()
+        .find(|lint| lint.code == name || lint.name == name)
+        .map(|lint| Some(*lint))
+        .ok_or_else(|| format!("unknown lint '{}'", name))
+}
+
+impl Levels {
+    /// set the level of the lint `name`, or of all of them for `warnings`
+    pub fn set(&mut self, name: &str, level: Level) -> Result<(), String> {
+        match find(name)? {
+            Some(lint) => {
+                self.0.insert(lint.code, level);
+            }
+            None => {
+                for lint in LINTS {
+                    self.0.insert(lint.code, level);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn level(&self, lint: &Lint) -> Level {
+        self.0.get(lint.code).copied().unwrap_or(Level::Warn)
+    }
+
+    /// the levels asked for by [`args`], later flags winning over earlier ones
+    pub fn from_args(args: &ArgMatches) -> Self {
+        let mut flags = vec![];
+        for (flag, level) in [("allow", Level::Allow), ("deny", Level::Deny)] {
+            if let (Some(names), Some(indices)) = (args.values_of(flag), args.indices_of(flag)) {
+                flags.extend(indices.zip(names).map(|(index, name)| (index, name, level)));
+            }
+        }
+        flags.sort_by_key(|(index, _, _)| *index);
+        let mut levels = Levels::default();
+        for (_, name, level) in flags {
+            levels.set(name, level).expect("validated by clap");
+        }
+        levels
+    }
+}
+
+/// the arguments read by [`Levels::from_args`]
+pub fn args() -> Vec<Arg<'static, 'static>> {
+    let lint = |name: &'static str, help| {
+        Arg::with_name(name)
+            .long(name)
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("LINT")
+            .validator(|name| find(&name).map(|_| ()))
+            .help(help)
+    };
+    vec![
+        lint(
+            "allow",
+            "do not warn about LINT, e.g. W003, missing-argument or warnings",
+        ),
+        lint(
+            "deny",
+            "do not run the program if LINT applies, e.g. W003 or warnings",
+        ),
+    ]
+}
+
+/// print the warnings for `source` that are not allowed, false if one of them is denied
+pub fn report(source: &str, warnings: &[Warning], levels: &Levels) -> bool {
+    let mut denied = false;
+    for warning in warnings {
+        let kind = match levels.level(warning.lint) {
+            Level::Allow => continue,
+            Level::Warn => "warning",
+            Level::Deny => {
+                denied = true;
+                "error"
+            }
+        };
+        let (line, column) = position(source, warning.span.start);
+        eprintln!(
+            "{}[{}]: {}:{}: {} ({})",
+            kind, warning.lint.code, line, column, warning.lint.message, warning.lint.name
+        );
+    }
+    !denied
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lint::{check, Level, Levels, DIVISION_BY_ZERO, MISSING_ARGUMENT, USELESS_DUP};
+
+    #[test]
+    fn test_check() {
+        let source = "🥺4 💖0 ✨0 ✨";
+        let warnings: Vec<_> = check(source, false)
+            .into_iter()
+            .map(|warning| (warning.lint.code, &source[warning.span]))
+            .collect();
+        assert_eq!(warnings, [("W004", "💖0"), ("W005", "✨0"), ("W003", "✨")]);
+        assert!(check("🥺4 💖2 ✨1", false).is_empty());
+    }
+
+    #[test]
+    fn test_levels() {
+        let mut levels = Levels::default();
+        assert_eq!(levels.level(&USELESS_DUP), Level::Warn);
+        levels.set("warnings", Level::Deny).unwrap();
+        levels.set("useless-dup", Level::Allow).unwrap();
+        assert_eq!(levels.level(&DIVISION_BY_ZERO), Level::Deny);
+        assert_eq!(levels.level(&USELESS_DUP), Level::Allow);
+        levels.set("W003", Level::Warn).unwrap();
+        assert_eq!(levels.level(&MISSING_ARGUMENT), Level::Warn);
+        assert!(levels.set("W999", Level::Allow).is_err());
+    }
+}
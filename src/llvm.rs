@@ -0,0 +1,623 @@
+/*!
+ * # LLVM IR
+ *
+ * `bottom compile --target llvm FILE` lowers the [bytecode](bottom::bytecode) of a program to
+ * LLVM IR: a `main` running it that prints what is left on the unstack like `bottom` does, and
+ * `--opt-level` runs LLVM's `opt` on the result. `llc` or `clang` then turns it into a native
+ * program:
+ *
+ * ```sh
+ * bottom compile --target llvm --opt-level 3 fib.🥺 -o fib.ll
+ * clang fib.ll -o fib
+ * ```
+ *
+ * the unstack is an array growing with `realloc`, values are 64 bits wide and wrap around when
+ * they overflow, and failing prints the error of the interpreter without its line and column and
+ * exits with 1. `--floor-div` and `--div-by-zero` choose what 💖 does like they do for `bottom`,
+ * the other [semantics](bottom::Semantics) cannot be chosen. the core operations, 🦘, 🧹, 📚,
+ * 📖0, 📝1, 📝2, 💤 and 🛑 are lowered, other programs do not compile
+ *
+ * `bottom build FILE -o PROGRAM` does all of that, with `llc` and `cc`
+ *
+ * the IR uses opaque pointers, which LLVM 14 only reads with `-opaque-pointers`: `opt` and `llc`
+ * get that flag from `bottom` if they are version 14, `lli` needs it passed by hand
+ */
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+use std::process::{self, Command, Stdio};
+use std::{env, fs};
+
+use bottom::bytecode::{self, Instruction};
+use bottom::{DivisionByZero, Operations, Overflow, Semantics};
+
+/// the unstack, growing it, failing and printing it at the end
+const RUNTIME: &str = r#"@stack = internal global ptr null
+@len = internal global i64 0
+@cap = internal global i64 0
+
+declare ptr @realloc(ptr, i64)
+declare i32 @printf(ptr, ...)
+declare i32 @dprintf(i32, ptr, ...)
+declare i32 @getchar()
+declare i32 @putchar(i32)
+declare i64 @write(i32, ptr, i64)
+declare void @exit(i32) noreturn
+
+define internal void @push(i64 %value) {
+entry:
+  %len = load i64, ptr @len
+  %cap = load i64, ptr @cap
+  %full = icmp eq i64 %len, %cap
+  br i1 %full, label %grow, label %store
+grow:
+  %doubled = mul i64 %cap, 2
+  %grown = add i64 %doubled, 16
+  %bytes = mul i64 %grown, 8
+  %old = load ptr, ptr @stack
+  %new = call ptr @realloc(ptr %old, i64 %bytes)
+  %failed = icmp eq ptr %new, null
+  br i1 %failed, label %oom, label %resized
+oom:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @out_of_memory)
+  call void @exit(i32 1)
+  unreachable
+resized:
+  store ptr %new, ptr @stack
+  store i64 %grown, ptr @cap
+  br label %store
+store:
+  %stack = load ptr, ptr @stack
+  %slot = getelementptr i64, ptr %stack, i64 %len
+  store i64 %value, ptr %slot
+  %next = add i64 %len, 1
+  store i64 %next, ptr @len
+  ret void
+}
+
+define internal i64 @pop() {
+entry:
+  %len = load i64, ptr @len
+  %top = sub i64 %len, 1
+  store i64 %top, ptr @len
+  %stack = load ptr, ptr @stack
+  %slot = getelementptr i64, ptr %stack, i64 %top
+  %value = load i64, ptr %slot
+  ret i64 %value
+}
+
+define internal ptr @slot(i64 %depth) {
+entry:
+  %len = load i64, ptr @len
+  %top = sub i64 %len, 1
+  %index = sub i64 %top, %depth
+  %stack = load ptr, ptr @stack
+  %slot = getelementptr i64, ptr %stack, i64 %index
+  ret ptr %slot
+}
+
+; not internal, LLVM 14 crashes promoting the pointer argument of an internal function
+define void @bottom_need(i64 %needed, ptr %emoji, i64 %ip) {
+entry:
+  %len = load i64, ptr @len
+  %short = icmp ult i64 %len, %needed
+  br i1 %short, label %fail, label %enough
+enough:
+  ret void
+fail:
+  %empty = icmp eq i64 %len, 0
+  br i1 %empty, label %nothing, label %few
+nothing:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @empty_unstack, ptr %emoji, i64 %ip)
+  call void @exit(i32 1)
+  unreachable
+few:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @small_unstack, ptr %emoji, i64 %needed, i64 %len, i64 %ip)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal void @divide_by_zero(i64 %ip) {
+entry:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @division_by_zero, i64 %ip)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal void @dup(i64 %count) {
+entry:
+  %len = load i64, ptr @len
+  %base = sub i64 %len, %count
+  br label %room
+room:
+  %pushed = phi i64 [0, %entry], [%more, %push]
+  %roomy = icmp eq i64 %pushed, %count
+  br i1 %roomy, label %copy, label %push
+push:
+  call void @push(i64 0)
+  %more = add i64 %pushed, 1
+  br label %room
+copy:
+  %stack = load ptr, ptr @stack
+  br label %next
+next:
+  %left = phi i64 [%count, %copy], [%index, %body]
+  %done = icmp eq i64 %left, 0
+  br i1 %done, label %end, label %body
+body:
+  %index = sub i64 %left, 1
+  %from = add i64 %base, %index
+  %source = getelementptr i64, ptr %stack, i64 %from
+  %value = load i64, ptr %source
+  %twice = mul i64 %index, 2
+  %to = add i64 %base, %twice
+  %first = getelementptr i64, ptr %stack, i64 %to
+  store i64 %value, ptr %first
+  %second = getelementptr i64, ptr %first, i64 1
+  store i64 %value, ptr %second
+  br label %next
+end:
+  ret void
+}
+
+define internal void @read() {
+entry:
+  %char = call i32 @getchar()
+  %value = sext i32 %char to i64
+  call void @push(i64 %value)
+  ret void
+}
+
+define internal void @write_byte(i32 %fd) {
+entry:
+  %value = call i64 @pop()
+  %byte = trunc i64 %value to i8
+  %stdout = icmp eq i32 %fd, 1
+  br i1 %stdout, label %buffered, label %direct
+buffered:
+  %char = zext i8 %byte to i32
+  call i32 @putchar(i32 %char)
+  ret void
+direct:
+  %buffer = alloca i8
+  store i8 %byte, ptr %buffer
+  call i64 @write(i32 %fd, ptr %buffer, i64 1)
+  ret void
+}
+
+define internal void @finish() {
+entry:
+  call i32 (ptr, ...) @printf(ptr @open)
+  %len = load i64, ptr @len
+  %stack = load ptr, ptr @stack
+  br label %next
+next:
+  %left = phi i64 [%len, %entry], [%index, %value]
+  %done = icmp eq i64 %left, 0
+  br i1 %done, label %end, label %value
+value:
+  %index = sub i64 %left, 1
+  %slot = getelementptr i64, ptr %stack, i64 %index
+  %number = load i64, ptr %slot
+  %first = icmp eq i64 %left, %len
+  %format = select i1 %first, ptr @number, ptr @separated
+  call i32 (ptr, ...) @printf(ptr %format, i64 %number)
+  br label %next
+end:
+  call i32 (ptr, ...) @printf(ptr @close)
+  ret void
+}
+"#;
+
+/// the messages and formats of the runtime
+const STRINGS: [(&str, &str); 8] = [
+    ("out_of_memory", "error: out of memory\n"),
+    ("empty_unstack", "error: %s : empty unstack at %lld\n"),
+    (
+        "small_unstack",
+        "error: %s : unstack too small (expected at least %lld, had %lld) at %lld\n",
+    ),
+    (
+        "division_by_zero",
+        "error: 💖0 : division by zero at %lld\n",
+    ),
+    ("open", "["),
+    ("number", "%lld"),
+    ("separated", ", %lld"),
+    ("close", "]\n"),
+];
+
+/// `text` as a constant C string named `name`
+fn constant(name: &str, text: &str) -> String {
+    let mut escaped = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => escaped.push(byte as char),
+            _ => write!(escaped, "\\{:02X}", byte).expect("writing to a string cannot fail"),
+        }
+    }
+    format!(
+        "@{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n",
+        name,
+        text.len() + 1,
+        escaped
+    )
+}
+
+/// the IR of `main`, one block per instruction named after its index
+struct Lowering<'a> {
+    tokens: &'a [Operations],
+    semantics: Semantics,
+    code: String,
+    /// the emoji failures name, each a constant `@emoji.N`
+    emoji: BTreeMap<&'static str, usize>,
+}
+
+impl Lowering<'_> {
+    fn line(&mut self, line: std::fmt::Arguments) {
+        self.code += "  ";
+        self.code
+            .write_fmt(line)
+            .expect("writing to a string cannot fail");
+        self.code.push('\n');
+    }
+
+    /// fail unless there are `needed` values for the operation at `origin`
+    fn need(&mut self, needed: usize, origin: usize) {
+        self.need_as(needed, self.tokens[origin].emoji(), origin);
+    }
+
+    fn need_as(&mut self, needed: usize, emoji: &'static str, origin: usize) {
+        if needed == 0 {
+            return;
+        }
+        let next = self.emoji.len();
+        let index = *self.emoji.entry(emoji).or_insert(next);
+        self.line(format_args!(
+            "call void @bottom_need(i64 {}, ptr @emoji.{}, i64 {})",
+            needed, index, origin
+        ));
+    }
+
+    /// jump to `target` if `%{condition}` is set and to the next instruction otherwise
+    fn branch(&mut self, condition: &str, target: usize, index: usize) {
+        self.line(format_args!(
+            "br i1 %{}, label %i{}, label %i{}",
+            condition,
+            target,
+            index + 1
+        ));
+    }
+
+    /// lower the instruction at `index`, false if it has no lowering
+    fn instruction(&mut self, index: usize, instruction: Instruction, origin: usize) -> bool {
+        let k = index;
+        match instruction {
+            Instruction::Single(operation) => {
+                if !self.operation(k, operation, origin) {
+                    return false;
+                }
+            }
+            Instruction::Hug(target) | Instruction::Skip(target) => {
+                self.need(1, origin);
+                self.line(format_args!("%value.{} = call i64 @pop()", k));
+                let jumps = match instruction {
+                    Instruction::Hug(_) => "ne",
+                    _ => "eq",
+                };
+                self.line(format_args!(
+                    "%jump.{} = icmp {} i64 %value.{}, 0",
+                    k, jumps, k
+                ));
+                self.branch(&format!("jump.{}", k), target, k);
+                return true;
+            }
+            Instruction::Multiply(value) => {
+                // fail like the 💓 after the 🥺 would have
+                self.line(format_args!("%len.{} = load i64, ptr @len", k));
+                self.line(format_args!("%empty.{} = icmp eq i64 %len.{}, 0", k, k));
+                self.line(format_args!(
+                    "br i1 %empty.{}, label %fail.{}, label %multiply.{}",
+                    k, k, k
+                ));
+                self.code += &format!("fail.{}:\n", k);
+                self.line(format_args!("call void @push(i64 {})", value));
+                self.need_as(2, "💓", origin + 1);
+                self.line(format_args!("unreachable"));
+                self.code += &format!("multiply.{}:\n", k);
+                self.line(format_args!("%slot.{} = call ptr @slot(i64 0)", k));
+                self.line(format_args!("%value.{} = load i64, ptr %slot.{}", k, k));
+                self.line(format_args!(
+                    "%product.{} = mul i64 %value.{}, {}",
+                    k, k, value
+                ));
+                self.line(format_args!("store i64 %product.{}, ptr %slot.{}", k, k));
+            }
+            Instruction::Jump(target) => {
+                self.line(format_args!("br label %i{}", target));
+                return true;
+            }
+            Instruction::DupHug(target) => {
+                self.need(1, origin);
+                self.line(format_args!("%slot.{} = call ptr @slot(i64 0)", k));
+                self.line(format_args!("%value.{} = load i64, ptr %slot.{}", k, k));
+                self.line(format_args!("%jump.{} = icmp ne i64 %value.{}, 0", k, k));
+                self.branch(&format!("jump.{}", k), target, k);
+                return true;
+            }
+        }
+        self.line(format_args!("br label %i{}", k + 1));
+        true
+    }
+
+    fn operation(&mut self, k: usize, operation: Operations, origin: usize) -> bool {
+        if let Some((needed, _)) = operation.stack_effect() {
+            self.need(needed, origin);
+        }
+        match operation {
+            Operations::Push(value) => self.line(format_args!("call void @push(i64 {})", value)),
+            Operations::Pop(0) => match self.semantics.division_by_zero {
+                DivisionByZero::Error => {
+                    self.line(format_args!("call void @divide_by_zero(i64 {})", origin));
+                    self.line(format_args!("unreachable"));
+                    // nothing comes after, so no branch either
+                    self.code += &format!("after.{}:\n", k);
+                }
+                DivisionByZero::Zero => {
+                    self.line(format_args!("%slot.{} = call ptr @slot(i64 0)", k));
+                    self.line(format_args!("store i64 0, ptr %slot.{}", k));
+                }
+                DivisionByZero::Skip => {}
+            },
+            Operations::Pop(divisor) => {
+                self.line(format_args!("%slot.{} = call ptr @slot(i64 0)", k));
+                self.line(format_args!("%value.{} = load i64, ptr %slot.{}", k, k));
+                // i64::MIN / -1 is undefined for sdiv, and never inexact
+                if divisor == -1 {
+                    self.line(format_args!("%quotient.{} = sub i64 0, %value.{}", k, k));
+                } else if self.semantics.floor_division {
+                    // sdiv rounds toward zero, one too high when the remainder and the divisor
+                    // have different signs
+                    self.line(format_args!(
+                        "%truncated.{} = sdiv i64 %value.{}, {}",
+                        k, k, divisor
+                    ));
+                    self.line(format_args!(
+                        "%remainder.{} = srem i64 %value.{}, {}",
+                        k, k, divisor
+                    ));
+                    let sign = match divisor < 0 {
+                        true => "sgt",
+                        false => "slt",
+                    };
+                    self.line(format_args!(
+                        "%down.{} = icmp {} i64 %remainder.{}, 0",
+                        k, sign, k
+                    ));
+                    self.line(format_args!("%adjust.{} = zext i1 %down.{} to i64", k, k));
+                    self.line(format_args!(
+                        "%quotient.{} = sub i64 %truncated.{}, %adjust.{}",
+                        k, k, k
+                    ));
+                } else {
+                    self.line(format_args!(
+                        "%quotient.{} = sdiv i64 %value.{}, {}",
+                        k, k, divisor
+                    ));
+                }
+                self.line(format_args!("store i64 %quotient.{}, ptr %slot.{}", k, k));
+            }
+            Operations::Swap(0) => {}
+            Operations::Swap(steps) => {
+                self.line(format_args!("%top.{} = call ptr @slot(i64 0)", k));
+                self.line(format_args!("%other.{} = call ptr @slot(i64 {})", k, steps));
+                self.line(format_args!("%a.{} = load i64, ptr %top.{}", k, k));
+                self.line(format_args!("%b.{} = load i64, ptr %other.{}", k, k));
+                self.line(format_args!("store i64 %b.{}, ptr %top.{}", k, k));
+                self.line(format_args!("store i64 %a.{}, ptr %other.{}", k, k));
+            }
+            Operations::Heart(discarded) => {
+                self.line(format_args!("%a.{} = call i64 @pop()", k));
+                self.line(format_args!("%b.{} = call i64 @pop()", k));
+                self.line(format_args!("%len.{} = load i64, ptr @len", k));
+                self.line(format_args!(
+                    "%kept.{} = sub i64 %len.{}, {}",
+                    k, k, discarded
+                ));
+                self.line(format_args!("store i64 %kept.{}, ptr @len", k));
+                self.line(format_args!("%product.{} = mul i64 %a.{}, %b.{}", k, k, k));
+                self.line(format_args!("call void @push(i64 %product.{})", k));
+            }
+            Operations::Dup(0) => {}
+            Operations::Dup(count) => self.line(format_args!("call void @dup(i64 {})", count)),
+            Operations::Clear(0) => self.line(format_args!("store i64 0, ptr @len")),
+            Operations::Clear(count) => {
+                self.line(format_args!("%len.{} = load i64, ptr @len", k));
+                self.line(format_args!("%kept.{} = sub i64 %len.{}, {}", k, k, count));
+                self.line(format_args!("store i64 %kept.{}, ptr @len", k));
+            }
+            Operations::Depth(_) => {
+                self.line(format_args!("%len.{} = load i64, ptr @len", k));
+                self.line(format_args!("call void @push(i64 %len.{})", k));
+            }
+            Operations::Read(0) => self.line(format_args!("call void @read()")),
+            Operations::Write(fd @ (1 | 2)) => {
+                self.line(format_args!("call void @write_byte(i32 {})", fd))
+            }
+            Operations::Nop(_) | Operations::Break(_) => {}
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// lower `tokens` to a module of LLVM IR with a `main` running them with `semantics`
+///
+/// only the semantics of 💖 can be chosen, values are always 64 bits wide and wrap around and
+/// jumps are never strict
+pub fn lower(tokens: &[Operations], semantics: Semantics) -> Result<String, String> {
+    if semantics.strict_jumps || semantics.overflow != Overflow::Wrap {
+        return Err(
+            "only the semantics of 💖 can be chosen, values wrap around and jumps are not strict"
+                .to_string(),
+        );
+    }
+    let program = bytecode::compile(tokens, true).ok_or_else(|| {
+        "only programs the bytecode runs can be lowered, without 🧵🪢🛫🛬📥📤💾💿 and a 🫂 \
+         jumping before the start"
+            .to_string()
+    })?;
+    let mut lowering = Lowering {
+        tokens,
+        semantics,
+        code: String::new(),
+        emoji: BTreeMap::new(),
+    };
+    for (index, &instruction) in program.code.iter().enumerate() {
+        lowering.code += &format!("i{}:\n", index);
+        let origin = program.origins[index];
+        if !lowering.instruction(index, instruction, origin) {
+            return Err(format!(
+                "{} at {} has no LLVM lowering",
+                tokens[origin], origin
+            ));
+        }
+    }
+
+    let mut module = String::from("; compiled from 🥺 by bottom\n\n");
+    for (name, text) in STRINGS {
+        module += &constant(name, text);
+    }
+    for (emoji, index) in &lowering.emoji {
+        module += &constant(&format!("emoji.{}", index), emoji);
+    }
+    module += "\n";
+    module += RUNTIME;
+    module += "\ndefine i32 @main() {\nentry:\n  br label %i0\n";
+    module += &lowering.code;
+    writeln!(module, "i{}:", program.code.len()).expect("writing to a string cannot fail");
+    module += "  call void @finish()\n  ret i32 0\n}\n";
+    Ok(module)
+}
+
+/// run `ir` through LLVM's `opt` at `-O{level}`
+pub fn optimize(ir: &str, level: u8) -> Result<String, String> {
+    let mut opt = llvm_tool("opt")?;
+    opt.arg(format!("-O{}", level)).arg("-S");
+    let output = pipe(&mut opt, "opt", ir)?;
+    String::from_utf8(output).map_err(|_| "opt wrote something that is not UTF-8".to_string())
+}
+
+/// turn `ir` into the native program `output` with LLVM's `llc` at `-O{level}` and `cc`
+pub fn link(ir: &str, level: u8, output: &Path) -> Result<(), String> {
+    let object = env::temp_dir().join(format!("bottom-build-{}.o", process::id()));
+    let mut llc = llvm_tool("llc")?;
+    llc.arg(format!("-O{}", level))
+        .arg("-filetype=obj")
+        // cc links position independent executables by default
+        .arg("-relocation-model=pic")
+        .arg("-o")
+        .arg(&object);
+    let linked = pipe(&mut llc, "llc", ir).and_then(|_| {
+        let cc = Command::new("cc")
+            .arg(&object)
+            .arg("-o")
+            .arg(output)
+            .output()
+            .map_err(|err| format!("could not run cc: {}", err))?;
+        match cc.status.success() {
+            true => Ok(()),
+            false => Err(format!(
+                "cc failed: {}",
+                String::from_utf8_lossy(&cc.stderr).trim()
+            )),
+        }
+    });
+    let _ = fs::remove_file(&object);
+    linked
+}
+
+/// `tool` reading the opaque pointers of the IR, which LLVM 14 only does with `-opaque-pointers`
+fn llvm_tool(tool: &str) -> Result<Command, String> {
+    let version = Command::new(tool)
+        .arg("--version")
+        .output()
+        .map_err(|err| format!("could not run {}: {}", tool, err))?;
+    let version = String::from_utf8_lossy(&version.stdout);
+    let mut command = Command::new(tool);
+    if version.contains("LLVM version 14.") {
+        command.arg("-opaque-pointers");
+    }
+    Ok(command)
+}
+
+/// run `command` with `input` on stdin and return what it writes to stdout
+fn pipe(command: &mut Command, name: &str, input: &str) -> Result<Vec<u8>, String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("could not run {}: {}", name, err))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input.as_bytes())
+        .map_err(|err| format!("could not write to {}: {}", name, err))?;
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("{} failed: {}", name, err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use bottom::{parse, DivisionByZero, Operations, Semantics};
+
+    use crate::llvm::{constant, lower};
+
+    #[test]
+    fn test_lower() {
+        assert_eq!(
+            constant("x", "💖\"%\n"),
+            "@x = private unnamed_addr constant [8 x i8] c\"\\F0\\9F\\92\\96\\22%\\0A\\00\"\n"
+        );
+        let ir = lower(&parse("🥺8 💖2 ✨1 🫂4 📝1", true), Semantics::default()).unwrap();
+        assert!(ir.contains("define i32 @main()"));
+        // ✨1 🫂4 is one instruction jumping back to 💖2
+        assert!(ir.contains("br i1 %jump.2, label %i1, label %i3"));
+        assert!(ir.contains("call void @write_byte(i32 1)"));
+        assert!(lower(&parse("🥺1 📥0", true), Semantics::default()).is_err());
+        assert!(lower(&parse("🥺1 🔢0", true), Semantics::default()).is_err());
+        assert!(lower(&parse("🥺1", true), Semantics::spec()).is_err());
+
+        let floor = Semantics {
+            floor_division: true,
+            ..Semantics::default()
+        };
+        assert!(!ir.contains("srem"));
+        let ir = lower(&parse("✨1 💖3", true), floor).unwrap();
+        assert!(ir.contains("%down.1 = icmp slt i64 %remainder.1, 0"));
+        let ir = lower(&[Operations::Dup(1), Operations::Pop(-3)], floor).unwrap();
+        assert!(ir.contains("%down.1 = icmp sgt i64 %remainder.1, 0"));
+        let ir = lower(&parse("🥺8 💖0", true), Semantics::default()).unwrap();
+        assert!(ir.contains("call void @divide_by_zero(i64 1)"));
+        let zero = Semantics {
+            division_by_zero: DivisionByZero::Zero,
+            ..Semantics::default()
+        };
+        let ir = lower(&parse("🥺8 💖0", true), zero).unwrap();
+        assert!(!ir.contains("@divide_by_zero(i64 1)"));
+        assert!(ir.contains("store i64 0, ptr %slot.1"));
+    }
+}
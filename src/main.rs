@@ -1,389 +1,1099 @@
 /*!
- * # 🥺
- *
- * 🥺 is an unstack-based esoteric programming language written in 2021 by [User:RocketRace](https://esolangs.org/wiki/User:RocketRace).
- * It is inspired by [bottom](https://github.com/kaylynn234/bottom) by [kaylynn234](https://github.com/kaylynn234).
- * It is a dialect of "bottom", a natural language used by bottoms.
- *
- * See: [🥺 on esolangs.org](https://esolangs.org/wiki/%F0%9F%A5%BA)
- *
- * |    Please  |  🥺🥺🥺                                                                                                                                                           |
- * | -------    | ------                                                                                                                                                            |
- * |     🥺N    | Pushes an integer `N` to the bottom of the unstack                                                                                                                |
- * |     💖N    | Pops an integer from the unstack, and pushes the result of floor division of that integer by `N` to the unstack.                                                  |
- * |     👉👈N  | Take the `N`th value in the unstack and swap it with the bottom value.                                                                                            |
- * |     💓N    | Pops two integers from the unstack, then pops and discards `N` values from the unstack, then pushes the product of the two popped integers to the unstack         |
- * |     ✨N    | Duplicates the `N` values at the bottom of the unstack                                                                                                            |
- * |    🫂N     | Pop a value from the bottom of the unstack. Jump back `N` instructions if the value is nonzero                                                                    |
- *
+ * # bottom
  *
+ * the command line interface of the 🥺 interpreter and the tools built around it,
+ * the language itself lives in the library
  */
 
-use std::fs;
+use std::io::{self, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use clap::{App, Arg};
+use bottom::cell::Cell;
+use bottom::ext::{Descriptor, Host};
+use bottom::interpreter::{interpret, Interpreter};
+use bottom::pragma::{Pragma, Unmet, Version, FEATURES};
+use bottom::replay::Session;
+use bottom::sandbox::{Failure, Sandbox};
+use bottom::unstack::{Representation, Unstack};
+use bottom::{
+    parse, parse_spanned, parse_spec, parse_strict, DivisionByZero, Limits, Operations, Overflow,
+    RuntimeError, Semantics,
+};
+use clap::{App, AppSettings, Arg, ArgMatches};
 
-// 🥺
+use crate::asm::resolve_labels;
+use crate::batch::panic_message;
+use crate::include::{vendor_dir, Expander};
+use crate::lint::Levels;
+use crate::optimize::{optimize, unroll};
+use crate::output::{Output, Template};
+use crate::report::{position, Exit, Reports, SNAPSHOTS};
+#[cfg(feature = "fetch")]
+use crate::source::fetch_source;
+use crate::source::{is_url, read_source};
 
-/**
- * # Possible Operations
- *
- * enum that represents an action and the argument associated
- *
- * would allow for remixing the commands associated with an operation
- */
-#[derive(Debug, Clone, Copy)]
-enum Operations {
-    /// 🥺      :
-    /// Pushes an integer N to the bottom of the unstack.
-    Push(i64),
-    /// 💖      :
-    /// Pops an integer from the unstack, and pushes the result of floor division of that integer by N to the unstack.
-    Pop(i64),
-    /// 👉👈    :
-    /// Take the Nth value in the unstack and swap it with the bottom value.
-    Swap(usize),
-    /// 💓      :
-    /// Pops two integers from the unstack, then pops and discards N values from the unstack, then pushes the product of the two popped integers to the unstack.
-    Heart(usize),
-    /// ✨      :
-    /// Duplicates the N values at the bottom of the unstack.
-    Dup(usize),
-    /// 🫂      :
-    /// Pop a value from the bottom of the unstack. Jump back N instructions if the value is nonzero.
-    Hug(usize),
+mod asm;
+mod batch;
+mod bench;
+mod bf;
+mod build;
+mod canonical;
+mod chrome;
+mod compile;
+mod crosscheck;
+mod debug;
+mod deps;
+mod diff;
+mod disasm;
+mod equiv;
+mod golden;
+mod gzip;
+mod include;
+mod json;
+mod kernel;
+mod lint;
+#[cfg(feature = "llvm")]
+mod llvm;
+mod manifest;
+mod minify;
+mod obfuscate;
+mod optimize;
+mod output;
+mod report;
+mod rng;
+mod serve;
+mod sha256;
+mod source;
+mod symbolic;
+mod toml;
+mod trace;
+mod translate;
+mod vectors;
+mod verify;
+mod zmtp;
+
+/// how data read from stdin is turned into initial unstack values
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InputMode {
+    /// every byte is pushed as its code
+    Bytes,
+    /// every non-empty line is parsed as one integer
+    Lines,
+    /// every whitespace separated word is parsed as one integer
+    Ints,
 }
 
-mod unstack {
-    use std::fmt::{Debug, Formatter};
-
-    #[derive(Clone)]
-    struct UnstackNode {
-        prev: Option<Box<UnstackNode>>,
-        value: i64,
-    }
-
-    /**
-     * # Unstack
-     *
-     * it's like a stack but you push to the bottom
-     *
-     * this is a linked list implementation of an Unstack
-     *
-     * ## Usage
-     *
-     * ```rust
-     * let unstack: Unstack = Unstack::new();
-     *
-     * unstack.push(10);
-     * unstack.push(42);
-     *
-     * assert_eq!(unstack.pop(), 42);
-     * assert_eq!(unstack.pop(), 10);
-     * assert!(unstack.is_empty());
-     * ```
-     */
-    pub struct Unstack {
-        bottom: Option<UnstackNode>,
-        size: usize,
-    }
-
-    impl Debug for Unstack {
-        fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-            if self.size == 0 {
-                write!(f, "[]")
-            } else {
-                let mut node = self.bottom.clone().unwrap();
-                let mut tmp = vec![node.value];
-
-                while let Some(prev) = node.prev {
-                    node = *prev;
-                    tmp.push(node.value);
-                }
-                let mut builder = "[ ".to_string();
-                for (i, j) in tmp.iter().rev().enumerate() {
-                    builder += &*format!("{}", j);
-                    if i != tmp.len() - 1 {
-                        builder += ", "
-                    }
-                }
-                write!(f, "{} ] ", builder)
-            }
-        }
+/// read `source` according to `mode` and return the values in the order they should be pushed
+fn read_input(mode: InputMode, mut source: impl Read) -> Result<Vec<i64>, String> {
+    let mut data = vec![];
+    source
+        .read_to_end(&mut data)
+        .map_err(|err| format!("could not read stdin: {}", err))?;
+    let text = String::from_utf8_lossy(&data);
+    match mode {
+        InputMode::Bytes => Ok(data.iter().map(|&b| b as i64).collect()),
+        InputMode::Lines => text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index, line.trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(index, line)| {
+                line.parse().map_err(|_| {
+                    format!("line {} of stdin, '{}', is not an integer", index + 1, line)
+                })
+            })
+            .collect(),
+        InputMode::Ints => text
+            .split_whitespace()
+            .map(|word| {
+                word.parse()
+                    .map_err(|_| format!("'{}' on stdin is not an integer", word))
+            })
+            .collect(),
     }
+}
 
-    impl Unstack {
-        /// create an empty Unstack
-        pub fn new() -> Self {
-            Unstack {
-                bottom: None,
-                size: 0,
-            }
-        }
-
-        /// push new value to the bottom of the unstack
-        pub fn push(&mut self, value: i64) {
-            if self.size == 0 {
-                self.bottom = Some(UnstackNode { prev: None, value });
-            } else {
-                let newbottom = UnstackNode {
-                    prev: Some(Box::new(self.bottom.clone().unwrap())),
-                    value,
-                };
-                self.bottom = Some(newbottom);
-            }
-            self.size += 1;
-        }
+/// parse a comma separated list of integers as given to `--init` and `--push`
+fn parse_values(values: &str) -> Result<Vec<i64>, String> {
+    values
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("'{}' is not an integer", value))
+        })
+        .collect()
+}
 
-        /// pop a value off the bottom of the unstack and return it
-        pub fn pop(&mut self) -> i64 {
-            if self.size == 0 {
-                panic!("out of bounds");
-            }
-            let bottom = self.bottom.clone().unwrap();
-            self.bottom = bottom.prev.map(|prev| *prev);
-            self.size -= 1;
-            bottom.value
-        }
+/// parse a file descriptor assignment of the form `N=PATH` as given to `--fd` and `--listen`
+fn parse_fd(fd: &str) -> Result<(usize, &str), String> {
+    let (number, path) = fd
+        .split_once('=')
+        .ok_or_else(|| format!("'{}' is not of the form N=PATH", fd))?;
+    let number = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{}' is not a file descriptor number", number))?;
+    Ok((number, path))
+}
 
-        /// swaps the bottom of the unstack with the provided index
-        /// note that since unstacks do not support indexing this will run in O(steps)
-        pub fn swap_first(&mut self, steps: usize) {
-            let mut tmp = Unstack::new();
-            let bottom_val = self.bottom.clone().unwrap().value;
-            for _ in 0..steps {
-                tmp.push(self.pop());
-            }
-            let top_val = self.pop();
-            self.push(bottom_val);
-            for _ in 0..steps - 1 {
-                self.push(tmp.pop());
-            }
-            self.push(top_val);
+/// parse a duration like `5s`, `250ms` or `2m`, a plain number is taken as seconds
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let duration = duration.trim();
+    let split = duration
+        .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+        .unwrap_or(duration.len());
+    let (number, unit) = duration.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is not a duration", duration))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => {
+            return Err(format!(
+                "unknown unit '{}' in duration '{}'",
+                unit, duration
+            ))
         }
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("duration '{}' is too long", duration))
+}
 
-        /// returns the size of the unstack
-        pub fn len(&self) -> usize {
-            self.size
-        }
+/// `--div-by-zero` and `--floor-div`, choosing what 💖 does
+fn division_args() -> [Arg<'static, 'static>; 2] {
+    [
+        Arg::with_name("div-by-zero")
+            .long("div-by-zero")
+            .takes_value(true)
+            .value_name("BEHAVIOR")
+            .possible_values(&["error", "zero", "skip"])
+            .default_value("error")
+            .help("what 💖0 does: fail, push 0 or leave the unstack as it is"),
+        Arg::with_name("floor-div")
+            .long("floor-div")
+            .help("round the quotients of 💖 toward negative infinity instead of toward 0"),
+    ]
+}
 
-        pub fn is_empty(&self) -> bool {
-            self.size == 0
-        }
+/// the default semantics with 💖 doing what the [`division_args`] in `args` choose
+fn division_semantics(args: &ArgMatches) -> Semantics {
+    Semantics {
+        division_by_zero: match args.value_of("div-by-zero") {
+            Some("zero") => DivisionByZero::Zero,
+            Some("skip") => DivisionByZero::Skip,
+            _ => DivisionByZero::Error,
+        },
+        floor_division: args.is_present("floor-div"),
+        ..Semantics::default()
     }
+}
 
-    #[cfg(test)]
-    mod tests {
-        use crate::unstack::Unstack;
-
-        #[test]
-        fn test_push() {
-            let mut unstack: Unstack = Unstack::new();
-            assert_eq!(unstack.size, 0);
-            assert!(unstack.is_empty());
-            assert!(unstack.bottom.is_none());
-
-            let to_test = vec![420, 69, 42069, -1, -1948];
-            for (size, i) in to_test.iter().enumerate() {
-                unstack.push(*i);
-                assert!(unstack.bottom.is_some());
-                assert_eq!(unstack.bottom.clone().unwrap().value, *i);
-                assert_eq!(unstack.len(), size + 1);
-            }
-
-            assert_eq!(unstack.bottom.unwrap().prev.unwrap().value, -1);
+/// refuse to run a program whose [pragma](bottom::pragma) asks for what this `bottom` lacks
+fn check_pragma(source: &str, args: &ArgMatches) {
+    let pragma = match Pragma::of(source) {
+        Ok(Some(pragma)) => pragma,
+        Ok(None) => return,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1);
         }
-
-        #[test]
-        fn test_pop() {
-            let mut unstack = Unstack::new();
-            assert!(unstack.is_empty());
-            assert!(unstack.bottom.is_none());
-
-            unstack.push(1);
-            unstack.push(2);
-            unstack.push(3);
-            unstack.push(4);
-
-            unstack.pop();
-            assert!(unstack.bottom.is_some());
-            assert_eq!(unstack.bottom.as_ref().unwrap().value, 3);
-            unstack.pop();
-            assert!(unstack.bottom.is_some());
-            assert_eq!(unstack.bottom.as_ref().unwrap().value, 2);
-
-            assert!(!unstack.is_empty());
+    };
+    let mut available = vec![];
+    for flag in ["extensions", "labels"] {
+        if args.is_present(flag) {
+            available.push(flag);
         }
     }
-}
-
-use crate::unstack::Unstack;
-
-/// tokenize a 🥺 program
-fn parse(source: &str) -> Vec<Operations> {
-    let mut tokens = vec![];
-
-    let mut word: String = "".to_string();
-    let mut operation: Option<String> = None;
-    for ch in source.chars() {
-        if !(matches!(ch, '🥺' | '💖' | '👉' | '👈' | '💓' | '✨' | '🫂') || ch.is_ascii_digit())
-        {
-            if let Some(op) = &operation {
-                let value: i64 = if let Ok(num) = word.parse() {
-                    num
-                } else {
-                    word.chars().count() as i64
-                };
-                tokens.push(match op.as_str() {
-                    "🥺" => Operations::Push(value),
-                    "💖" => Operations::Pop(value),
-                    "👉👈" => Operations::Swap(value as usize),
-                    "💓" => Operations::Heart(value as usize),
-                    "✨" => Operations::Dup(value as usize),
-                    "🫂" => Operations::Hug(value as usize),
-                    _ => unreachable!(),
-                });
+    if cfg!(feature = "net") {
+        available.push("net");
+    }
+    let unmet = pragma.unmet(&available);
+    for unmet in &unmet {
+        match unmet {
+            Unmet::Version(version) => eprintln!(
+                "error: the program needs bottom {} or newer, this is {}",
+                version,
+                Version::current()
+            ),
+            Unmet::Feature(feature) if feature == "net" => {
+                eprintln!("error: the program needs bottom built with --features net")
             }
-            word = "".to_string();
-            operation = None;
-            continue;
-        }
-        word += ch.to_string().as_ref();
-
-        if matches!(operation, None) && matches!(&*word, "🥺" | "💖" | "👉👈" | "💓" | "✨" | "🫂")
-        {
-            operation = Some(word);
-            word = "".to_string();
+            Unmet::Feature(feature) if FEATURES.contains(&feature.as_str()) => {
+                eprintln!("error: the program needs --{}", feature)
+            }
+            Unmet::Feature(feature) => eprintln!(
+                "error: the program needs {}, which this bottom does not know",
+                feature
+            ),
         }
     }
-    if word != "" {
-        if let Some(op) = &operation {
-            let value: i64 = if let Ok(num) = word.parse() {
-                num
-            } else {
-                word.chars().count() as i64
-            };
-            tokens.push(match op.as_str() {
-                "🥺" => Operations::Push(value),
-                "💖" => Operations::Pop(value),
-                "👉👈" => Operations::Swap(value as usize),
-                "💓" => Operations::Heart(value as usize),
-                "✨" => Operations::Dup(value as usize),
-                "🫂" => Operations::Hug(value as usize),
-                _ => unreachable!(),
-            });
-        }
+    if !unmet.is_empty() {
+        process::exit(1);
     }
-    tokens
 }
 
-/// interpret the tokens of a 🥺  program
-fn interpret(tokens: Vec<Operations>) -> Vec<i64> {
-    let mut unstack = Unstack::new();
-    let mut instruction_pointer = 0;
-    while instruction_pointer < tokens.len() {
-        match tokens[instruction_pointer] {
-            Operations::Push(val) => unstack.push(val),
-            Operations::Pop(val) => {
-                if unstack.is_empty() {
-                    panic!("💖 : empty unstack at {}", instruction_pointer);
-                }
-                let value = unstack.pop() / val;
-                unstack.push(value);
-            }
-            Operations::Swap(steps) => {
-                if unstack.len() < steps {
-                    panic!(
-                        "👉👈 : unstack too small (expected at least {}, had {}) at {}",
-                        steps,
-                        unstack.len(),
-                        instruction_pointer
-                    );
-                }
-                unstack.swap_first(steps)
-            }
-            Operations::Heart(val) => {
-                if unstack.len() < 2 + val {
-                    panic!(
-                        "💓 : unstack too small (expected at least {}, had {}) at {}",
-                        val + 2,
-                        unstack.len(),
-                        instruction_pointer
-                    )
-                }
-                let value = unstack.pop() * unstack.pop();
-                for _ in 0..val {
-                    unstack.pop();
-                }
-                unstack.push(value);
-            }
-            Operations::Dup(val) => {
-                if unstack.len() < val {
-                    panic!(
-                        "✨ : unstack too small (expected at least {}, had {}) at {}",
-                        val,
-                        unstack.len(),
-                        instruction_pointer
-                    )
-                }
-                let mut tmp = Unstack::new();
-                for _ in 0..val {
-                    tmp.push(unstack.pop());
-                }
-                for _ in 0..val {
-                    let value = tmp.pop();
-                    unstack.push(value);
-                    unstack.push(value);
-                }
-            }
-            Operations::Hug(val) => {
-                if unstack.is_empty() {
-                    panic!("🫂 : empty unstack at {}", instruction_pointer);
-                }
-                if unstack.pop() != 0 {
-                    instruction_pointer -= val - 1;
-                }
-            }
-        }
-        instruction_pointer += 1;
+/// the program to run, given with `-e`, in a file or at a URL
+fn load_source(args: &ArgMatches) -> String {
+    if let Some(source) = args.value_of("eval") {
+        return expand_includes(source, Path::new("."), args.is_present("extensions"));
     }
-    let mut out = vec![];
-    for _ in 0..unstack.len() {
-        out.push(unstack.pop());
+    let filename = args.value_of("filename").expect("missing filename");
+    let source = if is_url(filename) {
+        if args.is_present("no-net") {
+            eprintln!("error: not downloading {} because of --no-net", filename);
+            process::exit(1);
+        }
+        #[cfg(feature = "fetch")]
+        let fetched = fetch_source(filename);
+        #[cfg(not(feature = "fetch"))]
+        let fetched = Err(io::Error::other(
+            "running programs from URLs needs the fetch feature",
+        ));
+        fetched
+    } else {
+        read_source(filename)
+    };
+    let source = source.unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", filename, err);
+        // a WASI runtime only lets the program see the directories it preopened
+        if cfg!(target_os = "wasi") {
+            eprintln!(
+                "note: grant access to its directory, e.g. `wasmtime run --dir . bottom.wasm`"
+            );
+        }
+        process::exit(1);
+    });
+    match is_url(filename) {
+        // includes of a downloaded program would read local files
+        true => source,
+        false => expand_includes(
+            &source,
+            Path::new(filename).parent().unwrap_or(Path::new(".")),
+            args.is_present("extensions"),
+        ),
     }
-    out
+}
+
+/// `source` with its includes replaced, packages being those of the project around `dir`
+fn expand_includes(source: &str, dir: &Path, extensions: bool) -> String {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let vendor = dir
+        .canonicalize()
+        .ok()
+        .and_then(|dir| manifest::find(&dir))
+        .and_then(|manifest| manifest.parent().map(vendor_dir));
+    Expander::new(vendor, extensions)
+        .expand(source, dir)
+        .unwrap_or_else(|err| {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        })
 }
 
 fn main() {
-    let args = App::new("🥺 interpreter")
+    let app = App::new("🥺 interpreter")
         .version("0.1.0")
         .author("hyde <hiddy.tiddey@gmail.com>")
         .about("see🥺 https://esolangs.org/wiki/%F0%9F%A5%BA for documentation")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::ArgsNegateSubcommands)
+        .subcommand(asm::subcommand())
+        .subcommand(batch::subcommand())
+        .subcommand(bench::subcommand())
+        .subcommand(build::subcommand())
+        .subcommand(canonical::subcommand())
+        .subcommand(compile::subcommand())
+        .subcommand(crosscheck::subcommand())
+        .subcommand(debug::subcommand())
+        .subcommand(deps::subcommand())
+        .subcommand(diff::subcommand())
+        .subcommand(disasm::subcommand())
+        .subcommand(equiv::subcommand())
+        .subcommand(golden::subcommand())
+        .subcommand(bf::subcommand())
+        .subcommand(kernel::subcommand())
+        .subcommand(minify::subcommand())
+        .subcommand(obfuscate::subcommand())
+        .subcommand(manifest::subcommand())
+        .subcommand(serve::subcommand())
+        .subcommand(trace::subcommand())
+        .subcommand(translate::subcommand())
+        .subcommand(vectors::subcommand())
+        .subcommand(verify::subcommand())
         .arg(
             Arg::with_name("filename")
                 .takes_value(true)
-                .required(true)
+                .required_unless("eval")
                 .value_name("FILE")
-                .help("name of the file 🥺🥺🥺👉👈🥺")
+                .help("name of the file 🥺🥺🥺👉👈🥺, or its URL with the fetch feature")
                 .index(1),
         )
         .arg(
-            Arg::with_name("a")
-                .short("a")
+            Arg::with_name("eval")
+                .short("e")
+                .long("eval")
+                .takes_value(true)
+                .value_name("SOURCE")
+                .conflicts_with("filename")
+                .help("run SOURCE instead of the program in a file"),
+        )
+        .args(&output::args())
+        .args(&lint::args())
+        .args(&report::args())
+        .arg(
+            Arg::with_name("input")
+                .long("input")
+                .takes_value(true)
+                .value_name("MODE")
+                .possible_values(&["bytes", "lines", "ints"])
+                .help("read stdin before running and push its content onto the unstack"),
+        )
+        .arg(
+            Arg::with_name("init")
+                .long("init")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .value_name("VALUES")
+                .validator(|values| parse_values(&values).map(|_| ()))
+                .help("comma separated values pushed onto the unstack before running"),
+        )
+        .arg(
+            Arg::with_name("push")
+                .long("push")
+                .takes_value(true)
                 .multiple(true)
-                .help("display output as ascii"),
+                .number_of_values(1)
+                .allow_hyphen_values(true)
+                .value_name("N")
+                .validator(|value| parse_values(&value).map(|_| ()))
+                .help("push a value onto the unstack before running, may be repeated"),
         )
-        .get_matches();
-    let filename = args.value_of("filename").expect("missing filename");
-    let source = fs::read_to_string(filename).expect("could not read file");
-    let tokens = parse(source.as_str());
-    let output = interpret(tokens);
-    if args.occurrences_of("a") == 0 {
-        println!("{:?}", output);
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("enable the extension operations"),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("reject stray digits, partial emoji and an operation without an argument at the end instead of skipping them"),
+        )
+        .arg(
+            Arg::with_name("spec")
+                .long("spec")
+                .conflicts_with_all(&["extensions", "labels", "div-by-zero", "strict"])
+                .help("run the program exactly as the reference on esolangs.org would, rejecting anything it might disagree on"),
+        )
+        .arg(
+            Arg::with_name("labels")
+                .long("labels")
+                .help("resolve the jump labels (loop: ... 🫂@loop) of the label dialect before running"),
+        )
+        .arg(
+            Arg::with_name("env")
+                .long("env")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME")
+                .requires("extensions")
+                .help("environment variable read by 🏠N, where N is its position among all --env"),
+        )
+        .arg(
+            Arg::with_name("fd")
+                .long("fd")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("N=PATH")
+                .requires("extensions")
+                .validator(|fd| parse_fd(&fd).map(|_| ()))
+                .help("make file descriptor N refer to the file at PATH for 📖N and 📝N"),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .takes_value(true)
+                .value_name("LOG")
+                .requires("extensions")
+                .conflicts_with("replay")
+                .help("write every value the program reads to LOG"),
+        )
+        .arg(
+            Arg::with_name("replay")
+                .long("replay")
+                .takes_value(true)
+                .value_name("LOG")
+                .requires("extensions")
+                .help("read values from a LOG written by --record instead of the outside world"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .value_name("DURATION")
+                .validator(|duration| parse_duration(&duration).map(|_| ()))
+                .help("abort after running for DURATION, e.g. 5s or 250ms"),
+        )
+        .args(&division_args())
+        .arg(
+            Arg::with_name("overflow")
+                .long("overflow")
+                .takes_value(true)
+                .value_name("MODE")
+                .possible_values(&["wrap", "checked"])
+                .help("wrap around when 🥺, 💓, 💖 or a math intrinsic overflows, or fail with the operands"),
+        )
+        .arg(
+            Arg::with_name("keep-going")
+                .long("keep-going")
+                .conflicts_with("sandbox")
+                .help("skip instructions that fail and report all their errors at the end"),
+        )
+        .arg(
+            Arg::with_name("max-memory")
+                .long("max-memory")
+                .takes_value(true)
+                .value_name("CELLS")
+                .validator(|cells| {
+                    cells
+                        .parse::<usize>()
+                        .map(|_| ())
+                        .map_err(|err| err.to_string())
+                })
+                .help("abort when 💾 or 💿 use an address of CELLS or more"),
+        )
+        .arg(
+            Arg::with_name("no-net")
+                .long("no-net")
+                .help("never download the program, even if FILE is a URL"),
+        )
+        .arg(
+            Arg::with_name("sandbox")
+                .long("sandbox")
+                .conflicts_with_all(&["env", "fd", "record", "replay", "snapshot-every"])
+                .help("run an untrusted program: limit its instructions, values and time and refuse all I/O"),
+        )
+        .arg(
+            Arg::with_name("optimize")
+                .short("O")
+                .conflicts_with("loops")
+                .help("merge constant operations and unroll short loops before running"),
+        )
+        .arg(
+            Arg::with_name("engine")
+                .long("engine")
+                .takes_value(true)
+                .value_name("ENGINE")
+                .possible_values(&["interpreter", "closures", "native"])
+                .conflicts_with_all(&[
+                    "sandbox",
+                    "keep-going",
+                    "spec",
+                    "floor-div",
+                    "report",
+                    "histogram",
+                    "loops",
+                    "profile",
+                    "flamegraph",
+                    "snapshot-every",
+                    "trace-out",
+                    "chrome-trace",
+                ])
+                .help("run the program with the interpreter, as closures or, with the native feature, as x86-64 machine code"),
+        )
+        .arg(
+            Arg::with_name("int-width")
+                .long("int-width")
+                .takes_value(true)
+                .value_name("BITS")
+                .possible_values(&["32", "64", "128"])
+                .help("run the program with integers of BITS bits instead of 64"),
+        )
+        .arg(
+            Arg::with_name("timeout-check-interval")
+                .long("timeout-check-interval")
+                .takes_value(true)
+                .value_name("STEPS")
+                .requires("timeout")
+                .validator(|steps| {
+                    steps
+                        .parse::<usize>()
+                        .map(|_| ())
+                        .map_err(|err| err.to_string())
+                })
+                .help("number of instructions executed between checks of the timeout"),
+        );
+    #[cfg(feature = "net")]
+    let app = app.arg(
+        Arg::with_name("listen")
+            .long("listen")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("N=PORT")
+            .requires("extensions")
+            .conflicts_with("sandbox")
+            .validator(|fd| parse_fd(&fd).map(|_| ()))
+            .help("make file descriptor N a socket listening on PORT (or HOST:PORT) for 📞N"),
+    );
+    let args = app.get_matches();
+    match args.subcommand() {
+        ("asm", Some(args)) => return asm::main(args),
+        ("run-all", Some(args)) => return batch::main(args),
+        ("bench", Some(args)) => return bench::main(args),
+        ("build", Some(args)) => return build::main(args),
+        ("canonicalize", Some(args)) => return canonical::main(args),
+        ("compile", Some(args)) => return compile::main(args),
+        ("crosscheck", Some(args)) => return crosscheck::main(args),
+        ("debug", Some(args)) => return debug::main(args),
+        ("fetch", Some(args)) => return deps::main(args),
+        ("diff", Some(args)) => return diff::main(args),
+        ("disasm", Some(args)) => return disasm::main(args),
+        ("equiv", Some(args)) => return equiv::main(args),
+        ("test", Some(args)) => return golden::main(args),
+        ("import-bf", Some(args)) => return bf::main(args),
+        ("kernel", Some(args)) => return kernel::main(args),
+        ("minify", Some(args)) => return minify::main(args),
+        ("obfuscate", Some(args)) => return obfuscate::main(args),
+        ("replay", Some(args)) => return trace::main(args),
+        ("run", Some(args)) => return manifest::main(args),
+        ("serve", Some(args)) => return serve::main(args),
+        ("translate", Some(args)) => return translate::main(args),
+        ("vectors", Some(args)) => return vectors::main(args),
+        ("verify", Some(args)) => return verify::main(args),
+        _ => {}
+    }
+    let mut source = load_source(&args);
+    check_pragma(&source, &args);
+    if args.is_present("labels") {
+        source = resolve_labels(&source, args.is_present("extensions")).unwrap_or_else(|err| {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        });
+    }
+    let strict = args.is_present("strict") || args.is_present("spec");
+    let mut tokens = if strict {
+        let parsed = match args.is_present("spec") {
+            true => parse_spec(&source),
+            false => parse_strict(&source, args.is_present("extensions")),
+        };
+        let spanned = parsed.unwrap_or_else(|err| {
+            let (line, column) = position(&source, err.span.start);
+            eprintln!("error: {}:{}: {}", line, column, err.malformed);
+            process::exit(1);
+        });
+        spanned
+            .into_iter()
+            .map(|(operation, _)| operation)
+            .collect()
     } else {
-        for i in output {
-            print!("{}", (i & 0xff) as u8 as char)
+        parse(source.as_str(), args.is_present("extensions"))
+    };
+    let warnings = lint::check(&source, args.is_present("extensions"));
+    if !lint::report(&source, &warnings, &Levels::from_args(&args)) {
+        process::exit(1);
+    }
+    // the instructions of an optimized program are not where they were written
+    let locations = match args.is_present("optimize") {
+        true => vec![],
+        false => locations(&source, args.is_present("extensions")),
+    };
+    // only the 64 bit interpreter has the other engines, reports and traces
+    if let Some(bits) = args.value_of("int-width").filter(|&bits| bits != "64") {
+        let flags = [
+            "sandbox",
+            "keep-going",
+            "engine",
+            "report",
+            "histogram",
+            "loops",
+            "profile",
+            "flamegraph",
+            "snapshot-every",
+            "trace-out",
+            "chrome-trace",
+        ];
+        if let Some(flag) = flags.iter().find(|&&flag| args.is_present(flag)) {
+            eprintln!("error: --{} cannot be used with --int-width {}", flag, bits);
+            process::exit(1);
+        }
+    }
+    if args.is_present("optimize") {
+        // the optimizer folds constants in 64 bits
+        if args.value_of("int-width").is_some_and(|bits| bits != "64") {
+            eprintln!("error: -O only optimizes programs with --int-width 64");
+            process::exit(1);
+        }
+        tokens = optimize(&unroll(&tokens));
+    }
+    let mut host = Host::new();
+    if let Some(names) = args.values_of("env") {
+        host.env_names = names.map(String::from).collect();
+    }
+    for fd in args.values_of("fd").into_iter().flatten() {
+        let (fd, path) = parse_fd(fd).expect("validated by clap");
+        host.descriptors.insert(fd, Descriptor::file(path));
+    }
+    #[cfg(feature = "net")]
+    for fd in args.values_of("listen").into_iter().flatten() {
+        let (fd, address) = parse_fd(fd).expect("validated by clap");
+        let socket = bottom::net::Socket::bind(address).expect("could not listen on socket");
+        host.descriptors.insert(fd, Descriptor::Socket(socket));
+    }
+    if let Some(log) = args.value_of("record") {
+        host.session = Session::record(log).expect("could not create session log");
+    }
+    if let Some(log) = args.value_of("replay") {
+        host.session = Session::replay(log).expect("could not read session log");
+    }
+    let unstack = initial_unstack(&args);
+    let mut limits = Limits::default();
+    if let Some(timeout) = args.value_of("timeout") {
+        limits.timeout = Some(parse_duration(timeout).expect("validated by clap"));
+    }
+    if let Some(interval) = args.value_of("timeout-check-interval") {
+        limits.check_interval = interval.parse().expect("validated by clap");
+    }
+    if let Some(cells) = args.value_of("max-memory") {
+        limits.max_memory = Some(cells.parse().expect("validated by clap"));
+    }
+    let sandbox = args.is_present("sandbox");
+    let list = Template::parse("list").expect("list is a preset");
+    let output = Output::from_args(&args, list).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
+    let reports = Reports::from_args(&args, &source, args.is_present("extensions"));
+    let overflow = match args.value_of("overflow") {
+        Some("checked") => Overflow::Checked,
+        _ => Overflow::Wrap,
+    };
+    let semantics = match args.is_present("spec") {
+        true => Semantics {
+            overflow,
+            ..Semantics::spec()
+        },
+        false => Semantics {
+            overflow,
+            ..division_semantics(&args)
+        },
+    };
+    let engine = args.value_of("engine").unwrap_or("interpreter");
+    if engine != "interpreter" && semantics != Semantics::default() {
+        eprintln!(
+            "error: --engine {} only runs programs with the default semantics",
+            engine
+        );
+        process::exit(1);
+    }
+    let mode = match (sandbox, args.is_present("keep-going"), engine) {
+        (true, _, _) => Mode::Sandbox,
+        (false, true, _) => Mode::KeepGoing,
+        (false, false, "closures") => Mode::Closures,
+        (false, false, "native") => Mode::Native,
+        (false, false, _) => Mode::Normal,
+    };
+    match args.value_of("int-width") {
+        Some("32") => run_cells::<i32>(
+            tokens, unstack, host, &limits, semantics, &locations, &output,
+        ),
+        Some("128") => run_cells::<i128>(
+            tokens, unstack, host, &limits, semantics, &locations, &output,
+        ),
+        _ => run_program(
+            tokens, unstack, host, &limits, semantics, &locations, mode, &output, &reports,
+        ),
+    }
+}
+
+/// the unstack a program starts with, from `--input`, `--init` and `--push`
+fn initial_unstack(args: &ArgMatches) -> Unstack {
+    let mut unstack = Unstack::new();
+    if let Some(mode) = args.value_of("input") {
+        let mode = match mode {
+            "bytes" => InputMode::Bytes,
+            "lines" => InputMode::Lines,
+            "ints" => InputMode::Ints,
+            _ => unreachable!(),
+        };
+        unstack.extend(read_input(mode, io::stdin()).unwrap_or_else(|err| {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        }));
+    }
+    let init = args.value_of("init").into_iter();
+    let pushes = args.values_of("push").into_iter().flatten();
+    for values in init.chain(pushes) {
+        unstack.extend(parse_values(values).expect("validated by clap"));
+    }
+    unstack
+}
+
+/// the status in a report and the exit code for a program failing with `err`
+fn runtime_failure(err: &RuntimeError) -> (&'static str, i32) {
+    match err {
+        RuntimeError::Timeout { .. } => ("timeout", 124),
+        RuntimeError::Deadlock { .. } => ("deadlock", 1),
+        RuntimeError::OutOfMemory { .. } => ("out-of-memory", 1),
+        RuntimeError::MemoryOutOfBounds { .. } => ("memory-out-of-bounds", 1),
+        RuntimeError::DivisionByZero { .. } => ("division-by-zero", 1),
+        RuntimeError::JumpOutOfRange { .. } => ("jump-out-of-range", 1),
+        RuntimeError::AssertionFailed { .. } => ("assertion-failed", 1),
+        RuntimeError::Underflow { .. } => ("underflow", 1),
+        RuntimeError::Overflow { .. } => ("overflow", 1),
+        RuntimeError::Failed { .. } => ("failed", 1),
+    }
+}
+
+/// the line and column of every instruction of `source`
+fn locations(source: &str, extensions: bool) -> Vec<(usize, usize)> {
+    parse_spanned(source, extensions)
+        .into_iter()
+        .map(|(_, span)| position(source, span.start))
+        .collect()
+}
+
+/// the message for `err`, with the line and column of its instruction if they are known
+fn located(err: &RuntimeError, locations: &[(usize, usize)]) -> String {
+    match locations.get(err.instruction_pointer()) {
+        Some((line, column)) => format!("{} (line {}, column {})", err, line, column),
+        None => err.to_string(),
+    }
+}
+
+/// how [`run_program`] runs a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Normal,
+    /// within the limits of a [`Sandbox`], see `--sandbox`
+    Sandbox,
+    /// skipping the instructions that fail, see `--keep-going`
+    KeepGoing,
+    /// as a closure for every instruction, see `--engine closures`
+    Closures,
+    /// as machine code, see `--engine native`
+    Native,
+}
+
+/// run `tokens` and print the unstack it leaves, exiting with an error if it fails
+///
+/// `locations` has the line and column of every instruction, or nothing if they moved
+#[allow(clippy::too_many_arguments)]
+fn run_program(
+    tokens: Vec<Operations>,
+    unstack: Unstack,
+    mut host: Host,
+    limits: &Limits,
+    semantics: Semantics,
+    locations: &[(usize, usize)],
+    mode: Mode,
+    output: &Output,
+    reports: &Reports,
+) {
+    let (mut observers, stats) = match reports.observer() {
+        Some((observer, stats)) => (vec![observer], stats),
+        None => (vec![], Default::default()),
+    };
+    observers.extend(reports.tracers(&tokens, &unstack));
+    let start = Instant::now();
+    // the errors of the instructions skipped in `Mode::KeepGoing`
+    let mut skipped = vec![];
+    let exit = if mode == Mode::Sandbox {
+        let mut sandbox = Sandbox::default();
+        if let Some(timeout) = limits.timeout {
+            sandbox.timeout = timeout;
+        }
+        if let Some(max_values) = limits.max_values {
+            sandbox.max_values = max_values;
         }
-        println!();
+        if let Some(max_memory) = limits.max_memory {
+            sandbox.max_memory = max_memory;
+        }
+        sandbox.semantics = semantics;
+        // failures are reported as errors below
+        panic::set_hook(Box::new(|_| {}));
+        match sandbox.run_observed(tokens, unstack, observers) {
+            Ok(values) => Exit::Finished(values),
+            Err(failure) => {
+                let (status, code) = match &failure {
+                    Failure::Runtime(err) => runtime_failure(err),
+                    Failure::OutOfFuel { .. } => ("out-of-fuel", 124),
+                    Failure::Panicked(_) => ("panicked", 1),
+                };
+                let message = match &failure {
+                    Failure::Runtime(err) => located(err, locations),
+                    failure => failure.to_string(),
+                };
+                Exit::Failed {
+                    status,
+                    message,
+                    code,
+                }
+            }
+        }
+    } else if mode == Mode::Closures || mode == Mode::Native {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| match mode {
+            Mode::Closures => run_closures(&tokens, unstack, &mut host, limits),
+            _ => run_native(&tokens, unstack, &mut host, limits),
+        }));
+        flush(&mut host);
+        finished(result, locations)
+    } else {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut interpreter = Interpreter::new(tokens, unstack, &mut host);
+            interpreter.set_semantics(semantics);
+            interpreter.set_keep_going(mode == Mode::KeepGoing);
+            if let Some((every, _)) = reports.snapshots {
+                interpreter.set_representation(Representation::Persistent);
+                interpreter.set_snapshots(every, SNAPSHOTS);
+            }
+            for observer in observers {
+                interpreter.observe(observer);
+            }
+            let result = interpreter.run(limits).map(|_| interpreter.output());
+            skipped = interpreter.skipped().to_vec();
+            reports.write_snapshots(interpreter.snapshots());
+            result
+        }));
+        flush(&mut host);
+        finished(result, locations)
+    };
+    reports.write(&stats.borrow(), start.elapsed(), &exit);
+    for err in &skipped {
+        eprintln!("skipped: {}", located(err, locations));
+    }
+    match exit {
+        Exit::Finished(values) => output.write(&values),
+        Exit::Failed {
+            status,
+            message,
+            code,
+        } => {
+            if status != "panicked" || mode == Mode::Sandbox {
+                eprintln!("error: {}", message);
+            }
+            process::exit(code);
+        }
+    }
+    if !skipped.is_empty() {
+        match skipped.len() {
+            1 => eprintln!("error: skipped 1 failing instruction"),
+            count => eprintln!("error: skipped {} failing instructions", count),
+        }
+        process::exit(1);
+    }
+}
+
+/// run `tokens` with values of the [`Cell`] `C` and print the unstack it leaves, see
+/// `--int-width`
+///
+/// the initial values wrap around to fit in the cells
+fn run_cells<C: Cell>(
+    tokens: Vec<Operations>,
+    unstack: Unstack,
+    mut host: Host,
+    limits: &Limits,
+    semantics: Semantics,
+    locations: &[(usize, usize)],
+    output: &Output,
+) {
+    let unstack: Unstack<C> = Vec::from(unstack).into_iter().map(C::from_i64).collect();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut interpreter = Interpreter::with_cells(tokens, unstack, &mut host);
+        interpreter.set_semantics(semantics);
+        interpreter.run(limits).map(|_| interpreter.output())
+    }));
+    flush(&mut host);
+    match result {
+        Ok(Ok(values)) => output.write(&values),
+        Ok(Err(err)) => {
+            eprintln!("error: {}", located(&err, locations));
+            process::exit(runtime_failure(&err).1);
+        }
+        // the panic hook already printed the message
+        Err(_) => process::exit(101),
+    }
+}
+
+/// flush what the program wrote, a reader that went away early like `head` is no error
+fn flush(host: &mut Host) {
+    match host.flush() {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => {
+            eprintln!("error: could not flush file descriptors: {}", err);
+            process::exit(1);
+        }
+        _ => {}
+    }
+}
+
+/// how a program run by the interpreter or as machine code ended
+fn finished(
+    result: thread::Result<Result<Vec<i64>, RuntimeError>>,
+    locations: &[(usize, usize)],
+) -> Exit {
+    match result {
+        Ok(Ok(values)) => Exit::Finished(values),
+        Ok(Err(err)) => {
+            let (status, code) = runtime_failure(&err);
+            Exit::Failed {
+                status,
+                message: located(&err, locations),
+                code,
+            }
+        }
+        // the panic hook already printed the message
+        Err(payload) => Exit::Failed {
+            status: "panicked",
+            message: panic_message(payload),
+            code: 101,
+        },
+    }
+}
+
+/// run `tokens` compiled to closures, see `--engine closures`
+///
+/// programs that cannot be compiled run with the interpreter, so the engine never decides
+/// whether a program runs
+fn run_closures(
+    tokens: &[Operations],
+    unstack: Unstack,
+    host: &mut Host,
+    limits: &Limits,
+) -> Result<Vec<i64>, RuntimeError> {
+    match bottom::closures::compile(tokens) {
+        Some(closures) => bottom::closures::run(&closures, unstack, host, limits),
+        None => interpret(tokens.to_vec(), unstack, host, limits),
+    }
+}
+
+/// run `tokens` assembled into machine code, see `--engine native`
+///
+/// like [`run_closures`], programs that cannot be assembled run with the interpreter
+#[cfg(all(feature = "native", target_arch = "x86_64"))]
+fn run_native(
+    tokens: &[Operations],
+    unstack: Unstack,
+    host: &mut Host,
+    limits: &Limits,
+) -> Result<Vec<i64>, RuntimeError> {
+    match bottom::native::compile(tokens) {
+        Some(native) => bottom::native::run(&native, unstack, host, limits),
+        None => interpret(tokens.to_vec(), unstack, host, limits),
+    }
+}
+
+#[cfg(not(all(feature = "native", target_arch = "x86_64")))]
+fn run_native(
+    _: &[Operations],
+    _: Unstack,
+    _: &mut Host,
+    _: &Limits,
+) -> Result<Vec<i64>, RuntimeError> {
+    eprintln!("error: --engine native needs the native feature on x86-64");
+    process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bottom::ext::Host;
+    use bottom::unstack::Unstack;
+    use bottom::{parse, Limits};
+
+    use crate::{parse_duration, parse_fd, parse_values, read_input, run_closures, InputMode};
+
+    #[test]
+    fn test_read_input() {
+        assert_eq!(
+            read_input(InputMode::Bytes, &b"hi\n"[..]),
+            Ok(vec![104, 105, 10])
+        );
+        assert_eq!(
+            read_input(InputMode::Lines, &b"3\n\n-42\n 7 \n"[..]),
+            Ok(vec![3, -42, 7])
+        );
+        assert_eq!(
+            read_input(InputMode::Ints, &b"1 2\t3\n4"[..]),
+            Ok(vec![1, 2, 3, 4])
+        );
+        assert_eq!(
+            read_input(InputMode::Lines, &b"1\n\nx\n"[..]),
+            Err("line 3 of stdin, 'x', is not an integer".to_string())
+        );
+        assert!(read_input(InputMode::Ints, &b"1 abc"[..]).is_err());
+    }
+
+    #[test]
+    fn test_run_closures() {
+        let run = |source| {
+            run_closures(
+                &parse(source, false),
+                Unstack::new(),
+                &mut Host::new(),
+                &Limits::default(),
+            )
+        };
+        assert_eq!(run("🥺6 🥺7 💓0"), Ok(vec![42]));
+        // the bytecode has no forward 🫂 or 🫂 before the start, the interpreter runs them
+        assert_eq!(run("🥺2 🫂0 🥺3 🥺4"), Ok(vec![4]));
+        assert_eq!(run("🥺12 🫂4"), Ok(vec![]));
+    }
+
+    #[test]
+    #[cfg(all(feature = "native", target_arch = "x86_64"))]
+    fn test_run_native() {
+        use crate::run_native;
+
+        let run = |source| {
+            run_native(
+                &parse(source, false),
+                Unstack::new(),
+                &mut Host::new(),
+                &Limits::default(),
+            )
+        };
+        assert_eq!(run("🥺6 🥺7 💓0"), Ok(vec![42]));
+        assert_eq!(run("🥺2 🫂0 🥺3 🥺4"), Ok(vec![4]));
+        assert_eq!(run("🥺12 🫂4"), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(parse_values("3,42"), Ok(vec![3, 42]));
+        assert_eq!(parse_values(" -1 , 2,"), Ok(vec![-1, 2]));
+        assert!(parse_values("3,four").is_err());
+    }
+
+    #[test]
+    fn test_parse_fd() {
+        assert_eq!(parse_fd("1=out.bin"), Ok((1, "out.bin")));
+        assert_eq!(parse_fd("3=a=b"), Ok((3, "a=b")));
+        assert!(parse_fd("out.bin").is_err());
+        assert!(parse_fd("x=out.bin").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(parse_duration("5 parsecs").is_err());
+        assert!(parse_duration("99999999999999999999h").is_err());
     }
 }
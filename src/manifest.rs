@@ -0,0 +1,284 @@
+/*!
+ * # Manifest
+ *
+ * a `bottom.toml` says how to run the project in its directory, so `bottom run` anywhere inside
+ * the project needs no flags:
+ *
+ * ```toml
+ * entry = "main.🥺"
+ * extensions = true
+ * # "list" prints the unstack as numbers, "ascii" as characters like -a, anything else is a
+ * # template like --format
+ * output = "ascii"
+ *
+ * [limits]
+ * timeout = "5s"
+ * check-interval = 1024
+ * max-values = 1000000
+ * max-memory = 65536
+ *
+ * [deps]
+ * fib = "../fib"
+ * ```
+ *
+ * every key is optional, paths are relative to the directory of the manifest. the packages in
+ * `[deps]` are described in [`deps`](crate::deps)
+ */
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use bottom::ext::Host;
+use bottom::{parse, Limits, Semantics};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::deps::{self, Dependency};
+use crate::include::{vendor_dir, Expander};
+use crate::json::Json;
+use crate::output::{self, Output, Template};
+use crate::report::{self, Reports};
+use crate::source::read_source;
+use crate::{initial_unstack, locations, parse_duration, parse_values, run_program, toml, Mode};
+
+/// the name of the manifest file
+pub const FILENAME: &str = "bottom.toml";
+
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    /// the directory the manifest is in
+    pub root: PathBuf,
+    /// the program `bottom run` runs
+    pub entry: PathBuf,
+    pub extensions: bool,
+    /// how the unstack left by the program is printed
+    pub output: Template,
+    pub limits: Limits,
+    pub deps: Vec<Dependency>,
+}
+
+/// complain about keys of `table` not in `known`, which are most likely typos
+fn check_keys(table: &Json, known: &[&str], name: &str) -> Result<(), String> {
+    let Json::Object(members) = table else {
+        return Err(format!("{} is not a table", name));
+    };
+    match members
+        .iter()
+        .find(|(key, _)| !known.contains(&key.as_str()))
+    {
+        Some((key, _)) => Err(format!("unknown key '{}' in {}", key, name)),
+        None => Ok(()),
+    }
+}
+
+fn string<'a>(table: &'a Json, key: &str) -> Result<Option<&'a str>, String> {
+    table
+        .get(key)
+        .map(|value| value.as_str().ok_or(format!("'{}' must be a string", key)))
+        .transpose()
+}
+
+fn count(table: &Json, key: &str) -> Result<Option<usize>, String> {
+    table
+        .get(key)
+        .map(|value| {
+            value
+                .as_i64()
+                .and_then(|count| usize::try_from(count).ok())
+                .ok_or(format!("'{}' must be a positive integer", key))
+        })
+        .transpose()
+}
+
+impl Manifest {
+    /// read the manifest `source` of the project in `root`
+    pub fn parse(source: &str, root: &Path) -> Result<Self, String> {
+        let document = toml::parse(source)?;
+        check_keys(
+            &document,
+            &["entry", "extensions", "output", "limits", "deps"],
+            FILENAME,
+        )?;
+        let extensions = match document.get("extensions") {
+            Some(value) => value.as_bool().ok_or("'extensions' must be a boolean")?,
+            None => false,
+        };
+        let output = Template::parse(string(&document, "output")?.unwrap_or("list"))
+            .map_err(|err| format!("invalid output: {}", err))?;
+
+        let mut limits = Limits::default();
+        if let Some(table) = document.get("limits") {
+            check_keys(
+                table,
+                &["timeout", "check-interval", "max-values", "max-memory"],
+                "[limits]",
+            )?;
+            if let Some(timeout) = string(table, "timeout")? {
+                limits.timeout = Some(parse_duration(timeout)?);
+            }
+            if let Some(interval) = count(table, "check-interval")? {
+                limits.check_interval = interval;
+            }
+            limits.max_values = count(table, "max-values")?;
+            limits.max_memory = count(table, "max-memory")?;
+        }
+
+        let deps = match document.get("deps") {
+            Some(Json::Object(deps)) => deps
+                .iter()
+                .map(|(name, value)| Dependency::parse(name, value))
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err("[deps] is not a table".to_string()),
+            None => vec![],
+        };
+
+        Ok(Manifest {
+            root: root.to_path_buf(),
+            entry: root.join(string(&document, "entry")?.unwrap_or("main.🥺")),
+            extensions,
+            output,
+            limits,
+            deps,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = read_source(path).map_err(|err| err.to_string())?;
+        let root = path.parent().unwrap_or(Path::new("."));
+        Manifest::parse(&source, root)
+    }
+}
+
+/// the manifest in `dir` or the closest directory above it that has one
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(FILENAME))
+        .find(|path| path.is_file())
+}
+
+/// the manifest at `path`, or the closest one if no path is given, exiting if there is none
+pub fn open(path: Option<&str>) -> Manifest {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let dir = env::current_dir().expect("could not get the current directory");
+            find(&dir).unwrap_or_else(|| {
+                eprintln!(
+                    "error: no {} in {} or any directory above it",
+                    FILENAME,
+                    dir.display()
+                );
+                process::exit(1);
+            })
+        }
+    };
+    Manifest::load(&path).unwrap_or_else(|err| {
+        eprintln!("error: could not load {}: {}", path.display(), err);
+        process::exit(1);
+    })
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("run")
+        .about("run the project described by the closest bottom.toml")
+        .arg(
+            Arg::with_name("manifest-path")
+                .long("manifest-path")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("use this manifest instead of looking for bottom.toml"),
+        )
+        .args(&output::args())
+        .args(&report::args())
+        .arg(
+            Arg::with_name("input")
+                .long("input")
+                .takes_value(true)
+                .value_name("MODE")
+                .possible_values(&["bytes", "lines", "ints"])
+                .help("read stdin before running and push its content onto the unstack"),
+        )
+        .arg(
+            Arg::with_name("init")
+                .long("init")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .value_name("VALUES")
+                .validator(|values| parse_values(&values).map(|_| ()))
+                .help("comma separated values pushed onto the unstack before running"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .value_name("DURATION")
+                .validator(|duration| parse_duration(&duration).map(|_| ()))
+                .help("abort after running for DURATION instead of the manifest's timeout"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let mut manifest = open(args.value_of("manifest-path"));
+    if let Some(timeout) = args.value_of("timeout") {
+        manifest.limits.timeout = Some(parse_duration(timeout).expect("validated by clap"));
+    }
+    if let Err(err) = deps::check(&manifest, false) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+
+    let mut expander = Expander::new(Some(vendor_dir(&manifest.root)), manifest.extensions);
+    let source = expander.expand_file(&manifest.entry).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
+    let tokens = parse(&source, manifest.extensions);
+    let output = Output::from_args(args, manifest.output).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
+    run_program(
+        tokens,
+        initial_unstack(args),
+        Host::new(),
+        &manifest.limits,
+        Semantics::default(),
+        &locations(&source, manifest.extensions),
+        Mode::Normal,
+        &output,
+        &Reports::from_args(args, &source, manifest.extensions),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use crate::manifest::Manifest;
+    use crate::output::Template;
+
+    #[test]
+    fn test_parse() {
+        let root = Path::new("project");
+        let manifest = Manifest::parse(
+            "entry = \"src/hi.🥺\"\nextensions = true\noutput = \"ascii\"\n\n[limits]\ntimeout = \"250ms\"\n",
+            root,
+        )
+        .unwrap();
+        assert_eq!(manifest.entry, root.join("src/hi.🥺"));
+        assert!(manifest.extensions);
+        assert_eq!(manifest.output, Template::parse("ascii").unwrap());
+        assert_eq!(manifest.limits.timeout, Some(Duration::from_millis(250)));
+        assert_eq!(manifest.limits.max_values, None);
+
+        let defaults = Manifest::parse("", root).unwrap();
+        assert_eq!(defaults.entry, root.join("main.🥺"));
+        assert_eq!(defaults.output, Template::parse("list").unwrap());
+        assert!(Manifest::parse("entyr = \"main.🥺\"", root).is_err());
+        assert!(Manifest::parse("[limits]\nmax-values = -1", root).is_err());
+        let limited = Manifest::parse("[limits]\nmax-memory = 64", root).unwrap();
+        assert_eq!(limited.limits.max_memory, Some(64));
+        assert!(Manifest::parse("output = \"hex\"", root).is_err());
+    }
+}
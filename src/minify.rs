@@ -0,0 +1,155 @@
+/*!
+ * # Minifier
+ *
+ * `bottom minify` writes the shortest source for a program it can find: comments are dropped,
+ * constant operations are merged by the [optimizer](crate::optimize) and every operation is
+ * separated by a single space with its argument in the shortest representation
+ *
+ * the result is run next to the original and only written if both behave the same
+ */
+
+use std::process;
+use std::time::Duration;
+
+use bottom::{parse, Limits, Operations, RuntimeError};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::batch::without_panic_messages;
+use crate::golden::run_captured;
+use crate::optimize::optimize;
+use crate::source::{read_source, write_output};
+
+/// the shortest source for exactly these operations
+pub fn minify(operations: &[Operations]) -> String {
+    let mut source = String::new();
+    for (index, operation) in operations.iter().enumerate() {
+        if index > 0 {
+            source.push(' ');
+        }
+        source += operation.emoji();
+        // an empty argument is 0, but only if something follows it
+        if operation.argument() != 0 || index + 1 == operations.len() {
+            source += &operation.argument().to_string();
+        }
+    }
+    source
+}
+
+/// why the minified program could not be checked or was rejected
+#[derive(Debug)]
+pub enum Check {
+    /// both programs behaved the same
+    Same,
+    /// the original did not finish within the time limit
+    Unchecked,
+    /// the programs behaved differently
+    Different { original: String, minified: String },
+}
+
+/// run both programs with an empty unstack and stdin and compare what they print
+pub fn check(original: &str, minified: &str, extensions: bool, limits: &Limits) -> Check {
+    let (before, after) = without_panic_messages(|| {
+        (
+            run_captured(original, extensions, limits),
+            run_captured(minified, extensions, limits),
+        )
+    });
+    if let Some(RuntimeError::Timeout { .. }) = before.error {
+        return Check::Unchecked;
+    }
+    if before.same_behavior(&after) {
+        Check::Same
+    } else {
+        Check::Different {
+            original: before.output,
+            minified: after.output,
+        }
+    }
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("minify")
+        .about("write the shortest equivalent source of a 🥺 program")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .value_name("FILE")
+                .help("🥺 program to read"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("recognize the extension operations"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("write the program to FILE instead of stdout"),
+        )
+        .arg(
+            Arg::with_name("compress")
+                .long("compress")
+                .help("write the program gzip compressed, e.g. to a .🥺.gz file"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let file = args.value_of("file").expect("required by clap");
+    let source = read_source(file).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", file, err);
+        process::exit(1);
+    });
+    let extensions = args.is_present("extensions");
+    let minified = minify(&optimize(&parse(&source, extensions)));
+
+    let limits = Limits {
+        timeout: Some(Duration::from_secs(1)),
+        ..Limits::default()
+    };
+    match check(&source, &minified, extensions, &limits) {
+        Check::Same => {}
+        Check::Unchecked => {
+            eprintln!(
+                "warning: the program did not finish within a second, its behavior was not checked"
+            )
+        }
+        Check::Different { original, minified } => {
+            eprintln!("error: the minified program behaves differently, this is a bug");
+            eprintln!("original:\n{}minified:\n{}", original, minified);
+            process::exit(1);
+        }
+    }
+    let output = args.value_of("output");
+    let compress = args.is_present("compress");
+    // a newline keeps the terminal tidy, but does not belong in a file
+    if output.is_none() && !compress {
+        println!("{}", minified);
+    } else {
+        write_output(output, &minified, compress);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::minify::{check, minify, Check};
+    use crate::optimize::optimize;
+    use bottom::{parse, Limits};
+
+    #[test]
+    fn test_minify() {
+        let source = "🥺🥺🥺 the answer\n🥺7 💓0 ✨🥺 🥺0 🫂1 ✨0";
+        let minified = minify(&optimize(&parse(source, false)));
+        assert_eq!(minified, "🥺14 ✨1");
+        assert!(matches!(
+            check(source, &minified, false, &Limits::default()),
+            Check::Same
+        ));
+        assert!(matches!(
+            check(source, "🥺13 ✨1", false, &Limits::default()),
+            Check::Different { .. }
+        ));
+    }
+}
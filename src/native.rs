@@ -0,0 +1,742 @@
+/*!
+ * # Native code
+ *
+ * an experimental engine for `--engine native`, assembling the [bytecode](crate::bytecode) of a
+ * program into x86-64 machine code with dynasm-rs and jumping into it
+ *
+ * the unstack is a buffer of values in the order they were pushed, the machine code keeps where
+ * it starts in `rbx`, the end of the values in `r12` and how far they may grow in `r13`. `r14`
+ * points at the [`Context`] the code calls back into to grow the buffer, read, write, check the
+ * timeout and fail, and `r15` counts down the jumps back until the next time the timeout is
+ * checked. operations that are neither arithmetic nor jumps, 📖 and 📝 aside, are executed by
+ * the interpreter, which copies the unstack there and back
+ *
+ * failures are the errors of the interpreter, values wrap around when they overflow and the
+ * semantics are the default ones, like for the bytecode
+ */
+
+use core::mem::{self, offset_of};
+use core::ptr;
+use std::time::{Duration, Instant};
+
+use dynasmrt::x64::Assembler;
+use dynasmrt::{dynasm, AssemblyOffset, DynamicLabel, DynasmApi, DynasmLabelApi, ExecutableBuffer};
+
+use crate::bytecode::{self, Instruction};
+use crate::interpreter::{execute, Io};
+use crate::unstack::Unstack;
+use crate::{Limits, Operations, RuntimeError, Semantics};
+
+/// the biggest ✨ copying values in machine code, bigger ones go through the interpreter
+const MAX_DUP: usize = 16;
+
+/// a program assembled into machine code, see [`compile`]
+pub struct Native {
+    code: ExecutableBuffer,
+    entry: AssemblyOffset,
+    program: bytecode::Program,
+}
+
+/// what the machine code calls back into, it reads and writes the first fields itself
+#[repr(C)]
+struct Context<'a> {
+    /// where the values start, `rbx`
+    base: *mut i64,
+    /// past the last value pushed, `r12`
+    top: *mut i64,
+    /// how far the values may go before the buffer grows, `r13`
+    end: *mut i64,
+    /// jumps back until the timeout is checked, `r15`
+    countdown: u64,
+    values: Vec<i64>,
+    program: &'a bytecode::Program,
+    host: &'a mut dyn Io,
+    limits: &'a Limits,
+    deadline: Option<(Instant, Duration)>,
+    error: Option<RuntimeError>,
+}
+
+const BASE: i32 = offset_of!(Context<'static>, base) as i32;
+const TOP: i32 = offset_of!(Context<'static>, top) as i32;
+const END: i32 = offset_of!(Context<'static>, end) as i32;
+const COUNTDOWN: i32 = offset_of!(Context<'static>, countdown) as i32;
+
+/// a function the machine code calls with the index of its instruction, returning 0 to go on or
+/// 1 if it failed
+type Callback = extern "sysv64" fn(&mut Context, usize) -> u64;
+
+impl Context<'_> {
+    /// take the values back from the machine code
+    fn load(&mut self) {
+        // SAFETY: the machine code only moves `top` within the buffer and writes every value below
+        unsafe {
+            let len = self.top.offset_from(self.base) as usize;
+            self.values.set_len(len);
+        }
+    }
+
+    /// hand the values to the machine code
+    fn store(&mut self) {
+        let len = self.values.len();
+        let room = match self.limits.max_values {
+            Some(limit) => self.values.capacity().min(limit).max(len),
+            None => self.values.capacity(),
+        };
+        self.base = self.values.as_mut_ptr();
+        // SAFETY: both are within the buffer or one past it
+        unsafe {
+            self.top = self.base.add(len);
+            self.end = self.base.add(room);
+        }
+    }
+
+    /// fail if the instruction at `index` pushed more values than the limit allows
+    fn check(&mut self, index: usize) -> u64 {
+        match self.limits.max_values {
+            Some(limit) if self.values.len() > limit => {
+                self.error = Some(RuntimeError::OutOfMemory {
+                    limit,
+                    instruction_pointer: self.program.origins[index],
+                });
+                1
+            }
+            _ => {
+                self.store();
+                0
+            }
+        }
+    }
+
+    /// the operation the instruction at `index` was compiled from
+    fn operation(&self, index: usize) -> Operations {
+        self.program.operations[index]
+    }
+}
+
+/// make room for the values the instruction at `index` pushes
+extern "sysv64" fn reserve(context: &mut Context, index: usize) -> u64 {
+    context.load();
+    let needed = match context.program.code[index] {
+        Instruction::Single(Operations::Dup(count)) => count,
+        _ => 1,
+    };
+    if let Some(limit) = context.limits.max_values {
+        if context.values.len() + needed > limit {
+            context.error = Some(RuntimeError::OutOfMemory {
+                limit,
+                instruction_pointer: context.program.origins[index],
+            });
+            return 1;
+        }
+    }
+    context.values.reserve(needed);
+    context.store();
+    0
+}
+
+extern "sysv64" fn read(context: &mut Context, index: usize) -> u64 {
+    context.load();
+    let operation = context.operation(index);
+    let Operations::Read(fd) = operation else {
+        unreachable!("only called for 📖");
+    };
+    match context.host.read(fd) {
+        Ok(value) => {
+            context.values.push(value);
+            context.check(index)
+        }
+        Err(err) => {
+            let unstack = Unstack::from(mem::take(&mut context.values));
+            let origin = context.program.origins[index];
+            context.error = Some(RuntimeError::failed(operation, origin, &unstack, err));
+            1
+        }
+    }
+}
+
+extern "sysv64" fn write(context: &mut Context, index: usize) -> u64 {
+    context.load();
+    let operation = context.operation(index);
+    let Operations::Write(fd) = operation else {
+        unreachable!("only called for 📝");
+    };
+    let value = context.values.pop().expect("the machine code checked");
+    match context.host.write(fd, value) {
+        Ok(()) => {
+            context.store();
+            0
+        }
+        Err(err) => {
+            let unstack = Unstack::from(mem::take(&mut context.values));
+            let origin = context.program.origins[index];
+            context.error = Some(RuntimeError::failed(operation, origin, &unstack, err));
+            1
+        }
+    }
+}
+
+/// execute the operation at `index` in the interpreter
+extern "sysv64" fn single(context: &mut Context, index: usize) -> u64 {
+    context.load();
+    let mut unstack = Unstack::from(mem::take(&mut context.values));
+    let mut instruction_pointer = context.program.origins[index];
+    let result = execute(
+        context.operation(index),
+        &mut unstack,
+        &mut instruction_pointer,
+        &mut *context.host,
+        &Semantics::default(),
+    );
+    context.values = Vec::from(unstack);
+    match result {
+        Ok(()) => context.check(index),
+        Err(err) => {
+            context.error = Some(err);
+            1
+        }
+    }
+}
+
+/// fail like the instruction at `index` does in the bytecode
+extern "sysv64" fn fail(context: &mut Context, index: usize) -> u64 {
+    context.load();
+    let mut unstack = Unstack::from(mem::take(&mut context.values));
+    let (operation, mut instruction_pointer) = match context.program.code[index] {
+        Instruction::Multiply(value) => {
+            // the 💓 after the 🥺 is what fails
+            unstack.push(value);
+            (Operations::Heart(0), context.program.origins[index] + 1)
+        }
+        _ => (context.operation(index), context.program.origins[index]),
+    };
+    let result = execute(
+        operation,
+        &mut unstack,
+        &mut instruction_pointer,
+        &mut *context.host,
+        &Semantics::default(),
+    );
+    context.error = Some(result.expect_err("only called for instructions that fail"));
+    1
+}
+
+/// check the timeout before jumping back to the instruction at `index`
+extern "sysv64" fn tick(context: &mut Context, index: usize) -> u64 {
+    context.countdown = context.limits.check_interval.max(1) as u64;
+    match context.deadline {
+        Some((deadline, timeout)) if Instant::now() >= deadline => {
+            context.error = Some(RuntimeError::Timeout {
+                timeout,
+                instruction_pointer: context.program.origins[index],
+            });
+            1
+        }
+        _ => 0,
+    }
+}
+
+/// call `callback` with the index `index`, leaving the machine code if it failed
+macro_rules! call {
+    ($ops:ident, $callback:expr, $index:expr) => {
+        dynasm!($ops
+            ; .arch x64
+            ; mov [r14 + TOP], r12
+            ; mov rdi, r14
+            ; mov rsi, QWORD $index as i64
+            ; mov rax, QWORD $callback as Callback as usize as i64
+            ; call rax
+            ; mov rbx, [r14 + BASE]
+            ; mov r12, [r14 + TOP]
+            ; mov r13, [r14 + END]
+            ; mov r15, [r14 + COUNTDOWN]
+            ; test rax, rax
+            ; jnz ->failed
+        );
+    };
+}
+
+/// assembles the instructions of a program one after the other
+struct Emitter {
+    ops: Assembler,
+    /// the label of every instruction, and of the end after the last one
+    labels: Vec<DynamicLabel>,
+    /// the instructions that fail somewhere in their code, and where it jumps to
+    failures: Vec<(usize, DynamicLabel)>,
+    /// the jumps back, which check the timeout every so often, to the instruction at an index
+    ticks: Vec<(usize, DynamicLabel)>,
+}
+
+impl Emitter {
+    /// the label the instruction at `index` jumps to if it fails
+    fn failure(&mut self, index: usize) -> DynamicLabel {
+        let label = self.ops.new_dynamic_label();
+        self.failures.push((index, label));
+        label
+    }
+
+    /// fail unless there are `needed` values
+    fn need(&mut self, needed: usize, index: usize) {
+        if needed == 0 {
+            return;
+        }
+        let failure = self.failure(index);
+        let bytes = (needed as i64).checked_mul(8).unwrap_or(i64::MAX);
+        let ops = &mut self.ops;
+        dynasm!(ops
+            ; .arch x64
+            ; mov rax, r12
+            ; sub rax, rbx
+            ; mov rcx, QWORD bytes
+            ; cmp rax, rcx
+            ; jb =>failure
+        );
+    }
+
+    /// make room for one more value
+    fn room(&mut self, index: usize) {
+        let ops = &mut self.ops;
+        dynasm!(ops
+            ; .arch x64
+            ; cmp r12, r13
+            ; jb >room
+        );
+        call!(ops, reserve, index);
+        dynasm!(ops
+            ; .arch x64
+            ; room:
+        );
+    }
+
+    /// jump to the instruction at `target` from the one at `index` if the flags say so
+    fn jump(&mut self, target: usize, index: usize, condition: Condition) {
+        let label = self.labels[target];
+        let ops = &mut self.ops;
+        if target > index {
+            match condition {
+                Condition::Always => dynasm!(ops ; .arch x64 ; jmp =>label),
+                Condition::Zero => dynasm!(ops ; .arch x64 ; je =>label),
+                Condition::NotZero => dynasm!(ops ; .arch x64 ; jne =>label),
+            }
+            return;
+        }
+        // jumping back counts down until the timeout is checked
+        let tick = ops.new_dynamic_label();
+        self.ticks.push((target, tick));
+        match condition {
+            Condition::Always => {}
+            Condition::Zero => dynasm!(ops ; .arch x64 ; jne >next),
+            Condition::NotZero => dynasm!(ops ; .arch x64 ; je >next),
+        }
+        dynasm!(ops
+            ; .arch x64
+            ; dec r15
+            ; jz =>tick
+            ; jmp =>label
+            ; next:
+        );
+    }
+
+    fn instruction(&mut self, index: usize, instruction: Instruction) {
+        match instruction {
+            Instruction::Single(operation) => self.operation(index, operation),
+            Instruction::Hug(target) | Instruction::Skip(target) => {
+                self.need(1, index);
+                let ops = &mut self.ops;
+                dynasm!(ops
+                    ; .arch x64
+                    ; sub r12, 8
+                    ; cmp QWORD [r12], 0
+                );
+                let condition = match instruction {
+                    Instruction::Hug(_) => Condition::NotZero,
+                    _ => Condition::Zero,
+                };
+                self.jump(target, index, condition);
+            }
+            Instruction::Multiply(value) => {
+                let failure = self.failure(index);
+                let ops = &mut self.ops;
+                dynasm!(ops
+                    ; .arch x64
+                    ; cmp r12, rbx
+                    ; je =>failure
+                    ; mov rax, QWORD value
+                    ; imul rax, QWORD [r12 - 8]
+                    ; mov [r12 - 8], rax
+                );
+            }
+            Instruction::Jump(target) => self.jump(target, index, Condition::Always),
+            Instruction::DupHug(target) => {
+                self.need(1, index);
+                let ops = &mut self.ops;
+                dynasm!(ops
+                    ; .arch x64
+                    ; cmp QWORD [r12 - 8], 0
+                );
+                self.jump(target, index, Condition::NotZero);
+            }
+        }
+    }
+
+    fn operation(&mut self, index: usize, operation: Operations) {
+        match operation {
+            Operations::Push(value) => {
+                self.room(index);
+                let ops = &mut self.ops;
+                dynasm!(ops
+                    ; .arch x64
+                    ; mov rax, QWORD value
+                    ; mov [r12], rax
+                    ; add r12, 8
+                );
+            }
+            Operations::Pop(0) => {
+                let failure = self.failure(index);
+                let ops = &mut self.ops;
+                dynasm!(ops ; .arch x64 ; jmp =>failure);
+            }
+            Operations::Pop(-1) => {
+                // i64::MIN / -1 would trap
+                self.need(1, index);
+                let ops = &mut self.ops;
+                dynasm!(ops ; .arch x64 ; neg QWORD [r12 - 8]);
+            }
+            Operations::Pop(divisor) => {
+                self.need(1, index);
+                let ops = &mut self.ops;
+                dynasm!(ops
+                    ; .arch x64
+                    ; mov rax, [r12 - 8]
+                    ; cqo
+                    ; mov rcx, QWORD divisor
+                    ; idiv rcx
+                    ; mov [r12 - 8], rax
+                );
+            }
+            Operations::Dup(0) | Operations::Nop(_) | Operations::Break(_) => {}
+            Operations::Swap(steps) => {
+                self.need(steps + 1, index);
+                let offset = (steps as i64).wrapping_mul(-8);
+                let ops = &mut self.ops;
+                dynasm!(ops
+                    ; .arch x64
+                    ; mov rcx, QWORD offset
+                    ; mov rax, [r12 - 8]
+                    ; mov rdx, [r12 + rcx - 8]
+                    ; mov [r12 - 8], rdx
+                    ; mov [r12 + rcx - 8], rax
+                );
+            }
+            Operations::Heart(discarded) => {
+                self.need(discarded + 2, index);
+                let popped = (discarded as i64 + 1).wrapping_mul(8);
+                let ops = &mut self.ops;
+                dynasm!(ops
+                    ; .arch x64
+                    ; mov rax, [r12 - 8]
+                    ; imul rax, QWORD [r12 - 16]
+                    ; mov rcx, QWORD popped
+                    ; sub r12, rcx
+                    ; mov [r12 - 8], rax
+                );
+            }
+            Operations::Dup(count) if count <= MAX_DUP => {
+                self.need(count, index);
+                let bytes = count as i32 * 8;
+                let ops = &mut self.ops;
+                dynasm!(ops
+                    ; .arch x64
+                    ; lea rax, [r12 + bytes]
+                    ; cmp rax, r13
+                    ; jbe >room
+                );
+                call!(ops, reserve, index);
+                dynasm!(ops ; .arch x64 ; room:);
+                // every value moves twice as far from the first one, so the last one goes first
+                for value in (0..count as i32).rev() {
+                    let from = value * 8 - bytes;
+                    let to = value * 16 - bytes;
+                    dynasm!(ops
+                        ; .arch x64
+                        ; mov rax, [r12 + from]
+                        ; mov [r12 + to], rax
+                        ; mov [r12 + to + 8], rax
+                    );
+                }
+                dynasm!(ops ; .arch x64 ; add r12, bytes);
+            }
+            Operations::Clear(0) => {
+                let ops = &mut self.ops;
+                dynasm!(ops ; .arch x64 ; mov r12, rbx);
+            }
+            Operations::Clear(count) => {
+                self.need(count, index);
+                let bytes = (count as i64).wrapping_mul(8);
+                let ops = &mut self.ops;
+                dynasm!(ops
+                    ; .arch x64
+                    ; mov rcx, QWORD bytes
+                    ; sub r12, rcx
+                );
+            }
+            Operations::Depth(_) => {
+                self.room(index);
+                let ops = &mut self.ops;
+                dynasm!(ops
+                    ; .arch x64
+                    ; mov rax, r12
+                    ; sub rax, rbx
+                    ; sar rax, 3
+                    ; mov [r12], rax
+                    ; add r12, 8
+                );
+            }
+            Operations::Read(_) => {
+                let ops = &mut self.ops;
+                call!(ops, read, index);
+            }
+            Operations::Write(_) => {
+                self.need(1, index);
+                let ops = &mut self.ops;
+                call!(ops, write, index);
+            }
+            _ => {
+                let ops = &mut self.ops;
+                call!(ops, single, index);
+            }
+        }
+    }
+}
+
+/// what a conditional jump looks at, the flags of comparing a value with 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Condition {
+    Always,
+    Zero,
+    NotZero,
+}
+
+/// assemble `tokens` into machine code
+///
+/// `None` for the same programs the [bytecode](crate::bytecode::compile) cannot run
+pub fn compile(tokens: &[Operations]) -> Option<Native> {
+    let program = bytecode::compile(tokens, true)?;
+    let mut ops = Assembler::new().expect("could not map memory for machine code");
+    let labels = (0..=program.code.len())
+        .map(|_| ops.new_dynamic_label())
+        .collect();
+    let mut emitter = Emitter {
+        ops,
+        labels,
+        failures: vec![],
+        ticks: vec![],
+    };
+
+    let entry = emitter.ops.offset();
+    let ops = &mut emitter.ops;
+    dynasm!(ops
+        ; .arch x64
+        ; push rbx
+        ; push r12
+        ; push r13
+        ; push r14
+        ; push r15
+        ; mov r14, rdi
+        ; mov rbx, [r14 + BASE]
+        ; mov r12, [r14 + TOP]
+        ; mov r13, [r14 + END]
+        ; mov r15, [r14 + COUNTDOWN]
+    );
+    for (index, &instruction) in program.code.iter().enumerate() {
+        let label = emitter.labels[index];
+        let ops = &mut emitter.ops;
+        dynasm!(ops ; .arch x64 ; =>label);
+        emitter.instruction(index, instruction);
+    }
+    let end = emitter.labels[program.code.len()];
+    let ops = &mut emitter.ops;
+    dynasm!(ops
+        ; .arch x64
+        ; =>end
+        ; mov [r14 + TOP], r12
+        ; xor eax, eax
+        ; ->exit:
+        ; pop r15
+        ; pop r14
+        ; pop r13
+        ; pop r12
+        ; pop rbx
+        ; ret
+        ; ->failed:
+        ; mov eax, 1
+        ; jmp ->exit
+    );
+    for (index, label) in mem::take(&mut emitter.failures) {
+        let ops = &mut emitter.ops;
+        dynasm!(ops ; .arch x64 ; =>label);
+        call!(ops, fail, index);
+        dynasm!(ops ; .arch x64 ; jmp ->failed);
+    }
+    for (target, label) in mem::take(&mut emitter.ticks) {
+        let resume = emitter.labels[target];
+        let ops = &mut emitter.ops;
+        dynasm!(ops ; .arch x64 ; =>label);
+        call!(ops, tick, target);
+        dynasm!(ops ; .arch x64 ; jmp =>resume);
+    }
+    let code = emitter
+        .ops
+        .finalize()
+        .unwrap_or_else(|_| panic!("nothing else uses the machine code"));
+    Some(Native {
+        code,
+        entry,
+        program,
+    })
+}
+
+/// run a program assembled by [`compile`] starting with the given unstack and return what is
+/// left on it, bottom first
+pub fn run(
+    native: &Native,
+    unstack: Unstack,
+    host: &mut dyn Io,
+    limits: &Limits,
+) -> Result<Vec<i64>, RuntimeError> {
+    let deadline = limits
+        .timeout
+        .map(|timeout| (Instant::now() + timeout, timeout));
+    let mut context = Context {
+        base: ptr::null_mut(),
+        top: ptr::null_mut(),
+        end: ptr::null_mut(),
+        countdown: match deadline {
+            Some(_) => limits.check_interval.max(1) as u64,
+            None => u64::MAX,
+        },
+        values: Vec::from(unstack),
+        program: &native.program,
+        host,
+        limits,
+        deadline,
+        error: None,
+    };
+    context.store();
+    // SAFETY: the code at `entry` is a function taking the context in the System V ABI
+    let entry: extern "sysv64" fn(&mut Context) -> u64 =
+        unsafe { mem::transmute(native.code.ptr(native.entry)) };
+    match entry(&mut context) {
+        0 => {
+            context.load();
+            let mut values = mem::take(&mut context.values);
+            values.reverse();
+            Ok(values)
+        }
+        _ => Err(context.error.take().expect("failing sets the error")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use proptest::prelude::*;
+
+    use crate::ext::Host;
+    use crate::interpreter::interpret;
+    use crate::native::{compile, run};
+    use crate::unstack::Unstack;
+    use crate::{bytecode, parse, Limits, Operations, RuntimeError};
+
+    #[test]
+    fn test_native() {
+        let programs = [
+            // halves 2^62 until it is 0
+            "🥺4611686018427387904 💖2 🥺1 💓0 ✨1 🫂6",
+            "🥺3 🥺4 💓0 🥺9 🥺2 ✨2 👉👈3 🥺5 🥺6 🥺7 💓1 📚0 🧹1 🥺0 🦘2 🥺1 🥺1",
+            "🥺72 📝1 🥺105 📝1 📖0 📖0 🥺1 ✨3 ✨6 ✨12 ✨24 🪞3 🔄4",
+            "🥺1 🥺2 💓1",
+            "🥺1 🥺0 🦘1 💖0",
+            "🥺1 🥺1 🦘1 💖0",
+            "✨1 🫂1",
+            "💓0",
+        ];
+        for source in programs {
+            let tokens = parse(source, true);
+            let limits = Limits::default();
+            let mut host = Host::captured(b"x");
+            let expected = interpret(tokens.clone(), Unstack::new(), &mut host, &limits);
+            let expected_output = host.captured_output(1);
+            let mut host = Host::captured(b"x");
+            let native = compile(&tokens).unwrap();
+            let result = run(&native, Unstack::new(), &mut host, &limits);
+            assert_eq!(result, expected, "{}", source);
+            assert_eq!(host.captured_output(1), expected_output, "{}", source);
+        }
+
+        // the values are limited even though the buffer grows in steps
+        let limits = Limits {
+            max_values: Some(100),
+            ..Limits::default()
+        };
+        let native = compile(&parse("🥺1 🥺1 🫂4", false)).unwrap();
+        let result = run(
+            &native,
+            Unstack::from(vec![5, 6]),
+            &mut Host::new(),
+            &limits,
+        );
+        assert!(matches!(
+            result,
+            Err(RuntimeError::OutOfMemory { limit: 100, .. })
+        ));
+        assert!(compile(&parse("🥺1 📥0", true)).is_none());
+    }
+
+    /// an operation the machine code or the interpreter behind it executes
+    fn operation() -> impl Strategy<Value = Operations> {
+        let argument = 0..6usize;
+        prop_oneof![
+            (0..100i64).prop_map(Operations::Push),
+            (-3..4i64).prop_map(Operations::Pop),
+            argument.clone().prop_map(Operations::Swap),
+            argument.clone().prop_map(Operations::Heart),
+            (0..20usize).prop_map(Operations::Dup),
+            argument.clone().prop_map(Operations::Hug),
+            argument.clone().prop_map(Operations::Skip),
+            argument.clone().prop_map(Operations::Clear),
+            argument.clone().prop_map(Operations::Reverse),
+            Just(Operations::Depth(0)),
+            Just(Operations::Read(0)),
+            Just(Operations::Write(1)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_native_agrees(
+            program in proptest::collection::vec(operation(), 0..16),
+            values in proptest::collection::vec(-100..100i64, 0..6),
+        ) {
+            let Some(native) = compile(&program) else {
+                return Ok(());
+            };
+            let limits = Limits {
+                max_values: Some(1000),
+                timeout: Some(Duration::from_millis(20)),
+                ..Limits::default()
+            };
+            let compiled = bytecode::compile(&program, true).unwrap();
+            let mut host = Host::captured(b"ab");
+            let expected = bytecode::run(&compiled, Unstack::from(values.clone()), &mut host, &limits);
+            // loops may run until the timeout
+            prop_assume!(!matches!(expected, Err(RuntimeError::Timeout { .. })));
+            let output = host.captured_output(1);
+            let mut host = Host::captured(b"ab");
+            let result = run(&native, Unstack::from(values), &mut host, &limits);
+            prop_assume!(!matches!(result, Err(RuntimeError::Timeout { .. })));
+            prop_assert_eq!(result, expected);
+            prop_assert_eq!(host.captured_output(1), output);
+        }
+    }
+}
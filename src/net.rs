@@ -0,0 +1,98 @@
+/*!
+ * # Networking
+ *
+ * experimental TCP sockets for the 📞 extension, only built with the `net` feature
+ *
+ * a socket is a file descriptor that listens on a port, 📞 accepts a connection on it
+ * and 📖 / 📝 then read from and write to that connection
+ */
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+#[derive(Debug)]
+pub struct Socket {
+    listener: TcpListener,
+    /// the last accepted connection
+    connection: Option<TcpStream>,
+}
+
+impl Socket {
+    /// listen on `address`, which is either a port on localhost or a full `host:port`
+    pub fn bind(address: &str) -> io::Result<Self> {
+        let listener = if address.contains(':') {
+            TcpListener::bind(address)?
+        } else {
+            let port: u16 = address
+                .parse()
+                .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "invalid port"))?;
+            TcpListener::bind(("127.0.0.1", port))?
+        };
+        Ok(Socket {
+            listener,
+            connection: None,
+        })
+    }
+
+    /// wait for a new connection, replacing the previous one
+    pub fn accept(&mut self) -> io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        self.connection = Some(stream);
+        Ok(())
+    }
+
+    fn connection(&mut self) -> io::Result<&mut TcpStream> {
+        self.connection
+            .as_mut()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotConnected, "no connection accepted"))
+    }
+
+    /// read a single byte, `None` once the peer closed the connection
+    pub fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut buf = [0];
+        let read = self.connection()?.read(&mut buf)?;
+        Ok(if read == 0 { None } else { Some(buf[0]) })
+    }
+
+    pub fn write_byte(&mut self, byte: u8) -> io::Result<()> {
+        self.connection()?.write_all(&[byte])
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.connection {
+            Some(connection) => connection.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+
+    use crate::net::Socket;
+
+    #[test]
+    fn test_echo() {
+        let mut socket = Socket::bind("127.0.0.1:0").unwrap();
+        let address = socket.listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"hi").unwrap();
+            stream.shutdown(std::net::Shutdown::Write).unwrap();
+            let mut echoed = vec![];
+            stream.read_to_end(&mut echoed).unwrap();
+            echoed
+        });
+
+        assert!(socket.read_byte().is_err());
+        socket.accept().unwrap();
+        while let Some(byte) = socket.read_byte().unwrap() {
+            socket.write_byte(byte).unwrap();
+        }
+        socket.connection = None;
+        assert_eq!(client.join().unwrap(), b"hi");
+    }
+}
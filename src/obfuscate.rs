@@ -0,0 +1,256 @@
+/*!
+ * # Obfuscator
+ *
+ * `bottom obfuscate` rewrites constants into chains of 💓 and 💖, sprinkles in operations that
+ * do nothing and mixes numeric and length encoded arguments
+ *
+ * like the [minifier](crate::minify) the result is run next to the original and only written if both behave the same
+ */
+
+use std::fs;
+use std::process;
+use std::time::Duration;
+
+use bottom::{hug_target, parse, skip_target, Limits, Operations};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::canonical::{write_operation, ArgumentStyle};
+use crate::minify::{check, Check};
+use crate::rng::Rng;
+use crate::source::read_source;
+
+/// the largest argument written as the length of a word
+const MAX_LENGTH_ARGUMENT: i64 = 8;
+
+/// operations computing `value` with 💓 and 💖, leaving nothing else on the unstack
+fn arithmetic_chain(value: i64, rng: &mut Rng) -> Option<Vec<Operations>> {
+    let divisor = rng.range(2, 9);
+    let remainder = rng.range(0, divisor - 1);
+    let product = value.checked_mul(divisor)?.checked_add(remainder)?;
+    // a random factor of the product, or 1 if it is prime
+    let factors: Vec<_> = (2..=product.min(1000))
+        .filter(|factor| factor * factor <= product && product % factor == 0)
+        .collect();
+    let factor = if factors.is_empty() {
+        1
+    } else {
+        factors[rng.below(factors.len() as u64) as usize]
+    };
+    Some(vec![
+        Operations::Push(factor),
+        Operations::Push(product / factor),
+        Operations::Heart(0),
+        Operations::Pop(divisor),
+    ])
+}
+
+/// operations that do nothing, inserted before the instruction at `instruction_pointer` of the result
+fn noise(instruction_pointer: usize, rng: &mut Rng) -> Vec<Operations> {
+    if rng.chance(50) {
+        vec![Operations::Dup(0)]
+    } else {
+        // a loop that is never taken, jumping somewhere believable
+        let hug_at = instruction_pointer + 1;
+        vec![
+            Operations::Push(0),
+            Operations::Hug(rng.range(1, hug_at as i64 + 2) as usize),
+        ]
+    }
+}
+
+/// rewrite `operations` into an equivalent but harder to read program
+///
+/// `noise` is the chance in percent of inserting operations that do nothing before each operation.
+/// programs with a 🫂 that jumps outside of the program, a 🛫 or a 🧵, which spawns a number of
+/// the instructions after it, are returned unchanged
+pub fn obfuscate(operations: &[Operations], noise_percent: u64, rng: &mut Rng) -> Vec<Operations> {
+    let mut targets = vec![];
+    for (instruction_pointer, operation) in operations.iter().enumerate() {
+        match *operation {
+            Operations::Hug(n) => match hug_target(instruction_pointer, n) {
+                Some(target) if target <= instruction_pointer + 1 => targets.push(target),
+                _ => return operations.to_vec(),
+            },
+            Operations::Skip(n) => {
+                targets.push(skip_target(instruction_pointer, n).min(operations.len()))
+            }
+            Operations::Call(_) | Operations::Return(_) | Operations::Spawn(_) => {
+                return operations.to_vec()
+            }
+            _ => {}
+        }
+    }
+
+    let mut obfuscated = vec![];
+    let mut new_index = vec![0; operations.len() + 1];
+    // where the 🫂 and 🦘 of the original ended up
+    let mut jumps = vec![];
+    for (instruction_pointer, operation) in operations.iter().enumerate() {
+        new_index[instruction_pointer] = obfuscated.len();
+        if rng.chance(noise_percent) {
+            obfuscated.extend(noise(obfuscated.len(), rng));
+        }
+        match operation {
+            Operations::Push(value) if rng.chance(50) => match arithmetic_chain(*value, rng) {
+                Some(chain) => obfuscated.extend(chain),
+                None => obfuscated.push(*operation),
+            },
+            Operations::Hug(_) | Operations::Skip(_) => {
+                jumps.push(obfuscated.len());
+                obfuscated.push(*operation);
+            }
+            _ => obfuscated.push(*operation),
+        }
+    }
+    new_index[operations.len()] = obfuscated.len();
+    for (jump, target) in jumps.into_iter().zip(targets) {
+        let target = new_index[target];
+        obfuscated[jump] = match obfuscated[jump] {
+            Operations::Hug(_) => Operations::Hug(jump + 2 - target),
+            _ => Operations::Skip(target - jump - 1),
+        };
+    }
+    obfuscated
+}
+
+/// write `operations` with a random mix of numeric and length encoded arguments and separators
+pub fn write_obfuscated(operations: &[Operations], rng: &mut Rng) -> String {
+    const SEPARATORS: &[&str] = &[" ", "\n", "  ", " 🥹 ", "\t", "~"];
+    let mut source = String::new();
+    for operation in operations {
+        let style = if operation.argument() <= MAX_LENGTH_ARGUMENT && rng.chance(50) {
+            ArgumentStyle::Length
+        } else {
+            ArgumentStyle::Numeric
+        };
+        source += &write_operation(operation, style);
+        source += SEPARATORS[rng.below(SEPARATORS.len() as u64) as usize];
+    }
+    source
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("obfuscate")
+        .about("rewrite a 🥺 program into an equivalent but harder to read one")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .value_name("FILE")
+                .help("🥺 program to read"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .value_name("N")
+                .validator(|seed| seed.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                .help("seed for the random choices, defaults to the current time"),
+        )
+        .arg(
+            Arg::with_name("noise")
+                .long("noise")
+                .takes_value(true)
+                .value_name("PERCENT")
+                .default_value("30")
+                .validator(|noise| match noise.parse::<u64>() {
+                    Ok(0..=100) => Ok(()),
+                    _ => Err("expected a percentage from 0 to 100".to_string()),
+                })
+                .help("chance of inserting operations that do nothing before each operation"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("recognize the extension operations"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("write the program to FILE instead of stdout"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let file = args.value_of("file").expect("required by clap");
+    let source = read_source(file).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", file, err);
+        process::exit(1);
+    });
+    let extensions = args.is_present("extensions");
+    let seed = match args.value_of("seed") {
+        Some(seed) => seed.parse().expect("validated by clap"),
+        None => Rng::seed_from_time(),
+    };
+    let noise = args
+        .value_of("noise")
+        .and_then(|noise| noise.parse().ok())
+        .expect("validated by clap");
+    let mut rng = Rng::new(seed);
+    let obfuscated = obfuscate(&parse(&source, extensions), noise, &mut rng);
+    let obfuscated = write_obfuscated(&obfuscated, &mut rng);
+
+    let limits = Limits {
+        timeout: Some(Duration::from_secs(1)),
+        ..Limits::default()
+    };
+    match check(&source, &obfuscated, extensions, &limits) {
+        Check::Same => {}
+        Check::Unchecked => {
+            eprintln!(
+                "warning: the program did not finish within a second, its behavior was not checked"
+            )
+        }
+        Check::Different { original, minified } => {
+            eprintln!(
+                "error: the obfuscated program (seed {}) behaves differently, this is a bug",
+                seed
+            );
+            eprintln!("original:\n{}obfuscated:\n{}", original, minified);
+            process::exit(1);
+        }
+    }
+    match args.value_of("output") {
+        Some(path) => fs::write(path, obfuscated).unwrap_or_else(|err| {
+            eprintln!("error: could not write {}: {}", path, err);
+            process::exit(1);
+        }),
+        None => println!("{}", obfuscated),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::minify::{check, Check};
+    use crate::obfuscate::{obfuscate, write_obfuscated};
+    use crate::rng::Rng;
+    use bottom::{parse, Limits};
+
+    #[test]
+    fn test_obfuscate() {
+        // halves 16 until it reaches 0, leaving 16 8 4 2 1 0 behind
+        let source = "🥺16 ✨1 💖2 ✨1 🫂5\n";
+        let limits = Limits {
+            timeout: Some(Duration::from_secs(1)),
+            ..Limits::default()
+        };
+        for seed in 0..20 {
+            let mut rng = Rng::new(seed);
+            let operations = obfuscate(&parse(source, false), 50, &mut rng);
+            let obfuscated = write_obfuscated(&operations, &mut rng);
+            assert!(
+                matches!(check(source, &obfuscated, false, &limits), Check::Same),
+                "seed {} gave {}",
+                seed,
+                obfuscated
+            );
+        }
+
+        let spawn = parse("🥺3 🧵0 🥺6 🥺7 💓0 🪢0", true);
+        assert_eq!(obfuscate(&spawn, 100, &mut Rng::new(1)), spawn);
+    }
+}
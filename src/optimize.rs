@@ -0,0 +1,544 @@
+/*!
+ * # Optimizer
+ *
+ * peephole rewrites that make a program shorter without changing what it does. operations that
+ * only work on constants pushed right before them, copied by ✨ or moved by 👉👈 included, are
+ * replaced by pushing their result, and operations that do nothing like ✨0 and 💤 are removed
+ *
+ * merging operations moves instructions, so every 🫂 and 🦘 is tracked by the instruction it jumps
+ * to and gets a new argument afterwards. operations that something jumps to are never merged into
+ * the operation before them
+ *
+ * [`unroll`] replaces loops that provably run a few times by copies of their body, which the
+ * peephole rewrites can then often merge into a handful of constants
+ */
+
+use std::collections::{HashMap, HashSet};
+
+use bottom::{hug_target, skip_target, Operations};
+
+/// an operation, with the instruction it jumps to if it is a 🫂 or 🦘
+type Located = (Operations, Option<usize>);
+
+/// the 🫂 or 🦘 `operation` at `instruction_pointer` with the argument that jumps to `target`
+fn relocated(operation: Operations, instruction_pointer: usize, target: usize) -> Operations {
+    match operation {
+        Operations::Skip(_) => Operations::Skip(target - instruction_pointer - 1),
+        _ => Operations::Hug(instruction_pointer + 2 - target),
+    }
+}
+
+/// `value / divisor`, if rounding toward zero and toward negative infinity agree on it so the
+/// result is the same with `--floor-div`
+fn quotient(value: i64, divisor: i64) -> Option<i64> {
+    let quotient = value.checked_div(divisor)?;
+    (value % divisor == 0 || (value < 0) == (divisor < 0)).then_some(quotient)
+}
+
+/// the most operations folded into pushes at once
+const MAX_FOLDED: usize = 64;
+
+/// how many operations at the start of `operations` only work on values pushed among them, and
+/// the values they leave behind to push instead
+///
+/// `None` if that does not make the program shorter. operations that would fail are never
+/// folded, so they still fail when the program runs
+fn fold(operations: impl Iterator<Item = Operations>) -> Option<(usize, Vec<i64>)> {
+    let mut values: Vec<i64> = vec![];
+    let mut best = None;
+    let mut saved = 0;
+    for (index, operation) in operations.take(MAX_FOLDED).enumerate() {
+        let depth = values.len();
+        match operation {
+            Operations::Push(value) => values.push(value),
+            Operations::Pop(divisor) if depth >= 1 => {
+                let Some(value) = quotient(values[depth - 1], divisor) else {
+                    break;
+                };
+                values[depth - 1] = value;
+            }
+            Operations::Heart(count) if depth >= count + 2 => {
+                let Some(product) = values[depth - 1].checked_mul(values[depth - 2]) else {
+                    break;
+                };
+                values.truncate(depth - count - 2);
+                values.push(product);
+            }
+            Operations::Dup(count) if depth >= count => {
+                let top = values.split_off(depth - count);
+                values.extend(top.iter().flat_map(|&value| [value, value]));
+            }
+            // the top value trades places with the one `steps` below it
+            Operations::Swap(steps) if depth > steps => values.swap(depth - 1, depth - 1 - steps),
+            _ => break,
+        }
+        // the shortest run that saves the most, the rest is left as it was written
+        let consumed = index + 1;
+        if consumed > values.len() + saved {
+            saved = consumed - values.len();
+            best = Some((consumed, values.clone()));
+        }
+    }
+    best
+}
+
+/// one round of rewrites, returns the new operations and for every old instruction the new one execution continues at
+fn peephole(operations: &[Located]) -> (Vec<Located>, Vec<usize>) {
+    let targets: HashSet<usize> = operations
+        .iter()
+        .filter_map(|(_, target)| *target)
+        .collect();
+    let mut rewritten: Vec<Located> = vec![];
+    let mut new_index = vec![0; operations.len() + 1];
+    let mut index = 0;
+    while index < operations.len() {
+        // the number of operations following `index` that can be merged with it
+        let mergeable = (1..)
+            .take_while(|offset| {
+                index + offset < operations.len() && !targets.contains(&(index + offset))
+            })
+            .count();
+        let window: Vec<_> = operations[index..=index + mergeable.min(1)]
+            .iter()
+            .map(|(operation, _)| *operation)
+            .collect();
+        let folded = fold(
+            operations[index..=index + mergeable]
+                .iter()
+                .map(|(op, _)| *op),
+        );
+        let (consumed, replacement) = match (folded, &window[..]) {
+            (Some((consumed, values)), _) => {
+                (consumed, values.into_iter().map(Operations::Push).collect())
+            }
+            (None, [Operations::Dup(0) | Operations::Nop(_), ..]) => (1, vec![]),
+            (None, [Operations::Push(0), Operations::Hug(_), ..]) => (2, vec![]),
+            (None, [Operations::Pop(a), Operations::Pop(b), ..]) if *a > 0 && *b > 0 => {
+                match a.checked_mul(*b) {
+                    Some(divisor) => (2, vec![Operations::Pop(divisor)]),
+                    None => (1, vec![window[0]]),
+                }
+            }
+            _ => (1, vec![window[0]]),
+        };
+        new_index[index..index + consumed].fill(rewritten.len());
+        match &replacement[..] {
+            [operation] if consumed == 1 => rewritten.push((*operation, operations[index].1)),
+            replacement => rewritten.extend(replacement.iter().map(|operation| (*operation, None))),
+        }
+        index += consumed;
+    }
+    new_index[operations.len()] = rewritten.len();
+    (rewritten, new_index)
+}
+
+/// the operations with the target of every 🫂 and 🦘, `None` if a 🫂 jumps outside of the
+/// program or there are routines, whose returns cannot be tracked, or 🧵, which spawns a number
+/// of the instructions after it that rewriting would change
+fn locate(operations: &[Operations]) -> Option<Vec<Located>> {
+    let mut located = vec![];
+    for (instruction_pointer, operation) in operations.iter().enumerate() {
+        let target = match *operation {
+            Operations::Hug(n) => match hug_target(instruction_pointer, n) {
+                Some(target) if target <= instruction_pointer + 1 => Some(target),
+                _ => return None,
+            },
+            // skipping past the end is the same as skipping to it
+            Operations::Skip(n) => Some(skip_target(instruction_pointer, n).min(operations.len())),
+            Operations::Call(_) | Operations::Return(_) | Operations::Spawn(_) => return None,
+            _ => None,
+        };
+        located.push((*operation, target));
+    }
+    Some(located)
+}
+
+/// rewrite `operations` into a shorter program that behaves the same
+///
+/// programs with a 🫂 that jumps outside of the program, a 🛫 or a 🧵 are returned unchanged
+pub fn optimize(operations: &[Operations]) -> Vec<Operations> {
+    let Some(mut located) = locate(operations) else {
+        return operations.to_vec();
+    };
+
+    loop {
+        let (rewritten, new_index) = peephole(&located);
+        let changed = rewritten.len() != located.len()
+            || rewritten
+                .iter()
+                .zip(&located)
+                .any(|(new, old)| new.0 != old.0);
+        located = rewritten
+            .into_iter()
+            .map(|(operation, target)| (operation, target.map(|target| new_index[target])))
+            .collect();
+        if !changed {
+            break;
+        }
+    }
+
+    located
+        .iter()
+        .enumerate()
+        .map(|(instruction_pointer, (operation, target))| match target {
+            Some(target) => relocated(*operation, instruction_pointer, *target),
+            None => *operation,
+        })
+        .collect()
+}
+
+/// the most iterations of a loop that is unrolled
+const MAX_ITERATIONS: usize = 32;
+/// the most operations a single loop is unrolled into
+const MAX_UNROLLED: usize = 256;
+/// the most operations run looking for loops to unroll
+const MAX_STEPS: usize = 100_000;
+
+/// whether the loop from `start` to the 🫂 at `end` is entered at most once and only at its
+/// start, and has no other jumps inside it
+fn unrollable(operations: &[Located], start: usize, end: usize) -> bool {
+    let jumps_inside = operations[start..end]
+        .iter()
+        .any(|(_, target)| target.is_some());
+    let entered_otherwise = operations
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index != end)
+        .filter_map(|(index, (_, target))| target.map(|target| (index, target)))
+        .any(|(index, target)| {
+            (start < target && target <= end) || (target <= start && index > end)
+        });
+    !jumps_inside && !entered_otherwise
+}
+
+/// the values on top of the unstack, `None` for the ones that are not known before running
+#[derive(Default)]
+struct Values(Vec<Option<i64>>);
+
+impl Values {
+    fn pop(&mut self) -> Option<i64> {
+        self.0.pop().flatten()
+    }
+
+    fn push(&mut self, value: Option<i64>) {
+        self.0.push(value);
+    }
+
+    /// run the straight line `operation`, `None` if it fails or its effect cannot be tracked
+    fn run(&mut self, operation: Operations) -> Option<()> {
+        match operation {
+            Operations::Push(value) => self.push(Some(value)),
+            Operations::Pop(divisor) => match self.pop() {
+                Some(value) => {
+                    value.checked_div(divisor)?;
+                    self.push(quotient(value, divisor));
+                }
+                None => self.push(None),
+            },
+            Operations::Heart(count) => {
+                let product = match (self.pop(), self.pop()) {
+                    (Some(a), Some(b)) => Some(a.checked_mul(b)?),
+                    _ => None,
+                };
+                for _ in 0..count {
+                    self.pop();
+                }
+                self.push(product);
+            }
+            Operations::Dup(count) => {
+                let top: Vec<_> = (0..count).map(|_| self.pop()).collect();
+                for value in top.into_iter().rev() {
+                    self.push(value);
+                    self.push(value);
+                }
+            }
+            // the values it moves go deeper than the ones tracked
+            Operations::Reverse(0) => return None,
+            operation => {
+                let (needed, left) = operation.stack_effect()?;
+                for _ in 0..needed {
+                    self.pop();
+                }
+                for _ in 0..left {
+                    self.push(None);
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+/// the number of iterations of every loop that is proven to run a few times, by its first and
+/// last instruction
+///
+/// the program is run from the start for as long as every 🫂 and 🦘 on the way has a known
+/// condition, a loop that is entered only once and ends with a 0 on the way is proven to always
+/// run that often.
+/// a failure on the way is no problem, the unrolled program fails at the same operation
+fn trip_counts(operations: &[Located]) -> HashMap<usize, (usize, usize)> {
+    let mut values = Values::default();
+    let mut rounds = HashMap::new();
+    let mut proven = HashMap::new();
+    let mut instruction_pointer = 0;
+    for _ in 0..MAX_STEPS {
+        let Some(&(operation, target)) = operations.get(instruction_pointer) else {
+            break;
+        };
+        let Some(target) = target else {
+            if matches!(operation, Operations::Spawn(_) | Operations::Join(_))
+                || values.run(operation).is_none()
+            {
+                break;
+            }
+            instruction_pointer += 1;
+            continue;
+        };
+        let Some(condition) = values.pop() else {
+            break;
+        };
+        if target <= instruction_pointer && unrollable(operations, target, instruction_pointer) {
+            let iterations = rounds.entry(instruction_pointer).or_insert(0);
+            *iterations += 1;
+            let length = instruction_pointer + 1 - target;
+            if condition == 0
+                && *iterations <= MAX_ITERATIONS
+                && *iterations * length <= MAX_UNROLLED
+            {
+                proven.insert(target, (instruction_pointer, *iterations));
+            }
+        }
+        let jumps = match operation {
+            Operations::Skip(_) => condition == 0,
+            _ => condition != 0,
+        };
+        instruction_pointer = if jumps {
+            target
+        } else {
+            instruction_pointer + 1
+        };
+    }
+    proven
+}
+
+/// replace loops that always run the same small number of times with that many copies of their
+/// body
+///
+/// the 🫂 of every copy only drops its condition, together with the operation that pushed it if
+/// that was a 🥺 or a ✨1
+pub fn unroll(operations: &[Operations]) -> Vec<Operations> {
+    let Some(located) = locate(operations) else {
+        return operations.to_vec();
+    };
+    let proven = trip_counts(&located);
+    let mut unrolled: Vec<Located> = vec![];
+    let mut new_index = vec![0; located.len() + 1];
+    let mut index = 0;
+    while index < located.len() {
+        new_index[index] = unrolled.len();
+        let Some(&(end, iterations)) = proven.get(&index) else {
+            unrolled.push(located[index]);
+            index += 1;
+            continue;
+        };
+        let body = &located[index..end];
+        // the condition is known, so a ✨1 before the 🫂 copies a value that exists
+        let pushes_condition = matches!(
+            body.last(),
+            Some((Operations::Push(_) | Operations::Dup(1), _))
+        );
+        for _ in 0..iterations {
+            match pushes_condition {
+                true => unrolled.extend(&body[..body.len() - 1]),
+                // a 🫂1 continues with the next operation either way
+                false => unrolled.extend(body.iter().chain([&(Operations::Hug(1), None)])),
+            }
+        }
+        index = end + 1;
+    }
+    new_index[located.len()] = unrolled.len();
+
+    unrolled
+        .iter()
+        .enumerate()
+        .map(|(instruction_pointer, (operation, target))| match target {
+            Some(target) => relocated(*operation, instruction_pointer, new_index[*target]),
+            None => *operation,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::equiv::{compare, inputs, Equivalence};
+    use crate::optimize::{optimize, unroll};
+    use crate::rng::Rng;
+    use bottom::Limits;
+    use bottom::Operations::{self, *};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_optimize() {
+        assert_eq!(
+            optimize(&[Push(6), Push(7), Heart(0), Pop(2)]),
+            vec![Push(21)]
+        );
+        assert_eq!(optimize(&[Pop(2), Pop(3), Dup(0)]), vec![Pop(6)]);
+        assert_eq!(optimize(&[Push(6), Dup(1), Heart(0)]), vec![Push(36)]);
+        assert_eq!(
+            optimize(&[Push(2), Push(7), Swap(1), Pop(2)]),
+            vec![Push(7), Push(1)]
+        );
+        // the 💓 needs a value from before the 🥺s, so only the ✨ is left out
+        assert_eq!(
+            optimize(&[Push(3), Dup(1), Heart(1)]),
+            vec![Push(3), Dup(1), Heart(1)]
+        );
+        assert_eq!(optimize(&[Push(1), Pop(0)]), vec![Push(1), Pop(0)]);
+        // -7 / 2 is -3 or -4 depending on --floor-div
+        assert_eq!(optimize(&[Push(7), Pop(-2)]), vec![Push(7), Pop(-2)]);
+        assert_eq!(optimize(&[Push(8), Pop(-2)]), vec![Push(-4)]);
+        assert_eq!(optimize(&[Push(0), Hug(1)]), vec![]);
+        assert_eq!(optimize(&[Push(0), Hug(5)]), vec![Push(0), Hug(5)]);
+        // the 🧵 spawns the next 3 instructions, which must stay 3
+        let spawn = [Push(3), Spawn(0), Push(6), Push(7), Heart(0), Join(0)];
+        assert_eq!(optimize(&spawn), spawn);
+        assert_eq!(unroll(&spawn), spawn);
+    }
+
+    #[test]
+    fn test_optimize_differential() {
+        let mut rng = Rng::new(643);
+        let inputs = inputs(20, &mut rng);
+        for _ in 0..200 {
+            let program: Vec<_> = (0..rng.below(12))
+                .map(|_| {
+                    let n = rng.below(4) as usize;
+                    match rng.below(5) {
+                        0 | 1 => Push(rng.range(-9, 9)),
+                        2 => Pop(rng.range(-3, 3)),
+                        3 => [Dup(n), Heart(n)][rng.below(2) as usize],
+                        _ => Swap(n),
+                    }
+                })
+                .collect();
+            let optimized = optimize(&program);
+            let compared = compare(&program, &optimized, &inputs, &Limits::default());
+            assert!(
+                matches!(compared, Equivalence::Same { .. }),
+                "{:?} optimized to {:?}: {:?}",
+                program,
+                optimized,
+                compared
+            );
+        }
+    }
+
+    #[test]
+    fn test_optimize_jumps() {
+        // the loop body starts at the 💖 so it must not be merged into the 🥺 before it
+        let program = [Push(1), Push(8), Pop(2), Dup(0), Dup(1), Hug(5)];
+        assert_eq!(
+            optimize(&program),
+            vec![Push(1), Push(8), Pop(2), Dup(1), Hug(4)]
+        );
+        // the 🦘 skips the 🥺 the pair is merged into
+        assert_eq!(
+            optimize(&[Dup(1), Skip(2), Push(6), Pop(2), Push(1)]),
+            vec![Dup(1), Skip(1), Push(3), Push(1)]
+        );
+        // jumping to a 💤 jumps to what comes after it
+        assert_eq!(
+            optimize(&[Dup(1), Nop(0), Pop(2), Dup(1), Hug(5), Nop(2)]),
+            vec![Dup(1), Pop(2), Dup(1), Hug(4)]
+        );
+    }
+
+    #[test]
+    fn test_unroll() {
+        // halves the 8 until it is 0, four times around
+        let program = [Push(8), Pop(2), Dup(1), Hug(4)];
+        assert_eq!(
+            unroll(&program),
+            vec![Push(8), Pop(2), Pop(2), Pop(2), Pop(2)]
+        );
+        assert_eq!(optimize(&unroll(&program)), vec![Push(0)]);
+
+        // the condition is computed, so only the 🫂 goes
+        let program = [Push(4), Pop(2), Dup(1), Push(1), Heart(0), Hug(6), Push(1)];
+        let body = [Pop(2), Dup(1), Push(1), Heart(0), Hug(1)];
+        assert_eq!(
+            unroll(&program),
+            [&[Push(4)][..], &body, &body, &body, &[Push(1)]].concat()
+        );
+
+        // the loop after it depends on the environment and still jumps to its own start
+        let program = [
+            Push(2),
+            Pop(2),
+            Dup(1),
+            Hug(4),
+            Env(0),
+            Pop(2),
+            Dup(1),
+            Hug(4),
+        ];
+        assert_eq!(
+            unroll(&program),
+            vec![Push(2), Pop(2), Pop(2), Env(0), Pop(2), Dup(1), Hug(4)]
+        );
+    }
+
+    #[test]
+    fn test_unroll_unknown() {
+        // the number of iterations depends on the input
+        let program = [Pop(2), Dup(1), Hug(4)];
+        assert_eq!(unroll(&program), program);
+        // and here on how often the outer loop ran
+        let program = [
+            Push(8),
+            Dup(1),
+            Pop(2),
+            Dup(1),
+            Hug(3),
+            Pop(2),
+            Dup(1),
+            Hug(7),
+        ];
+        assert_eq!(unroll(&program), program);
+        // too many iterations
+        let program = [Push(1 << 40), Pop(2), Dup(1), Hug(4)];
+        assert_eq!(unroll(&program), program);
+    }
+
+    /// a straight-line operation like the ones above, a loop running up to 7 times or a task
+    fn piece() -> impl Strategy<Value = Vec<Operations>> {
+        let n = 0..4usize;
+        prop_oneof![
+            2 => (-9..9i64).prop_map(|value| vec![Push(value)]),
+            1 => (-3..3i64).prop_map(|value| vec![Pop(value)]),
+            1 => n.clone().prop_map(|n| vec![Dup(n)]),
+            1 => n.clone().prop_map(|n| vec![Heart(n)]),
+            1 => n.prop_map(|n| vec![Swap(n)]),
+            1 => (0..100i64).prop_map(|times| vec![Push(times), Pop(2), Dup(1), Hug(4)]),
+            1 => Just(vec![Push(3), Spawn(0), Push(6), Push(7), Heart(0), Join(0)]),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_optimized_agrees(pieces in proptest::collection::vec(piece(), 0..8)) {
+            let program = pieces.concat();
+            let inputs = inputs(10, &mut Rng::new(677));
+            for optimized in [optimize(&program), optimize(&unroll(&program))] {
+                let compared = compare(&program, &optimized, &inputs, &Limits::default());
+                prop_assert!(
+                    matches!(compared, Equivalence::Same { .. }),
+                    "{:?} optimized to {:?}: {:?}",
+                    program,
+                    optimized,
+                    compared
+                );
+            }
+        }
+    }
+}
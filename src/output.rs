@@ -0,0 +1,352 @@
+/*!
+ * # Output
+ *
+ * how the unstack left by a program is printed, given as a template like
+ *
+ * ```text
+ * sum of {count} values: {values/x:, }
+ * ```
+ *
+ * `{values}` is replaced by the values, most recent first, and `{count}` by their number. the
+ * values can be followed by `/RADIX` to print them in binary (`b`), octal (`o`), hex (`x` or
+ * `X`), decimal (`d`) or as characters (`c`), and by `:SEPARATOR` for what goes between them,
+ * anything up to the closing brace. without one they are separated by `, `, characters by
+ * nothing. `{{` and `}}` are literal braces, `\n` and `\t` a newline and a tab
+ *
+ * the output modes are templates too, `list` is `[{values:, }]` and `ascii` is `{values/c}`
+ *
+ * instead of text, `--output bytes` writes the lowest byte of every value and `--output i64`
+ * every value as 8 little endian bytes, wrapping around wider ones, and `--out PATH` writes to a
+ * file instead of stdout
+ */
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+use bottom::cell::Cell;
+use clap::{Arg, ArgMatches};
+
+/// how a single value is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Binary,
+    Octal,
+    Decimal,
+    Hex,
+    UpperHex,
+    /// the lowest byte as a character
+    Char,
+}
+
+impl Radix {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "b" => Ok(Radix::Binary),
+            "o" => Ok(Radix::Octal),
+            "d" => Ok(Radix::Decimal),
+            "x" => Ok(Radix::Hex),
+            "X" => Ok(Radix::UpperHex),
+            "c" => Ok(Radix::Char),
+            _ => Err(format!(
+                "unknown radix '{}', expected b, o, d, x, X or c",
+                name
+            )),
+        }
+    }
+
+    fn write(self, out: &mut String, value: i128) {
+        // negative values keep their sign instead of showing their two's complement
+        let sign = if value < 0 { "-" } else { "" };
+        let magnitude = value.unsigned_abs();
+        let result = match self {
+            Radix::Binary => write!(out, "{}{:b}", sign, magnitude),
+            Radix::Octal => write!(out, "{}{:o}", sign, magnitude),
+            Radix::Decimal => write!(out, "{}", value),
+            Radix::Hex => write!(out, "{}{:x}", sign, magnitude),
+            Radix::UpperHex => write!(out, "{}{:X}", sign, magnitude),
+            Radix::Char => write!(out, "{}", (value & 0xff) as u8 as char),
+        };
+        result.expect("writing to a string cannot fail");
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Values { radix: Radix, separator: String },
+    Count,
+}
+
+/// a parsed output template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+/// the named templates, usable wherever a template is
+pub const PRESETS: &[(&str, &str)] = &[("list", "[{values:, }]"), ("ascii", "{values/c}")];
+
+/// `text` with `\n`, `\t` and `\\` replaced
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.clone().next()) {
+            ('\\', Some(escaped @ ('n' | 't' | '\\'))) => {
+                chars.next();
+                unescaped.push(match escaped {
+                    'n' => '\n',
+                    't' => '\t',
+                    _ => '\\',
+                });
+            }
+            _ => unescaped.push(ch),
+        }
+    }
+    unescaped
+}
+
+impl Template {
+    /// a preset by name or else a template
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let template = PRESETS
+            .iter()
+            .find(|(name, _)| *name == template)
+            .map_or(template, |(_, preset)| preset);
+
+        let mut pieces = vec![];
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(at) = rest.find(['{', '}']) {
+            text += &rest[..at];
+            let (brace, after) = rest[at..].split_at(1);
+            if let Some(after) = after.strip_prefix(brace) {
+                text += brace;
+                rest = after;
+                continue;
+            }
+            if brace == "}" {
+                return Err("unmatched '}', write '}}' for a literal brace".to_string());
+            }
+            let (placeholder, after) = after
+                .split_once('}')
+                .ok_or("unclosed '{', write '{{' for a literal brace")?;
+            if !text.is_empty() {
+                pieces.push(Piece::Text(unescape(&text)));
+                text.clear();
+            }
+            pieces.push(Template::placeholder(placeholder)?);
+            rest = after;
+        }
+        text += rest;
+        if !text.is_empty() {
+            pieces.push(Piece::Text(unescape(&text)));
+        }
+        // most likely a misspelled preset
+        if pieces.iter().all(|piece| matches!(piece, Piece::Text(_))) {
+            return Err(format!(
+                "'{}' has no {{values}} or {{count}} and is not one of the presets list or ascii",
+                template
+            ));
+        }
+        Ok(Template { pieces })
+    }
+
+    fn placeholder(placeholder: &str) -> Result<Piece, String> {
+        let (name, separator) = match placeholder.split_once(':') {
+            Some((name, separator)) => (name, Some(unescape(separator))),
+            None => (placeholder, None),
+        };
+        let (name, radix) = match name.split_once('/') {
+            Some((name, radix)) => (name, Some(Radix::parse(radix)?)),
+            None => (name, None),
+        };
+        match name {
+            "values" => {
+                let radix = radix.unwrap_or(Radix::Decimal);
+                let separator = separator.unwrap_or_else(|| match radix {
+                    Radix::Char => String::new(),
+                    _ => ", ".to_string(),
+                });
+                Ok(Piece::Values { radix, separator })
+            }
+            "count" if radix.is_none() && separator.is_none() => Ok(Piece::Count),
+            "count" => Err("{count} takes no radix or separator".to_string()),
+            _ => Err(format!(
+                "unknown placeholder '{{{}}}', expected {{values}} or {{count}}",
+                name
+            )),
+        }
+    }
+
+    /// the output for the final unstack `values`, without a newline at the end
+    pub fn render<C: Cell>(&self, values: &[C]) -> String {
+        let mut out = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(text) => out += text,
+                Piece::Values { radix, separator } => {
+                    for (index, &value) in values.iter().enumerate() {
+                        if index > 0 {
+                            out += separator;
+                        }
+                        radix.write(&mut out, value.to_i128());
+                    }
+                }
+                Piece::Count => out += &values.len().to_string(),
+            }
+        }
+        out
+    }
+}
+
+/// how the values are encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// the template and a newline
+    Text,
+    /// the lowest byte of every value
+    Bytes,
+    /// every value as 8 little endian bytes
+    I64,
+}
+
+/// where and how the final unstack is written
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub template: Template,
+    pub encoding: Encoding,
+    /// the file written to, stdout if `None`
+    pub path: Option<String>,
+}
+
+/// the arguments read by [`Output::from_args`]
+pub fn args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("a")
+            .short("a")
+            .multiple(true)
+            .help("display output as ascii"),
+        Arg::with_name("format")
+            .long("format")
+            .takes_value(true)
+            .value_name("TEMPLATE")
+            .conflicts_with("a")
+            .validator(|template| Template::parse(&template).map(|_| ()))
+            .help("print the unstack as TEMPLATE, e.g. \"{values/x:, }\", list or ascii"),
+        Arg::with_name("output")
+            .long("output")
+            .takes_value(true)
+            .value_name("ENCODING")
+            .possible_values(&["text", "bytes", "i64"])
+            .help(
+                "write the unstack as text, as the lowest byte of each value or as 64 bit integers",
+            ),
+        Arg::with_name("out")
+            .long("out")
+            .takes_value(true)
+            .value_name("PATH")
+            .help("write the unstack to PATH instead of stdout"),
+    ]
+}
+
+impl Output {
+    /// the output asked for by [`args`], printing `template` if there is no `-a` or `--format`
+    pub fn from_args(args: &ArgMatches, template: Template) -> Result<Self, String> {
+        let encoding = match args.value_of("output") {
+            None | Some("text") => Encoding::Text,
+            Some("bytes") => Encoding::Bytes,
+            Some("i64") => Encoding::I64,
+            Some(_) => unreachable!("validated by clap"),
+        };
+        let ascii = args.occurrences_of("a") > 0;
+        if encoding != Encoding::Text && (ascii || args.is_present("format")) {
+            return Err("-a and --format only apply to --output text".to_string());
+        }
+        let template = match args.value_of("format") {
+            Some(format) => Template::parse(format)?,
+            None if ascii => Template::parse("ascii")?,
+            None => template,
+        };
+        Ok(Output {
+            template,
+            encoding,
+            path: args.value_of("out").map(String::from),
+        })
+    }
+
+    pub fn encode<C: Cell>(&self, values: &[C]) -> Vec<u8> {
+        match self.encoding {
+            Encoding::Text => format!("{}\n", self.template.render(values)).into_bytes(),
+            Encoding::Bytes => values.iter().map(|value| value.to_i64() as u8).collect(),
+            Encoding::I64 => values
+                .iter()
+                .flat_map(|value| value.to_i64().to_le_bytes())
+                .collect(),
+        }
+    }
+
+    /// write `values`, exiting with an error if they cannot be written and quietly if stdout was
+    /// closed early
+    pub fn write<C: Cell>(&self, values: &[C]) {
+        let bytes = self.encode(values);
+        let written = match &self.path {
+            Some(path) => fs::write(path, bytes).map_err(|err| (path.as_str(), err)),
+            None => {
+                let mut stdout = io::stdout();
+                stdout
+                    .write_all(&bytes)
+                    .and_then(|_| stdout.flush())
+                    .map_err(|err| ("stdout", err))
+            }
+        };
+        match written {
+            Ok(()) => {}
+            Err((_, err)) if err.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+            Err((path, err)) => {
+                eprintln!("error: could not write {}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::{Encoding, Output, Template};
+
+    #[test]
+    fn test_render() {
+        let render = |template| Template::parse(template).unwrap().render(&[104, -31, 10]);
+        assert_eq!(render("list"), format!("{:?}", [104, -31, 10]));
+        assert_eq!(render("{values/x:-}"), "68--1f-a");
+        assert_eq!(render("{count}: {values/b: }"), "3: 1101000 -11111 1010");
+        assert_eq!(render("{{{values:\\t}}}"), "{104\t-31\t10}");
+        assert_eq!(Template::parse("ascii").unwrap().render(&[104, 105]), "hi");
+
+        assert!(Template::parse("{values").is_err());
+        assert!(Template::parse("}").is_err());
+        assert!(Template::parse("{values/z}").is_err());
+        assert!(Template::parse("{value}").is_err());
+        assert!(Template::parse("hex").is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut output = Output {
+            template: Template::parse("list").unwrap(),
+            encoding: Encoding::Text,
+            path: None,
+        };
+        assert_eq!(output.encode(&[1, 2]), b"[1, 2]\n");
+        output.encoding = Encoding::Bytes;
+        assert_eq!(output.encode(&[104, 361, -1]), [104, 105, 255]);
+        output.encoding = Encoding::I64;
+        assert_eq!(
+            output.encode(&[-2]),
+            [254, 255, 255, 255, 255, 255, 255, 255]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+/*!
+ * # Pragmas
+ *
+ * the first line of a program, or the second after a `#!` line, may say what the program needs:
+ *
+ * ```text
+ * #pragma bottom 0.1 extensions net
+ * ```
+ *
+ * a word of digits and dots is the oldest version of `bottom` that runs the program, and the
+ * other words are the [features](FEATURES) it needs, `bottom` itself is only there to be read.
+ * the parser skips the line, and `bottom` refuses to run a program that asks for a newer version
+ * or for a feature it lacks, including one it does not know yet
+ */
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+use core::ops::Range;
+use core::str::FromStr;
+
+/// what a pragma line starts with
+pub const PREFIX: &str = "#pragma";
+
+/// the features a pragma can ask for: the extension operations, labels, and 📞, which `bottom`
+/// only has when built with the `net` feature
+pub const FEATURES: &[&str] = &["extensions", "labels", "net"];
+
+/// a version of this crate, missing parts are 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    /// the version of this crate
+    pub fn current() -> Version {
+        env!("CARGO_PKG_VERSION")
+            .parse()
+            .expect("the crate has a valid version")
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        let mut parts = [0; 3];
+        for (index, part) in version.split('.').enumerate() {
+            let slot = parts.get_mut(index).ok_or_else(|| {
+                format!("'{}' is not a version, it has more than 3 parts", version)
+            })?;
+            *slot = part
+                .parse()
+                .map_err(|_| format!("'{}' is not a version", version))?;
+        }
+        let [major, minor, patch] = parts;
+        Ok(Version {
+            major,
+            minor,
+            patch,
+        })
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// what a program says it needs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pragma {
+    /// the oldest version that runs the program
+    pub version: Option<Version>,
+    /// the features the program needs, in the order they were written
+    pub features: Vec<String>,
+}
+
+/// something a pragma asks for that is not there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unmet {
+    /// a version newer than this crate
+    Version(Version),
+    /// a feature that is not available
+    Feature(String),
+}
+
+impl Pragma {
+    /// the pragma of `source`, `None` if it has none
+    pub fn of(source: &str) -> Result<Option<Pragma>, String> {
+        let Some(span) = span(source) else {
+            return Ok(None);
+        };
+        let mut pragma = Pragma::default();
+        for word in source[span][PREFIX.len()..].split_whitespace() {
+            if word.starts_with(|ch: char| ch.is_ascii_digit()) {
+                pragma.version = Some(word.parse()?);
+            } else if word != "bottom" {
+                pragma.features.push(word.to_string());
+            }
+        }
+        Ok(Some(pragma))
+    }
+
+    /// everything the pragma asks for that this crate with the `available` features lacks
+    pub fn unmet(&self, available: &[&str]) -> Vec<Unmet> {
+        let version = self
+            .version
+            .filter(|&version| version > Version::current())
+            .map(Unmet::Version);
+        let features = self
+            .features
+            .iter()
+            .filter(|feature| !available.contains(&feature.as_str()))
+            .map(|feature| Unmet::Feature(feature.clone()));
+        version.into_iter().chain(features).collect()
+    }
+}
+
+/// the pragma line asking for the same as this one
+impl Display for Pragma {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} bottom", PREFIX)?;
+        if let Some(version) = self.version {
+            write!(f, " {}", version)?;
+        }
+        for feature in &self.features {
+            write!(f, " {}", feature)?;
+        }
+        Ok(())
+    }
+}
+
+/// the byte range of the pragma line of `source` without its line break, `None` if it has none
+pub fn span(source: &str) -> Option<Range<usize>> {
+    let start = match source.starts_with("#!") {
+        true => source.find('\n')? + 1,
+        false => 0,
+    };
+    let line = &source[start..];
+    let end = start + line.find('\n').unwrap_or(line.len());
+    let after = source[start..end].strip_prefix(PREFIX)?;
+    match after.chars().next() {
+        None => Some(start..end),
+        Some(ch) if ch.is_whitespace() => Some(start..end),
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    use crate::pragma::{span, Pragma, Unmet, Version};
+    use crate::{parse, Operations};
+
+    #[test]
+    fn test_pragma() {
+        let source = "#!/usr/bin/env bottom\n#pragma bottom 0.1 extensions net\n🥺1 🔢1";
+        assert_eq!(span(source), Some(22..55));
+        let pragma = Pragma::of(source).unwrap().unwrap();
+        assert_eq!(pragma.version, Some("0.1".parse().unwrap()));
+        assert_eq!(pragma.features, ["extensions", "net"]);
+        assert_eq!(pragma.to_string(), "#pragma bottom 0.1.0 extensions net");
+        assert_eq!(
+            pragma.unmet(&["extensions"]),
+            [Unmet::Feature("net".to_string())]
+        );
+        // the digits of the version are no stray arguments
+        assert_eq!(
+            parse(source, true),
+            [Operations::Push(1), Operations::Print(1)]
+        );
+
+        let newer = Pragma::of("#pragma 99.0\n").unwrap().unwrap();
+        assert_eq!(
+            newer.unmet(&[]),
+            vec![Unmet::Version("99".parse().unwrap())]
+        );
+        assert!(Pragma::of("#pragma 0.x").is_err());
+        assert_eq!(Pragma::of("🥺1\n#pragma net"), Ok(None));
+        assert_eq!(span("#pragmatic"), None);
+        assert!("1.2.3.4".parse::<Version>().is_err());
+    }
+}
@@ -0,0 +1,189 @@
+/*!
+ * # Record and replay
+ *
+ * with `--record` every value a program receives from the [`Host`](crate::ext::Host) is written to a session log,
+ * `--replay` feeds those values back instead of touching the outside world so a run can be reproduced exactly
+ *
+ * the log is plain text with one event per line, e.g.
+ *
+ * ```text
+ * read 0 104
+ * env 1 42
+ * accept 3
+ * ```
+ */
+
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// a value the program received from the host
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    /// 📖 read `value` from descriptor `fd`
+    Read { fd: usize, value: i64 },
+    /// 🏠 read `value` from the environment variable `index`
+    Env { index: usize, value: i64 },
+    /// 📞 accepted a connection on socket `fd`
+    Accept { fd: usize },
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Event::Read { fd, value } => write!(f, "read {} {}", fd, value),
+            Event::Env { index, value } => write!(f, "env {} {}", index, value),
+            Event::Accept { fd } => write!(f, "accept {}", fd),
+        }
+    }
+}
+
+impl FromStr for Event {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid event '{}'", line);
+        let words: Vec<_> = line.split_whitespace().collect();
+        let index = |i: usize| words.get(i).and_then(|word| word.parse().ok());
+        let value = |i: usize| words.get(i).and_then(|word| word.parse().ok());
+        let event = match (words.first(), words.len()) {
+            (Some(&"read"), 3) => Event::Read {
+                fd: index(1).ok_or_else(invalid)?,
+                value: value(2).ok_or_else(invalid)?,
+            },
+            (Some(&"env"), 3) => Event::Env {
+                index: index(1).ok_or_else(invalid)?,
+                value: value(2).ok_or_else(invalid)?,
+            },
+            (Some(&"accept"), 2) => Event::Accept {
+                fd: index(1).ok_or_else(invalid)?,
+            },
+            _ => return Err(invalid()),
+        };
+        Ok(event)
+    }
+}
+
+/// whether values come from the outside world and if they are logged
+#[derive(Debug, Default)]
+pub enum Session {
+    /// values come from the outside world
+    #[default]
+    Live,
+    /// values come from the outside world and are written to a log
+    Recording(BufWriter<File>),
+    /// values come from a previously recorded log
+    Replaying(VecDeque<Event>),
+}
+
+impl Session {
+    /// start recording to the file at `path`
+    pub fn record(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Session::Recording(BufWriter::new(File::create(path)?)))
+    }
+
+    /// replay the log at `path`, empty lines and lines starting with `#` are ignored
+    pub fn replay(path: impl AsRef<Path>) -> io::Result<Self> {
+        let events = fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                line.parse()
+                    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Session::Replaying(events))
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self, Session::Replaying(_))
+    }
+
+    /// log `event` if recording
+    pub fn record_event(&mut self, event: Event) -> io::Result<()> {
+        match self {
+            Session::Recording(log) => writeln!(log, "{}", event),
+            _ => Ok(()),
+        }
+    }
+
+    /// the next recorded event, which has to match `expected` apart from its value
+    pub fn replay_event(&mut self, expected: Event) -> io::Result<Event> {
+        let Session::Replaying(events) = self else {
+            panic!("not replaying");
+        };
+        let event = events.pop_front().ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("replay log ended, expected {}", expected),
+            )
+        })?;
+        let matches = match (event, expected) {
+            (Event::Read { fd, .. }, Event::Read { fd: expected, .. }) => fd == expected,
+            (
+                Event::Env { index, .. },
+                Event::Env {
+                    index: expected, ..
+                },
+            ) => index == expected,
+            (event, expected) => event == expected,
+        };
+        if matches {
+            Ok(event)
+        } else {
+            Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "replay diverged, log has {} but program did {}",
+                    event, expected
+                ),
+            ))
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Session::Recording(log) => log.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replay::{Event, Session};
+
+    #[test]
+    fn test_event_round_trip() {
+        for event in [
+            Event::Read { fd: 0, value: -1 },
+            Event::Env {
+                index: 2,
+                value: 42,
+            },
+            Event::Accept { fd: 3 },
+        ] {
+            assert_eq!(event.to_string().parse(), Ok(event));
+        }
+        assert!("read 1".parse::<Event>().is_err());
+    }
+
+    #[test]
+    fn test_replay_diverged() {
+        let mut session = Session::Replaying(vec![Event::Read { fd: 0, value: 7 }].into());
+        assert!(session
+            .replay_event(Event::Read { fd: 3, value: 0 })
+            .is_err());
+
+        let mut session = Session::Replaying(vec![Event::Read { fd: 0, value: 7 }].into());
+        let event = session
+            .replay_event(Event::Read { fd: 0, value: 0 })
+            .unwrap();
+        assert_eq!(event, Event::Read { fd: 0, value: 7 });
+        assert!(session.replay_event(Event::Accept { fd: 0 }).is_err());
+    }
+}
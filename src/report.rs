@@ -0,0 +1,693 @@
+/*!
+ * # Reports
+ *
+ * statistics about a run, collected by an [`ExecutionObserver`] only when a report is asked for
+ * so normal runs do not pay for them
+ *
+ * `--report report.json` writes them as a single JSON object next to the normal output:
+ *
+ * ```json
+ * {"status":"finished","exit_code":0,"error":null,"steps":12,"peak_depth":3,
+ *  "arena_capacity":4,"duration_ms":0.04,"opcodes":{"🥺":4,"💓":2},"output":[42]}
+ * ```
+ *
+ * the report is written even if the program fails, `status` then says how
+ *
+ * `--histogram` prints how often each operation ran to stderr, a quick profile for a terminal
+ *
+ * `--loops` prints every 🫂 loop that ran with the lines and columns of its body, how often it
+ * went around and how many instructions were executed inside it, the loop doing the most first.
+ * instructions of a nested loop count for the loops around it too. the JSON report lists the
+ * same loops under `loops`
+ *
+ * `--profile` prints where the instructions were spent as folded stacks, the loops around each
+ * instruction as its callers, the way flamegraph tools read them:
+ *
+ * ```text
+ * program;🫂 1:4-2:12;🫂 1:7-1:15;💖2 1:7 6
+ * ```
+ *
+ * `--flamegraph out.svg` renders them as an SVG flamegraph instead, with the `flamegraph` feature
+ *
+ * `--snapshot-every N --snapshot-out PATH` snapshots the state every N instructions and writes
+ * the last [`SNAPSHOTS`] of them to PATH when the program ends, to see how a run that failed
+ * deep into it got there:
+ *
+ * ```json
+ * {"every":100,"snapshots":[{"steps":4200,"task":0,"instruction_pointer":7,"unstack":[3,1],
+ *  "calls":[],"memory":[[0,42]]}]}
+ * ```
+ */
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter, Write};
+use std::fs;
+use std::io;
+use std::process;
+use std::rc::Rc;
+use std::time::Duration;
+
+use bottom::interpreter::{ExecutionObserver, Snapshot};
+use bottom::unstack::Unstack;
+use bottom::{hug_target, parse_spanned, Operations};
+use clap::{Arg, ArgMatches};
+
+use crate::chrome::ChromeTrace;
+use crate::json::Json;
+use crate::trace::Recorder;
+
+/// what happened while a program ran
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// instructions executed by all tasks
+    pub steps: usize,
+    /// the most values any unstack held after an instruction
+    pub peak_depth: usize,
+    /// the most nodes the arena of any unstack had room for
+    pub arena_capacity: usize,
+    /// how often each operation was executed, by emoji
+    pub counts: BTreeMap<&'static str, usize>,
+    /// how often the instruction at each instruction pointer was executed
+    pub executed: Vec<usize>,
+}
+
+/// stats filled in while the program runs and read once it is done
+pub type SharedStats = Rc<RefCell<Stats>>;
+
+/// fills in the [`Stats`] it shares with whoever wants to read them afterwards
+struct Collector(SharedStats);
+
+impl ExecutionObserver for Collector {
+    fn after_instruction(
+        &mut self,
+        _task: usize,
+        instruction_pointer: usize,
+        operation: Operations,
+        unstack: &Unstack,
+    ) {
+        let mut stats = self.0.borrow_mut();
+        if stats.executed.len() <= instruction_pointer {
+            stats.executed.resize(instruction_pointer + 1, 0);
+        }
+        stats.executed[instruction_pointer] += 1;
+        stats.steps += 1;
+        stats.peak_depth = stats.peak_depth.max(unstack.len());
+        stats.arena_capacity = stats.arena_capacity.max(unstack.capacity());
+        *stats.counts.entry(operation.emoji()).or_default() += 1;
+    }
+}
+
+/// where an operation or a run of them is in the source, as lines and columns counting from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: (usize, usize),
+    /// the position just after the last character
+    pub end: (usize, usize),
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start.0, self.start.1, self.end.0, self.end.1
+        )
+    }
+}
+
+/// the line and column of the byte at `offset` in `source`
+pub fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// a 🫂 jumping back that was executed at least once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Loop {
+    /// the instruction pointer of the first instruction of the body
+    pub start: usize,
+    /// the instruction pointer of the 🫂
+    pub end: usize,
+    /// where the body is in the source
+    pub span: Span,
+    /// how often the 🫂 was executed, once for each time around
+    pub iterations: usize,
+    /// the instructions executed inside the body
+    pub instructions: usize,
+}
+
+/// how a run ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Exit {
+    Finished(Vec<i64>),
+    Failed {
+        /// what went wrong in a word, like `timeout` or `panicked`
+        status: &'static str,
+        message: String,
+        /// the exit code of `bottom`
+        code: i32,
+    },
+}
+
+/// the reports asked for on the command line
+#[derive(Debug, Clone, Default)]
+pub struct Reports {
+    /// where the JSON report goes
+    pub json: Option<String>,
+    /// whether to print the histogram of operations
+    pub histogram: bool,
+    /// whether to print the loops
+    pub loops: bool,
+    /// whether to print the folded stacks
+    pub profile: bool,
+    /// where the flamegraph of the folded stacks goes
+    pub flamegraph: Option<String>,
+    /// the operations of the program and where they are in its source
+    pub program: Vec<(Operations, Span)>,
+    /// how many instructions apart snapshots are taken and where they go
+    pub snapshots: Option<(usize, String)>,
+    /// where the binary trace goes
+    pub trace: Option<String>,
+    /// where the Chrome trace goes
+    pub chrome_trace: Option<String>,
+}
+
+/// the number of snapshots `--snapshot-out` keeps, the latest ones
+pub const SNAPSHOTS: usize = 64;
+
+/// the arguments read by [`Reports::from_args`]
+pub fn args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("report")
+            .long("report")
+            .takes_value(true)
+            .value_name("PATH")
+            .help("write statistics about the run to PATH as JSON"),
+        Arg::with_name("histogram")
+            .long("histogram")
+            .help("print how often each operation was executed to stderr"),
+        Arg::with_name("loops").long("loops").help(
+            "print how often each loop went around and how many instructions it ran to stderr",
+        ),
+        Arg::with_name("profile")
+            .long("profile")
+            .help("print where instructions were spent as folded stacks of loops to stderr"),
+        Arg::with_name("flamegraph")
+            .long("flamegraph")
+            .takes_value(true)
+            .value_name("PATH")
+            .requires("profile")
+            .help("write the profile to PATH as an SVG flamegraph instead of printing it"),
+        Arg::with_name("snapshot-every")
+            .long("snapshot-every")
+            .takes_value(true)
+            .value_name("N")
+            .requires("snapshot-out")
+            .validator(|every| match every.parse::<usize>() {
+                Ok(0) => Err("must be at least 1".to_string()),
+                Ok(_) => Ok(()),
+                Err(err) => Err(err.to_string()),
+            })
+            .help("snapshot the state of the program every N instructions"),
+        Arg::with_name("trace-out")
+            .long("trace-out")
+            .takes_value(true)
+            .value_name("PATH")
+            .help("write every executed instruction to PATH for `bottom replay`"),
+        Arg::with_name("chrome-trace")
+            .long("chrome-trace")
+            .takes_value(true)
+            .value_name("PATH")
+            .help("write the instructions and loops that ran to PATH for chrome://tracing"),
+        Arg::with_name("snapshot-out")
+            .long("snapshot-out")
+            .takes_value(true)
+            .value_name("PATH")
+            .requires("snapshot-every")
+            .help("write the latest snapshots to PATH as JSON when the program ends"),
+    ]
+}
+
+impl Reports {
+    /// the reports asked for by `args` for the program in `source`
+    pub fn from_args(args: &ArgMatches, source: &str, extensions: bool) -> Self {
+        let mut reports = Reports {
+            json: args.value_of("report").map(String::from),
+            histogram: args.is_present("histogram"),
+            loops: args.is_present("loops"),
+            profile: args.is_present("profile"),
+            flamegraph: args.value_of("flamegraph").map(String::from),
+            program: vec![],
+            snapshots: args
+                .value_of("snapshot-every")
+                .zip(args.value_of("snapshot-out"))
+                .map(|(every, path)| (every.parse().expect("validated by clap"), path.to_string())),
+            trace: args.value_of("trace-out").map(String::from),
+            chrome_trace: args.value_of("chrome-trace").map(String::from),
+        };
+        if reports.wanted() || reports.chrome_trace.is_some() {
+            reports.program = parse_spanned(source, extensions)
+                .into_iter()
+                .map(|(operation, range)| {
+                    let span = Span {
+                        start: position(source, range.start),
+                        end: position(source, range.end),
+                    };
+                    (operation, span)
+                })
+                .collect();
+        }
+        reports
+    }
+
+    fn wanted(&self) -> bool {
+        self.json.is_some() || self.histogram || self.loops || self.profile
+    }
+
+    /// the observer collecting the statistics and where to find them, `None` if no report is
+    /// asked for
+    pub fn observer(&self) -> Option<(Box<dyn ExecutionObserver>, SharedStats)> {
+        if !self.wanted() {
+            return None;
+        }
+        let stats = Rc::new(RefCell::new(Stats::default()));
+        Some((Box::new(Collector(Rc::clone(&stats))), stats))
+    }
+
+    /// the observers writing the traces of `program` starting with `initial` that are asked
+    /// for, exiting with an error if one cannot be created
+    pub fn tracers(
+        &self,
+        program: &[Operations],
+        initial: &Unstack,
+    ) -> Vec<Box<dyn ExecutionObserver>> {
+        let mut tracers: Vec<Box<dyn ExecutionObserver>> = vec![];
+        let fail = |path: &str, err: io::Error| -> ! {
+            eprintln!("error: could not write {}: {}", path, err);
+            process::exit(1);
+        };
+        if let Some(path) = &self.trace {
+            match Recorder::create(path, program, initial) {
+                Ok(recorder) => tracers.push(Box::new(recorder)),
+                Err(err) => fail(path, err),
+            }
+        }
+        if let Some(path) = &self.chrome_trace {
+            // the spans are only right if the program was not changed after parsing it
+            let spans: Vec<_> = match self
+                .program
+                .iter()
+                .map(|(operation, _)| operation)
+                .eq(program)
+            {
+                true => self.program.iter().map(|&(_, span)| span).collect(),
+                false => vec![],
+            };
+            match ChromeTrace::create(path, program, &spans) {
+                Ok(trace) => tracers.push(Box::new(trace)),
+                Err(err) => fail(path, err),
+            }
+        }
+        tracers
+    }
+
+    /// write every report asked for, exiting with an error if one cannot be written
+    pub fn write(&self, stats: &Stats, duration: Duration, exit: &Exit) {
+        if let Some(path) = &self.json {
+            let loops = self.find_loops(stats);
+            let report = format!("{}\n", json_report(stats, &loops, duration, exit));
+            if let Err(err) = fs::write(path, report) {
+                eprintln!("error: could not write {}: {}", path, err);
+                process::exit(1);
+            }
+        }
+        if self.histogram {
+            eprint!("{}", histogram(stats));
+        }
+        if self.loops {
+            eprint!("{}", loop_table(stats, &self.find_loops(stats)));
+        }
+        if self.profile {
+            let stacks = self.folded_stacks(stats);
+            match &self.flamegraph {
+                Some(path) => {
+                    if let Err(err) = flamegraph(&stacks, path) {
+                        eprintln!("error: could not write {}: {}", path, err);
+                        process::exit(1);
+                    }
+                }
+                None => stacks.iter().for_each(|stack| eprintln!("{}", stack)),
+            }
+        }
+    }
+
+    /// write `snapshots` where `--snapshot-out` asked for, exiting with an error if they cannot be
+    pub fn write_snapshots<'a>(&self, snapshots: impl Iterator<Item = &'a Snapshot>) {
+        let Some((every, path)) = &self.snapshots else {
+            return;
+        };
+        let report = Json::Object(vec![
+            ("every".to_string(), Json::Int(*every as i64)),
+            (
+                "snapshots".to_string(),
+                Json::Array(snapshots.map(snapshot_json).collect()),
+            ),
+        ]);
+        if let Err(err) = fs::write(path, format!("{}\n", report)) {
+            eprintln!("error: could not write {}: {}", path, err);
+            process::exit(1);
+        }
+    }
+
+    /// the loops of the program that ran, the one that executed the most instructions first
+    pub fn find_loops(&self, stats: &Stats) -> Vec<Loop> {
+        let executed = |instruction_pointer: usize| {
+            stats
+                .executed
+                .get(instruction_pointer)
+                .copied()
+                .unwrap_or(0)
+        };
+        let mut loops: Vec<_> = self
+            .program
+            .iter()
+            .enumerate()
+            .filter_map(|(end, (operation, end_span))| {
+                let Operations::Hug(n) = *operation else {
+                    return None;
+                };
+                let start = hug_target(end, n).filter(|&start| start <= end)?;
+                let iterations = executed(end);
+                (iterations > 0).then(|| Loop {
+                    start,
+                    end,
+                    span: Span {
+                        start: self.program[start].1.start,
+                        end: end_span.end,
+                    },
+                    iterations,
+                    instructions: (start..=end).map(executed).sum(),
+                })
+            })
+            .collect();
+        loops.sort_by_key(|found| (usize::MAX - found.instructions, found.end));
+        loops
+    }
+
+    /// where the instructions were spent as folded stacks, one line for every instruction that
+    /// ran: the loops around it outermost first, the instruction and how often it was executed
+    pub fn folded_stacks(&self, stats: &Stats) -> Vec<String> {
+        let loops = self.find_loops(stats);
+        self.program
+            .iter()
+            .enumerate()
+            .filter_map(|(instruction_pointer, (operation, span))| {
+                let executed = stats.executed.get(instruction_pointer).copied();
+                let executed = executed.filter(|&executed| executed > 0)?;
+                let mut around: Vec<_> = loops
+                    .iter()
+                    .filter(|found| (found.start..=found.end).contains(&instruction_pointer))
+                    .collect();
+                around.sort_by_key(|found| (found.start, usize::MAX - found.end));
+                let mut stack = vec!["program".to_string()];
+                stack.extend(around.iter().map(|found| format!("🫂 {}", found.span)));
+                stack.push(format!("{} {}:{}", operation, span.start.0, span.start.1));
+                Some(format!("{} {}", stack.join(";"), executed))
+            })
+            .collect()
+    }
+}
+
+/// render the folded `stacks` as an SVG flamegraph at `path`
+#[cfg(feature = "flamegraph")]
+fn flamegraph(stacks: &[String], path: &str) -> io::Result<()> {
+    use inferno::flamegraph::{from_lines, Options};
+
+    let mut options = Options::default();
+    options.title = "bottom".to_string();
+    options.count_name = "instructions".to_string();
+    let out = io::BufWriter::new(fs::File::create(path)?);
+    from_lines(&mut options, stacks.iter().map(String::as_str), out)
+        .map_err(|err| io::Error::other(err.to_string()))
+}
+
+#[cfg(not(feature = "flamegraph"))]
+fn flamegraph(_: &[String], _: &str) -> io::Result<()> {
+    Err(io::Error::other("flamegraphs need the flamegraph feature"))
+}
+
+/// the loops as a table, with the share of all instructions executed in each
+pub fn loop_table(stats: &Stats, loops: &[Loop]) -> String {
+    if loops.is_empty() {
+        return "no loops ran\n".to_string();
+    }
+    let mut table = String::new();
+    for found in loops {
+        writeln!(
+            table,
+            "🫂 {:<16} {:>10} iterations {:>12} instructions {:>5.1}%",
+            found.span.to_string(),
+            found.iterations,
+            found.instructions,
+            found.instructions as f64 / stats.steps.max(1) as f64 * 100.0
+        )
+        .expect("writing to a string cannot fail");
+    }
+    table
+}
+
+/// the operations of 🥺 itself, always in the histogram even if they never ran
+const CORE: [&str; 6] = ["🥺", "💖", "👉👈", "💓", "✨", "🫂"];
+
+/// a table of how often each operation ran with a bar for its share of all instructions,
+/// the core operations first and then the extensions that ran, most frequent first
+pub fn histogram(stats: &Stats) -> String {
+    let mut extensions: Vec<_> = stats
+        .counts
+        .iter()
+        .filter(|(emoji, _)| !CORE.contains(emoji))
+        .map(|(&emoji, &count)| (emoji, count))
+        .collect();
+    extensions.sort_by_key(|&(emoji, count)| (usize::MAX - count, emoji));
+    let rows = CORE
+        .iter()
+        .map(|&emoji| (emoji, stats.counts.get(emoji).copied().unwrap_or(0)))
+        .chain(extensions);
+
+    let width = stats.counts.values().max().unwrap_or(&0).to_string().len();
+    let mut table = String::new();
+    for (emoji, count) in rows {
+        let share = match stats.steps {
+            0 => 0.0,
+            steps => count as f64 / steps as f64,
+        };
+        // emoji are two columns wide, 👉👈 is four
+        let padding = if emoji == "👉👈" { "" } else { "  " };
+        write!(
+            table,
+            "{}{} {:>width$} {:>5.1}%",
+            emoji,
+            padding,
+            count,
+            share * 100.0,
+            width = width
+        )
+        .expect("writing to a string cannot fail");
+        match (share * 40.0).round() as usize {
+            0 => table.push('\n'),
+            bar => table += &format!(" {}\n", "#".repeat(bar)),
+        }
+    }
+    table
+}
+
+pub fn json_report(stats: &Stats, loops: &[Loop], duration: Duration, exit: &Exit) -> Json {
+    let (status, code, error, output) = match exit {
+        Exit::Finished(values) => (
+            "finished",
+            0,
+            Json::Null,
+            Json::Array(values.iter().copied().map(Json::Int).collect()),
+        ),
+        Exit::Failed {
+            status,
+            message,
+            code,
+        } => (*status, *code, Json::String(message.clone()), Json::Null),
+    };
+    let mut counts: Vec<_> = stats.counts.iter().collect();
+    counts.sort_by_key(|&(emoji, count)| (usize::MAX - count, *emoji));
+    let opcodes = counts
+        .into_iter()
+        .map(|(emoji, &count)| (emoji.to_string(), Json::Int(count as i64)))
+        .collect();
+    Json::Object(vec![
+        ("status".to_string(), Json::String(status.to_string())),
+        ("exit_code".to_string(), Json::Int(code as i64)),
+        ("error".to_string(), error),
+        ("steps".to_string(), Json::Int(stats.steps as i64)),
+        ("peak_depth".to_string(), Json::Int(stats.peak_depth as i64)),
+        (
+            "arena_capacity".to_string(),
+            Json::Int(stats.arena_capacity as i64),
+        ),
+        (
+            "duration_ms".to_string(),
+            Json::Float(duration.as_secs_f64() * 1000.0),
+        ),
+        ("opcodes".to_string(), Json::Object(opcodes)),
+        (
+            "loops".to_string(),
+            Json::Array(
+                loops
+                    .iter()
+                    .map(|found| {
+                        Json::Object(vec![
+                            ("start".to_string(), Json::Int(found.start as i64)),
+                            ("end".to_string(), Json::Int(found.end as i64)),
+                            ("span".to_string(), Json::String(found.span.to_string())),
+                            ("iterations".to_string(), Json::Int(found.iterations as i64)),
+                            (
+                                "instructions".to_string(),
+                                Json::Int(found.instructions as i64),
+                            ),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+        ("output".to_string(), output),
+    ])
+}
+
+/// a snapshot as a JSON object
+fn snapshot_json(snapshot: &Snapshot) -> Json {
+    let unstack = snapshot.unstack().values().into_iter().map(Json::Int);
+    let calls = snapshot.calls().iter().map(|&call| Json::Int(call as i64));
+    let memory = snapshot
+        .memory()
+        .iter()
+        .map(|(&address, &value)| Json::Array(vec![Json::Int(address as i64), Json::Int(value)]))
+        .collect();
+    Json::Object(vec![
+        ("steps".to_string(), Json::Int(snapshot.steps() as i64)),
+        (
+            "task".to_string(),
+            Json::Int(snapshot.current_task() as i64),
+        ),
+        (
+            "instruction_pointer".to_string(),
+            Json::Int(snapshot.instruction_pointer() as i64),
+        ),
+        ("unstack".to_string(), Json::Array(unstack.collect())),
+        ("calls".to_string(), Json::Array(calls.collect())),
+        ("memory".to_string(), Json::Array(memory)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bottom::ext::Host;
+    use bottom::interpreter::Interpreter;
+    use bottom::unstack::Unstack;
+    use bottom::{parse, Limits};
+    use clap::App;
+
+    use crate::json::Json;
+    use crate::report::{args, histogram, json_report, Exit, Reports, Span, Stats};
+
+    /// run `source` with the reports asked for by `flags` and return them with the stats
+    fn run(source: &str, flags: &[&str]) -> (Reports, Stats, Exit) {
+        let matches = App::new("test")
+            .args(&args())
+            .get_matches_from(["test"].iter().chain(flags));
+        let reports = Reports::from_args(&matches, source, true);
+        let (observer, stats) = reports.observer().unwrap();
+        let mut host = Host::new();
+        let mut interpreter = Interpreter::new(parse(source, true), Unstack::new(), &mut host);
+        interpreter.observe(observer);
+        interpreter.run(&Limits::default()).unwrap();
+        let exit = Exit::Finished(interpreter.output());
+        drop(interpreter);
+        let stats = stats.borrow().clone();
+        (reports, stats, exit)
+    }
+
+    #[test]
+    fn test_report() {
+        let (reports, stats, exit) = run("🥺6 🥺7 💓0", &["--report", "report.json"]);
+        let loops = reports.find_loops(&stats);
+        let report = json_report(&stats, &loops, Duration::from_millis(2), &exit);
+        assert_eq!(
+            report.get("status"),
+            Some(&Json::String("finished".to_string()))
+        );
+        assert_eq!(report.get("steps"), Some(&Json::Int(3)));
+        assert_eq!(report.get("peak_depth"), Some(&Json::Int(2)));
+        assert!(
+            matches!(report.get("arena_capacity"), Some(&Json::Int(capacity)) if capacity >= 2)
+        );
+        assert_eq!(
+            report.get("opcodes").unwrap().to_string(),
+            r#"{"🥺":2,"💓":1}"#
+        );
+        assert_eq!(
+            report.get("output"),
+            Some(&Json::Array(vec![Json::Int(42)]))
+        );
+        assert_eq!(report.get("duration_ms"), Some(&Json::Float(2.0)));
+        assert_eq!(report.get("loops"), Some(&Json::Array(vec![])));
+    }
+
+    #[test]
+    fn test_histogram() {
+        let stats = Stats {
+            steps: 4,
+            peak_depth: 2,
+            counts: [("🥺", 2), ("🔢", 1), ("💓", 1)].into_iter().collect(),
+            ..Stats::default()
+        };
+        let histogram = histogram(&stats);
+        let lines: Vec<_> = histogram.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], format!("🥺   2  50.0% {}", "#".repeat(20)));
+        assert_eq!(lines[2], "👉👈 0   0.0%");
+        assert_eq!(lines[6], format!("🔢   1  25.0% {}", "#".repeat(10)));
+    }
+
+    #[test]
+    fn test_loops() {
+        // halves 8 until it is 0, going around 4 times
+        let (reports, stats, _) = run("🥺8\n💖2 ✨1 🫂4\n🥺1 🫂1", &["--loops"]);
+        let loops = reports.find_loops(&stats);
+        assert_eq!(loops.len(), 1);
+        assert_eq!((loops[0].start, loops[0].end), (1, 3));
+        assert_eq!(
+            loops[0].span,
+            Span {
+                start: (2, 1),
+                end: (2, 9)
+            }
+        );
+        assert_eq!(loops[0].iterations, 4);
+        assert_eq!(loops[0].instructions, 12);
+    }
+
+    #[test]
+    fn test_profile() {
+        let source = "🥺2 🥺4 💖2 ✨1 🫂4\n🧹1 💖2 ✨1 🫂9";
+        let (reports, stats, _) = run(source, &["--profile"]);
+        let stacks = reports.folded_stacks(&stats);
+        assert_eq!(stacks.len(), 9);
+        assert_eq!(stacks[0], "program;🥺2 1:1 1");
+        assert_eq!(stacks[2], "program;🫂 1:4-2:12;🫂 1:7-1:15;💖2 1:7 6");
+        assert_eq!(stacks[5], "program;🫂 1:4-2:12;🧹1 2:1 2");
+    }
+}
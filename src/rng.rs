@@ -0,0 +1,71 @@
+/*!
+ * # Random numbers
+ *
+ * a small xorshift generator for the tools that need random programs or inputs,
+ * seeded explicitly so every run can be reproduced
+ */
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck at 0, and small seeds need a few rounds to spread out
+        let mut rng = Rng(seed ^ 0x9e37_79b9_7f4a_7c15);
+        for _ in 0..4 {
+            rng.next_u64();
+        }
+        rng
+    }
+
+    /// a seed taken from the current time
+    pub fn seed_from_time() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// a number in `0..n`, `n` must not be 0
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// a number in `low..=high`
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        let span = high.wrapping_sub(low) as u64;
+        if span == u64::MAX {
+            return self.next_u64() as i64;
+        }
+        low.wrapping_add(self.below(span + 1) as i64)
+    }
+
+    /// true with a probability of `percent` percent
+    pub fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rng::Rng;
+
+    #[test]
+    fn test_range() {
+        let mut rng = Rng::new(1);
+        for _ in 0..1000 {
+            assert!((-3..=3).contains(&rng.range(-3, 3)));
+        }
+        assert_eq!(Rng::new(42).next_u64(), Rng::new(42).next_u64());
+    }
+}
@@ -0,0 +1,183 @@
+/*!
+ * # Sandbox
+ *
+ * a [`Sandbox`] runs programs nobody vouched for: it limits how many instructions they execute,
+ * how many values they keep and how long they run, refuses every request of the extension
+ * operations to reach the outside world and turns panics into a [`Failure`] instead of letting
+ * them unwind into the embedder
+ *
+ * panics still reach the panic hook, which prints them unless the embedder replaced it
+ */
+
+use std::fmt::{self, Display, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+use crate::interpreter::{ExecutionObserver, Interpreter, Io, Status};
+use crate::unstack::Unstack;
+use crate::{Limits, Operations, RuntimeError, Semantics};
+
+/// how a sandboxed program failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    Runtime(RuntimeError),
+    /// the program executed its whole fuel without finishing
+    OutOfFuel {
+        steps: usize,
+    },
+    /// the interpreter panicked
+    Panicked(String),
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Failure::Runtime(err) => write!(f, "{}", err),
+            Failure::OutOfFuel { steps } => {
+                write!(f, "ran out of fuel after {} instructions", steps)
+            }
+            Failure::Panicked(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// an [`Io`] that refuses everything
+struct Refuse;
+
+impl Io for Refuse {
+    fn env(&mut self, _index: usize) -> Result<i64, String> {
+        Err("environment variables are not available in the sandbox".to_string())
+    }
+
+    fn read(&mut self, _fd: usize) -> Result<i64, String> {
+        Err("reading is not available in the sandbox".to_string())
+    }
+
+    fn write(&mut self, _fd: usize, _value: i64) -> Result<(), String> {
+        Err("writing is not available in the sandbox".to_string())
+    }
+
+    fn accept(&mut self, _fd: usize) -> Result<(), String> {
+        Err("sockets are not available in the sandbox".to_string())
+    }
+}
+
+/// the limits untrusted programs run under
+#[derive(Debug, Clone, Copy)]
+pub struct Sandbox {
+    /// instructions a program may execute
+    pub fuel: usize,
+    /// values all unstacks of a program may hold together
+    pub max_values: usize,
+    /// memory cells a program may use
+    pub max_memory: usize,
+    pub timeout: Duration,
+    /// what the operations do where implementations disagree
+    pub semantics: Semantics,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Sandbox {
+            fuel: 10_000_000,
+            max_values: 1_000_000,
+            max_memory: 65_536,
+            timeout: Duration::from_secs(5),
+            semantics: Semantics::default(),
+        }
+    }
+}
+
+impl Sandbox {
+    pub fn limits(&self) -> Limits {
+        Limits {
+            timeout: Some(self.timeout),
+            max_values: Some(self.max_values),
+            max_memory: Some(self.max_memory),
+            ..Limits::default()
+        }
+    }
+
+    /// run `tokens` starting with the given unstack and return what is left on the unstack of
+    /// the main task, bottom first
+    pub fn run(&self, tokens: Vec<Operations>, unstack: Unstack) -> Result<Vec<i64>, Failure> {
+        self.run_observed(tokens, unstack, vec![])
+    }
+
+    /// [`run`](Sandbox::run) with `observers` watching every instruction
+    pub fn run_observed(
+        &self,
+        tokens: Vec<Operations>,
+        unstack: Unstack,
+        observers: Vec<Box<dyn ExecutionObserver>>,
+    ) -> Result<Vec<i64>, Failure> {
+        let mut io = Refuse;
+        let mut interpreter = Interpreter::new(tokens, unstack, &mut io);
+        interpreter.set_semantics(self.semantics);
+        for observer in observers {
+            interpreter.observe(observer);
+        }
+        let limits = self.limits();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            interpreter.run_budgeted(self.fuel, &limits)
+        }));
+        match result {
+            Ok(Ok(Status::Finished)) => Ok(interpreter.output()),
+            Ok(Ok(_)) => Err(Failure::OutOfFuel {
+                steps: interpreter.steps(),
+            }),
+            Ok(Err(err)) => Err(Failure::Runtime(err)),
+            Err(payload) => {
+                let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                    message.to_string()
+                } else if let Some(message) = payload.downcast_ref::<String>() {
+                    message.clone()
+                } else {
+                    "unknown panic".to_string()
+                };
+                Err(Failure::Panicked(message))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::sandbox::{Failure, Sandbox};
+    use crate::unstack::Unstack;
+    use crate::{parse, RuntimeError};
+
+    #[test]
+    fn test_sandbox() {
+        let sandbox = Sandbox {
+            fuel: 1000,
+            max_values: 100,
+            max_memory: 16,
+            timeout: Duration::from_secs(5),
+            ..Sandbox::default()
+        };
+        let run = |source| sandbox.run(parse(source, true), Unstack::new());
+
+        assert_eq!(run("🥺6 🥺7 💓0"), Ok(vec![42]));
+        assert_eq!(run("🥺1 ✨1 🫂3"), Err(Failure::OutOfFuel { steps: 1000 }));
+        assert!(matches!(
+            run("🥺1 🥺1 ✨2 🫂3"),
+            Err(Failure::Runtime(RuntimeError::OutOfMemory {
+                limit: 100,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            run("💖2"),
+            Err(Failure::Runtime(RuntimeError::Underflow { had: 0, .. }))
+        ));
+        match run("🥺104 📝1") {
+            Err(Failure::Runtime(RuntimeError::Failed { message, .. })) => {
+                assert!(message.contains("sandbox"))
+            }
+            result => panic!("writing succeeded: {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,426 @@
+/*!
+ * # Evaluation server
+ *
+ * `bottom serve --port 8080` evaluates programs sent over HTTP, so playgrounds and bots do not
+ * have to start a process for every program
+ *
+ * `POST /eval` takes a JSON object with the program as `source` and optionally the bytes of stdin
+ * as `input`, the initial unstack as `init` and `extensions`:
+ *
+ * ```text
+ * {"source": "🥺104 📝1 🥺2", "input": "", "init": [3], "extensions": true}
+ * ```
+ *
+ * and answers with the final unstack (`null` if the program failed), what it wrote to stdout,
+ * the number of instructions it executed and what went wrong, if anything:
+ *
+ * ```text
+ * {"output": [2, 3], "stdout": "h", "steps": 3, "error": null}
+ * ```
+ *
+ * every program runs with its own in-memory standard streams, 🏠 and 📞 are not available,
+ * and it is stopped once it uses up its fuel, time or memory
+ */
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use bottom::ext::Host;
+use bottom::interpreter::{Interpreter, Io, Status};
+use bottom::unstack::Unstack;
+use bottom::{parse, Limits};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::batch::panic_message;
+use crate::json::Json;
+use crate::parse_duration;
+
+/// the largest request body accepted, in bytes
+const MAX_BODY: usize = 1 << 20;
+
+/// how much each request may use
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    /// instructions a program may execute
+    pub fuel: usize,
+    pub limits: Limits,
+}
+
+/// the standard streams of a single request, without access to the environment or the network
+struct Playground(Host);
+
+impl Io for Playground {
+    fn env(&mut self, _index: usize) -> Result<i64, String> {
+        Err("environment variables are not available".to_string())
+    }
+
+    fn read(&mut self, fd: usize) -> Result<i64, String> {
+        self.0.read(fd).map_err(|err| err.to_string())
+    }
+
+    fn write(&mut self, fd: usize, value: i64) -> Result<(), String> {
+        self.0.write(fd, value).map_err(|err| err.to_string())
+    }
+
+    fn accept(&mut self, _fd: usize) -> Result<(), String> {
+        Err("sockets are not available".to_string())
+    }
+}
+
+/// run the program described by `request` and describe how it went,
+/// an error means the request itself was malformed
+pub fn evaluate(request: &Json, budget: &Budget) -> Result<Json, String> {
+    let source = request
+        .get("source")
+        .and_then(Json::as_str)
+        .ok_or("'source' must be a string")?;
+    let input = match request.get("input") {
+        None | Some(Json::Null) => "",
+        Some(input) => input.as_str().ok_or("'input' must be a string")?,
+    };
+    let extensions = match request.get("extensions") {
+        None | Some(Json::Null) => false,
+        Some(extensions) => extensions
+            .as_bool()
+            .ok_or("'extensions' must be a boolean")?,
+    };
+    let mut unstack = Unstack::new();
+    if let Some(init) = request.get("init").filter(|init| **init != Json::Null) {
+        let values = init
+            .as_array()
+            .ok_or("'init' must be an array of integers")?;
+        unstack = values
+            .iter()
+            .map(|value| value.as_i64().ok_or("'init' must be an array of integers"))
+            .collect::<Result<_, _>>()?;
+    }
+
+    let mut playground = Playground(Host::captured(input.as_bytes()));
+    let mut interpreter = Interpreter::new(parse(source, extensions), unstack, &mut playground);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        interpreter.run_budgeted(budget.fuel, &budget.limits)
+    }));
+    let steps = interpreter.steps();
+    let (output, error) = match result {
+        Ok(Ok(Status::Finished)) => {
+            let output = interpreter.output().into_iter().map(Json::Int).collect();
+            (Json::Array(output), Json::Null)
+        }
+        Ok(Ok(_)) => (
+            Json::Null,
+            Json::String(format!("ran out of fuel after {} instructions", steps)),
+        ),
+        Ok(Err(err)) => (Json::Null, Json::String(err.to_string())),
+        Err(payload) => (Json::Null, Json::String(panic_message(payload))),
+    };
+    drop(interpreter);
+    let stdout = String::from_utf8_lossy(&playground.0.captured_output(1)).into_owned();
+    Ok(Json::Object(vec![
+        ("output".to_string(), output),
+        ("stdout".to_string(), Json::String(stdout)),
+        ("steps".to_string(), Json::Int(steps as i64)),
+        ("error".to_string(), error),
+    ]))
+}
+
+/// the parts of an HTTP request the server looks at
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// read a request with a `Content-Length` body, chunked bodies are not supported
+fn read_request(reader: &mut impl BufRead) -> io::Result<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut words = line.split_whitespace();
+    let (Some(method), Some(path)) = (words.next(), words.next()) else {
+        return Err(invalid("malformed request line"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+    let mut length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("connection closed in the headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid("invalid content length"))?;
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return Err(invalid("request body too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Request { method, path, body })
+}
+
+fn write_response(stream: &mut impl Write, status: &str, body: &Json) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn error_body(message: &str) -> Json {
+    Json::Object(vec![(
+        "error".to_string(),
+        Json::String(message.to_string()),
+    )])
+}
+
+/// answer a single request on `stream`, then close it
+fn handle(stream: TcpStream, budget: &Budget) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+    let request = match read_request(&mut reader) {
+        Ok(request) => request,
+        Err(err) if err.kind() == ErrorKind::InvalidData => {
+            return write_response(
+                &mut stream,
+                "400 Bad Request",
+                &error_body(&err.to_string()),
+            )
+        }
+        Err(err) => return Err(err),
+    };
+    let (status, body) = match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/eval") => {
+            let request = String::from_utf8(request.body)
+                .map_err(|_| "the body is not UTF-8".to_string())
+                .and_then(|body| body.parse::<Json>())
+                .and_then(|request| evaluate(&request, budget));
+            match request {
+                Ok(response) => ("200 OK", response),
+                Err(err) => ("400 Bad Request", error_body(&err)),
+            }
+        }
+        (_, "/eval") => ("405 Method Not Allowed", error_body("use POST")),
+        _ => ("404 Not Found", error_body("not found, use POST /eval")),
+    };
+    write_response(&mut stream, status, &body)
+}
+
+/// answer requests on `listener` forever, each connection on its own thread
+pub fn serve(listener: TcpListener, budget: Budget) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("error: could not accept connection: {}", err);
+                continue;
+            }
+        };
+        thread::spawn(move || {
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+            if let Err(err) = handle(stream, &budget) {
+                eprintln!("error: {}", err);
+            }
+        });
+    }
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    let number = |value: String| {
+        value
+            .parse::<usize>()
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    };
+    SubCommand::with_name("serve")
+        .about("evaluate programs sent to POST /eval as JSON")
+        .arg(
+            Arg::with_name("port")
+                .long("port")
+                .takes_value(true)
+                .value_name("PORT")
+                .default_value("8080")
+                .help("port to listen on, or HOST:PORT"),
+        )
+        .arg(
+            Arg::with_name("fuel")
+                .long("fuel")
+                .takes_value(true)
+                .value_name("STEPS")
+                .default_value("1000000")
+                .validator(number)
+                .help("number of instructions each program may execute"),
+        )
+        .arg(
+            Arg::with_name("max-values")
+                .long("max-values")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("100000")
+                .validator(number)
+                .help("number of values each program may have on its unstacks"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .value_name("DURATION")
+                .default_value("1s")
+                .validator(|duration| parse_duration(&duration).map(|_| ()))
+                .help("abort each program after running for DURATION"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let port = args.value_of("port").expect("defaulted by clap");
+    let address = if port.contains(':') {
+        port.to_string()
+    } else {
+        format!("127.0.0.1:{}", port)
+    };
+    let budget = Budget {
+        fuel: args
+            .value_of("fuel")
+            .and_then(|fuel| fuel.parse().ok())
+            .expect("validated by clap"),
+        limits: Limits {
+            timeout: args
+                .value_of("timeout")
+                .map(|timeout| parse_duration(timeout).expect("validated by clap")),
+            max_values: args.value_of("max-values").and_then(|max| max.parse().ok()),
+            ..Limits::default()
+        },
+    };
+    let listener = TcpListener::bind(&address).unwrap_or_else(|err| {
+        eprintln!("error: could not listen on {}: {}", address, err);
+        process::exit(1);
+    });
+    eprintln!("listening on http://{}/eval", address);
+    // panics are part of the response, not something the server operator needs to see
+    panic::set_hook(Box::new(|_| {}));
+    serve(listener, budget);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use crate::json::Json;
+    use crate::serve::{evaluate, serve, Budget};
+    use bottom::Limits;
+
+    fn budget() -> Budget {
+        Budget {
+            fuel: 1000,
+            limits: Limits {
+                max_values: Some(100),
+                ..Limits::default()
+            },
+        }
+    }
+
+    fn eval(request: &str) -> Json {
+        evaluate(&request.parse().unwrap(), &budget()).unwrap()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let response = eval(r#"{"source": "🥺104 📝1 🥺2", "init": [3], "extensions": true}"#);
+        assert_eq!(
+            response.to_string(),
+            r#"{"output":[2,3],"stdout":"h","steps":3,"error":null}"#
+        );
+
+        // 📖0 reads the input, 🏠 has no environment to read from
+        let response = eval(r#"{"source": "📖0", "input": "A", "extensions": true}"#);
+        assert_eq!(
+            response.get("output"),
+            Some(&Json::Array(vec![Json::Int(65)]))
+        );
+        let response = eval(r#"{"source": "🏠0", "extensions": true}"#);
+        assert!(response
+            .get("error")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .contains("environment"));
+
+        // runs forever, and grows the unstack forever
+        let response = eval(r#"{"source": "🥺1 ✨1 🫂3"}"#);
+        assert!(response
+            .get("error")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .contains("fuel"));
+        let response = eval(r#"{"source": "🥺1 🥺1 ✨2 🫂3"}"#);
+        assert!(response
+            .get("error")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .contains("100 values"));
+
+        let response = eval(r#"{"source": "💖2"}"#);
+        assert_eq!(response.get("output"), Some(&Json::Null));
+        assert!(evaluate(&r#"{"init": [1]}"#.parse().unwrap(), &budget()).is_err());
+    }
+
+    #[test]
+    fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, budget()));
+
+        let request = |request: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let body = r#"{"source": "🥺6 🥺7 💓0"}"#;
+        let response = request(&format!(
+            "POST /eval HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        ));
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.ends_with(r#"{"output":[42],"stdout":"","steps":3,"error":null}"#));
+
+        let response = request("POST /eval HTTP/1.1\r\nContent-Length: 3\r\n\r\n{]}");
+        assert!(
+            response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+            "{}",
+            response
+        );
+        let response = request("GET / HTTP/1.1\r\n\r\n");
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{}",
+            response
+        );
+    }
+}
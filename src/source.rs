@@ -0,0 +1,189 @@
+/*!
+ * # Source files
+ *
+ * editors on Windows like to save programs with a byte order mark or as UTF-16, both are turned
+ * into plain UTF-8 before anything looks at the source
+ *
+ * gzip compressed files, like `prog.🥺.gz`, are recognized by their first bytes and decompressed
+ *
+ * with the `fetch` feature programs can also be loaded from an `http://` or `https://` URL
+ */
+
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::path::Path;
+use std::process;
+
+use crate::gzip;
+
+/// the text encodings a source file can be in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+/// the encoding of `bytes` and where its text starts after the byte order mark
+///
+/// without a mark UTF-16 is recognized by ASCII characters having a zero byte, so a file
+/// that is not plain UTF-8 is only taken as UTF-16 if it has many of those
+pub fn detect(bytes: &[u8]) -> (Encoding, usize) {
+    match bytes {
+        [0xef, 0xbb, 0xbf, ..] => (Encoding::Utf8, 3),
+        [0xff, 0xfe, ..] => (Encoding::Utf16Le, 2),
+        [0xfe, 0xff, ..] => (Encoding::Utf16Be, 2),
+        _ if std::str::from_utf8(bytes).is_ok() => (Encoding::Utf8, 0),
+        _ => {
+            let zeros_at = |parity| {
+                bytes
+                    .iter()
+                    .skip(parity)
+                    .step_by(2)
+                    .filter(|&&byte| byte == 0)
+                    .count()
+            };
+            let (even, odd) = (zeros_at(0), zeros_at(1));
+            if odd > bytes.len() / 8 && odd > even {
+                (Encoding::Utf16Le, 0)
+            } else if even > bytes.len() / 8 {
+                (Encoding::Utf16Be, 0)
+            } else {
+                (Encoding::Utf8, 0)
+            }
+        }
+    }
+}
+
+/// the text in `bytes` in whichever encoding [`detect`] finds
+pub fn decode(bytes: &[u8]) -> Result<String, String> {
+    let (encoding, start) = detect(bytes);
+    let bytes = &bytes[start..];
+    match encoding {
+        Encoding::Utf8 => String::from_utf8(bytes.to_vec()).map_err(|err| {
+            format!(
+                "not valid UTF-8 or UTF-16, invalid byte at offset {}",
+                start + err.utf8_error().valid_up_to()
+            )
+        }),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            if !bytes.len().is_multiple_of(2) {
+                return Err("not valid UTF-16, odd number of bytes".to_string());
+            }
+            let units = bytes.chunks_exact(2).map(|unit| match encoding {
+                Encoding::Utf16Le => u16::from_le_bytes([unit[0], unit[1]]),
+                _ => u16::from_be_bytes([unit[0], unit[1]]),
+            });
+            char::decode_utf16(units)
+                .enumerate()
+                .map(|(i, unit)| {
+                    unit.map_err(|_| {
+                        format!(
+                            "not valid UTF-16, unpaired surrogate at offset {}",
+                            start + 2 * i
+                        )
+                    })
+                })
+                .collect()
+        }
+    }
+}
+
+/// the largest program fetched from a URL, in bytes
+#[cfg(feature = "fetch")]
+const MAX_DOWNLOAD: u64 = 16 << 20;
+
+/// whether `location` names a program on the web rather than a file
+pub fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// download the program at `url`
+#[cfg(feature = "fetch")]
+pub fn fetch_source(url: &str) -> io::Result<String> {
+    use std::io::Read;
+    use std::time::Duration;
+
+    let response = ureq::get(url)
+        .timeout(Duration::from_secs(30))
+        .call()
+        .map_err(|err| io::Error::other(err.to_string()))?;
+    let mut bytes = vec![];
+    response
+        .into_reader()
+        .take(MAX_DOWNLOAD + 1)
+        .read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_DOWNLOAD {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("program is larger than {} bytes", MAX_DOWNLOAD),
+        ));
+    }
+    decode_source(bytes)
+}
+
+/// the text of a program stored as `bytes`
+fn decode_source(mut bytes: Vec<u8>) -> io::Result<String> {
+    if bytes.starts_with(&gzip::MAGIC) {
+        bytes =
+            gzip::decompress(&bytes).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+    }
+    decode(&bytes).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+}
+
+/// read the program at `path`, whatever encoding it was saved in and whether it is compressed
+pub fn read_source(path: impl AsRef<Path>) -> io::Result<String> {
+    decode_source(fs::read(path)?)
+}
+
+/// write a generated program to `path` or stdout, gzip compressed if `compress` is set
+pub fn write_output(path: Option<&str>, program: &str, compress: bool) {
+    let bytes = if compress {
+        gzip::compress(program.as_bytes())
+    } else {
+        program.as_bytes().to_vec()
+    };
+    let written = match path {
+        Some(path) => fs::write(path, bytes).map_err(|err| (path, err)),
+        None => io::stdout()
+            .write_all(&bytes)
+            .map_err(|err| ("stdout", err)),
+    };
+    if let Err((path, err)) = written {
+        eprintln!("error: could not write {}: {}", path, err);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::decode;
+
+    #[test]
+    fn test_decode() {
+        let utf16 = |text: &str, little_endian: bool| -> Vec<u8> {
+            text.encode_utf16()
+                .flat_map(|unit| {
+                    if little_endian {
+                        unit.to_le_bytes()
+                    } else {
+                        unit.to_be_bytes()
+                    }
+                })
+                .collect()
+        };
+        let source = "🥺104 ✨1\n";
+        assert_eq!(decode(source.as_bytes()).as_deref(), Ok(source));
+        assert_eq!(
+            decode(&[b"\xef\xbb\xbf", source.as_bytes()].concat()).as_deref(),
+            Ok(source)
+        );
+        for little_endian in [true, false] {
+            let with_bom = utf16("\u{feff}🥺104 ✨1\n", little_endian);
+            assert_eq!(decode(&with_bom).as_deref(), Ok(source));
+            assert_eq!(decode(&utf16(source, little_endian)).as_deref(), Ok(source));
+        }
+        assert!(decode(b"\xff\xfe\x3d\xd8").is_err());
+        assert!(decode(b"\xc3\x28 latin-1 garbage").is_err());
+    }
+}
@@ -0,0 +1,530 @@
+/*!
+ * # Symbolic execution
+ *
+ * runs a program on an initial unstack of unknown values and describes every way it can end:
+ * each [`Path`] holds the conditions on the initial values under which it is taken and either
+ * the final unstack as [expressions](Expr) over those values or that the program failed
+ *
+ * a 🫂 or 🦘 whose condition depends on the initial values forks the execution, loops are unrolled
+ * until [`MAX_STEPS`] or [`MAX_PATHS`] is reached, so this is meant for loop-free fragments.
+ * operations talking to the host, the memory of 💾 and 💿 and the math intrinsics can not be
+ * executed symbolically
+ *
+ * arithmetic is modelled as wrapping, so two programs that only differ in when they overflow are
+ * considered the same
+ */
+
+use std::fmt::{Display, Formatter};
+
+use bottom::interpreter::AUXILIARY_UNSTACKS;
+use bottom::{hug_target, skip_target, Operations};
+
+/// instructions executed over all paths before giving up
+pub const MAX_STEPS: usize = 100_000;
+/// paths explored before giving up
+pub const MAX_PATHS: usize = 256;
+
+/// a value computed from the initial unstack
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Expr {
+    Constant(i64),
+    /// the value at this index of the initial unstack, 0 being the one pushed first
+    Input(usize),
+    /// a coefficient times at least one factor, the factors kept sorted so products compare equal
+    Product(i64, Vec<Expr>),
+    /// truncating division by a constant that is not 0
+    Quotient(Box<Expr>, i64),
+}
+
+impl Expr {
+    /// the coefficient and factors of a product, a single factor for anything else
+    fn factors(self) -> (i64, Vec<Expr>) {
+        match self {
+            Expr::Constant(value) => (value, vec![]),
+            Expr::Product(coefficient, factors) => (coefficient, factors),
+            other => (1, vec![other]),
+        }
+    }
+
+    /// the product of both expressions, as 💓 computes it
+    pub fn mul(self, other: Expr) -> Expr {
+        let (left, mut factors) = self.factors();
+        let (right, others) = other.factors();
+        let coefficient = left.wrapping_mul(right);
+        factors.extend(others);
+        if coefficient == 0 || factors.is_empty() {
+            return Expr::Constant(coefficient);
+        }
+        if coefficient == 1 && factors.len() == 1 {
+            return factors.remove(0);
+        }
+        factors.sort();
+        Expr::Product(coefficient, factors)
+    }
+
+    /// the quotient by `divisor`, as 💖 computes it, `divisor` must not be 0
+    pub fn div(self, divisor: i64) -> Expr {
+        match self {
+            _ if divisor == 1 => self,
+            Expr::Constant(value) => Expr::Constant(value.wrapping_div(divisor)),
+            // a multiple of the divisor divides exactly
+            Expr::Product(coefficient, factors) if coefficient % divisor == 0 => {
+                Expr::Constant(coefficient / divisor).mul(Expr::Product(1, factors))
+            }
+            // dividing twice truncates the same as dividing once by the product
+            Expr::Quotient(inner, first) => match first.checked_mul(divisor) {
+                Some(both) => Expr::Quotient(inner, both),
+                None => Expr::Quotient(Box::new(Expr::Quotient(inner, first)), divisor),
+            },
+            other => Expr::Quotient(Box::new(other), divisor),
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Constant(value) => write!(f, "{}", value),
+            Expr::Input(index) => write!(f, "x{}", index),
+            Expr::Product(coefficient, factors) => {
+                if *coefficient != 1 {
+                    write!(f, "{}·", coefficient)?;
+                }
+                for (index, factor) in factors.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, "·")?;
+                    }
+                    match factor {
+                        Expr::Quotient(..) => write!(f, "({})", factor)?,
+                        _ => write!(f, "{}", factor)?,
+                    }
+                }
+                Ok(())
+            }
+            Expr::Quotient(inner, divisor) => match **inner {
+                Expr::Product(..) | Expr::Quotient(..) => write!(f, "({})/{}", inner, divisor),
+                _ => write!(f, "{}/{}", inner, divisor),
+            },
+        }
+    }
+}
+
+/// what has to hold for a path to be taken
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Condition {
+    Zero(Expr),
+    NonZero(Expr),
+    Equal(Expr, Expr),
+    NotEqual(Expr, Expr),
+}
+
+impl Condition {
+    /// whether the condition holds on every path, on none or depends on the initial values
+    fn decided(&self, conditions: &[Condition]) -> Option<bool> {
+        let constant = match self {
+            Condition::Zero(Expr::Constant(value)) => Some(*value == 0),
+            Condition::NonZero(Expr::Constant(value)) => Some(*value != 0),
+            Condition::Equal(left, right) if left == right => Some(true),
+            Condition::NotEqual(left, right) if left == right => Some(false),
+            Condition::Equal(Expr::Constant(left), Expr::Constant(right)) => Some(left == right),
+            Condition::NotEqual(Expr::Constant(left), Expr::Constant(right)) => Some(left != right),
+            _ => None,
+        };
+        constant.or_else(|| {
+            if conditions.contains(self) {
+                Some(true)
+            } else if conditions.contains(&self.negated()) {
+                Some(false)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn negated(&self) -> Condition {
+        match self.clone() {
+            Condition::Zero(expr) => Condition::NonZero(expr),
+            Condition::NonZero(expr) => Condition::Zero(expr),
+            Condition::Equal(left, right) => Condition::NotEqual(left, right),
+            Condition::NotEqual(left, right) => Condition::Equal(left, right),
+        }
+    }
+
+    /// the same condition with its sides in a fixed order
+    fn normalized(self) -> Condition {
+        match self {
+            Condition::Equal(left, right) if right < left => Condition::Equal(right, left),
+            Condition::NotEqual(left, right) if right < left => Condition::NotEqual(right, left),
+            other => other,
+        }
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::Zero(expr) => write!(f, "{} = 0", expr),
+            Condition::NonZero(expr) => write!(f, "{} ≠ 0", expr),
+            Condition::Equal(left, right) => write!(f, "{} = {}", left, right),
+            Condition::NotEqual(left, right) => write!(f, "{} ≠ {}", left, right),
+        }
+    }
+}
+
+/// how a path ends
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum End {
+    /// the final unstack in the order the values were pushed, so the bottom comes last
+    Finished(Vec<Expr>),
+    /// the program panicked or an assertion failed
+    Failed,
+}
+
+/// one way through the program
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Path {
+    /// sorted and without duplicates
+    pub conditions: Vec<Condition>,
+    pub end: End,
+}
+
+/// why a program could not be executed symbolically
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unsupported {
+    Operation {
+        operation: Operations,
+        instruction_pointer: usize,
+    },
+    /// more than [`MAX_STEPS`] instructions or [`MAX_PATHS`] paths
+    TooLong,
+}
+
+impl Display for Unsupported {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unsupported::Operation {
+                operation,
+                instruction_pointer,
+            } => write!(
+                f,
+                "{} can not be executed symbolically at {}",
+                operation, instruction_pointer
+            ),
+            Unsupported::TooLong => write!(
+                f,
+                "more than {} instructions or {} paths",
+                MAX_STEPS, MAX_PATHS
+            ),
+        }
+    }
+}
+
+/// a path that is still being executed
+struct State {
+    instruction_pointer: usize,
+    unstack: Vec<Expr>,
+    conditions: Vec<Condition>,
+    /// where every 🛬 continues, the innermost 🛫 last
+    calls: Vec<usize>,
+    /// the auxiliary unstacks of 📥 and 📤
+    auxiliary: Vec<Vec<Expr>>,
+}
+
+/// what a single operation did to a state
+enum Step {
+    Continue,
+    /// always continue at this instruction
+    Jump(usize),
+    Failed,
+    /// the state can go either way, the condition holds when jumping or continuing
+    Fork(Condition, usize),
+}
+
+fn step(operation: Operations, state: &mut State) -> Result<Step, Unsupported> {
+    let unstack = &mut state.unstack;
+    let len = unstack.len();
+    let step = match operation {
+        Operations::Push(value) => {
+            unstack.push(Expr::Constant(value));
+            Step::Continue
+        }
+        Operations::Pop(value) => match unstack.pop() {
+            Some(top) if value != 0 => {
+                unstack.push(top.div(value));
+                Step::Continue
+            }
+            _ => Step::Failed,
+        },
+        Operations::Swap(steps) if len > steps => {
+            unstack.swap(len - 1, len - 1 - steps);
+            Step::Continue
+        }
+        Operations::Heart(count) if len >= count + 2 => {
+            let product = unstack.pop().unwrap().mul(unstack.pop().unwrap());
+            unstack.truncate(len - 2 - count);
+            unstack.push(product);
+            Step::Continue
+        }
+        Operations::Dup(count) if len >= count => {
+            for value in unstack.split_off(len - count) {
+                unstack.push(value.clone());
+                unstack.push(value);
+            }
+            Step::Continue
+        }
+        Operations::Hug(n) => match (unstack.pop(), hug_target(state.instruction_pointer, n)) {
+            (None, _) => Step::Failed,
+            (Some(value), Some(target)) => Step::Fork(Condition::NonZero(value), target),
+            // jumping before the start of the program panics
+            (Some(value), None) => Step::Fork(Condition::NonZero(value), usize::MAX),
+        },
+        Operations::Skip(n) => match unstack.pop() {
+            None => Step::Failed,
+            Some(value) => Step::Fork(
+                Condition::Zero(value),
+                skip_target(state.instruction_pointer, n),
+            ),
+        },
+        Operations::Call(target) => {
+            state.calls.push(state.instruction_pointer + 1);
+            Step::Jump(target)
+        }
+        Operations::Return(_) => match state.calls.pop() {
+            Some(target) => Step::Jump(target),
+            None => Step::Failed,
+        },
+        Operations::Stash(index) => match (state.auxiliary.get_mut(index), unstack.pop()) {
+            (Some(auxiliary), Some(value)) => {
+                auxiliary.push(value);
+                Step::Continue
+            }
+            _ => Step::Failed,
+        },
+        Operations::Unstash(index) => match state.auxiliary.get_mut(index).and_then(Vec::pop) {
+            Some(value) => {
+                unstack.push(value);
+                Step::Continue
+            }
+            None => Step::Failed,
+        },
+        Operations::Assert(_) if len >= 2 => {
+            let left = unstack.pop().unwrap();
+            let right = unstack.pop().unwrap();
+            Step::Fork(Condition::NotEqual(left, right).normalized(), usize::MAX)
+        }
+        Operations::Rotate(count) if len >= count => {
+            unstack[len - count..].rotate_left(1);
+            Step::Continue
+        }
+        Operations::Reverse(count) if len >= count => {
+            let count = if count == 0 { len } else { count };
+            unstack[len - count..].reverse();
+            Step::Continue
+        }
+        // what 👀 writes is for debugging, not part of what the program does
+        Operations::Peek(_) | Operations::Break(_) | Operations::Nop(_) => Step::Continue,
+        Operations::Depth(_) => {
+            unstack.push(Expr::Constant(len as i64));
+            Step::Continue
+        }
+        Operations::Clear(count) if len >= count => {
+            unstack.truncate(if count == 0 { 0 } else { len - count });
+            Step::Continue
+        }
+        Operations::Swap(_)
+        | Operations::Heart(_)
+        | Operations::Dup(_)
+        | Operations::Assert(_)
+        | Operations::Rotate(_)
+        | Operations::Reverse(_)
+        | Operations::Clear(_) => Step::Failed,
+        Operations::Env(_)
+        | Operations::Read(_)
+        | Operations::Write(_)
+        | Operations::Print(_)
+        | Operations::Scan(_)
+        | Operations::Accept(_)
+        | Operations::Spawn(_)
+        | Operations::Join(_)
+        | Operations::Store(_)
+        | Operations::Load(_)
+        | Operations::Math(..) => {
+            return Err(Unsupported::Operation {
+                operation,
+                instruction_pointer: state.instruction_pointer,
+            })
+        }
+    };
+    Ok(step)
+}
+
+/// every path through `operations` starting with an unstack of `depth` unknown values,
+/// forks are followed with the condition holding first
+pub fn execute(operations: &[Operations], depth: usize) -> Result<Vec<Path>, Unsupported> {
+    let mut pending = vec![State {
+        instruction_pointer: 0,
+        unstack: (0..depth).map(Expr::Input).collect(),
+        conditions: vec![],
+        calls: vec![],
+        auxiliary: vec![vec![]; AUXILIARY_UNSTACKS],
+    }];
+    let mut paths = vec![];
+    let mut steps = 0;
+    let finish = |paths: &mut Vec<Path>, mut conditions: Vec<Condition>, end| {
+        conditions.sort();
+        conditions.dedup();
+        paths.push(Path { conditions, end });
+    };
+
+    while let Some(mut state) = pending.pop() {
+        loop {
+            let Some(&operation) = operations.get(state.instruction_pointer) else {
+                finish(&mut paths, state.conditions, End::Finished(state.unstack));
+                break;
+            };
+            steps += 1;
+            if steps > MAX_STEPS || paths.len() + pending.len() >= MAX_PATHS {
+                return Err(Unsupported::TooLong);
+            }
+            match step(operation, &mut state)? {
+                Step::Continue => state.instruction_pointer += 1,
+                Step::Jump(target) => state.instruction_pointer = target,
+                Step::Failed => {
+                    finish(&mut paths, state.conditions, End::Failed);
+                    break;
+                }
+                Step::Fork(condition, target) => {
+                    let condition = match condition {
+                        // only the factors decide whether a product is 0
+                        Condition::NonZero(Expr::Product(coefficient, factors)) => {
+                            Condition::NonZero(Expr::Product(coefficient.signum(), factors))
+                        }
+                        Condition::Zero(Expr::Product(coefficient, factors)) => {
+                            Condition::Zero(Expr::Product(coefficient.signum(), factors))
+                        }
+                        other => other,
+                    };
+                    let holds = condition.decided(&state.conditions);
+                    if holds != Some(true) {
+                        let mut otherwise = State {
+                            instruction_pointer: state.instruction_pointer + 1,
+                            unstack: state.unstack.clone(),
+                            conditions: state.conditions.clone(),
+                            calls: state.calls.clone(),
+                            auxiliary: state.auxiliary.clone(),
+                        };
+                        if holds.is_none() {
+                            otherwise.conditions.push(condition.negated());
+                        }
+                        if holds == Some(false) {
+                            state = otherwise;
+                            continue;
+                        }
+                        pending.push(otherwise);
+                    }
+                    if holds.is_none() {
+                        state.conditions.push(condition);
+                    }
+                    if target == usize::MAX {
+                        finish(&mut paths, state.conditions, End::Failed);
+                        break;
+                    }
+                    state.instruction_pointer = target;
+                }
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// whether both programs are proven to behave the same for every initial unstack of `depth` values
+///
+/// `false` means no proof was found, not that the programs differ
+pub fn proven_equivalent(first: &[Operations], second: &[Operations], depth: usize) -> bool {
+    match (execute(first, depth), execute(second, depth)) {
+        (Ok(first), Ok(second)) => first == second,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::optimize::optimize;
+    use crate::symbolic::{execute, proven_equivalent, Condition, End, Expr, Path};
+    use bottom::parse;
+
+    #[test]
+    fn test_execute() {
+        let program = parse("🥺3 💓0 🥺2 💖2", false);
+        assert_eq!(
+            execute(&program, 1).unwrap(),
+            vec![Path {
+                conditions: vec![],
+                end: End::Finished(vec![
+                    Expr::Product(3, vec![Expr::Input(0)]),
+                    Expr::Constant(1)
+                ]),
+            }]
+        );
+        assert_eq!(
+            execute(&program, 0).unwrap()[0].end,
+            End::Failed,
+            "💓 needs two values"
+        );
+
+        // ✅ fails unless both values are the same
+        let paths = execute(&parse("✅1", true), 2).unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(
+            paths[0].conditions,
+            vec![Condition::Equal(Expr::Input(0), Expr::Input(1))]
+        );
+        assert_eq!(paths[1].end, End::Failed);
+
+        // a 🦘 forks on whether the value is 0
+        let paths = execute(&parse("🦘1 🥺2 🥺3", true), 1).unwrap();
+        assert_eq!(paths.len(), 2);
+        assert!(paths.contains(&Path {
+            conditions: vec![Condition::Zero(Expr::Input(0))],
+            end: End::Finished(vec![Expr::Constant(3)]),
+        }));
+
+        // a routine called twice
+        assert!(proven_equivalent(
+            &parse("🛫4 🛫4 🥺0 🦘9 ✨1 💓0 🛬0", true),
+            &parse("✨1 💓0 ✨1 💓0", true),
+            1
+        ));
+
+        // moving two values aside and back in the other order swaps them
+        assert!(proven_equivalent(
+            &parse("📥0 📥1 📤0 📤1", true),
+            &parse("👉👈1", false),
+            2
+        ));
+
+        // a loop halving its counter until it reaches 0 is unrolled
+        let halving = parse("🥺16 ✨1 💖2 ✨1 🫂5", false);
+        assert_eq!(execute(&halving, 0).unwrap().len(), 1);
+
+        let program = parse("🥺🥺🥺 🥺7 💓0 ✨🥺 🥺0 🫂1 ✨0", false);
+        for depth in 0..4 {
+            assert!(proven_equivalent(&program, &optimize(&program), depth));
+        }
+        // rotating three values three times changes nothing
+        let rotated = parse("🔄3 🔄3 🔄3", true);
+        assert!(proven_equivalent(&rotated, &[], 3));
+        assert!(!proven_equivalent(&rotated[1..], &[], 3));
+        assert!(proven_equivalent(
+            &parse("🪞0", true),
+            &parse("👉👈2", false),
+            3
+        ));
+        assert!(!proven_equivalent(
+            &parse("🥺2 💓0", false),
+            &parse("🥺3 💓0", false),
+            1
+        ));
+    }
+}
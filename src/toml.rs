@@ -0,0 +1,346 @@
+/*!
+ * # TOML
+ *
+ * just enough TOML for `bottom.toml` manifests: tables, dotted keys, strings, integers,
+ * booleans, arrays and inline tables
+ *
+ * a document is read into a [`Json::Object`], which can hold everything a manifest needs
+ */
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::json::Json;
+
+type Table = Vec<(String, Json)>;
+
+/// the table at `path` below `root`, created if it does not exist yet
+fn table<'a>(root: &'a mut Table, path: &[String]) -> Result<&'a mut Table, String> {
+    let mut table = root;
+    for key in path {
+        let index = match table.iter().position(|(name, _)| name == key) {
+            Some(index) => index,
+            None => {
+                table.push((key.clone(), Json::Object(vec![])));
+                table.len() - 1
+            }
+        };
+        table = match &mut table[index].1 {
+            Json::Object(members) => members,
+            _ => return Err(format!("'{}' is not a table", key)),
+        };
+    }
+    Ok(table)
+}
+
+/// add `key = value` to `table`, complaining about keys defined twice
+fn insert(table: &mut Table, key: &[String], value: Json) -> Result<(), String> {
+    let (last, path) = key.split_last().expect("keys are never empty");
+    let table = self::table(table, path)?;
+    if table.iter().any(|(name, _)| name == last) {
+        return Err(format!("'{}' is defined twice", key.join(".")));
+    }
+    table.push((last.clone(), value));
+    Ok(())
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn error(&mut self, expected: &str) -> String {
+        match self.chars.peek() {
+            Some(&(at, ch)) => {
+                let line = self.source[..at].lines().count().max(1);
+                format!("expected {} on line {}, found '{}'", expected, line, ch)
+            }
+            None => format!("expected {} at the end of the file", expected),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, ch)| ch)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.chars.next_if(|&(_, ch)| ch == expected).is_some()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_spaces();
+        match self.eat(expected) {
+            true => Ok(()),
+            false => Err(self.error(&format!("'{}'", expected))),
+        }
+    }
+
+    /// skip spaces and a comment, but not the end of the line
+    fn skip_spaces(&mut self) {
+        while self
+            .chars
+            .next_if(|(_, ch)| matches!(ch, ' ' | '\t'))
+            .is_some()
+        {}
+        if self.peek() == Some('#') {
+            while self.chars.next_if(|&(_, ch)| ch != '\n').is_some() {}
+        }
+    }
+
+    /// skip spaces, comments and empty lines
+    fn skip_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            if !(self.eat('\n') || self.eat('\r')) {
+                return;
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        self.eat('\r');
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.chars.next();
+                Ok(())
+            }
+            Some(_) => Err(self.error("the end of the line")),
+        }
+    }
+
+    /// a key, which may be made of dotted parts
+    fn key(&mut self) -> Result<Vec<String>, String> {
+        let mut parts = vec![];
+        loop {
+            self.skip_spaces();
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let mut part = String::new();
+                    while let Some((_, ch)) = self
+                        .chars
+                        .next_if(|&(_, ch)| ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-'))
+                    {
+                        part.push(ch);
+                    }
+                    if part.is_empty() {
+                        return Err(self.error("a key"));
+                    }
+                    part
+                }
+            };
+            parts.push(part);
+            self.skip_spaces();
+            if !self.eat('.') {
+                return Ok(parts);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('"') => self.basic_string().map(Json::String),
+            Some('\'') => self.literal_string().map(Json::String),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some('t' | 'f') => {
+                let word = self.word();
+                match word.as_str() {
+                    "true" => Ok(Json::Bool(true)),
+                    "false" => Ok(Json::Bool(false)),
+                    _ => Err(format!("'{}' is not a value", word)),
+                }
+            }
+            Some(ch) if ch == '-' || ch == '+' || ch.is_ascii_digit() => {
+                let word = self.word();
+                word.replace('_', "")
+                    .parse()
+                    .map(Json::Int)
+                    .map_err(|_| format!("'{}' is not an integer", word))
+            }
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some((_, ch)) = self
+            .chars
+            .next_if(|&(_, ch)| ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-' | '+'))
+        {
+            word.push(ch);
+        }
+        word
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.chars.next().map(|(_, ch)| ch) {
+                None | Some('\n') => return Err("unterminated string".to_string()),
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let escaped = match self.chars.next().map(|(_, ch)| ch) {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let mut code = 0;
+                            for _ in 0..4 {
+                                let digit = self
+                                    .peek()
+                                    .and_then(|ch| ch.to_digit(16))
+                                    .ok_or_else(|| self.error("a hex digit"))?;
+                                self.chars.next();
+                                code = code * 16 + digit;
+                            }
+                            char::from_u32(code).ok_or("invalid \\u escape")?
+                        }
+                        _ => return Err(self.error("an escape sequence")),
+                    };
+                    string.push(escaped);
+                }
+                Some(ch) => string.push(ch),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.expect('\'')?;
+        let mut string = String::new();
+        loop {
+            match self.chars.next().map(|(_, ch)| ch) {
+                None | Some('\n') => return Err("unterminated string".to_string()),
+                Some('\'') => return Ok(string),
+                Some(ch) => string.push(ch),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut values = vec![];
+        loop {
+            self.skip_lines();
+            if self.eat(']') {
+                return Ok(Json::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_lines();
+            if !self.eat(',') {
+                self.expect(']')?;
+                return Ok(Json::Array(values));
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut members = vec![];
+        self.skip_spaces();
+        if self.eat('}') {
+            return Ok(Json::Object(members));
+        }
+        loop {
+            let key = self.key()?;
+            self.expect('=')?;
+            let value = self.value()?;
+            insert(&mut members, &key, value)?;
+            self.skip_spaces();
+            if !self.eat(',') {
+                self.expect('}')?;
+                return Ok(Json::Object(members));
+            }
+        }
+    }
+
+    fn document(&mut self) -> Result<Json, String> {
+        let mut root = vec![];
+        let mut current = vec![];
+        loop {
+            self.skip_lines();
+            match self.peek() {
+                None => return Ok(Json::Object(root)),
+                Some('[') => {
+                    self.chars.next();
+                    current = self.key()?;
+                    self.expect(']')?;
+                    table(&mut root, &current)?;
+                }
+                Some(_) => {
+                    let key = self.key()?;
+                    self.expect('=')?;
+                    let value = self.value()?;
+                    insert(table(&mut root, &current)?, &key, value)?;
+                }
+            }
+            self.end_of_line()?;
+        }
+    }
+}
+
+/// parse a TOML document into an object of its top level keys
+pub fn parse(source: &str) -> Result<Json, String> {
+    Parser {
+        source,
+        chars: source.char_indices().peekable(),
+    }
+    .document()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::Json;
+    use crate::toml::parse;
+
+    #[test]
+    fn test_parse() {
+        let document = parse(
+            r#"
+# a comment
+entry = "src/main.🥺" # another one
+extensions = true
+
+[limits]
+timeout = '5s'
+max-values = 1_000_000
+
+[deps]
+fib = { path = "../fib" }
+"quoted.name".url = "https://example.com/a.🥺"
+list = [
+    1, -2,
+]
+"#,
+        )
+        .unwrap();
+        assert_eq!(document.get("entry").unwrap().as_str(), Some("src/main.🥺"));
+        assert_eq!(document.get("extensions"), Some(&Json::Bool(true)));
+        let limits = document.get("limits").unwrap();
+        assert_eq!(limits.get("timeout").unwrap().as_str(), Some("5s"));
+        assert_eq!(limits.get("max-values").unwrap().as_i64(), Some(1_000_000));
+        let deps = document.get("deps").unwrap();
+        assert_eq!(
+            deps.get("fib").unwrap().get("path").unwrap().as_str(),
+            Some("../fib")
+        );
+        assert!(deps.get("quoted.name").unwrap().get("url").is_some());
+        assert_eq!(
+            deps.get("list"),
+            Some(&Json::Array(vec![Json::Int(1), Json::Int(-2)]))
+        );
+
+        assert!(parse("a = 1\na = 2").is_err());
+        assert!(parse("a = 1 b = 2").is_err());
+        assert!(parse("a = \"unterminated").is_err());
+        assert!(parse("a = 1\n[a]").is_err());
+    }
+}
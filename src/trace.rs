@@ -0,0 +1,347 @@
+/*!
+ * # Traces
+ *
+ * `--trace-out run.trace` writes every instruction a run executes to a compact binary trace, and
+ * `bottom replay run.trace` lists them with the unstack each one left, rebuilt from the trace
+ * alone without running the program again. `--at N` shows the unstacks of all tasks after the
+ * Nth instruction instead
+ *
+ * a trace is [`MAGIC`], the program and the initial unstack of the main task followed by one
+ * record per executed instruction: the task, the instruction pointer, the values it popped and
+ * the values it pushed. numbers are LEB128 varints, values zigzag encoded first so small negative
+ * ones stay short, and the program is its [canonical](crate::canonical) text. lists of values
+ * start with their length, values are popped bottom first and pushed in the order they were
+ */
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use bottom::interpreter::ExecutionObserver;
+use bottom::unstack::Unstack;
+use bottom::{parse, Operations};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::canonical::{canonicalize, ArgumentStyle};
+
+/// the bytes every trace starts with
+pub const MAGIC: &[u8] = b"bottom-trace\x01";
+
+/// an executed instruction
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Step {
+    pub task: usize,
+    pub instruction_pointer: usize,
+    /// the values taken off the unstack, bottom first
+    pub popped: Vec<i64>,
+    /// the values put on the unstack, in the order they were
+    pub pushed: Vec<i64>,
+}
+
+/// a decoded trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub program: Vec<Operations>,
+    /// the unstack the main task started with, bottom first
+    pub initial: Vec<i64>,
+    pub steps: Vec<Step>,
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_value(out: &mut Vec<u8>, value: i64) {
+    write_varint(out, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+fn write_values(out: &mut Vec<u8>, values: &[i64]) {
+    write_varint(out, values.len() as u64);
+    for &value in values {
+        write_value(out, value);
+    }
+}
+
+/// the start of a trace of `program` starting with `initial`
+pub fn header(program: &[Operations], initial: &[i64]) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    let text = canonicalize(program, ArgumentStyle::Numeric);
+    write_varint(&mut out, text.len() as u64);
+    out.extend_from_slice(text.as_bytes());
+    write_values(&mut out, initial);
+    out
+}
+
+impl Step {
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_varint(out, self.task as u64);
+        write_varint(out, self.instruction_pointer as u64);
+        write_values(out, &self.popped);
+        write_values(out, &self.pushed);
+    }
+}
+
+/// reads the numbers of a trace
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .bytes
+                .get(self.position)
+                .ok_or("the trace ends in the middle of a number")?;
+            self.position += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(format!("number too long at byte {}", self.position))
+    }
+
+    fn length(&mut self) -> Result<usize, String> {
+        let length = self.varint()? as usize;
+        // every value takes at least a byte, so longer lists cannot be in the trace
+        match length <= self.bytes.len() - self.position {
+            true => Ok(length),
+            false => Err(format!("list too long at byte {}", self.position)),
+        }
+    }
+
+    fn values(&mut self) -> Result<Vec<i64>, String> {
+        (0..self.length()?)
+            .map(|_| {
+                let zigzag = self.varint()?;
+                Ok((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64))
+            })
+            .collect()
+    }
+}
+
+impl Trace {
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let rest = bytes.strip_prefix(MAGIC).ok_or("not a trace")?;
+        let mut reader = Reader {
+            bytes: rest,
+            position: 0,
+        };
+        let length = reader.length()?;
+        let text = &rest[reader.position..reader.position + length];
+        let text = std::str::from_utf8(text).map_err(|_| "the program is not UTF-8")?;
+        reader.position += length;
+        let program = parse(text, true);
+        let initial = reader.values()?;
+        let mut steps = vec![];
+        while reader.position < rest.len() {
+            steps.push(Step {
+                task: reader.varint()? as usize,
+                instruction_pointer: reader.varint()? as usize,
+                popped: reader.values()?,
+                pushed: reader.values()?,
+            });
+        }
+        Ok(Trace {
+            program,
+            initial,
+            steps,
+        })
+    }
+
+    /// the unstacks of all tasks after every step, bottom first, by task id
+    ///
+    /// 🧵 starts a task with a copy of the values it pushed back and 🪢 takes the values it
+    /// pushes off the bottom of the joined task
+    pub fn unstacks(&self) -> impl Iterator<Item = (&Step, Vec<Vec<i64>>)> + '_ {
+        // the bottom of every unstack is its last value here
+        let mut tasks = vec![self.initial.iter().rev().copied().collect::<Vec<_>>()];
+        self.steps.iter().map(move |step| {
+            let unstack = &mut tasks[step.task];
+            unstack.truncate(unstack.len().saturating_sub(step.popped.len()));
+            unstack.extend(&step.pushed);
+            match self.program.get(step.instruction_pointer) {
+                Some(&Operations::Spawn(count)) => {
+                    tasks.push(step.pushed.iter().take(count).copied().collect());
+                }
+                Some(&Operations::Join(_)) => {
+                    let joined = step.popped.first().map(|&id| id as usize);
+                    if let Some(joined) = joined.and_then(|id| tasks.get_mut(id)) {
+                        joined.truncate(joined.len().saturating_sub(step.pushed.len()));
+                    }
+                }
+                _ => {}
+            }
+            let bottom_first = tasks
+                .iter()
+                .map(|unstack| unstack.iter().rev().copied().collect())
+                .collect();
+            (step, bottom_first)
+        })
+    }
+}
+
+/// writes the instructions a run executes to a trace
+pub struct Recorder {
+    out: BufWriter<File>,
+    /// the instruction being executed
+    step: Step,
+    buffer: Vec<u8>,
+}
+
+impl Recorder {
+    /// start the trace of `program` starting with `initial` at `path`
+    pub fn create(path: &str, program: &[Operations], initial: &Unstack) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&header(program, &initial.values()))?;
+        Ok(Recorder {
+            out,
+            step: Step::default(),
+            buffer: vec![],
+        })
+    }
+}
+
+impl ExecutionObserver for Recorder {
+    fn before_instruction(&mut self, task: usize, instruction_pointer: usize, _: Operations) {
+        // a 🪢 waiting for its task is announced again without being executed
+        self.step = Step {
+            task,
+            instruction_pointer,
+            ..Step::default()
+        };
+    }
+
+    fn on_pop(&mut self, _: usize, value: i64) {
+        self.step.popped.push(value);
+    }
+
+    fn on_push(&mut self, _: usize, value: i64) {
+        self.step.pushed.push(value);
+    }
+
+    fn after_instruction(&mut self, _: usize, _: usize, _: Operations, _: &Unstack) {
+        self.buffer.clear();
+        self.step.encode(&mut self.buffer);
+        self.out
+            .write_all(&self.buffer)
+            .expect("could not write the trace");
+    }
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("replay")
+        .about("list the instructions of a trace written with --trace-out and their unstacks")
+        .arg(
+            Arg::with_name("trace")
+                .required(true)
+                .value_name("TRACE")
+                .help("trace to read"),
+        )
+        .arg(
+            Arg::with_name("at")
+                .long("at")
+                .takes_value(true)
+                .value_name("N")
+                .validator(|n| {
+                    n.parse::<usize>()
+                        .map(|_| ())
+                        .map_err(|err| err.to_string())
+                })
+                .help("show the unstacks of all tasks after the Nth instruction instead"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let path = args.value_of("trace").expect("required by clap");
+    let trace = fs::read(path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| Trace::decode(&bytes))
+        .unwrap_or_else(|err| {
+            eprintln!("error: could not read {}: {}", path, err);
+            process::exit(1);
+        });
+    let at = args
+        .value_of("at")
+        .map(|n| n.parse::<usize>().expect("validated by clap"));
+    if at == Some(0) {
+        println!("task 0: {:?}", trace.initial);
+        return;
+    }
+    let width = trace.steps.len().to_string().len();
+    for (number, (step, tasks)) in (1..).zip(trace.unstacks()) {
+        let operation = trace.program.get(step.instruction_pointer);
+        match at {
+            Some(at) if at == number => {
+                for (id, unstack) in tasks.iter().enumerate() {
+                    println!("task {}: {:?}", id, unstack);
+                }
+                return;
+            }
+            Some(_) => {}
+            None => println!(
+                "{:>width$} {}:{} {} -> {:?}",
+                number,
+                step.task,
+                step.instruction_pointer,
+                operation.map_or("?".to_string(), Operations::to_string),
+                tasks[step.task]
+            ),
+        }
+    }
+    if at.is_some() {
+        eprintln!(
+            "error: the trace has only {} instructions",
+            trace.steps.len()
+        );
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bottom::ext::Host;
+    use bottom::interpreter::Interpreter;
+    use bottom::unstack::Unstack;
+    use bottom::{parse, Limits};
+
+    use crate::trace::{header, Recorder, Trace};
+
+    #[test]
+    fn test_trace() {
+        let path = std::env::temp_dir().join(format!("bottom-trace-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let tokens = parse("🥺5 🥺1 🧵1 🥺3 🪢0 🧹0", true);
+        let initial = Unstack::from(vec![2]);
+        let mut host = Host::new();
+        let recorder = Recorder::create(path, &tokens, &initial).unwrap();
+        let mut interpreter = Interpreter::new(tokens.clone(), initial, &mut host);
+        interpreter.observe(Box::new(recorder));
+        interpreter.run(&Limits::default()).unwrap();
+        drop(interpreter);
+
+        let trace = Trace::decode(&std::fs::read(path).unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(trace.program, tokens);
+        assert_eq!(trace.initial, [2]);
+        let unstacks: Vec<_> = trace.unstacks().map(|(_, tasks)| tasks).collect();
+        assert_eq!(unstacks.len(), 6);
+        // the spawned task gets a copy of the 5, the main task waits for it at the 🪢
+        assert_eq!(unstacks[2], [vec![1, 5, 2], vec![5]]);
+        assert_eq!(unstacks[3], [vec![1, 5, 2], vec![3, 5]]);
+        assert_eq!(unstacks[4], [vec![3, 5, 5, 2], vec![]]);
+        assert_eq!(unstacks[5], [vec![], vec![]]);
+
+        assert!(Trace::decode(b"not a trace").is_err());
+        let mut truncated = header(&trace.program, &[1]);
+        truncated.push(0x80);
+        assert!(Trace::decode(&truncated).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+/*!
+ * # Translation to bottom
+ *
+ * `bottom translate --to bottom FILE` writes a 🥺 program in the encoding of
+ * [bottom](https://github.com/kaylynn234/bottom), which its tools turn back into the program,
+ * and `bottom translate --to 🥺 FILE` turns bottom back into a program
+ *
+ * bottom writes every byte of the text as emoji adding up to it, the biggest first, followed by
+ * 👉👈: 🫂 is 200, 💖 50, ✨ 10, 🥺 5 and `,` 1, and a 0 byte is ❤️
+ */
+
+use std::process;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::source::{read_source, write_output};
+
+/// the characters of bottom and the values they add, the biggest first
+const VALUES: [(char, u8); 5] = [('🫂', 200), ('💖', 50), ('✨', 10), ('🥺', 5), (',', 1)];
+
+/// what bottom writes for a 0 byte
+const ZERO: &str = "❤️";
+
+/// what bottom writes after every byte
+const SEPARATOR: &str = "👉👈";
+
+/// `text` in the encoding of bottom
+pub fn encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for mut byte in text.bytes() {
+        if byte == 0 {
+            encoded += ZERO;
+        }
+        for (character, value) in VALUES {
+            while byte >= value {
+                encoded.push(character);
+                byte -= value;
+            }
+        }
+        encoded += SEPARATOR;
+    }
+    encoded
+}
+
+/// the text encoded by bottom in `encoded`
+pub fn decode(encoded: &str) -> Result<String, String> {
+    let encoded = encoded.trim();
+    if encoded.is_empty() {
+        return Ok(String::new());
+    }
+    let mut bytes = vec![];
+    for group in encoded
+        .strip_suffix(SEPARATOR)
+        .unwrap_or(encoded)
+        .split(SEPARATOR)
+    {
+        match group {
+            ZERO => {
+                bytes.push(0);
+                continue;
+            }
+            "" => return Err(format!("{} without a byte before it", SEPARATOR)),
+            _ => {}
+        }
+        let mut byte = 0u8;
+        for character in group.chars() {
+            let value = VALUES
+                .iter()
+                .find(|&&(known, _)| known == character)
+                .map(|&(_, value)| value)
+                .ok_or_else(|| format!("{} is not part of bottom", character))?;
+            byte = byte
+                .checked_add(value)
+                .ok_or_else(|| format!("{} adds up to more than a byte", group))?;
+        }
+        bytes.push(byte);
+    }
+    String::from_utf8(bytes).map_err(|_| "the decoded text is not UTF-8".to_string())
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("translate")
+        .about("write a 🥺 program in the bottom encoding or the other way around")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .value_name("FILE")
+                .help("program to read"),
+        )
+        .arg(
+            Arg::with_name("to")
+                .long("to")
+                .takes_value(true)
+                .required(true)
+                .possible_values(&["bottom", "🥺"])
+                .help("what to write, bottom for the encoding of the bottom tools"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("write the translation to FILE instead of stdout"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let file = args.value_of("file").expect("required by clap");
+    let source = read_source(file).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", file, err);
+        process::exit(1);
+    });
+    let translated = match args.value_of("to") {
+        Some("bottom") => encode(&source),
+        _ => decode(&source).unwrap_or_else(|err| {
+            eprintln!("error: {} is not bottom: {}", file, err);
+            process::exit(1);
+        }),
+    };
+    let output = args.value_of("output");
+    // a newline keeps the terminal tidy, but does not belong in a file
+    if output.is_none() && !translated.ends_with('\n') {
+        println!("{}", translated);
+    } else {
+        write_output(output, &translated, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::translate::{decode, encode};
+
+    #[test]
+    fn test_translate() {
+        // three bytes, 227, 129 and 130
+        assert_eq!(
+            encode("あ"),
+            "🫂✨✨🥺,,👉👈💖💖✨✨🥺,,,,👉👈💖💖✨✨✨👉👈"
+        );
+        assert_eq!(encode("\0"), "❤️👉👈");
+        let program = "🥺6 🥺7 💓0\n";
+        assert_eq!(decode(&encode(program)).unwrap(), program);
+        assert_eq!(decode("❤️👉👈🥺,👉👈\n").unwrap(), "\0\u{6}");
+        assert!(decode("🫂🫂👉👈").is_err());
+        assert!(decode("🥺x👉👈").is_err());
+        assert!(decode("👉👈👉👈").is_err());
+        assert_eq!(decode("").unwrap(), "");
+    }
+}
@@ -0,0 +1,234 @@
+/*!
+ * # Conformance test vectors
+ *
+ * `bottom vectors` prints programs together with what this crate does with them, one JSON object
+ * per line, so another implementation of 🥺 can check itself against it
+ *
+ * every vector has a `name`, the `program`, whether it needs `extensions`, the values pushed onto
+ * the `unstack` before it starts, so the last one is at the bottom, and the bytes on its `stdin`.
+ * what it should do is in `expected`: the final `unstack`, bottom first, or the kind of `error` it
+ * stops with, named like the `status` of a [report](crate::report), and what it wrote to `stdout`
+ * and `stderr`
+ *
+ * the vectors cover every operation and intrinsic but 🏠 and 📞, which depend on the environment,
+ * and use the default semantics, with 64 bit values that wrap around when they overflow
+ */
+
+use std::panic::{self, AssertUnwindSafe};
+
+use bottom::ext::Host;
+use bottom::interpreter::interpret;
+use bottom::unstack::Unstack;
+use bottom::{parse, Limits};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::batch::{panic_message, without_panic_messages};
+use crate::json::Json;
+use crate::runtime_failure;
+
+/// a program and what it starts with
+struct Case {
+    name: &'static str,
+    source: &'static str,
+    initial: &'static [i64],
+    stdin: &'static str,
+}
+
+const fn case(name: &'static str, source: &'static str, initial: &'static [i64]) -> Case {
+    Case {
+        name,
+        source,
+        initial,
+        stdin: "",
+    }
+}
+
+/// the vectors of the operations everyone implements
+const CORE: &[Case] = &[
+    case("push", "🥺5 🥺0 🥺12", &[]),
+    // the last operation of a program is skipped without an argument
+    case("push-no-argument", "🥺", &[]),
+    // a - ends the argument, leaving 🥺 without one and the digits after it skipped
+    case("push-minus", "🥺-3", &[]),
+    case("pop", "💖2", &[7]),
+    case("pop-negative", "💖2", &[-7]),
+    case("pop-minus", "💖-2", &[7]),
+    case("pop-one", "💖1", &[-9]),
+    case("pop-by-zero", "💖0", &[7]),
+    case("pop-underflow", "💖2", &[]),
+    case("swap", "👉👈2", &[1, 2, 3]),
+    case("swap-bottom", "👉👈0", &[1, 2]),
+    case("swap-underflow", "👉👈3", &[1, 2]),
+    case("heart", "💓0", &[6, 7]),
+    case("heart-discard", "💓2", &[2, 3, 4, 5, 6]),
+    case("heart-negative", "💓0", &[-6, 7]),
+    case("heart-overflow", "💓0", &[i64::MAX, 2]),
+    case("heart-underflow", "💓0", &[6]),
+    case("heart-discard-underflow", "💓2", &[2, 3, 4]),
+    case("dup", "✨1", &[4, 5]),
+    case("dup-many", "✨3", &[1, 2, 3, 4]),
+    case("dup-none", "✨0", &[1]),
+    case("dup-underflow", "✨3", &[1, 2]),
+    case("hug-taken", "🥺1 🥺0 🫂1 🥺2", &[]),
+    case("hug-not-taken", "🥺0 🫂1 🥺2", &[]),
+    case("hug-underflow", "🫂1", &[]),
+    case("loop", "🥺1 🥺1000 ✨1 💖3 ✨1 🫂4", &[]),
+    case("empty", "", &[3, 1]),
+];
+
+/// the vectors of the extension operations and intrinsics
+const EXTENSIONS: &[Case] = &[
+    Case {
+        name: "read",
+        source: "📖0 📖0 📖0",
+        initial: &[],
+        stdin: "hi",
+    },
+    case("write", "🥺104 📝1 🥺105 📝1", &[]),
+    case("write-low-byte", "🥺361 📝1", &[]),
+    case("write-stderr", "🥺33 📝2", &[]),
+    case("print", "🔢1 🔢1", &[-12, 34]),
+    Case {
+        name: "scan",
+        source: "🧮0 🧮0 🧮0",
+        initial: &[],
+        stdin: "12\n-5\n",
+    },
+    case("spawn-join", "🥺2 🧵1 🥺6 💓0 🪢1", &[7]),
+    case("assert", "✅0", &[3, 3]),
+    case("assert-failed", "✅4", &[3, 4]),
+    case("rotate", "🔄3", &[1, 2, 3, 4]),
+    case("reverse", "🪞3", &[1, 2, 3, 4]),
+    case("reverse-all", "🪞0", &[1, 2, 3]),
+    case("clear", "🧹2", &[1, 2, 3]),
+    case("clear-all", "🧹0", &[1, 2, 3]),
+    case("depth", "📚0", &[5, 6]),
+    case("skip-taken", "🥺0 🦘1 🥺1 🥺2", &[]),
+    case("skip-not-taken", "🥺3 🦘1 🥺1 🥺2", &[]),
+    case("call-return", "🛫4 🥺2 🥺0 🦘2 🥺1 🛬0", &[]),
+    case("return-without-call", "🛬0", &[]),
+    case("stash-unstash", "📥1 🥺9 📤1", &[4]),
+    case("unstash-empty", "📤0", &[]),
+    case("store-load", "🥺42 🥺3 💾1 🥺4 💿0 🥺9 💿0", &[]),
+    case("peek", "👀7", &[1, 2]),
+    case("break-nop", "🛑0 💤5", &[1]),
+    case("abs", "📏0", &[-5]),
+    case("abs-overflow", "📏0", &[i64::MIN]),
+    case("pow", "💪0", &[10, 2]),
+    case("pow-negative-exponent", "💪0", &[10, -1]),
+    case("min", "🔻1", &[4, 9, 7]),
+    case("max", "🔺0", &[9, -4]),
+    case("and", "🤝0", &[12, 10]),
+    case("or", "🔀0", &[12, 10]),
+    case("xor", "❌0", &[12, 10]),
+    case("shl", "⏪0", &[4, 1]),
+    case("shr", "⏩0", &[-16, 2]),
+    case("lt", "🤏0", &[3, 5]),
+    case("eq", "🟰0", &[5, 5]),
+];
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("vectors")
+        .about("print conformance test vectors for other implementations of 🥺")
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("include the vectors of the extension operations"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let extensions = args.is_present("extensions");
+    for vector in vectors(extensions) {
+        println!("{}", vector);
+    }
+}
+
+/// the vectors of the core operations, and of the extensions if `extensions` is set
+fn vectors(extensions: bool) -> Vec<Json> {
+    let extension_cases = match extensions {
+        true => EXTENSIONS,
+        false => &[],
+    };
+    without_panic_messages(|| {
+        CORE.iter()
+            .map(|case| vector(case, false))
+            .chain(extension_cases.iter().map(|case| vector(case, true)))
+            .collect()
+    })
+}
+
+/// run `case` and describe it and what it did
+fn vector(case: &Case, extensions: bool) -> Json {
+    let mut host = Host::captured(case.stdin.as_bytes());
+    let unstack = Unstack::from(case.initial.to_vec());
+    let tokens = parse(case.source, extensions);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        interpret(tokens, unstack, &mut host, &Limits::default())
+    }));
+    let mut expected = match result {
+        Ok(Ok(values)) => vec![("unstack".to_string(), integers(&values))],
+        Ok(Err(err)) => {
+            let (kind, _) = runtime_failure(&err);
+            vec![("error".to_string(), Json::String(kind.to_string()))]
+        }
+        Err(payload) => panic!("{} panicked: {}", case.name, panic_message(payload)),
+    };
+    for (name, fd) in [("stdout", 1), ("stderr", 2)] {
+        let output = String::from_utf8_lossy(&host.captured_output(fd)).into_owned();
+        expected.push((name.to_string(), Json::String(output)));
+    }
+    Json::Object(vec![
+        ("name".to_string(), Json::String(case.name.to_string())),
+        ("program".to_string(), Json::String(case.source.to_string())),
+        ("extensions".to_string(), Json::Bool(extensions)),
+        ("unstack".to_string(), integers(case.initial)),
+        ("stdin".to_string(), Json::String(case.stdin.to_string())),
+        ("expected".to_string(), Json::Object(expected)),
+    ])
+}
+
+fn integers(values: &[i64]) -> Json {
+    Json::Array(values.iter().map(|&value| Json::Int(value)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use bottom::ext::math::TABLE;
+    use bottom::{EXTENSIONS as EXTENSION_OPERATIONS, OPERATIONS};
+
+    use crate::json::Json;
+    use crate::vectors::{vectors, CORE, EXTENSIONS};
+
+    #[test]
+    fn test_vectors() {
+        let all = vectors(true);
+        assert_eq!(all.len(), CORE.len() + EXTENSIONS.len());
+        let programs: Vec<_> = all
+            .iter()
+            .map(|vector| vector.get("program").and_then(Json::as_str).unwrap())
+            .collect();
+        let emoji = OPERATIONS
+            .iter()
+            .chain(EXTENSION_OPERATIONS)
+            .map(|(emoji, _)| *emoji)
+            .chain(TABLE.iter().map(|entry| entry.emoji))
+            .filter(|emoji| !["🏠", "📞"].contains(emoji));
+        for emoji in emoji {
+            assert!(
+                programs.iter().any(|program| program.contains(emoji)),
+                "no vector for {}",
+                emoji
+            );
+        }
+        let pop = all
+            .iter()
+            .find(|vector| vector.get("name").and_then(Json::as_str) == Some("pop"))
+            .unwrap();
+        assert_eq!(
+            pop.to_string(),
+            r#"{"name":"pop","program":"💖2","extensions":false,"unstack":[7],"stdin":"","expected":{"unstack":[3],"stdout":"","stderr":""}}"#
+        );
+        assert_eq!(vectors(false).len(), CORE.len());
+    }
+}
@@ -0,0 +1,297 @@
+/*!
+ * # Stack safety
+ *
+ * `bottom verify` proves that a program never runs out of values on its unstack, or finds an
+ * initial unstack it does run out on
+ *
+ * how many values an operation [needs and leaves behind](Operations::stack_effect) does not depend on the values themselves,
+ * only where a 🫂 or 🦘 continues does. the depth analysis follows both ways at every jump, so when no
+ * underflow is reachable there none is possible at all. having more values never hurts, so this
+ * also holds for every deeper initial unstack, and a depth already seen at an instruction never
+ * needs to be looked at again with more values, which makes the analysis finish even for loops
+ *
+ * an underflow found this way might need a 🫂 to go a way it never goes, so it is only reported
+ * as certain once running the program on a generated initial unstack reproduces it
+ */
+
+use std::collections::{BTreeMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+
+use bottom::ext::Host;
+use bottom::interpreter::execute;
+use bottom::unstack::Unstack;
+use bottom::{hug_target, parse, skip_target, Operations, Semantics};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::batch::without_panic_messages;
+use crate::rng::Rng;
+use crate::source::read_source;
+
+/// the result of the depth analysis
+#[derive(Debug, PartialEq)]
+pub enum Analysis {
+    /// no underflow is possible
+    Safe,
+    /// the shortest way to an underflow, as the instructions executed and the depth before each
+    Underflow(Vec<(usize, usize)>),
+    /// the program uses an operation whose effect depends on the values, or like 🧹0 on the depth,
+    /// or one like 🛫 and 💾 that [needs the interpreter](Operations::needs_interpreter)
+    Unsupported(usize),
+}
+
+/// look for a way to run out of values, starting with `depth` values on the unstack
+pub fn analyze(operations: &[Operations], depth: usize) -> Analysis {
+    if let Some(index) = operations
+        .iter()
+        .position(|operation| operation.stack_effect().is_none() || operation.needs_interpreter())
+    {
+        return Analysis::Unsupported(index);
+    }
+    // the smallest depth seen at every instruction
+    let mut seen = BTreeMap::from([(0, depth)]);
+    // the instruction and depth every visit came from
+    let mut came_from = BTreeMap::new();
+    let mut queue = VecDeque::from([(0, depth)]);
+
+    while let Some((index, depth)) = queue.pop_front() {
+        if seen[&index] < depth {
+            // a shallower visit of the same instruction covers this one
+            continue;
+        }
+        let Some(operation) = operations.get(index) else {
+            continue;
+        };
+        let (needs, leaves) = operation.stack_effect().expect("checked above");
+        if depth < needs {
+            let mut path = vec![(index, depth)];
+            while let Some(from) = came_from.get(path.last().expect("never empty")) {
+                path.push(*from);
+            }
+            path.reverse();
+            return Analysis::Underflow(path);
+        }
+        let next = depth - needs + leaves;
+        let mut successors = vec![index + 1];
+        match *operation {
+            Operations::Hug(n) => successors.extend(hug_target(index, n)),
+            Operations::Skip(n) => successors.push(skip_target(index, n)),
+            _ => {}
+        }
+        for successor in successors {
+            if seen.get(&successor).is_none_or(|known| next < *known) {
+                seen.insert(successor, next);
+                came_from.insert((successor, next), (index, depth));
+                queue.push_back((successor, next));
+            }
+        }
+    }
+    Analysis::Safe
+}
+
+/// run the program on `initial` for at most `max_steps` instructions,
+/// the instructions executed and the depth before each if it ran out of values
+pub fn reproduce(
+    operations: &[Operations],
+    initial: &[i64],
+    max_steps: usize,
+) -> Option<Vec<(usize, usize)>> {
+    let mut unstack = Unstack::from(initial.to_vec());
+    let mut host = Host::captured(&[]);
+    let mut trace = vec![];
+    let mut instruction_pointer = 0;
+    while let Some(operation) = operations.get(instruction_pointer) {
+        if trace.len() == max_steps {
+            return None;
+        }
+        trace.push((instruction_pointer, unstack.len()));
+        let (needs, _) = operation.stack_effect()?;
+        if unstack.len() < needs {
+            return Some(trace);
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            execute(
+                *operation,
+                &mut unstack,
+                &mut instruction_pointer,
+                &mut host,
+                &Semantics::default(),
+            )
+        }));
+        // the program failed some other way
+        if !matches!(result, Ok(Ok(()))) {
+            return None;
+        }
+        instruction_pointer = instruction_pointer.wrapping_add(1);
+    }
+    None
+}
+
+/// initial unstacks of exactly `depth` values to reproduce an underflow with
+fn inputs(depth: usize, count: usize, rng: &mut Rng) -> impl Iterator<Item = Vec<i64>> + '_ {
+    let constants = [0, 1, -1, 2];
+    let random =
+        (constants.len()..count).map(move |_| (0..depth).map(|_| rng.range(-100, 100)).collect());
+    constants
+        .into_iter()
+        .take(count)
+        .map(move |value| vec![value; depth])
+        .chain(random)
+}
+
+fn print_trace(operations: &[Operations], trace: &[(usize, usize)]) {
+    for (index, depth) in trace {
+        println!(
+            "  {:02}: {:<8} with {} values",
+            index,
+            operations[*index].to_string(),
+            depth
+        );
+    }
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("verify")
+        .about("prove that a 🥺 program never runs out of values on its unstack")
+        .after_help(
+            "exits with 1 if the program runs out of values and 2 if it might but no initial unstack doing so was found",
+        )
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .value_name("FILE")
+                .help("🥺 program to verify"),
+        )
+        .arg(
+            Arg::with_name("depth")
+                .long("depth")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("0")
+                .validator(|n| n.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+                .help("verify for initial unstacks of at least N values"),
+        )
+        .arg(
+            Arg::with_name("inputs")
+                .long("inputs")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("1000")
+                .validator(|n| n.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+                .help("number of initial unstacks to try reproducing an underflow with"),
+        )
+        .arg(
+            Arg::with_name("max-steps")
+                .long("max-steps")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("100000")
+                .validator(|n| n.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+                .help("instructions to run each initial unstack for, unrolling loops at most this far"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .value_name("N")
+                .validator(|seed| seed.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                .help("seed for the generated initial unstacks, defaults to the current time"),
+        )
+        .arg(
+            Arg::with_name("extensions")
+                .long("extensions")
+                .help("recognize the extension operations"),
+        )
+}
+
+pub fn main(args: &ArgMatches) {
+    let file = args.value_of("file").expect("required by clap");
+    let source = read_source(file).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", file, err);
+        process::exit(1);
+    });
+    let operations = parse(&source, args.is_present("extensions"));
+    let number = |name| -> usize {
+        args.value_of(name)
+            .and_then(|n| n.parse().ok())
+            .expect("validated by clap")
+    };
+    let depth = number("depth");
+
+    let path = match analyze(&operations, depth) {
+        Analysis::Safe => {
+            println!(
+                "never runs out of values when started with at least {} values",
+                depth
+            );
+            return;
+        }
+        Analysis::Unsupported(index) => {
+            eprintln!(
+                "error: the effect of {} at {} depends on the values, it can not be verified",
+                operations[index], index
+            );
+            process::exit(1);
+        }
+        Analysis::Underflow(path) => path,
+    };
+
+    let seed = match args.value_of("seed") {
+        Some(seed) => seed.parse().expect("validated by clap"),
+        None => Rng::seed_from_time(),
+    };
+    let max_steps = number("max-steps");
+    let mut rng = Rng::new(seed);
+    let found = without_panic_messages(|| {
+        inputs(depth, number("inputs"), &mut rng).find_map(|initial| {
+            reproduce(&operations, &initial, max_steps).map(|trace| (initial, trace))
+        })
+    });
+    match found {
+        Some((initial, trace)) => {
+            println!("runs out of values when started with {:?}:", initial);
+            print_trace(&operations, &trace);
+            process::exit(1);
+        }
+        None => {
+            println!(
+                "might run out of values when started with {} values, but no initial unstack doing so was found (seed {}):",
+                depth, seed
+            );
+            print_trace(&operations, &path);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verify::{analyze, reproduce, Analysis};
+    use bottom::parse;
+
+    #[test]
+    fn test_analyze() {
+        // the halving loop grows the unstack by one every time around
+        let halving = parse("🥺16 ✨1 💖2 ✨1 🫂5", false);
+        assert_eq!(analyze(&halving, 0), Analysis::Safe);
+
+        let multiply = parse("🥺3 💓0", false);
+        assert_eq!(analyze(&multiply, 1), Analysis::Safe);
+        assert_eq!(
+            analyze(&multiply, 0),
+            Analysis::Underflow(vec![(0, 0), (1, 1)])
+        );
+        assert_eq!(reproduce(&multiply, &[], 100), Some(vec![(0, 0), (1, 1)]));
+
+        // only runs out of values if the first one is not 0
+        let branch = parse("🥺1 💓0 🫂2 🥺0 🫂1", false);
+        assert!(matches!(analyze(&branch, 1), Analysis::Underflow(_)));
+        assert_eq!(reproduce(&branch, &[0], 100), None);
+        assert!(reproduce(&branch, &[1], 100).is_some());
+
+        assert_eq!(
+            analyze(&parse("🥺1 🧵0", true), 0),
+            Analysis::Unsupported(1)
+        );
+    }
+}
@@ -0,0 +1,176 @@
+/*!
+ * # ZMTP
+ *
+ * the ZeroMQ wire protocol (ZMTP 3.0 with the NULL mechanism), as far as the Jupyter kernel needs it
+ *
+ * every peer gets its own [`Connection`], so a ROUTER socket simply answers on the connection
+ * a message came in on instead of routing by identity
+ */
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+/// the frame is followed by another one of the same message
+const MORE: u8 = 0x01;
+/// the size is 8 bytes long instead of 1
+const LONG: u8 = 0x02;
+const COMMAND: u8 = 0x04;
+
+/// a message, made of any number of frames
+pub type Message = Vec<Vec<u8>>;
+
+fn protocol_error(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// a connection from a peer that finished the handshake
+#[derive(Debug)]
+pub struct Connection {
+    stream: TcpStream,
+}
+
+impl Connection {
+    /// exchange greetings with a peer connected to a socket of type `socket_type`, e.g. `ROUTER`
+    pub fn accept(stream: TcpStream, socket_type: &str) -> io::Result<Self> {
+        let mut connection = Connection { stream };
+        let mut greeting = [0u8; 64];
+        greeting[0] = 0xff;
+        greeting[9] = 0x7f;
+        greeting[10] = 3;
+        greeting[12..16].copy_from_slice(b"NULL");
+        connection.stream.write_all(&greeting)?;
+
+        let mut peer = [0u8; 64];
+        connection.stream.read_exact(&mut peer)?;
+        if peer[0] != 0xff || peer[9] != 0x7f || peer[10] < 3 {
+            return Err(protocol_error("peer does not speak ZMTP 3"));
+        }
+        if !peer[12..32].starts_with(b"NULL") || peer[16..32].iter().any(|&byte| byte != 0) {
+            return Err(protocol_error("only the NULL mechanism is supported"));
+        }
+
+        let mut ready = vec![5];
+        ready.extend_from_slice(b"READY");
+        ready.push(11);
+        ready.extend_from_slice(b"Socket-Type");
+        ready.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+        ready.extend_from_slice(socket_type.as_bytes());
+        connection.write_frame(COMMAND, &ready)?;
+        loop {
+            let (flags, body) = connection.read_frame()?;
+            if flags & COMMAND == 0 {
+                return Err(protocol_error("expected READY"));
+            }
+            if body.starts_with(b"\x05READY") {
+                return Ok(connection);
+            }
+            if body.starts_with(b"\x05ERROR") {
+                return Err(protocol_error("peer refused the handshake"));
+            }
+        }
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Connection {
+            stream: self.stream.try_clone()?,
+        })
+    }
+
+    fn read_frame(&mut self) -> io::Result<(u8, Vec<u8>)> {
+        let mut flags = [0];
+        self.stream.read_exact(&mut flags)?;
+        let size = if flags[0] & LONG != 0 {
+            let mut size = [0; 8];
+            self.stream.read_exact(&mut size)?;
+            u64::from_be_bytes(size) as usize
+        } else {
+            let mut size = [0];
+            self.stream.read_exact(&mut size)?;
+            size[0] as usize
+        };
+        let mut body = vec![0; size];
+        self.stream.read_exact(&mut body)?;
+        Ok((flags[0], body))
+    }
+
+    fn write_frame(&mut self, flags: u8, body: &[u8]) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(body.len() + 9);
+        if body.len() > u8::MAX as usize {
+            frame.push(flags | LONG);
+            frame.extend_from_slice(&(body.len() as u64).to_be_bytes());
+        } else {
+            frame.push(flags);
+            frame.push(body.len() as u8);
+        }
+        frame.extend_from_slice(body);
+        self.stream.write_all(&frame)
+    }
+
+    /// the next message, answering pings and skipping other commands on the way
+    pub fn recv(&mut self) -> io::Result<Message> {
+        let mut message = vec![];
+        loop {
+            let (flags, body) = self.read_frame()?;
+            if flags & COMMAND != 0 {
+                if let Some(ping) = body.strip_prefix(b"\x04PING") {
+                    // the TTL comes before the context the PONG has to echo
+                    let mut pong = b"\x04PONG".to_vec();
+                    pong.extend_from_slice(ping.get(2..).unwrap_or_default());
+                    self.write_frame(COMMAND, &pong)?;
+                }
+                continue;
+            }
+            message.push(body);
+            if flags & MORE == 0 {
+                return Ok(message);
+            }
+        }
+    }
+
+    pub fn send(&mut self, message: &[Vec<u8>]) -> io::Result<()> {
+        for (i, frame) in message.iter().enumerate() {
+            let more = if i + 1 < message.len() { MORE } else { 0 };
+            self.write_frame(more, frame)?;
+        }
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use crate::zmtp::Connection;
+
+    #[test]
+    fn test_echo() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let peer = thread::spawn(move || {
+            // a REQ socket as libzmq would write it
+            let mut stream = TcpStream::connect(address).unwrap();
+            let mut greeting = [0u8; 64];
+            greeting[0] = 0xff;
+            greeting[9] = 0x7f;
+            greeting[10] = 3;
+            greeting[12..16].copy_from_slice(b"NULL");
+            stream.write_all(&greeting).unwrap();
+            stream
+                .write_all(b"\x04\x19\x05READY\x0bSocket-Type\0\0\0\x03REQ")
+                .unwrap();
+            stream.write_all(b"\x01\x00\x00\x04ping").unwrap();
+            let mut reply = vec![0; 64 + 27 + 8];
+            stream.read_exact(&mut reply).unwrap();
+            reply.split_off(64 + 27)
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut connection = Connection::accept(stream, "REP").unwrap();
+        let message = connection.recv().unwrap();
+        assert_eq!(message, vec![b"".to_vec(), b"ping".to_vec()]);
+        connection.send(&message).unwrap();
+        assert_eq!(peer.join().unwrap(), b"\x01\x00\x00\x04ping");
+    }
+}